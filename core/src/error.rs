@@ -0,0 +1,72 @@
+// Copyright (C) 2019  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::module::FromBytesError;
+use crate::scheduler::NewErr;
+use crate::{InterfaceHash, Pid};
+
+use core::fmt;
+
+/// Problem that has happened while building or running the [`System`](crate::System).
+///
+/// Errors returned while running the [`System`](crate::System) aren't fatal. The
+/// [`System`](crate::System) can continue running afterwards, and it is the responsibility of the
+/// caller to decide how to react, for example by logging the error.
+#[derive(Debug)]
+pub enum Error {
+    /// The loader has answered a request to load a program with an error, or with a response
+    /// that couldn't be decoded.
+    ProgramLoadFailed,
+    /// The program provided by the loader isn't a valid WASM module.
+    InvalidProgram(FromBytesError),
+    /// The program provided by the loader, or a program passed to
+    /// [`SystemBuilder::with_startup_process`](crate::SystemBuilder::with_startup_process),
+    /// couldn't be started.
+    ProgramStartFailed(NewErr),
+    /// A process has emitted a message that couldn't be decoded on an interface handled by the
+    /// [`System`](crate::System) itself. The message has been answered with
+    /// [`AnswerError::MalformedRequest`](crate::AnswerError::MalformedRequest) if it expected
+    /// an answer.
+    MalformedMessage {
+        /// Process that has emitted the message.
+        pid: Pid,
+        /// Interface the message was emitted on.
+        interface: InterfaceHash,
+    },
+    /// A process has asked for a new thread, but the function to start couldn't be found or
+    /// doesn't have the expected signature.
+    ThreadStartFailed {
+        /// Process that has asked for a new thread.
+        pid: Pid,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::ProgramLoadFailed => write!(f, "Failed to load program"),
+            Error::InvalidProgram(_) => write!(f, "Loaded program isn't a valid module"),
+            Error::ProgramStartFailed(err) => write!(f, "Failed to start program: {}", err),
+            Error::MalformedMessage { pid, interface } => write!(
+                f,
+                "Malformed message emitted by {:?} on interface {}",
+                pid, interface
+            ),
+            Error::ThreadStartFailed { pid } => {
+                write!(f, "Failed to start thread requested by {:?}", pid)
+            }
+        }
+    }
+}
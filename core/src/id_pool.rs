@@ -47,7 +47,8 @@ impl IdPool {
         IdPool {
             rngs: SegQueue::new(),
             distribution: Uniform::from(0..=u64::max_value()),
-            master_rng: Mutex::new(Hc128Rng::from_seed([0; 32])), // FIXME: proper seed
+            // FIXME: proper seed; must remain fixed when deterministic scheduling is enabled
+            master_rng: Mutex::new(Hc128Rng::from_seed([0; 32])),
         }
     }
 
@@ -117,18 +117,24 @@
 #![no_std]
 
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
+pub use self::error::Error;
 pub use self::module::Module;
-pub use self::system::{System, SystemBuilder, SystemRunOutcome};
+pub use self::signature::WasmValue;
+pub use self::system::{AnswerSender, CrashReason, System, SystemBuilder, SystemRunOutcome};
 pub use redshirt_syscalls_interface::{
-    Decode, Encode, EncodedMessage, InterfaceHash, MessageId, Pid, ThreadId,
+    AnswerError, Decode, Encode, EncodedMessage, InterfaceHash, MessageId, Pid, ThreadId,
 };
-pub use wasmi::RuntimeValue; // TODO: wrap around instead?
 
+mod error;
 mod id_pool;
 
 pub mod module;
 pub mod native;
 pub mod scheduler;
+pub mod schema;
 pub mod signature;
 pub mod system;
+pub mod time;
@@ -13,7 +13,10 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use core::fmt;
+use crate::signature::{Signature, ValueType};
+use alloc::{vec, vec::Vec};
+use core::{convert::TryFrom as _, fmt, mem};
+use parity_wasm::elements::{self, Instruction};
 use sha2::Digest as _;
 
 /// Represents a successfully-parsed binary.
@@ -21,37 +24,218 @@ use sha2::Digest as _;
 /// This is the equivalent of an [ELF](https://en.wikipedia.org/wiki/Executable_and_Linkable_Format)
 /// or a [PE](https://en.wikipedia.org/wiki/Portable_Executable).
 pub struct Module {
-    inner: wasmi::Module,
+    /// Parsed version of the original binary. Compiled by the engine when the module is
+    /// instantiated, and instrumented beforehand if necessary.
+    source: elements::Module,
     hash: ModuleHash,
 }
 
+/// Name under which a module instrumented by [`Module::with_fuel_metering`] exports the global
+/// holding the fuel that remains.
+pub(crate) const FUEL_GLOBAL: &str = "__redshirt_fuel";
+
 /// Hash of a module.
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct ModuleHash([u8; 32]);
 
+/// Item exported by a [`Module`]. See [`Module::exports`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportInfo<'a> {
+    /// Name under which the item is exported.
+    pub name: &'a str,
+    /// What the item is.
+    pub kind: ExternKind,
+}
+
+/// Item imported by a [`Module`]. See [`Module::imports`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportInfo<'a> {
+    /// Name of the module the item is imported from.
+    pub module: &'a str,
+    /// Name of the item within that module.
+    pub field: &'a str,
+    /// What the item is.
+    pub kind: ExternKind,
+}
+
+/// Kind of item that a module imports or exports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExternKind {
+    /// A function with the given signature.
+    Function(Signature),
+    /// A table of function references.
+    Table,
+    /// A linear memory.
+    Memory,
+    /// A global variable.
+    Global,
+}
+
 /// Error that can happen when calling `from_bytes`.
 #[derive(Debug)]
-pub struct FromBytesError {}
+pub enum FromBytesError {
+    /// The bytes aren't a well-formed WASM binary.
+    Decode(elements::Error),
+    /// The binary has been decoded but isn't a valid module.
+    Invalid(wasmi::Error),
+}
+
+/// Error that can happen when calling `from_file`.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum FromFileError {
+    /// Error while reading the file.
+    Io(std::io::Error),
+    /// The content of the file isn't a valid module.
+    Parse(FromBytesError),
+}
+
+/// Error that can happen when calling `from_wat`.
+#[cfg(test)]
+#[derive(Debug)]
+pub enum FromWatError {
+    /// The source isn't valid WASM text.
+    Parse {
+        /// Line of the problem, starting from 1. `None` if the parser didn't report it.
+        line: Option<usize>,
+        /// Column of the problem, starting from 1. `None` if the parser didn't report it.
+        column: Option<usize>,
+        /// Error reported by the parser.
+        error: wat::Error,
+    },
+    /// The source has been parsed, but the module isn't valid.
+    Module(FromBytesError),
+}
 
 impl Module {
     /// Parses a module from WASM bytes.
     pub fn from_bytes(buffer: impl AsRef<[u8]>) -> Result<Self, FromBytesError> {
-        let inner = wasmi::Module::from_buffer(buffer.as_ref()).map_err(|_| FromBytesError {})?;
+        let source: elements::Module =
+            parity_wasm::deserialize_buffer(buffer.as_ref()).map_err(FromBytesError::Decode)?;
+        // Compiling the module validates it. The compiled version is thrown away, as the engine
+        // compiles the module again when instantiating it.
+        wasmi::Module::from_parity_wasm_module(source.clone()).map_err(FromBytesError::Invalid)?;
         let hash = ModuleHash::from_bytes(buffer);
 
-        Ok(Module { inner, hash })
+        Ok(Module { source, hash })
+    }
+
+    /// Reads a module from a WASM file.
+    #[cfg(feature = "std")]
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, FromFileError> {
+        let buffer = std::fs::read(path).map_err(FromFileError::Io)?;
+        Self::from_bytes(buffer).map_err(FromFileError::Parse)
     }
 
     /// Turns some WASM text source into a `Module`.
+    ///
+    /// If the source can't be parsed, the error indicates the line and column of the problem.
     #[cfg(test)] // TODO: is `#[cfg(test)]` a good idea?
-    pub fn from_wat(source: impl AsRef<[u8]>) -> Result<Self, wat::Error> {
-        let wasm = wat::parse_bytes(source.as_ref())?;
-        Ok(Self::from_bytes(wasm).unwrap())
+    pub fn from_wat(source: impl AsRef<[u8]>) -> Result<Self, FromWatError> {
+        let wasm = wat::parse_bytes(source.as_ref()).map_err(FromWatError::from)?;
+        Self::from_bytes(wasm).map_err(FromWatError::Module)
+    }
+
+    /// Returns the list of items that the module exports.
+    pub fn exports<'a>(&'a self) -> impl Iterator<Item = ExportInfo<'a>> + 'a {
+        let entries = match self.source.export_section() {
+            Some(section) => section.entries(),
+            None => &[],
+        };
+
+        entries.iter().map(move |export| {
+            let kind = match export.internal() {
+                elements::Internal::Function(index) => {
+                    ExternKind::Function(self.function_signature(*index))
+                }
+                elements::Internal::Table(_) => ExternKind::Table,
+                elements::Internal::Memory(_) => ExternKind::Memory,
+                elements::Internal::Global(_) => ExternKind::Global,
+            };
+
+            ExportInfo {
+                name: export.field(),
+                kind,
+            }
+        })
+    }
+
+    /// Returns the list of items that the module imports and that must be resolved in order to
+    /// execute it.
+    pub fn imports<'a>(&'a self) -> impl Iterator<Item = ImportInfo<'a>> + 'a {
+        let entries = match self.source.import_section() {
+            Some(section) => section.entries(),
+            None => &[],
+        };
+
+        entries.iter().map(move |import| {
+            let kind = match import.external() {
+                elements::External::Function(type_index) => {
+                    ExternKind::Function(self.type_signature(*type_index))
+                }
+                elements::External::Table(_) => ExternKind::Table,
+                elements::External::Memory(_) => ExternKind::Memory,
+                elements::External::Global(_) => ExternKind::Global,
+            };
+
+            ImportInfo {
+                module: import.module(),
+                field: import.field(),
+                kind,
+            }
+        })
+    }
+
+    /// Returns the signature of the function with the given index. Imported functions come
+    /// first in the index space, followed with the functions defined by the module.
+    ///
+    /// The module has been validated, and the indices that it contains are thus valid.
+    fn function_signature(&self, function_index: u32) -> Signature {
+        let imports = match self.source.import_section() {
+            Some(section) => section.entries(),
+            None => &[],
+        };
+
+        let mut imported_functions = imports.iter().filter_map(|import| match import.external() {
+            elements::External::Function(type_index) => Some(*type_index),
+            _ => None,
+        });
+
+        let type_index = match imported_functions.nth(function_index as usize) {
+            Some(type_index) => type_index,
+            None => {
+                let num_imported = self
+                    .source
+                    .import_count(elements::ImportCountType::Function);
+                match self.source.function_section() {
+                    Some(section) => {
+                        section.entries()[function_index as usize - num_imported].type_ref()
+                    }
+                    None => unreachable!(),
+                }
+            }
+        };
+
+        self.type_signature(type_index)
+    }
+
+    /// Returns the signature of the type with the given index in the types section.
+    fn type_signature(&self, type_index: u32) -> Signature {
+        let ty = match self.source.type_section() {
+            Some(section) => &section.types()[type_index as usize],
+            None => unreachable!(),
+        };
+
+        let elements::Type::Function(ty) = ty;
+        Signature::new(
+            ty.params().iter().cloned().map(ValueType::from),
+            ty.return_type().map(ValueType::from),
+        )
     }
 
-    /// Returns a reference to the internal module.
-    pub(crate) fn as_ref(&self) -> &wasmi::Module {
-        &self.inner
+    /// Returns the parsed version of the module.
+    pub(crate) fn source(&self) -> &elements::Module {
+        &self.source
     }
 
     /// Returns the hash of that module.
@@ -60,6 +244,214 @@ impl Module {
     pub fn hash(&self) -> &ModuleHash {
         &self.hash
     }
+
+    /// Returns a copy of this module where the code keeps track of how many instructions it
+    /// executes.
+    ///
+    /// The returned module exports a mutable `i64` global named [`FUEL_GLOBAL`], holding the
+    /// fuel that remains. Each straight sequence of instructions starts by subtracting the number
+    /// of instructions in the sequence from this global.
+    ///
+    /// The returned module also imports an additional function named `field_name` from the
+    /// module named `module_name`, with one `i32` parameter and no return value. This function is
+    /// only called when the global doesn't hold enough fuel for the sequence that is about to be
+    /// executed, passing the number of instructions in the sequence. Once it returns, the
+    /// sequence is executed and its fuel subtracted.
+    ///
+    /// The hash of the returned module is the same as the hash of `self`.
+    pub(crate) fn with_fuel_metering(&self, module_name: &str, field_name: &str) -> Module {
+        // Importing a function shifts the indices of all the functions defined by the module.
+        let fuel_fn = u32::try_from(
+            self.source
+                .import_count(elements::ImportCountType::Function),
+        )
+        .unwrap();
+
+        // The global is added after the ones imported and defined by the module.
+        let fuel_global = u32::try_from(
+            self.source.import_count(elements::ImportCountType::Global)
+                + self
+                    .source
+                    .global_section()
+                    .map_or(0, |section| section.entries().len()),
+        )
+        .unwrap();
+
+        let mut source = {
+            let mut builder = parity_wasm::builder::from_module(self.source.clone());
+            let signature = builder.push_signature(
+                parity_wasm::builder::signature()
+                    .with_param(elements::ValueType::I32)
+                    .build_sig(),
+            );
+            builder.push_import(
+                parity_wasm::builder::import()
+                    .module(module_name)
+                    .field(field_name)
+                    .external()
+                    .func(signature)
+                    .build(),
+            );
+            builder
+                .with_global(elements::GlobalEntry::new(
+                    elements::GlobalType::new(elements::ValueType::I64, true),
+                    elements::InitExpr::new(vec![Instruction::I64Const(0), Instruction::End]),
+                ))
+                .with_export(elements::ExportEntry::new(
+                    FUEL_GLOBAL.into(),
+                    elements::Internal::Global(fuel_global),
+                ))
+                .build()
+        };
+
+        let shift = |index: &mut u32| {
+            if *index >= fuel_fn {
+                *index += 1;
+            }
+        };
+
+        // The names of the functions would be off by one, and are only useful for debugging.
+        source.sections_mut().retain(|section| match section {
+            elements::Section::Custom(custom) => custom.name() != "name",
+            elements::Section::Name(_) => false,
+            _ => true,
+        });
+
+        for section in source.sections_mut() {
+            match section {
+                elements::Section::Code(code) => {
+                    for body in code.bodies_mut() {
+                        let instructions = body.code_mut().elements_mut();
+                        *instructions = meter_instructions(
+                            mem::replace(instructions, Vec::new()),
+                            fuel_fn,
+                            fuel_global,
+                        );
+                    }
+                }
+                elements::Section::Export(exports) => {
+                    for export in exports.entries_mut() {
+                        if let elements::Internal::Function(index) = export.internal_mut() {
+                            shift(index);
+                        }
+                    }
+                }
+                elements::Section::Element(segments) => {
+                    for segment in segments.entries_mut() {
+                        for member in segment.members_mut() {
+                            shift(member);
+                        }
+                    }
+                }
+                elements::Section::Start(index) => shift(index),
+                _ => {}
+            }
+        }
+
+        Module {
+            source,
+            hash: self.hash.clone(),
+        }
+    }
+
+    /// Returns a copy of this module whose memory can't grow above `max_pages` pages of 64kiB.
+    ///
+    /// Past this limit, the `memory.grow` instruction fails and returns `-1`, as it does when the
+    /// maximum declared by the module itself is reached.
+    ///
+    /// Returns an error if the module requires more than `max_pages` pages right from the start.
+    ///
+    /// The hash of the returned module is the same as the hash of `self`.
+    pub(crate) fn with_memory_limit(&self, max_pages: u32) -> Result<Module, ()> {
+        let mut source = self.source.clone();
+
+        for section in source.sections_mut() {
+            if let elements::Section::Memory(memories) = section {
+                for memory in memories.entries_mut() {
+                    let initial = memory.limits().initial();
+                    if initial > max_pages {
+                        return Err(());
+                    }
+
+                    let maximum = match memory.limits().maximum() {
+                        Some(max) if max <= max_pages => max,
+                        _ => max_pages,
+                    };
+                    *memory = elements::MemoryType::new(initial, Some(maximum));
+                }
+            }
+        }
+
+        // Lowering the maximum of a memory, but not below its initial size, always produces a
+        // valid module.
+        Ok(Module {
+            source,
+            hash: self.hash.clone(),
+        })
+    }
+}
+
+/// Inserts at the start of each straight sequence of instructions some code that subtracts the
+/// length of the sequence from the global with index `fuel_global`, after calling the function
+/// with index `fuel_fn` if the global is lower than that length. Also shifts by one the indices
+/// of the functions called that are above or equal to `fuel_fn`.
+///
+/// A straight sequence ends with an instruction that can alter the flow of the execution. The
+/// execution always enters a sequence at its start, so that each executed instruction is counted
+/// exactly once.
+fn meter_instructions(
+    instructions: Vec<Instruction>,
+    fuel_fn: u32,
+    fuel_global: u32,
+) -> Vec<Instruction> {
+    let mut out = Vec::with_capacity(instructions.len());
+    let mut sequence = Vec::new();
+
+    for instruction in instructions {
+        let instruction = match instruction {
+            Instruction::Call(index) if index >= fuel_fn => Instruction::Call(index + 1),
+            other => other,
+        };
+
+        let ends_sequence = match instruction {
+            Instruction::Block(_)
+            | Instruction::Loop(_)
+            | Instruction::If(_)
+            | Instruction::Else
+            | Instruction::End
+            | Instruction::Br(_)
+            | Instruction::BrIf(_)
+            | Instruction::BrTable(_)
+            | Instruction::Return => true,
+            _ => false,
+        };
+
+        sequence.push(instruction);
+
+        if ends_sequence {
+            let len = sequence.len();
+            // The inserted block is closed before the instructions of the sequence, and thus
+            // doesn't change the depth of their branches.
+            out.extend_from_slice(&[
+                Instruction::GetGlobal(fuel_global),
+                Instruction::I64Const(len as i64),
+                Instruction::I64LtU,
+                Instruction::If(elements::BlockType::NoResult),
+                Instruction::I32Const(len as i32),
+                Instruction::Call(fuel_fn),
+                Instruction::End,
+                Instruction::GetGlobal(fuel_global),
+                Instruction::I64Const(len as i64),
+                Instruction::I64Sub,
+                Instruction::SetGlobal(fuel_global),
+            ]);
+            out.append(&mut sequence);
+        }
+    }
+
+    // The body of a function always finishes with `End`.
+    debug_assert!(sequence.is_empty());
+    out
 }
 
 impl From<[u8; 32]> for ModuleHash {
@@ -89,19 +481,127 @@ impl fmt::Debug for ModuleHash {
 
 impl fmt::Display for FromBytesError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "FromBytesError")
+        match self {
+            FromBytesError::Decode(err) => write!(f, "Failed to decode module: {}", err),
+            FromBytesError::Invalid(err) => write!(f, "Invalid module: {}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for FromFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FromFileError::Io(err) => write!(f, "Failed to read file: {}", err),
+            FromFileError::Parse(err) => fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+#[cfg(test)]
+impl From<wat::Error> for FromWatError {
+    fn from(error: wat::Error) -> Self {
+        // The parser only reports the position of the problem as part of its message, on a line
+        // of the form `--> <anon>:3:9`.
+        let message = alloc::format!("{}", error);
+        let position = message
+            .lines()
+            .map(|line| line.trim_start())
+            .find(|line| line.starts_with("--> "))
+            .and_then(|location| {
+                let mut parts = location.rsplitn(3, ':');
+                let column = parts.next()?.parse().ok()?;
+                let line = parts.next()?.parse().ok()?;
+                Some((line, column))
+            });
+
+        FromWatError::Parse {
+            line: position.map(|(line, _)| line),
+            column: position.map(|(_, column)| column),
+            error,
+        }
+    }
+}
+
+#[cfg(test)]
+impl fmt::Display for FromWatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FromWatError::Parse { error, .. } => fmt::Display::fmt(error, f),
+            FromWatError::Module(err) => fmt::Display::fmt(err, f),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Module;
+    use super::{
+        ExportInfo, ExternKind, FromBytesError, FromWatError, ImportInfo, Module, FUEL_GLOBAL,
+    };
+    use crate::sig;
+    use alloc::{vec, vec::Vec};
 
     #[test]
     fn empty_wat_works() {
         let _ = Module::from_wat("(module)").unwrap();
     }
 
+    #[test]
+    fn invalid_wat_reports_position() {
+        match Module::from_wat("(module\n    (func $f (result i32)\n        i32.cosnt 5))") {
+            Err(FromWatError::Parse {
+                line: Some(3),
+                column: Some(_),
+                ..
+            }) => {}
+            Err(err) => panic!("{:?}", err),
+            Ok(_) => panic!(),
+        }
+    }
+
+    #[test]
+    fn invalid_wat_module() {
+        // Well-formed, but the function doesn't return the `i32` it declares.
+        match Module::from_wat("(module (func (result i32)))") {
+            Err(FromWatError::Module(FromBytesError::Invalid(_))) => {}
+            Err(err) => panic!("{:?}", err),
+            Ok(_) => panic!(),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn from_file_missing() {
+        let path = std::env::temp_dir().join("redshirt-core-test-missing.wasm");
+        match Module::from_file(&path) {
+            Err(super::FromFileError::Io(_)) => {}
+            Err(err) => panic!("{:?}", err),
+            Ok(_) => panic!(),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn from_file_valid() {
+        let wasm = wat::parse_str(r#"(module (func (export "_start")))"#).unwrap();
+        let path = std::env::temp_dir().join(alloc::format!(
+            "redshirt-core-test-{}.wasm",
+            std::process::id()
+        ));
+        std::fs::write(&path, &wasm).unwrap();
+        let module = Module::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let module = module.unwrap();
+        assert_eq!(module.hash(), Module::from_bytes(&wasm).unwrap().hash());
+        assert!(module.exports().any(|export| export.name == "_start"));
+    }
+
+    #[test]
+    fn invalid_bytes_error() {
+        assert!(Module::from_bytes(&[0x00, 0x61, 0x73]).is_err());
+    }
+
     #[test]
     fn simple_wat_works() {
         let _ = Module::from_wat(
@@ -116,4 +616,77 @@ mod tests {
         )
         .unwrap();
     }
+
+    #[test]
+    fn exports_and_imports_listed() {
+        let module = Module::from_wat(
+            r#"
+            (module
+                (import "foo" "bar" (func $bar (param i64 f32)))
+                (func $add (param i32 i32) (result i32)
+                    get_local 0
+                    get_local 1
+                    i32.add)
+                (func $noop)
+                (export "add" (func $add))
+                (export "noop" (func $noop)))
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            module.imports().collect::<Vec<_>>(),
+            vec![ImportInfo {
+                module: "foo",
+                field: "bar",
+                kind: ExternKind::Function(sig!((I64, F32))),
+            }]
+        );
+
+        assert_eq!(
+            module.exports().collect::<Vec<_>>(),
+            vec![
+                ExportInfo {
+                    name: "add",
+                    kind: ExternKind::Function(sig!((I32, I32) -> I32)),
+                },
+                ExportInfo {
+                    name: "noop",
+                    kind: ExternKind::Function(sig!(())),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fuel_metering_produces_valid_module() {
+        let module = Module::from_wat(
+            r#"
+            (module
+                (func $loop (export "loop") (param i32)
+                    block
+                        loop
+                            get_local 0
+                            i32.eqz
+                            br_if 1
+                            get_local 0
+                            i32.const 1
+                            i32.sub
+                            set_local 0
+                            br 0
+                        end
+                    end))
+            "#,
+        )
+        .unwrap()
+        .with_fuel_metering("foo", "fuel");
+
+        assert!(wasmi::Module::from_parity_wasm_module(module.source().clone()).is_ok());
+        assert!(module
+            .imports()
+            .any(|import| import.module == "foo" && import.field == "fuel"));
+        assert!(module
+            .exports()
+            .any(|export| export.name == FUEL_GLOBAL && export.kind == ExternKind::Global));
+    }
 }
@@ -16,9 +16,12 @@
 pub use self::collection::{
     NativeProgramsCollection, NativeProgramsCollectionEvent, NativeProgramsCollectionMessageIdWrite,
 };
+pub use self::group::NativeProgramsCollectionGroup;
 pub use self::traits::{
     DummyMessageIdWrite, NativeProgramEvent, NativeProgramMessageIdWrite, NativeProgramRef,
 };
 
 mod collection;
+mod group;
+pub(crate) mod test_program;
 mod traits;
@@ -15,18 +15,71 @@
 
 use crate::native::traits::{NativeProgramEvent, NativeProgramMessageIdWrite, NativeProgramRef};
 
-use alloc::{boxed::Box, vec::Vec};
-use core::{mem, task::Context, task::Poll};
-use futures::prelude::*;
-use hashbrown::HashSet;
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc, vec::Vec};
+use core::{
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::Context,
+    task::Poll,
+};
+use futures::{
+    prelude::*,
+    task::{ArcWake, AtomicWaker},
+};
+use hashbrown::{hash_map::Entry, HashMap};
 use redshirt_interface_interface::ffi::InterfaceMessage;
-use redshirt_syscalls_interface::{Decode as _, EncodedMessage, InterfaceHash, MessageId, Pid};
+use redshirt_syscalls_interface::{
+    AnswerError, Decode as _, EncodedMessage, InterfaceHash, MessageId, Pid,
+};
 use spin::Mutex;
 
 /// Collection of objects that implement the [`NativeProgram`] trait.
+///
+/// A collection is itself a [`NativeProgram`], which makes it possible to push a collection
+/// within another collection. The programs of the inner collection are then seen by the outer
+/// collection as a single program. [`MessageId`]s are never generated by collections but always
+/// come from the outside, so inner and outer collections share the same message ids.
 pub struct NativeProgramsCollection<'ext> {
-    /// Collection of processes and their `Pid`.
-    processes: Vec<(Pid, Box<dyn AdapterAbstract + Send + 'ext>)>,
+    /// Collection of processes, indexed by their `Pid`.
+    processes: HashMap<Pid, Process<'ext>>,
+    /// For each interface registered by one of the processes, the `Pid` of this process.
+    interfaces: Mutex<HashMap<InterfaceHash, Pid>>,
+    /// For each message emitted by one of the processes and that expects a response, the `Pid`
+    /// of the emitter.
+    expected_responses: Mutex<HashMap<MessageId, Pid>>,
+    /// Registrations of interfaces that were already registered by another process, and that
+    /// haven't been reported by `next_event` yet. Contains the interface, the `Pid` of the process
+    /// that has tried to register it, and the `Pid` of its actual handler.
+    rejected_registrations: Mutex<VecDeque<(InterfaceHash, Pid, Pid)>>,
+    /// Processes that must be polled by `next_event`.
+    ready: Arc<ReadyQueue>,
+}
+
+/// Process within a [`NativeProgramsCollection`].
+struct Process<'ext> {
+    /// The program itself.
+    adapter: Box<dyn AdapterAbstract + Send + Sync + 'ext>,
+    /// Waker passed to the program when it is polled.
+    waker: Arc<ProcessWaker>,
+}
+
+/// Queue of processes that must be polled. Shared between the collection and the wakers passed
+/// to the programs.
+struct ReadyQueue {
+    /// `Pid`s of the processes to poll, in order.
+    pids: Mutex<VecDeque<Pid>>,
+    /// Waken up when a `Pid` is pushed to `pids`.
+    waker: AtomicWaker,
+}
+
+/// Waker passed to a program. Pushes the program to the [`ReadyQueue`] when woken up.
+struct ProcessWaker {
+    /// `Pid` of the process.
+    pid: Pid,
+    /// True if `pid` is in the [`ReadyQueue`].
+    queued: AtomicBool,
+    /// Queue to push the process to.
+    ready: Arc<ReadyQueue>,
 }
 
 /// Event generated by a [`NativeProgram`].
@@ -44,7 +97,13 @@ pub enum NativeProgramsCollectionEvent<'col> {
         message_id_write: Option<NativeProgramsCollectionMessageIdWrite<'col>>,
     },
     /// Request to cancel a previously-emitted message.
+    ///
+    /// Any response to this message is no longer delivered to the program, and
+    /// [`message_response`](NativeProgramsCollection::message_response) gives it back instead.
     CancelMessage {
+        /// Pid of the program that cancels the message. Same as a value that was passed to
+        /// [`push`](NativeProgramsCollection::push).
+        emitter_pid: Pid,
         /// Message to cancel.
         message_id: MessageId,
     },
@@ -54,7 +113,21 @@ pub enum NativeProgramsCollectionEvent<'col> {
         /// Message to answer.
         message_id: MessageId,
         /// The produced answer, or an `Err` if the message is invalid.
-        answer: Result<EncodedMessage, ()>,
+        answer: Result<EncodedMessage, AnswerError>,
+    },
+    /// A program has tried to register an interface that another program of the collection has
+    /// already registered. The registration has been ignored, and messages on this interface
+    /// are still delivered to the program that has registered it first.
+    ///
+    /// The message containing the registration request has been yielded as an
+    /// [`Emit`](NativeProgramsCollectionEvent::Emit) event beforehand.
+    InterfaceRegistrationRejected {
+        /// Interface that the program has tried to register.
+        interface: InterfaceHash,
+        /// Pid of the program that has tried to register the interface.
+        emitter_pid: Pid,
+        /// Pid of the program that has registered the interface.
+        handler_pid: Pid,
     },
 }
 
@@ -67,15 +140,17 @@ pub struct NativeProgramsCollectionMessageIdWrite<'col> {
 /// Wraps around a [`NativeProgram`].
 struct Adapter<T> {
     inner: T,
-    registered_interfaces: Mutex<HashSet<InterfaceHash>>,
-    expected_responses: Mutex<HashSet<MessageId>>,
 }
 
 /// Abstracts over [`Adapter`] so that we can box it.
 trait AdapterAbstract {
+    /// Polls the next event of the program. `pid` is the `Pid` of the program, and
+    /// `expected_responses` the list of messages that the collection expects a response for.
     fn poll_next_event<'col>(
         &'col self,
         cx: &mut Context,
+        pid: Pid,
+        expected_responses: &'col Mutex<HashMap<MessageId, Pid>>,
     ) -> Poll<NativeProgramEvent<Box<dyn AbstractMessageIdWrite + 'col>>>;
     fn deliver_interface_message(
         &self,
@@ -83,12 +158,12 @@ trait AdapterAbstract {
         message_id: Option<MessageId>,
         emitter_pid: Pid,
         message: EncodedMessage,
-    ) -> Result<(), EncodedMessage>;
+    );
     fn deliver_response(
         &self,
         message_id: MessageId,
-        response: Result<EncodedMessage, ()>,
-    ) -> Result<(), Result<EncodedMessage, ()>>;
+        response: Result<EncodedMessage, AnswerError>,
+    );
     fn process_destroyed(&self, pid: Pid);
 }
 
@@ -98,7 +173,8 @@ trait AbstractMessageIdWrite {
 
 struct MessageIdWriteAdapter<'col, T> {
     inner: Option<T>,
-    expected_responses: &'col Mutex<HashSet<MessageId>>,
+    emitter_pid: Pid,
+    expected_responses: &'col Mutex<HashMap<MessageId, Pid>>,
 }
 
 impl<'ext> NativeProgramsCollection<'ext> {
@@ -107,7 +183,14 @@ impl<'ext> NativeProgramsCollection<'ext> {
     /// Calling [`next_event`](NativeProgramsCollection::next_event) will never yield anything.
     pub fn new() -> Self {
         NativeProgramsCollection {
-            processes: Vec::new(),
+            processes: HashMap::new(),
+            interfaces: Mutex::new(HashMap::new()),
+            expected_responses: Mutex::new(HashMap::new()),
+            rejected_registrations: Mutex::new(VecDeque::new()),
+            ready: Arc::new(ReadyQueue {
+                pids: Mutex::new(VecDeque::new()),
+                waker: AtomicWaker::new(),
+            }),
         }
     }
 
@@ -119,52 +202,176 @@ impl<'ext> NativeProgramsCollection<'ext> {
     ///
     pub fn push<T>(&mut self, pid: Pid, program: T)
     where
-        T: Send + 'ext,
+        T: Send + Sync + 'ext,
         for<'r> &'r T: NativeProgramRef<'r>,
     {
-        let adapter = Box::new(Adapter {
-            inner: program,
-            registered_interfaces: Mutex::new(HashSet::new()),
-            expected_responses: Mutex::new(HashSet::new()),
+        let adapter = Box::new(Adapter { inner: program });
+        let waker = Arc::new(ProcessWaker {
+            pid,
+            queued: AtomicBool::new(false),
+            ready: self.ready.clone(),
         });
 
-        assert!(!self
-            .processes
-            .iter()
-            .any(|(existing_pid, _)| *existing_pid == pid));
-        self.processes.push((pid, adapter));
+        match self.processes.entry(pid) {
+            Entry::Occupied(_) => panic!(),
+            Entry::Vacant(e) => e.insert(Process {
+                adapter,
+                waker: waker.clone(),
+            }),
+        };
+
+        // The program has never been polled, and might have something to do.
+        ArcWake::wake_by_ref(&waker);
 
         // We assume that `push` is only ever called at initialization.
         self.processes.shrink_to_fit();
     }
 
+    /// Removes a program from the collection. Returns `false` if `pid` wasn't in the collection.
+    ///
+    /// The interfaces registered by this program and the responses it was waiting for are
+    /// forgotten.
+    pub fn remove(&mut self, pid: Pid) -> bool {
+        if self.processes.remove(&pid).is_none() {
+            return false;
+        }
+
+        self.interfaces.get_mut().retain(|_, p| *p != pid);
+        self.expected_responses.get_mut().retain(|_, p| *p != pid);
+        self.rejected_registrations
+            .get_mut()
+            .retain(|(_, emitter, _)| *emitter != pid);
+        true
+    }
+
+    /// Returns the [`Pid`]s of the programs of this collection.
+    pub fn pids(&self) -> impl ExactSizeIterator<Item = Pid> + '_ {
+        self.processes.keys().cloned()
+    }
+
+    /// Returns the interfaces currently registered by the programs of this collection, alongside
+    /// with the [`Pid`] of the program that has registered them.
+    ///
+    /// The returned iterator is a snapshot, and isn't updated when programs register or
+    /// unregister interfaces afterwards.
+    pub fn registered_interfaces(&self) -> impl ExactSizeIterator<Item = (Pid, InterfaceHash)> {
+        self.interfaces
+            .lock()
+            .iter()
+            .map(|(interface, pid)| (*pid, interface.clone()))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
     /// Returns a `Future` that yields the next event generated by one of the programs.
+    ///
+    /// Only the programs that have woken up the waker they were passed, or that have been
+    /// notified of something since they were last polled, are polled again.
     pub fn next_event<'collec>(
         &'collec self,
     ) -> impl Future<Output = NativeProgramsCollectionEvent<'collec>> + 'collec {
         future::poll_fn(move |cx| {
-            for (pid, process) in self.processes.iter() {
-                match process.poll_next_event(cx) {
-                    Poll::Pending => {}
-                    Poll::Ready(NativeProgramEvent::Emit {
+            if let Some((interface, emitter_pid, handler_pid)) =
+                self.rejected_registrations.lock().pop_front()
+            {
+                return Poll::Ready(
+                    NativeProgramsCollectionEvent::InterfaceRegistrationRejected {
+                        interface,
+                        emitter_pid,
+                        handler_pid,
+                    },
+                );
+            }
+
+            self.ready.waker.register(cx.waker());
+
+            loop {
+                let pid = match self.ready.pids.lock().pop_front() {
+                    Some(pid) => pid,
+                    None => return Poll::Pending,
+                };
+
+                // The queue can contain processes that have been removed.
+                let process = match self.processes.get(&pid) {
+                    Some(p) => p,
+                    None => continue,
+                };
+
+                // The flag is reset before polling, so that the program can wake itself up while
+                // being polled.
+                process.waker.queued.store(false, Ordering::SeqCst);
+                let waker = futures::task::waker_ref(&process.waker);
+                let event = match process.adapter.poll_next_event(
+                    &mut Context::from_waker(&waker),
+                    pid,
+                    &self.expected_responses,
+                ) {
+                    Poll::Pending => continue,
+                    Poll::Ready(event) => event,
+                };
+
+                // The program might have other events ready, and must be polled again. Pushing
+                // it at the back of the queue guarantees that a program that is always ready
+                // can't starve the others.
+                process.waker.enqueue();
+
+                match event {
+                    NativeProgramEvent::Emit {
                         interface,
                         message_id_write,
                         message,
-                    }) => {
+                    } => {
+                        if interface == redshirt_interface_interface::ffi::INTERFACE {
+                            // TODO: check whether registration succeeds, but hard if `message_id_write` is `None
+                            match InterfaceMessage::decode(message.clone()) {
+                                Ok(InterfaceMessage::Register(to_reg)) => {
+                                    match self.interfaces.lock().entry(to_reg.clone()) {
+                                        Entry::Vacant(e) => {
+                                            e.insert(pid);
+                                        }
+                                        Entry::Occupied(e) if *e.get() != pid => {
+                                            self.rejected_registrations.lock().push_back((
+                                                to_reg,
+                                                pid,
+                                                *e.get(),
+                                            ));
+                                        }
+                                        Entry::Occupied(_) => {}
+                                    }
+                                }
+                                Ok(InterfaceMessage::Unregister(to_unreg)) => {
+                                    let mut interfaces = self.interfaces.lock();
+                                    if interfaces.get(&to_unreg) == Some(&pid) {
+                                        interfaces.remove(&to_unreg);
+                                    }
+                                }
+                                _ => {}
+                            }
+                        }
+
                         return Poll::Ready(NativeProgramsCollectionEvent::Emit {
-                            emitter_pid: *pid,
+                            emitter_pid: pid,
                             interface,
                             message,
                             message_id_write: message_id_write
                                 .map(|w| NativeProgramsCollectionMessageIdWrite { write: w }),
-                        })
+                        });
                     }
-                    Poll::Ready(NativeProgramEvent::CancelMessage { message_id }) => {
+                    NativeProgramEvent::CancelMessage { message_id } => {
+                        // The program no longer wants the response, so we stop expecting it.
+                        // Programs can only cancel messages that they have emitted themselves.
+                        let mut expected_responses = self.expected_responses.lock();
+                        if expected_responses.get(&message_id) == Some(&pid) {
+                            expected_responses.remove(&message_id);
+                        }
+                        drop(expected_responses);
+
                         return Poll::Ready(NativeProgramsCollectionEvent::CancelMessage {
+                            emitter_pid: pid,
                             message_id,
-                        })
+                        });
                     }
-                    Poll::Ready(NativeProgramEvent::Answer { message_id, answer }) => {
+                    NativeProgramEvent::Answer { message_id, answer } => {
                         return Poll::Ready(NativeProgramsCollectionEvent::Answer {
                             message_id,
                             answer,
@@ -172,55 +379,157 @@ impl<'ext> NativeProgramsCollection<'ext> {
                     }
                 }
             }
-
-            Poll::Pending
         })
     }
 
     /// Notify the [`NativeProgram`] that a message has arrived on one of the interface that it
     /// has registered.
+    ///
+    /// Gives back the message if no program in this collection has registered the interface.
     pub fn interface_message(
         &self,
         interface: InterfaceHash,
         message_id: Option<MessageId>,
         emitter_pid: Pid,
-        mut message: EncodedMessage,
-    ) {
-        for (_, process) in &self.processes {
-            let msg = mem::replace(&mut message, EncodedMessage(Vec::new()));
-            match process.deliver_interface_message(interface.clone(), message_id, emitter_pid, msg)
-            {
-                Ok(_) => return,
-                Err(msg) => message = msg,
+        message: EncodedMessage,
+    ) -> Result<(), EncodedMessage> {
+        let handler_pid = match self.interfaces.lock().get(&interface) {
+            Some(pid) => *pid,
+            None => return Err(message),
+        };
+
+        match self.processes.get(&handler_pid) {
+            Some(process) => {
+                process.adapter.deliver_interface_message(
+                    interface,
+                    message_id,
+                    emitter_pid,
+                    message,
+                );
+                ArcWake::wake_by_ref(&process.waker);
+                Ok(())
             }
+            None => Err(message),
         }
-
-        panic!() // TODO: what to do here?
     }
 
     /// Notify the [`NativeProgram`]s that the program with the given [`Pid`] has terminated.
-    pub fn process_destroyed(&mut self, pid: Pid) {
-        for (_, process) in &self.processes {
-            process.process_destroyed(pid);
+    pub fn process_destroyed(&self, pid: Pid) {
+        for process in self.processes.values() {
+            process.adapter.process_destroyed(pid);
+            ArcWake::wake_by_ref(&process.waker);
         }
     }
 
     /// Notify the appropriate [`NativeProgram`] of a response to a message that it has previously
     /// emitted.
+    ///
+    /// Gives back the response if no program in this collection is waiting for it.
     pub fn message_response(
         &self,
         message_id: MessageId,
-        mut response: Result<EncodedMessage, ()>,
-    ) {
-        for (_, process) in &self.processes {
-            let msg = mem::replace(&mut response, Ok(EncodedMessage(Vec::new())));
-            match process.deliver_response(message_id, msg) {
-                Ok(_) => return,
-                Err(msg) => response = msg,
+        response: Result<EncodedMessage, AnswerError>,
+    ) -> Result<(), Result<EncodedMessage, AnswerError>> {
+        let emitter_pid = match self.expected_responses.lock().remove(&message_id) {
+            Some(pid) => pid,
+            None => return Err(response),
+        };
+
+        match self.processes.get(&emitter_pid) {
+            Some(process) => {
+                process.adapter.deliver_response(message_id, response);
+                ArcWake::wake_by_ref(&process.waker);
+                Ok(())
             }
+            None => Err(response),
         }
+    }
+}
+
+impl ProcessWaker {
+    /// Pushes the process to the [`ReadyQueue`], unless it is already in it.
+    fn enqueue(&self) {
+        if !self.queued.swap(true, Ordering::SeqCst) {
+            self.ready.pids.lock().push_back(self.pid);
+        }
+    }
+}
+
+impl ArcWake for ProcessWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.enqueue();
+        arc_self.ready.waker.wake();
+    }
+}
+
+impl<'a, 'ext> NativeProgramRef<'a> for &'a NativeProgramsCollection<'ext> {
+    type Future =
+        Pin<Box<dyn Future<Output = NativeProgramEvent<Self::MessageIdWrite>> + Send + 'a>>;
+    type MessageIdWrite = NativeProgramsCollectionMessageIdWrite<'a>;
+
+    fn next_event(self) -> Self::Future {
+        Box::pin(future::poll_fn(move |cx| loop {
+            let next_event = NativeProgramsCollection::next_event(self);
+            futures::pin_mut!(next_event);
+            let event = match next_event.poll(cx) {
+                Poll::Ready(event) => event,
+                Poll::Pending => return Poll::Pending,
+            };
+
+            return Poll::Ready(match event {
+                NativeProgramsCollectionEvent::Emit {
+                    interface,
+                    message,
+                    message_id_write,
+                    ..
+                } => NativeProgramEvent::Emit {
+                    interface,
+                    message,
+                    message_id_write,
+                },
+                NativeProgramsCollectionEvent::CancelMessage { message_id, .. } => {
+                    NativeProgramEvent::CancelMessage { message_id }
+                }
+                NativeProgramsCollectionEvent::Answer { message_id, answer } => {
+                    NativeProgramEvent::Answer { message_id, answer }
+                }
+                // The registration has already been ignored by the collection, and there is
+                // nobody to report it to.
+                NativeProgramsCollectionEvent::InterfaceRegistrationRejected { .. } => continue,
+            });
+        }))
+    }
 
-        panic!() // TODO: what to do here?
+    fn interface_message(
+        self,
+        interface: InterfaceHash,
+        message_id: Option<MessageId>,
+        emitter_pid: Pid,
+        message: EncodedMessage,
+    ) {
+        // Interface messages are only delivered to us for interfaces that one of our programs
+        // has registered, and the message is therefore never given back in practice.
+        let _ = NativeProgramsCollection::interface_message(
+            self,
+            interface,
+            message_id,
+            emitter_pid,
+            message,
+        );
+    }
+
+    fn process_destroyed(self, pid: Pid) {
+        NativeProgramsCollection::process_destroyed(self, pid);
+    }
+
+    fn message_response(
+        self,
+        message_id: MessageId,
+        response: Result<EncodedMessage, AnswerError>,
+    ) {
+        // Same as above, responses are only delivered to us for messages that one of our
+        // programs has emitted.
+        let _ = NativeProgramsCollection::message_response(self, message_id, response);
     }
 }
 
@@ -231,6 +540,8 @@ where
     fn poll_next_event<'col>(
         &'col self,
         cx: &mut Context,
+        pid: Pid,
+        expected_responses: &'col Mutex<HashMap<MessageId, Pid>>,
     ) -> Poll<NativeProgramEvent<Box<dyn AbstractMessageIdWrite + 'col>>> {
         let future = (&self.inner).next_event();
         futures::pin_mut!(future);
@@ -240,19 +551,11 @@ where
                 message_id_write,
                 message,
             }) => {
-                if interface == redshirt_interface_interface::ffi::INTERFACE {
-                    // TODO: check whether registration succeeds, but hard if `message_id_write` is `None
-                    if let Ok(msg) = InterfaceMessage::decode(message.clone()) {
-                        let InterfaceMessage::Register(to_reg) = msg;
-                        let mut registered_interfaces = self.registered_interfaces.lock();
-                        registered_interfaces.insert(to_reg);
-                    }
-                }
-
                 let message_id_write = message_id_write.map(|inner| {
                     Box::new(MessageIdWriteAdapter {
                         inner: Some(inner),
-                        expected_responses: &self.expected_responses,
+                        emitter_pid: pid,
+                        expected_responses,
                     }) as Box<_>
                 });
 
@@ -278,29 +581,17 @@ where
         message_id: Option<MessageId>,
         emitter_pid: Pid,
         message: EncodedMessage,
-    ) -> Result<(), EncodedMessage> {
-        let registered_interfaces = self.registered_interfaces.lock();
-        if registered_interfaces.contains(&interface) {
-            self.inner
-                .interface_message(interface, message_id, emitter_pid, message);
-            Ok(())
-        } else {
-            Err(message)
-        }
+    ) {
+        self.inner
+            .interface_message(interface, message_id, emitter_pid, message);
     }
 
     fn deliver_response(
         &self,
         message_id: MessageId,
-        response: Result<EncodedMessage, ()>,
-    ) -> Result<(), Result<EncodedMessage, ()>> {
-        let mut expected_responses = self.expected_responses.lock();
-        if expected_responses.remove(&message_id) {
-            self.inner.message_response(message_id, response);
-            Ok(())
-        } else {
-            Err(response)
-        }
+        response: Result<EncodedMessage, AnswerError>,
+    ) {
+        self.inner.message_response(message_id, response);
     }
 
     fn process_destroyed(&self, pid: Pid) {
@@ -317,8 +608,8 @@ where
             Some(inner) => inner.acknowledge(id),
             None => unreachable!(),
         };
-        let _was_inserted = self.expected_responses.lock().insert(id);
-        debug_assert!(_was_inserted);
+        let _prev_val = self.expected_responses.lock().insert(id, self.emitter_pid);
+        debug_assert!(_prev_val.is_none());
     }
 }
 
@@ -328,15 +619,342 @@ impl<'col> NativeProgramMessageIdWrite for NativeProgramsCollectionMessageIdWrit
     }
 }
 
-// TODO: impl<'col> NativeProgram<'col> for NativeProgramsCollection<'col>
-
 #[cfg(test)]
 mod tests {
-    use super::NativeProgramsCollection;
+    use super::{NativeProgramsCollection, NativeProgramsCollectionEvent};
+    use crate::native::{
+        test_program::TestProgram, NativeProgramEvent, NativeProgramMessageIdWrite as _,
+    };
+    use crate::{EncodedMessage, InterfaceHash, MessageId, Pid};
+    use alloc::{vec, vec::Vec};
+    use futures::prelude::*;
+    use redshirt_interface_interface::ffi::InterfaceMessage;
+    use redshirt_syscalls_interface::Encode as _;
 
     #[test]
     fn is_send() {
         fn req_send<T: Send>() {}
         req_send::<NativeProgramsCollection>();
     }
+
+    #[test]
+    fn undeliverable_message_given_back() {
+        let collection = NativeProgramsCollection::new();
+
+        let result = collection.interface_message(
+            InterfaceHash::from([1; 32]),
+            None,
+            Pid::from(1),
+            EncodedMessage(vec![1, 2, 3]),
+        );
+        assert_eq!(result, Err(EncodedMessage(vec![1, 2, 3])));
+
+        let result =
+            collection.message_response(MessageId::from(5), Ok(EncodedMessage(vec![4, 5])));
+        assert_eq!(result, Err(Ok(EncodedMessage(vec![4, 5]))));
+    }
+
+    #[test]
+    fn response_delivered_to_emitter_amongst_many() {
+        let programs = (0..1000).map(|_| TestProgram::new()).collect::<Vec<_>>();
+        let mut collection = NativeProgramsCollection::new();
+        for (n, program) in programs.iter().enumerate() {
+            collection.push(Pid::from(n as u64), program.clone());
+        }
+
+        programs[500].emit(InterfaceHash::from([1; 32]), EncodedMessage(vec![1]), true);
+        match collection.next_event().now_or_never() {
+            Some(NativeProgramsCollectionEvent::Emit {
+                emitter_pid,
+                message_id_write: Some(message_id_write),
+                ..
+            }) => {
+                assert_eq!(emitter_pid, Pid::from(500));
+                message_id_write.acknowledge(MessageId::from(42));
+            }
+            _ => panic!(),
+        }
+
+        collection
+            .message_response(MessageId::from(42), Ok(EncodedMessage(vec![2])))
+            .unwrap();
+        assert_eq!(
+            programs[500].responses(),
+            vec![(MessageId::from(42), Ok(EncodedMessage(vec![2])))]
+        );
+        assert!(programs
+            .iter()
+            .enumerate()
+            .all(|(n, p)| n == 500 || p.responses().is_empty()));
+    }
+
+    #[test]
+    fn cancelled_message_response_not_delivered() {
+        let program = TestProgram::new();
+        let mut collection = NativeProgramsCollection::new();
+        collection.push(Pid::from(1), program.clone());
+
+        program.emit(InterfaceHash::from([1; 32]), EncodedMessage(vec![1]), true);
+        match collection.next_event().now_or_never() {
+            Some(NativeProgramsCollectionEvent::Emit {
+                message_id_write: Some(message_id_write),
+                ..
+            }) => message_id_write.acknowledge(MessageId::from(42)),
+            _ => panic!(),
+        }
+
+        program.push_event(NativeProgramEvent::CancelMessage {
+            message_id: MessageId::from(42),
+        });
+        match collection.next_event().now_or_never() {
+            Some(NativeProgramsCollectionEvent::CancelMessage {
+                emitter_pid,
+                message_id,
+            }) => {
+                assert_eq!(emitter_pid, Pid::from(1));
+                assert_eq!(message_id, MessageId::from(42));
+            }
+            _ => panic!(),
+        }
+
+        let result = collection.message_response(MessageId::from(42), Ok(EncodedMessage(vec![2])));
+        assert_eq!(result, Err(Ok(EncodedMessage(vec![2]))));
+        assert!(program.responses().is_empty());
+    }
+
+    #[test]
+    fn nested_collection() {
+        let program = TestProgram::new();
+        let mut inner = NativeProgramsCollection::new();
+        inner.push(Pid::from(1), program.clone());
+        let mut outer = NativeProgramsCollection::new();
+        outer.push(Pid::from(2), inner);
+
+        program.register(InterfaceHash::from([1; 32]));
+        match outer.next_event().now_or_never() {
+            Some(NativeProgramsCollectionEvent::Emit { emitter_pid, .. }) => {
+                assert_eq!(emitter_pid, Pid::from(2))
+            }
+            _ => panic!(),
+        }
+
+        program.emit(InterfaceHash::from([2; 32]), EncodedMessage(vec![1]), true);
+        match outer.next_event().now_or_never() {
+            Some(NativeProgramsCollectionEvent::Emit {
+                interface,
+                emitter_pid,
+                message,
+                message_id_write: Some(message_id_write),
+            }) => {
+                assert_eq!(interface, InterfaceHash::from([2; 32]));
+                assert_eq!(emitter_pid, Pid::from(2));
+                assert_eq!(message, EncodedMessage(vec![1]));
+                message_id_write.acknowledge(MessageId::from(42));
+            }
+            _ => panic!(),
+        }
+
+        outer
+            .message_response(MessageId::from(42), Ok(EncodedMessage(vec![2])))
+            .unwrap();
+        assert_eq!(
+            program.responses(),
+            vec![(MessageId::from(42), Ok(EncodedMessage(vec![2])))]
+        );
+
+        outer
+            .interface_message(
+                InterfaceHash::from([1; 32]),
+                None,
+                Pid::from(3),
+                EncodedMessage(vec![3]),
+            )
+            .unwrap();
+        assert_eq!(
+            program.interface_messages(),
+            vec![(
+                InterfaceHash::from([1; 32]),
+                None,
+                Pid::from(3),
+                EncodedMessage(vec![3])
+            )]
+        );
+    }
+
+    #[test]
+    fn only_woken_up_programs_polled() {
+        let idle = (0..100).map(|_| TestProgram::new()).collect::<Vec<_>>();
+        let active = TestProgram::new();
+        let mut collection = NativeProgramsCollection::new();
+        for (n, program) in idle.iter().enumerate() {
+            collection.push(Pid::from(n as u64), program.clone());
+        }
+        collection.push(Pid::from(100), active.clone());
+
+        // All the programs are polled once after being pushed.
+        assert!(collection.next_event().now_or_never().is_none());
+        assert!(idle.iter().all(|p| p.polls() == 1));
+
+        for _ in 0..3 {
+            active.emit(InterfaceHash::from([1; 32]), EncodedMessage(vec![1]), false);
+            match collection.next_event().now_or_never() {
+                Some(NativeProgramsCollectionEvent::Emit { emitter_pid, .. }) => {
+                    assert_eq!(emitter_pid, Pid::from(100))
+                }
+                _ => panic!(),
+            }
+        }
+        assert!(collection.next_event().now_or_never().is_none());
+
+        assert!(idle.iter().all(|p| p.polls() == 1));
+        assert!(active.polls() > 1);
+    }
+
+    #[test]
+    fn next_event_round_robin() {
+        let program1 = TestProgram::new();
+        let program2 = TestProgram::new();
+        let mut collection = NativeProgramsCollection::new();
+        collection.push(Pid::from(1), program1.clone());
+        collection.push(Pid::from(2), program2.clone());
+
+        for _ in 0..5 {
+            program1.emit(InterfaceHash::from([1; 32]), EncodedMessage(vec![1]), false);
+            program2.emit(InterfaceHash::from([1; 32]), EncodedMessage(vec![2]), false);
+        }
+
+        let mut emitters = Vec::new();
+        for _ in 0..10 {
+            match collection.next_event().now_or_never() {
+                Some(NativeProgramsCollectionEvent::Emit { emitter_pid, .. }) => {
+                    emitters.push(emitter_pid)
+                }
+                _ => panic!(),
+            }
+        }
+
+        assert!(emitters.windows(2).all(|w| w[0] != w[1]));
+        assert!(collection.next_event().now_or_never().is_none());
+    }
+
+    #[test]
+    fn removed_program_not_polled() {
+        let program = TestProgram::new();
+        let mut collection = NativeProgramsCollection::new();
+        collection.push(Pid::from(1), program.clone());
+        program.register(InterfaceHash::from([1; 32]));
+        assert!(collection.next_event().now_or_never().is_some());
+
+        program.emit(InterfaceHash::from([2; 32]), EncodedMessage(vec![1]), false);
+        assert!(collection.remove(Pid::from(1)));
+        assert!(!collection.remove(Pid::from(1)));
+        assert!(collection.next_event().now_or_never().is_none());
+
+        let result = collection.interface_message(
+            InterfaceHash::from([1; 32]),
+            None,
+            Pid::from(2),
+            EncodedMessage(vec![3]),
+        );
+        assert_eq!(result, Err(EncodedMessage(vec![3])));
+    }
+
+    #[test]
+    fn registered_interfaces_listed() {
+        let program1 = TestProgram::new();
+        let program2 = TestProgram::new();
+        let mut collection = NativeProgramsCollection::new();
+        collection.push(Pid::from(1), program1.clone());
+        collection.push(Pid::from(2), program2.clone());
+        assert_eq!(collection.registered_interfaces().len(), 0);
+
+        program1.register(InterfaceHash::from([1; 32]));
+        program2.register(InterfaceHash::from([2; 32]));
+        assert!(collection.next_event().now_or_never().is_some());
+        assert!(collection.next_event().now_or_never().is_some());
+
+        let mut interfaces = collection.registered_interfaces().collect::<Vec<_>>();
+        interfaces.sort_by_key(|(pid, _)| u64::from(*pid));
+        assert_eq!(
+            interfaces,
+            vec![
+                (Pid::from(1), InterfaceHash::from([1; 32])),
+                (Pid::from(2), InterfaceHash::from([2; 32])),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_registration_rejected() {
+        let program1 = TestProgram::new();
+        let program2 = TestProgram::new();
+        let mut collection = NativeProgramsCollection::new();
+        collection.push(Pid::from(1), program1.clone());
+        collection.push(Pid::from(2), program2.clone());
+
+        program1.register(InterfaceHash::from([1; 32]));
+        program2.register(InterfaceHash::from([1; 32]));
+
+        let mut emitters = Vec::new();
+        for _ in 0..2 {
+            match collection.next_event().now_or_never() {
+                Some(NativeProgramsCollectionEvent::Emit { emitter_pid, .. }) => {
+                    emitters.push(emitter_pid)
+                }
+                _ => panic!(),
+            }
+        }
+
+        match collection.next_event().now_or_never() {
+            Some(NativeProgramsCollectionEvent::InterfaceRegistrationRejected {
+                interface,
+                emitter_pid,
+                handler_pid,
+            }) => {
+                assert_eq!(interface, InterfaceHash::from([1; 32]));
+                assert_eq!(emitter_pid, emitters[1]);
+                assert_eq!(handler_pid, emitters[0]);
+            }
+            _ => panic!(),
+        }
+        assert!(collection.next_event().now_or_never().is_none());
+
+        assert_eq!(
+            collection.registered_interfaces().collect::<Vec<_>>(),
+            vec![(emitters[0], InterfaceHash::from([1; 32]))]
+        );
+    }
+
+    #[test]
+    fn unregistered_interface_not_delivered() {
+        let program = TestProgram::new();
+        let mut collection = NativeProgramsCollection::new();
+        collection.push(Pid::from(1), program.clone());
+
+        program.register(InterfaceHash::from([1; 32]));
+        assert!(collection.next_event().now_or_never().is_some());
+        collection
+            .interface_message(
+                InterfaceHash::from([1; 32]),
+                None,
+                Pid::from(2),
+                EncodedMessage(vec![1]),
+            )
+            .unwrap();
+
+        program.emit(
+            redshirt_interface_interface::ffi::INTERFACE,
+            InterfaceMessage::Unregister(InterfaceHash::from([1; 32])).encode(),
+            false,
+        );
+        assert!(collection.next_event().now_or_never().is_some());
+        let result = collection.interface_message(
+            InterfaceHash::from([1; 32]),
+            None,
+            Pid::from(2),
+            EncodedMessage(vec![2]),
+        );
+        assert_eq!(result, Err(EncodedMessage(vec![2])));
+        assert_eq!(program.interface_messages().len(), 1);
+    }
 }
@@ -0,0 +1,269 @@
+// Copyright (C) 2019  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::native::collection::{NativeProgramsCollection, NativeProgramsCollectionEvent};
+
+use alloc::vec::Vec;
+use core::{
+    mem,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::Poll,
+};
+use futures::prelude::*;
+use redshirt_syscalls_interface::{AnswerError, EncodedMessage, InterfaceHash, MessageId, Pid};
+
+/// Group of multiple [`NativeProgramsCollection`]s that are presented as one.
+///
+/// Contrary to pushing all the programs in a single [`NativeProgramsCollection`], each collection
+/// added to the group is kept as a separate entity. This is useful in order to assemble a kernel
+/// from multiple bundles of native programs.
+///
+/// The `Pid`s of the programs must be unique across all the collections of the group.
+pub struct NativeProgramsCollectionGroup<'ext> {
+    /// List of collections within the group.
+    collections: Vec<NativeProgramsCollection<'ext>>,
+    /// Index within `collections` of the collection to poll first in `next_event`. Moves past
+    /// the collection that has produced the last event, so that a busy collection can't starve
+    /// the others.
+    next_start: AtomicUsize,
+}
+
+impl<'ext> NativeProgramsCollectionGroup<'ext> {
+    /// Builds an empty group.
+    ///
+    /// Calling [`next_event`](NativeProgramsCollectionGroup::next_event) will never yield
+    /// anything.
+    pub fn new() -> Self {
+        NativeProgramsCollectionGroup {
+            collections: Vec::new(),
+            next_start: AtomicUsize::new(0),
+        }
+    }
+
+    /// Adds a collection to the group.
+    ///
+    /// # Panic
+    ///
+    /// Panics if one of the programs of `collection` has the same `Pid` as a program of a
+    /// collection already in the group.
+    ///
+    pub fn push(&mut self, collection: NativeProgramsCollection<'ext>) {
+        for pid in collection.pids() {
+            assert!(self.collections.iter().all(|c| c.pids().all(|p| p != pid)));
+        }
+
+        self.collections.push(collection);
+
+        // We assume that `push` is only ever called at initialization.
+        self.collections.shrink_to_fit();
+    }
+
+    /// Returns a `Future` that yields the next event generated by one of the programs of one of
+    /// the collections.
+    pub fn next_event<'collec>(
+        &'collec self,
+    ) -> impl Future<Output = NativeProgramsCollectionEvent<'collec>> + 'collec {
+        future::poll_fn(move |cx| {
+            let num_collections = self.collections.len();
+            let start = self.next_start.load(Ordering::Relaxed);
+
+            for offset in 0..num_collections {
+                let index = (start + offset) % num_collections;
+                let next_event = self.collections[index].next_event();
+                futures::pin_mut!(next_event);
+                if let Poll::Ready(event) = next_event.poll(cx) {
+                    self.next_start
+                        .store((index + 1) % num_collections, Ordering::Relaxed);
+                    return Poll::Ready(event);
+                }
+            }
+
+            Poll::Pending
+        })
+    }
+
+    /// Notify the [`NativeProgram`] that a message has arrived on one of the interface that it
+    /// has registered.
+    ///
+    /// The message is delivered to the first collection that contains a program that has
+    /// registered this interface. Gives back the message if there is none.
+    pub fn interface_message(
+        &self,
+        interface: InterfaceHash,
+        message_id: Option<MessageId>,
+        emitter_pid: Pid,
+        mut message: EncodedMessage,
+    ) -> Result<(), EncodedMessage> {
+        for collection in &self.collections {
+            let msg = mem::replace(&mut message, EncodedMessage(Vec::new()));
+            match collection.interface_message(interface.clone(), message_id, emitter_pid, msg) {
+                Ok(()) => return Ok(()),
+                Err(msg) => message = msg,
+            }
+        }
+
+        Err(message)
+    }
+
+    /// Notify the [`NativeProgram`]s of all the collections that the program with the given
+    /// [`Pid`] has terminated.
+    pub fn process_destroyed(&self, pid: Pid) {
+        for collection in &self.collections {
+            collection.process_destroyed(pid);
+        }
+    }
+
+    /// Notify the appropriate [`NativeProgram`] of a response to a message that it has previously
+    /// emitted.
+    ///
+    /// Gives back the response if no program of any collection is waiting for it.
+    pub fn message_response(
+        &self,
+        message_id: MessageId,
+        mut response: Result<EncodedMessage, AnswerError>,
+    ) -> Result<(), Result<EncodedMessage, AnswerError>> {
+        for collection in &self.collections {
+            let msg = mem::replace(&mut response, Ok(EncodedMessage(Vec::new())));
+            match collection.message_response(message_id, msg) {
+                Ok(()) => return Ok(()),
+                Err(msg) => response = msg,
+            }
+        }
+
+        Err(response)
+    }
+}
+
+impl<'ext> Default for NativeProgramsCollectionGroup<'ext> {
+    fn default() -> Self {
+        NativeProgramsCollectionGroup::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NativeProgramsCollectionGroup;
+    use crate::native::{
+        test_program::TestProgram, NativeProgramsCollection, NativeProgramsCollectionEvent,
+    };
+    use crate::{EncodedMessage, InterfaceHash, Pid};
+    use alloc::{vec, vec::Vec};
+    use futures::prelude::*;
+
+    #[test]
+    fn is_send() {
+        fn req_send<T: Send>() {}
+        req_send::<NativeProgramsCollectionGroup>();
+    }
+
+    #[test]
+    fn events_and_messages_across_collections() {
+        let interface1 = InterfaceHash::from([1; 32]);
+        let interface2 = InterfaceHash::from([2; 32]);
+
+        let program1 = TestProgram::new();
+        program1.register(interface1.clone());
+        let program2 = TestProgram::new();
+        program2.register(interface2.clone());
+
+        let mut collection1 = NativeProgramsCollection::new();
+        collection1.push(Pid::from(1), program1.clone());
+        let mut collection2 = NativeProgramsCollection::new();
+        collection2.push(Pid::from(2), program2.clone());
+
+        let mut group = NativeProgramsCollectionGroup::new();
+        group.push(collection1);
+        group.push(collection2);
+
+        let mut emitters = Vec::new();
+        while let Some(event) = group.next_event().now_or_never() {
+            match event {
+                NativeProgramsCollectionEvent::Emit { emitter_pid, .. } => {
+                    emitters.push(emitter_pid)
+                }
+                _ => panic!(),
+            }
+        }
+        assert_eq!(emitters, vec![Pid::from(1), Pid::from(2)]);
+
+        group
+            .interface_message(
+                interface2.clone(),
+                None,
+                Pid::from(3),
+                EncodedMessage(vec![1, 2, 3]),
+            )
+            .unwrap();
+        assert!(program1.interface_messages().is_empty());
+        assert_eq!(
+            program2.interface_messages(),
+            vec![(
+                interface2,
+                None,
+                Pid::from(3),
+                EncodedMessage(vec![1, 2, 3])
+            )]
+        );
+
+        group.process_destroyed(Pid::from(3));
+        assert_eq!(program1.destroyed(), vec![Pid::from(3)]);
+        assert_eq!(program2.destroyed(), vec![Pid::from(3)]);
+    }
+
+    #[test]
+    fn next_event_round_robin() {
+        let program1 = TestProgram::new();
+        let program2 = TestProgram::new();
+        let mut collection1 = NativeProgramsCollection::new();
+        collection1.push(Pid::from(1), program1.clone());
+        let mut collection2 = NativeProgramsCollection::new();
+        collection2.push(Pid::from(2), program2.clone());
+
+        let mut group = NativeProgramsCollectionGroup::new();
+        group.push(collection1);
+        group.push(collection2);
+
+        for _ in 0..5 {
+            program1.emit(InterfaceHash::from([1; 32]), EncodedMessage(vec![1]), false);
+            program2.emit(InterfaceHash::from([1; 32]), EncodedMessage(vec![2]), false);
+        }
+
+        let mut emitters = Vec::new();
+        for _ in 0..10 {
+            match group.next_event().now_or_never() {
+                Some(NativeProgramsCollectionEvent::Emit { emitter_pid, .. }) => {
+                    emitters.push(emitter_pid)
+                }
+                _ => panic!(),
+            }
+        }
+
+        assert!(emitters.windows(2).all(|w| w[0] != w[1]));
+        assert!(group.next_event().now_or_never().is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn duplicate_pid_across_collections() {
+        let mut collection1 = NativeProgramsCollection::new();
+        collection1.push(Pid::from(1), TestProgram::new());
+        let mut collection2 = NativeProgramsCollection::new();
+        collection2.push(Pid::from(1), TestProgram::new());
+
+        let mut group = NativeProgramsCollectionGroup::new();
+        group.push(collection1);
+        group.push(collection2);
+    }
+}
@@ -0,0 +1,159 @@
+// Copyright (C) 2019  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Native program whose behaviour is scripted by the tests.
+
+#![cfg(test)]
+
+use crate::native::traits::{DummyMessageIdWrite, NativeProgramEvent, NativeProgramRef};
+
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc, vec::Vec};
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::Poll,
+};
+use futures::{prelude::*, task::AtomicWaker};
+use redshirt_interface_interface::ffi::InterfaceMessage;
+use redshirt_syscalls_interface::{
+    AnswerError, Encode as _, EncodedMessage, InterfaceHash, MessageId, Pid,
+};
+use spin::Mutex;
+
+/// Native program that yields the events that have been pushed to it, and records everything
+/// that it receives.
+///
+/// Cloning a [`TestProgram`] gives access to the same state, which makes it possible to inspect
+/// a program after it has been moved into a collection.
+#[derive(Clone, Default)]
+pub struct TestProgram {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Events to yield from `next_event`, in order.
+    events: Mutex<VecDeque<NativeProgramEvent<DummyMessageIdWrite>>>,
+    /// Waken up when an event is pushed to `events`.
+    waker: AtomicWaker,
+    /// Number of times the `Future` returned by `next_event` has been polled.
+    polls: AtomicUsize,
+    /// Interface messages that have been delivered.
+    interface_messages: Mutex<Vec<(InterfaceHash, Option<MessageId>, Pid, EncodedMessage)>>,
+    /// Responses that have been delivered.
+    responses: Mutex<Vec<(MessageId, Result<EncodedMessage, AnswerError>)>>,
+    /// Processes whose destruction has been reported.
+    destroyed: Mutex<Vec<Pid>>,
+}
+
+impl TestProgram {
+    /// Builds a program that doesn't yield any event.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Queues an event that registers the program as the handler of the given interface.
+    pub fn register(&self, interface: InterfaceHash) {
+        self.emit(
+            redshirt_interface_interface::ffi::INTERFACE,
+            InterfaceMessage::Register(interface).encode(),
+            false,
+        );
+    }
+
+    /// Queues an event that emits a message.
+    pub fn emit(&self, interface: InterfaceHash, message: EncodedMessage, needs_answer: bool) {
+        self.push_event(NativeProgramEvent::Emit {
+            interface,
+            message_id_write: if needs_answer {
+                Some(DummyMessageIdWrite)
+            } else {
+                None
+            },
+            message,
+        });
+    }
+
+    /// Queues an arbitrary event.
+    pub fn push_event(&self, event: NativeProgramEvent<DummyMessageIdWrite>) {
+        self.inner.events.lock().push_back(event);
+        self.inner.waker.wake();
+    }
+
+    /// Returns the number of times the program has been polled for events so far.
+    pub fn polls(&self) -> usize {
+        self.inner.polls.load(Ordering::SeqCst)
+    }
+
+    /// Returns the interface messages delivered so far.
+    pub fn interface_messages(
+        &self,
+    ) -> Vec<(InterfaceHash, Option<MessageId>, Pid, EncodedMessage)> {
+        self.inner.interface_messages.lock().clone()
+    }
+
+    /// Returns the responses delivered so far.
+    pub fn responses(&self) -> Vec<(MessageId, Result<EncodedMessage, AnswerError>)> {
+        self.inner.responses.lock().clone()
+    }
+
+    /// Returns the processes whose destruction has been reported so far.
+    pub fn destroyed(&self) -> Vec<Pid> {
+        self.inner.destroyed.lock().clone()
+    }
+}
+
+impl<'a> NativeProgramRef<'a> for &'a TestProgram {
+    type Future =
+        Pin<Box<dyn Future<Output = NativeProgramEvent<Self::MessageIdWrite>> + Send + 'a>>;
+    type MessageIdWrite = DummyMessageIdWrite;
+
+    fn next_event(self) -> Self::Future {
+        Box::pin(future::poll_fn(move |cx| {
+            self.inner.polls.fetch_add(1, Ordering::SeqCst);
+            self.inner.waker.register(cx.waker());
+            match self.inner.events.lock().pop_front() {
+                Some(ev) => Poll::Ready(ev),
+                None => Poll::Pending,
+            }
+        }))
+    }
+
+    fn interface_message(
+        self,
+        interface: InterfaceHash,
+        message_id: Option<MessageId>,
+        emitter_pid: Pid,
+        message: EncodedMessage,
+    ) {
+        self.inner
+            .interface_messages
+            .lock()
+            .push((interface, message_id, emitter_pid, message));
+    }
+
+    fn process_destroyed(self, pid: Pid) {
+        self.inner.destroyed.lock().push(pid);
+    }
+
+    fn message_response(
+        self,
+        message_id: MessageId,
+        response: Result<EncodedMessage, AnswerError>,
+    ) {
+        self.inner.responses.lock().push((message_id, response));
+    }
+}
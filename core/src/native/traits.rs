@@ -14,7 +14,7 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use core::future::Future;
-use redshirt_syscalls_interface::{EncodedMessage, InterfaceHash, MessageId, Pid};
+use redshirt_syscalls_interface::{AnswerError, EncodedMessage, InterfaceHash, MessageId, Pid};
 
 /// Reference to a native program.
 ///
@@ -48,7 +48,7 @@ pub trait NativeProgramRef<'a>: Clone {
     fn process_destroyed(self, pid: Pid);
 
     /// Notify the [`NativeProgram`] of a response to a message that it has previously emitted.
-    fn message_response(self, message_id: MessageId, response: Result<EncodedMessage, ()>);
+    fn message_response(self, message_id: MessageId, response: Result<EncodedMessage, AnswerError>);
 }
 
 /// Event generated by a [`NativeProgram`].
@@ -77,7 +77,7 @@ pub enum NativeProgramEvent<TMsgIdWrite> {
         /// Message to answer.
         message_id: MessageId,
         /// Answer to the message. Can be an error if the message is invalid.
-        answer: Result<EncodedMessage, ()>,
+        answer: Result<EncodedMessage, AnswerError>,
     },
 }
 
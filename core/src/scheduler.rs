@@ -20,4 +20,9 @@ mod tests;
 mod vm;
 
 // TODO: move definition?
-pub use self::ipc::{Core, CoreBuilder, CoreProcess, CoreRunOutcome, CoreThread};
+pub use self::extrinsics::{Extrinsic, ThreadState};
+pub use self::ipc::{
+    Core, CoreBuilder, CoreProcess, CoreRunOutcome, CoreStepOutcome, CoreThread,
+    InboxOverflowPolicy, MemoryFootprint, TrapInfo,
+};
+pub use self::vm::{Engine, EngineRunOutcome, NewErr, RunErr, StartErr, Trap, WasmiEngine};
@@ -16,67 +16,96 @@
 use crate::module::Module;
 use crate::scheduler::{processes, vm};
 use crate::sig;
+use crate::signature::WasmValue;
 use crate::{InterfaceHash, MessageId};
 
 use alloc::{vec, vec::Vec};
 use byteorder::{ByteOrder as _, LittleEndian};
 use core::{convert::TryFrom as _, fmt, mem};
-use redshirt_syscalls_interface::{EncodedMessage, Pid, ThreadId};
+use redshirt_syscalls_interface::{AnswerError, EncodedMessage, Pid, ThreadId};
 
 /// Wrapper around [`ProcessesCollection`](processes::ProcessesCollection), but that interprets
 /// the extrinsic calls and keeps track of the state in which pending threads are in.
 ///
 /// The generic parameters `TPud` and `TTud` are "user data"s that are stored respectively per
 /// process and per thread, and allows the user to put extra information associated to a process
-/// or a thread.
-pub struct ProcessesCollectionExtrinsics<TPud, TTud> {
-    inner: processes::ProcessesCollection<Extrinsic, TPud, LocalThreadUserData<TTud>>,
+/// or a thread. The generic parameter `E` is the [`Engine`](vm::Engine) that executes the code.
+pub struct ProcessesCollectionExtrinsics<TPud, TTud, E: vm::Engine = vm::WasmiEngine> {
+    inner: processes::ProcessesCollection<Extrinsic, TPud, LocalThreadUserData<TTud>, E>,
+
+    /// Fuel given to each thread when it starts. `None` if fuel metering is disabled.
+    fuel_limit: Option<u64>,
+
+    /// Maximum number of pages of 64kiB of the memory of each process. `None` if unlimited.
+    process_memory_limit: Option<u32>,
 }
 
 /// Prototype for a `ProcessesCollectionExtrinsics` under construction.
 pub struct ProcessesCollectionExtrinsicsBuilder {
     inner: processes::ProcessesCollectionBuilder<Extrinsic>,
+    /// See the corresponding field in `ProcessesCollectionExtrinsics`.
+    fuel_limit: Option<u64>,
+    /// See the corresponding field in `ProcessesCollectionExtrinsics`.
+    process_memory_limit: Option<u32>,
 }
 
 /// Access to a process within the collection.
-pub struct ProcessesCollectionExtrinsicsProc<'a, TPud, TTud> {
-    inner: processes::ProcessesCollectionProc<'a, TPud, LocalThreadUserData<TTud>>,
+pub struct ProcessesCollectionExtrinsicsProc<'a, TPud, TTud, E: vm::Engine = vm::WasmiEngine> {
+    inner: processes::ProcessesCollectionProc<'a, TPud, LocalThreadUserData<TTud>, E>,
+    /// Copy of the field in `ProcessesCollectionExtrinsics`.
+    fuel_limit: Option<u64>,
 }
 
 /// Access to a thread within the collection.
 ///
 /// Implements the [`ProcessesCollectionExtrinsicsThreadAccess`] trait.
-pub enum ProcessesCollectionExtrinsicsThread<'a, TPud, TTud> {
-    Regular(ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud>),
-    EmitMessage(ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TTud>),
-    WaitMessage(ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TTud>),
+pub enum ProcessesCollectionExtrinsicsThread<'a, TPud, TTud, E: vm::Engine = vm::WasmiEngine> {
+    Regular(ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud, E>),
+    EmitMessage(ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TTud, E>),
+    WaitMessage(ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TTud, E>),
 }
 
 /// Access to a thread within the collection.
 ///
 /// Implements the [`ProcessesCollectionExtrinsicsThreadAccess`] trait.
-pub struct ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud> {
-    inner: processes::ProcessesCollectionThread<'a, TPud, LocalThreadUserData<TTud>>,
+pub struct ProcessesCollectionExtrinsicsThreadRegular<
+    'a,
+    TPud,
+    TTud,
+    E: vm::Engine = vm::WasmiEngine,
+> {
+    inner: processes::ProcessesCollectionThread<'a, TPud, LocalThreadUserData<TTud>, E>,
 }
 
 /// Access to a thread within the collection.
 ///
 /// Implements the [`ProcessesCollectionExtrinsicsThreadAccess`] trait.
-pub struct ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TTud> {
-    inner: processes::ProcessesCollectionThread<'a, TPud, LocalThreadUserData<TTud>>,
+pub struct ProcessesCollectionExtrinsicsThreadEmitMessage<
+    'a,
+    TPud,
+    TTud,
+    E: vm::Engine = vm::WasmiEngine,
+> {
+    inner: processes::ProcessesCollectionThread<'a, TPud, LocalThreadUserData<TTud>, E>,
 }
 
 /// Access to a thread within the collection.
 ///
 /// Implements the [`ProcessesCollectionExtrinsicsThreadAccess`] trait.
-pub struct ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TTud> {
-    inner: processes::ProcessesCollectionThread<'a, TPud, LocalThreadUserData<TTud>>,
+pub struct ProcessesCollectionExtrinsicsThreadWaitMessage<
+    'a,
+    TPud,
+    TTud,
+    E: vm::Engine = vm::WasmiEngine,
+> {
+    inner: processes::ProcessesCollectionThread<'a, TPud, LocalThreadUserData<TTud>, E>,
 }
 
 /// Common trait amongst all the thread accessor structs.
 pub trait ProcessesCollectionExtrinsicsThreadAccess<'a> {
     type ProcessUserData;
     type ThreadUserData;
+    type Engine: vm::Engine;
 
     /// Returns the id of the thread. Allows later retrieval by calling
     /// [`thread_by_id`](ProcessesCollectionExtrinsics::thread_by_id).
@@ -94,7 +123,14 @@ pub trait ProcessesCollectionExtrinsicsThreadAccess<'a> {
     /// Threads are ordered arbitrarily. In particular, they are **not** ordered by [`ThreadId`].
     fn next_thread(
         self,
-    ) -> Option<ProcessesCollectionExtrinsicsThread<'a, Self::ProcessUserData, Self::ThreadUserData>>;
+    ) -> Option<
+        ProcessesCollectionExtrinsicsThread<
+            'a,
+            Self::ProcessUserData,
+            Self::ThreadUserData,
+            Self::Engine,
+        >,
+    >;
 
     /// Returns the user data that is associated to the process.
     fn process_user_data(&mut self) -> &mut Self::ProcessUserData;
@@ -103,14 +139,54 @@ pub trait ProcessesCollectionExtrinsicsThreadAccess<'a> {
     fn user_data(&mut self) -> &mut Self::ThreadUserData;
 }
 
-/// Possible function available to processes.
+/// State of a thread, as reported by [`ProcessesCollectionExtrinsicsThread::state`].
 #[derive(Debug, Clone, PartialEq, Eq)]
-enum Extrinsic {
+pub enum ThreadState {
+    /// Thread is ready to run, running, or has just called an extrinsic and the call is being
+    /// processed.
+    Running,
+    /// Thread has called `next_message` and is sleeping until a message arrives.
+    WaitingForMessage,
+    /// Thread has called `emit_message` and is sleeping until a handler for the given interface
+    /// is available.
+    WaitingForInterface(InterfaceHash),
+    /// Thread has run out of fuel and is paused until it is given more.
+    OutOfFuel,
+    /// Thread has called the given extrinsic and is sleeping until the call can be completed.
+    ///
+    /// > **Note**: This is never reported by [`ProcessesCollectionExtrinsicsThread::state`], as
+    /// >           only the upper layers know why a call can't be completed. For example, the
+    /// >           [`Core`](crate::scheduler::Core) reports it for a call to `emit_message` that is
+    /// >           delayed because the inbox of the handler is full.
+    BlockedOnExtrinsic(Extrinsic),
+}
+
+/// Maximum size, in bytes, of a message that a process can emit. Emitting a larger message is
+/// considered as an invalid extrinsic call.
+// TODO: arbitrary maximum message length
+const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Possible function available to processes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extrinsic {
+    /// `next_message`.
     NextMessage,
+    /// `emit_message`.
     EmitMessage,
+    /// `emit_message_error`.
     EmitMessageError,
+    /// `emit_answer`.
     EmitAnswer,
+    /// `emit_answer_error`.
+    EmitAnswerError,
+    /// `emit_answer_chunk`.
+    EmitAnswerChunk,
+    /// `cancel_message`.
     CancelMessage,
+    /// `consume_fuel`.
+    ConsumeFuel,
+    /// `sched_yield`.
+    SchedYield,
 }
 
 /// Structure passed to the underlying [`processes::ProcessesCollection`] that tracks the state
@@ -135,6 +211,10 @@ enum LocalThreadState {
 
     /// The thread called `emit_message` and wants to emit a message on an interface.
     EmitMessage(EmitMessage),
+
+    /// The thread doesn't have enough fuel to continue. Contains the quantity of fuel that the
+    /// thread needs.
+    OutOfFuel(u64),
 }
 
 /// How a process is waiting for messages.
@@ -151,6 +231,9 @@ struct MessageWait {
     out_size: u32,
     /// Whether to block the thread if no message is available.
     block: bool,
+    /// If `Some`, maximum number of nanoseconds to block the thread for. Only relevant if
+    /// `block` is true.
+    timeout_nanos: Option<u64>,
 }
 
 /// How a process is emitting a message.
@@ -165,6 +248,11 @@ struct EmitMessage {
     /// True if we're allowed to block the thread to wait for an interface handler to be
     /// available.
     allow_delay: bool,
+    /// Priority of the message, as passed by the emitter.
+    priority: u8,
+    /// If `Some`, number of nanoseconds after which the message must be answered with an error
+    /// if the handler hasn't answered it yet.
+    answer_timeout_nanos: Option<u64>,
 }
 
 /// How a process is emitting a response.
@@ -176,9 +264,20 @@ struct EmitAnswer {
     response: EncodedMessage,
 }
 
+/// How a process is emitting a piece of a response.
+#[derive(Debug, PartialEq, Eq)]
+struct EmitAnswerChunk {
+    /// Message to answer.
+    message_id: MessageId,
+    /// Piece of the response.
+    chunk: Vec<u8>,
+    /// True if this is the last piece of the response.
+    is_final: bool,
+}
+
 /// Outcome of the [`run`](ProcessesCollectionExtrinsics::run) function.
 #[derive(Debug)]
-pub enum RunOneOutcome<'a, TPud, TTud> {
+pub enum RunOneOutcome<'a, TPud, TTud, E: vm::Engine = vm::WasmiEngine> {
     /// Either the main thread of a process has finished, or a fatal error was encountered.
     ///
     /// The process no longer exists.
@@ -195,53 +294,90 @@ pub enum RunOneOutcome<'a, TPud, TTud> {
         dead_threads: Vec<(ThreadId, TTud)>,
 
         /// Value returned by the main thread that has finished, or error that happened.
-        outcome: Result<Option<wasmi::RuntimeValue>, wasmi::Trap>,
+        outcome: Result<Option<WasmValue>, vm::Trap>,
     },
 
     /// A thread in a process has finished.
     ThreadFinished {
         /// Process whose thread has finished.
-        process: ProcessesCollectionExtrinsicsProc<'a, TPud, TTud>,
+        process: ProcessesCollectionExtrinsicsProc<'a, TPud, TTud, E>,
 
         /// User data of the thread.
         user_data: TTud,
 
         /// Value returned by the function that was executed.
-        value: Option<wasmi::RuntimeValue>,
+        value: Option<WasmValue>,
     },
 
     /// A thread in a process wants to emit a message.
-    ThreadEmitMessage(ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TTud>),
+    ThreadEmitMessage(ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TTud, E>),
 
     /// A thread in a process is waiting for an incoming message.
-    ThreadWaitMessage(ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TTud>),
+    ThreadWaitMessage(ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TTud, E>),
 
     /// A thread in a process wants to answer a message.
     ThreadEmitAnswer {
         /// Thread that wants to emit an answer.
-        thread: ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud>,
+        thread: ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud, E>,
 
         /// Message to answer.
         message_id: MessageId,
 
-        /// The answer it self.
-        response: EncodedMessage,
+        /// The answer it self, or the error that the thread has answered with.
+        response: Result<EncodedMessage, AnswerError>,
+    },
+
+    /// A thread in a process wants to answer a message piece by piece.
+    ThreadEmitAnswerChunk {
+        /// Thread that wants to emit a piece of an answer.
+        thread: ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud, E>,
+
+        /// Message to answer.
+        message_id: MessageId,
+
+        /// Piece of the answer. Must be appended to the pieces previously emitted for the same
+        /// message.
+        chunk: Vec<u8>,
+
+        /// If true, this is the last piece and the answer is now complete.
+        is_final: bool,
     },
 
     /// A thread in a process wants to notify that a message is erroneous.
     ThreadEmitMessageError {
         /// Thread that wants to emit a message error.
-        thread: ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud>,
+        thread: ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud, E>,
 
         /// Message that is erroneous.
         message_id: MessageId,
     },
 
+    /// A thread in a process is no longer interested in the answer to a message it has emitted.
+    ThreadCancelMessage {
+        /// Thread that wants to cancel a message.
+        thread: ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud, E>,
+
+        /// Message whose answer is no longer expected.
+        message_id: MessageId,
+    },
+
+    /// A thread in a process has asked for fuel, and has enough left to continue running.
+    /// Nothing to do.
+    ThreadFuelConsumed(ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud, E>),
+
+    /// A thread in a process has yielded. The other threads of its process that are ready to run
+    /// will run before it. Nothing to do.
+    ThreadYielded(ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud, E>),
+
+    /// A thread in a process has run out of fuel and is paused. Call
+    /// [`refuel`](ProcessesCollectionExtrinsicsThread::refuel) to resume it.
+    ThreadOutOfFuel(ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud, E>),
+
     /// No thread is ready to run. Nothing was done.
     Idle,
 }
 
-impl<TPud, TTud> ProcessesCollectionExtrinsics<TPud, TTud> {
+impl<TPud, TTud, E: vm::Engine> ProcessesCollectionExtrinsics<TPud, TTud, E> {
     /// Creates a new process state machine from the given module.
     ///
     /// The closure is called for each import that the module has. It must assign a number to each
@@ -250,27 +386,64 @@ impl<TPud, TTud> ProcessesCollectionExtrinsics<TPud, TTud> {
     /// the call.
     ///
     /// A single main thread (whose user data is passed by parameter) is automatically created and
-    /// is paused at the start of the "_start" function of the module.
+    /// is paused at the start of the given entry point of the module.
     pub fn execute(
         &mut self,
         module: &Module,
+        entry_point: vm::EntryPoint,
         proc_user_data: TPud,
         main_thread_user_data: TTud,
-    ) -> Result<ProcessesCollectionExtrinsicsProc<TPud, TTud>, vm::NewErr> {
+    ) -> Result<ProcessesCollectionExtrinsicsProc<TPud, TTud, E>, vm::NewErr> {
+        let fuel_limit = self.fuel_limit;
+
+        let limited_module;
+        let module = if let Some(max_pages) = self.process_memory_limit {
+            limited_module = module
+                .with_memory_limit(max_pages)
+                .map_err(|()| vm::NewErr::MemoryLimitReached)?;
+            &limited_module
+        } else {
+            module
+        };
+
+        // If fuel metering is enabled, the module keeps track of its fuel itself, and only calls
+        // `consume_fuel` (see the `Default` implementation of the builder) when it runs out.
+        let metered_module;
+        let module = if fuel_limit.is_some() {
+            metered_module = module.with_fuel_metering("redshirt", "consume_fuel");
+            &metered_module
+        } else {
+            module
+        };
+
         let main_thread_user_data = LocalThreadUserData {
             state: LocalThreadState::ReadyToRun,
             external_user_data: main_thread_user_data,
         };
-        let process = self
-            .inner
-            .execute(module, proc_user_data, main_thread_user_data)?;
-        Ok(ProcessesCollectionExtrinsicsProc { inner: process })
+        let mut process =
+            self.inner
+                .execute(module, entry_point, proc_user_data, main_thread_user_data)?;
+
+        if let Some(fuel) = fuel_limit {
+            let pid = process.pid();
+            process.main_thread().set_fuel(fuel);
+            process = match self.inner.process_by_id(pid) {
+                Some(p) => p,
+                None => unreachable!(),
+            };
+        }
+
+        Ok(ProcessesCollectionExtrinsicsProc {
+            inner: process,
+            fuel_limit,
+        })
     }
 
     /// Runs one thread amongst the collection.
     ///
     /// Which thread is run is implementation-defined and no guarantee is made.
-    pub fn run(&mut self) -> RunOneOutcome<TPud, TTud> {
+    pub fn run(&mut self) -> RunOneOutcome<TPud, TTud, E> {
+        let fuel_limit = self.fuel_limit;
         match self.inner.run() {
             processes::RunOneOutcome::ProcessFinished {
                 pid,
@@ -293,7 +466,10 @@ impl<TPud, TTud> ProcessesCollectionExtrinsics<TPud, TTud> {
             } => {
                 debug_assert!(user_data.state.is_ready_to_run());
                 RunOneOutcome::ThreadFinished {
-                    process: ProcessesCollectionExtrinsicsProc { inner: process },
+                    process: ProcessesCollectionExtrinsicsProc {
+                        inner: process,
+                        fuel_limit,
+                    },
                     user_data: user_data.external_user_data,
                     value,
                 }
@@ -308,7 +484,7 @@ impl<TPud, TTud> ProcessesCollectionExtrinsics<TPud, TTud> {
                 debug_assert!(thread.user_data().state.is_ready_to_run());
                 let next_msg = match parse_extrinsic_next_message(&mut thread, params) {
                     Ok(m) => m,
-                    Err(_) => panic!(), // TODO:
+                    Err(()) => return invalid_extrinsic_call(thread),
                 };
                 thread.user_data().state = LocalThreadState::MessageWait(next_msg);
                 RunOneOutcome::ThreadWaitMessage(ProcessesCollectionExtrinsicsThreadWaitMessage {
@@ -324,7 +500,7 @@ impl<TPud, TTud> ProcessesCollectionExtrinsics<TPud, TTud> {
                 debug_assert!(thread.user_data().state.is_ready_to_run());
                 let emit_msg = match parse_extrinsic_emit_message(&mut thread, params) {
                     Ok(m) => m,
-                    Err(_) => panic!(), // TODO:
+                    Err(()) => return invalid_extrinsic_call(thread),
                 };
                 thread.user_data().state = LocalThreadState::EmitMessage(emit_msg);
                 RunOneOutcome::ThreadEmitMessage(ProcessesCollectionExtrinsicsThreadEmitMessage {
@@ -340,13 +516,51 @@ impl<TPud, TTud> ProcessesCollectionExtrinsics<TPud, TTud> {
                 debug_assert!(thread.user_data().state.is_ready_to_run());
                 let emit_resp = match parse_extrinsic_emit_answer(&mut thread, params) {
                     Ok(m) => m,
-                    Err(_) => panic!(), // TODO:
+                    Err(()) => return invalid_extrinsic_call(thread),
                 };
                 thread.resume(None);
                 RunOneOutcome::ThreadEmitAnswer {
                     thread: ProcessesCollectionExtrinsicsThreadRegular { inner: thread },
                     message_id: emit_resp.message_id,
-                    response: emit_resp.response,
+                    response: Ok(emit_resp.response),
+                }
+            }
+
+            processes::RunOneOutcome::Interrupted {
+                mut thread,
+                id: Extrinsic::EmitAnswerError,
+                params,
+            } => {
+                debug_assert!(thread.user_data().state.is_ready_to_run());
+                // `emit_answer_error` has the same parameters as `emit_answer`.
+                let emit_err = match parse_extrinsic_emit_answer(&mut thread, params) {
+                    Ok(m) => m,
+                    Err(()) => return invalid_extrinsic_call(thread),
+                };
+                thread.resume(None);
+                RunOneOutcome::ThreadEmitAnswer {
+                    thread: ProcessesCollectionExtrinsicsThreadRegular { inner: thread },
+                    message_id: emit_err.message_id,
+                    response: Err(AnswerError::HandlerError(emit_err.response.0)),
+                }
+            }
+
+            processes::RunOneOutcome::Interrupted {
+                mut thread,
+                id: Extrinsic::EmitAnswerChunk,
+                params,
+            } => {
+                debug_assert!(thread.user_data().state.is_ready_to_run());
+                let emit_chunk = match parse_extrinsic_emit_answer_chunk(&mut thread, params) {
+                    Ok(m) => m,
+                    Err(()) => return invalid_extrinsic_call(thread),
+                };
+                thread.resume(None);
+                RunOneOutcome::ThreadEmitAnswerChunk {
+                    thread: ProcessesCollectionExtrinsicsThreadRegular { inner: thread },
+                    message_id: emit_chunk.message_id,
+                    chunk: emit_chunk.chunk,
+                    is_final: emit_chunk.is_final,
                 }
             }
 
@@ -358,7 +572,7 @@ impl<TPud, TTud> ProcessesCollectionExtrinsics<TPud, TTud> {
                 debug_assert!(thread.user_data().state.is_ready_to_run());
                 let emit_msg_error = match parse_extrinsic_emit_message_error(&mut thread, params) {
                     Ok(m) => m,
-                    Err(_) => panic!(), // TODO:
+                    Err(()) => return invalid_extrinsic_call(thread),
                 };
                 thread.resume(None);
                 RunOneOutcome::ThreadEmitMessageError {
@@ -368,10 +582,72 @@ impl<TPud, TTud> ProcessesCollectionExtrinsics<TPud, TTud> {
             }
 
             processes::RunOneOutcome::Interrupted {
-                thread,
+                mut thread,
                 id: Extrinsic::CancelMessage,
                 params,
-            } => unimplemented!(),
+            } => {
+                debug_assert!(thread.user_data().state.is_ready_to_run());
+                let message_id = match parse_extrinsic_cancel_message(&mut thread, params) {
+                    Ok(m) => m,
+                    Err(()) => return invalid_extrinsic_call(thread),
+                };
+                thread.resume(None);
+                RunOneOutcome::ThreadCancelMessage {
+                    thread: ProcessesCollectionExtrinsicsThreadRegular { inner: thread },
+                    message_id,
+                }
+            }
+
+            processes::RunOneOutcome::Interrupted {
+                mut thread,
+                id: Extrinsic::ConsumeFuel,
+                params,
+            } => {
+                debug_assert!(thread.user_data().state.is_ready_to_run());
+                // We use an assert here rather than a runtime check because the WASM VM (rather
+                // than us) is supposed to check the function signature.
+                assert_eq!(params.len(), 1);
+                let required = match params[0].into_i32() {
+                    Some(v) => u64::from(v as u32),
+                    None => unreachable!(),
+                };
+
+                // The instrumented code only calls `consume_fuel` if the thread doesn't have
+                // enough fuel for the sequence of instructions it is about to execute, and
+                // subtracts the fuel itself afterwards.
+                let out_of_fuel = match thread.fuel() {
+                    Some(fuel) => fuel < required,
+                    None => false,
+                };
+
+                if out_of_fuel {
+                    thread.user_data().state = LocalThreadState::OutOfFuel(required);
+                    RunOneOutcome::ThreadOutOfFuel(ProcessesCollectionExtrinsicsThreadRegular {
+                        inner: thread,
+                    })
+                } else {
+                    thread.resume(None);
+                    RunOneOutcome::ThreadFuelConsumed(ProcessesCollectionExtrinsicsThreadRegular {
+                        inner: thread,
+                    })
+                }
+            }
+
+            processes::RunOneOutcome::Interrupted {
+                mut thread,
+                id: Extrinsic::SchedYield,
+                params,
+            } => {
+                debug_assert!(thread.user_data().state.is_ready_to_run());
+                // We use an assert here rather than a runtime check because the WASM VM (rather
+                // than us) is supposed to check the function signature.
+                assert!(params.is_empty());
+                thread.yield_to_other_threads();
+                thread.resume(Some(WasmValue::I32(0)));
+                RunOneOutcome::ThreadYielded(ProcessesCollectionExtrinsicsThreadRegular {
+                    inner: thread,
+                })
+            }
         }
     }
 
@@ -380,20 +656,57 @@ impl<TPud, TTud> ProcessesCollectionExtrinsics<TPud, TTud> {
         self.inner.pids()
     }
 
+    /// Returns an iterator to the user datas of all the processes that exist in the collection.
+    pub fn user_datas<'a>(&'a self) -> impl ExactSizeIterator<Item = &'a TPud> + 'a {
+        self.inner.user_datas()
+    }
+
+    /// Returns an iterator to the `Pid`s and user datas of all the processes that exist in the
+    /// collection.
+    pub fn pids_user_datas<'a>(&'a self) -> impl ExactSizeIterator<Item = (Pid, &'a TPud)> + 'a {
+        self.inner.pids_user_datas()
+    }
+
+    /// Returns the sum of the sizes, in bytes, of the linear memories of all the processes.
+    pub fn total_memory_size(&self) -> usize {
+        self.inner.total_memory_size()
+    }
+
+    /// Returns the size, in bytes, of the linear memory of the given process, or `None` if the
+    /// process doesn't exist.
+    pub fn process_memory_size(&self, pid: Pid) -> Option<usize> {
+        self.inner.process_memory_size(pid)
+    }
+
+    /// Calls `f` with a view of the given memory range of the given process, without copying
+    /// it.
+    ///
+    /// Returns an error if the process doesn't exist, or if the range is invalid or out of range.
+    pub fn with_process_memory<R>(
+        &self,
+        pid: Pid,
+        offset: u32,
+        size: u32,
+        f: impl FnOnce(&[u8]) -> R,
+    ) -> Result<R, ()> {
+        self.inner.with_process_memory(pid, offset, size, f)
+    }
+
     /// Returns a process by its [`Pid`], if it exists.
     pub fn process_by_id(
         &mut self,
         pid: Pid,
-    ) -> Option<ProcessesCollectionExtrinsicsProc<TPud, TTud>> {
+    ) -> Option<ProcessesCollectionExtrinsicsProc<TPud, TTud, E>> {
+        let fuel_limit = self.fuel_limit;
         let inner = self.inner.process_by_id(pid)?;
-        Some(ProcessesCollectionExtrinsicsProc { inner })
+        Some(ProcessesCollectionExtrinsicsProc { inner, fuel_limit })
     }
 
     /// Returns a thread by its [`ThreadId`], if it exists.
     pub fn thread_by_id(
         &mut self,
         id: ThreadId,
-    ) -> Option<ProcessesCollectionExtrinsicsThread<TPud, TTud>> {
+    ) -> Option<ProcessesCollectionExtrinsicsThread<TPud, TTud, E>> {
         let inner = self.inner.thread_by_id(id)?;
         Some(ProcessesCollectionExtrinsicsThread::from_inner(inner))
     }
@@ -405,13 +718,19 @@ impl Default for ProcessesCollectionExtrinsicsBuilder {
             .with_extrinsic(
                 "redshirt",
                 "next_message",
-                sig!((I32, I32, I32, I32, I32) -> I32),
+                sig!((I32, I32, I32, I32, I32, I64) -> I32),
                 Extrinsic::NextMessage,
             )
             .with_extrinsic(
                 "redshirt",
                 "emit_message",
-                sig!((I32, I32, I32, I32, I32, I32) -> I32),
+                sig!((I32, I32, I32, I32, I32, I32, I32) -> I32),
+                Extrinsic::EmitMessage,
+            )
+            .with_extrinsic(
+                "redshirt",
+                "emit_message_with_timeout",
+                sig!((I32, I32, I32, I32, I32, I32, I32, I64) -> I32),
                 Extrinsic::EmitMessage,
             )
             .with_extrinsic(
@@ -426,14 +745,42 @@ impl Default for ProcessesCollectionExtrinsicsBuilder {
                 sig!((I32, I32, I32)),
                 Extrinsic::EmitAnswer,
             )
+            .with_extrinsic(
+                "redshirt",
+                "emit_answer_error",
+                sig!((I32, I32, I32)),
+                Extrinsic::EmitAnswerError,
+            )
+            .with_extrinsic(
+                "redshirt",
+                "emit_answer_chunk",
+                sig!((I32, I32, I32, I32)),
+                Extrinsic::EmitAnswerChunk,
+            )
             .with_extrinsic(
                 "redshirt",
                 "cancel_message",
                 sig!((I32)),
                 Extrinsic::CancelMessage,
+            )
+            .with_extrinsic(
+                "redshirt",
+                "consume_fuel",
+                sig!((I32)),
+                Extrinsic::ConsumeFuel,
+            )
+            .with_extrinsic(
+                "redshirt",
+                "sched_yield",
+                sig!(() -> I32),
+                Extrinsic::SchedYield,
             );
 
-        ProcessesCollectionExtrinsicsBuilder { inner }
+        ProcessesCollectionExtrinsicsBuilder {
+            inner,
+            fuel_limit: None,
+            process_memory_limit: None,
+        }
     }
 }
 
@@ -448,15 +795,55 @@ impl ProcessesCollectionExtrinsicsBuilder {
         self.inner.reserve_pid()
     }
 
+    /// See [`processes::ProcessesCollectionBuilder::with_deterministic_scheduling`].
+    pub fn with_deterministic_scheduling(mut self) -> Self {
+        self.inner = self.inner.with_deterministic_scheduling();
+        self
+    }
+
+    /// Enables fuel metering. Each thread is given `limit` units of fuel when it starts, and
+    /// consumes one unit per WASM instruction that it executes.
+    ///
+    /// A thread that runs out of fuel is paused, and [`run`](ProcessesCollectionExtrinsics::run)
+    /// returns [`RunOneOutcome::ThreadOutOfFuel`].
+    ///
+    /// By default, fuel metering is disabled.
+    pub fn with_fuel_limit(mut self, limit: u64) -> Self {
+        self.fuel_limit = Some(limit);
+        self
+    }
+
+    /// Sets the maximum number of pages of 64kiB that the memory of each process can have.
+    ///
+    /// Past this limit, the `memory.grow` instruction returns `-1` to the process. Processes that
+    /// require more memory than that when they start fail to execute.
+    ///
+    /// By default, there is no limit other than the one declared by the module itself.
+    pub fn with_process_memory_limit(mut self, max_pages: u32) -> Self {
+        self.process_memory_limit = Some(max_pages);
+        self
+    }
+
+    /// See [`processes::ProcessesCollectionBuilder::with_memory_grow_hook`].
+    pub fn with_memory_grow_hook(
+        mut self,
+        hook: impl Fn(Pid, u32, u32) + Send + Sync + 'static,
+    ) -> Self {
+        self.inner = self.inner.with_memory_grow_hook(hook);
+        self
+    }
+
     /// Turns the builder into a [`ProcessesCollectionExtrinsics`].
-    pub fn build<TPud, TTud>(self) -> ProcessesCollectionExtrinsics<TPud, TTud> {
+    pub fn build<TPud, TTud, E: vm::Engine>(self) -> ProcessesCollectionExtrinsics<TPud, TTud, E> {
         ProcessesCollectionExtrinsics {
             inner: self.inner.build(),
+            fuel_limit: self.fuel_limit,
+            process_memory_limit: self.process_memory_limit,
         }
     }
 }
 
-impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsProc<'a, TPud, TTud> {
+impl<'a, TPud, TTud, E: vm::Engine> ProcessesCollectionExtrinsicsProc<'a, TPud, TTud, E> {
     /// Returns the [`Pid`] of the process. Allows later retrieval by calling
     /// [`process_by_id`](ProcessesCollection::process_by_id).
     pub fn pid(&self) -> Pid {
@@ -474,14 +861,14 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsProc<'a, TPud, TTud> {
     /// > **Note**: The "function ID" is the index of the function in the WASM module. WASM
     /// >           doesn't have function pointers. Instead, all the functions are part of a single
     /// >           global array of functions.
-    // TODO: don't expose wasmi::RuntimeValue in the API
     pub fn start_thread(
         self,
         fn_index: u32,
-        params: Vec<wasmi::RuntimeValue>,
+        params: Vec<WasmValue>,
         user_data: TTud,
-    ) -> Result<ProcessesCollectionExtrinsicsThread<'a, TPud, TTud>, vm::StartErr> {
-        let thread = self.inner.start_thread(
+    ) -> Result<ProcessesCollectionExtrinsicsThread<'a, TPud, TTud, E>, vm::StartErr> {
+        let fuel_limit = self.fuel_limit;
+        let mut thread = self.inner.start_thread(
             fn_index,
             params,
             LocalThreadUserData {
@@ -490,6 +877,10 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsProc<'a, TPud, TTud> {
             },
         )?;
 
+        if let Some(fuel) = fuel_limit {
+            thread.set_fuel(fuel);
+        }
+
         Ok(From::from(ProcessesCollectionExtrinsicsThreadRegular {
             inner: thread,
         }))
@@ -499,18 +890,23 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsProc<'a, TPud, TTud> {
     ///
     /// The "main thread" of a process is created automatically when you call
     /// [`ProcessesCollection::execute`]. If it stops, the entire process stops.
-    pub fn main_thread(self) -> ProcessesCollectionExtrinsicsThread<'a, TPud, TTud> {
+    pub fn main_thread(self) -> ProcessesCollectionExtrinsicsThread<'a, TPud, TTud, E> {
         ProcessesCollectionExtrinsicsThread::from_inner(self.inner.main_thread())
     }
 
     /// Aborts the process and returns the associated user data.
     pub fn abort(self) -> (TPud, Vec<(ThreadId, TTud)>) {
-        //self.inner.abort()
-        unimplemented!() // TODO:
+        let (user_data, dead_threads) = self.inner.abort();
+        let dead_threads = dead_threads
+            .into_iter()
+            .map(|(tid, thread)| (tid, thread.external_user_data))
+            .collect();
+        (user_data, dead_threads)
     }
 }
 
-impl<'a, TPud, TTud> fmt::Debug for ProcessesCollectionExtrinsicsProc<'a, TPud, TTud>
+impl<'a, TPud, TTud, E: vm::Engine> fmt::Debug
+    for ProcessesCollectionExtrinsicsProc<'a, TPud, TTud, E>
 where
     TPud: fmt::Debug,
     TTud: fmt::Debug,
@@ -520,9 +916,48 @@ where
     }
 }
 
-impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThread<'a, TPud, TTud> {
+impl<'a, TPud, TTud, E: vm::Engine> ProcessesCollectionExtrinsicsThread<'a, TPud, TTud, E> {
+    /// Returns the state the thread is in.
+    pub fn state(&mut self) -> ThreadState {
+        match self {
+            ProcessesCollectionExtrinsicsThread::Regular(t) => match t.inner.user_data().state {
+                LocalThreadState::OutOfFuel(_) => ThreadState::OutOfFuel,
+                _ => ThreadState::Running,
+            },
+            ProcessesCollectionExtrinsicsThread::EmitMessage(t) => {
+                ThreadState::WaitingForInterface(t.emit_interface().clone())
+            }
+            ProcessesCollectionExtrinsicsThread::WaitMessage(_) => ThreadState::WaitingForMessage,
+        }
+    }
+
+    /// Gives more fuel to the thread. Has no effect if fuel metering is disabled.
+    ///
+    /// If the thread has run out of fuel and now has enough to continue, it is resumed.
+    pub fn refuel(&mut self, amount: u64) {
+        let inner = match self {
+            ProcessesCollectionExtrinsicsThread::Regular(t) => &mut t.inner,
+            ProcessesCollectionExtrinsicsThread::EmitMessage(t) => &mut t.inner,
+            ProcessesCollectionExtrinsicsThread::WaitMessage(t) => &mut t.inner,
+        };
+
+        let fuel = match inner.fuel() {
+            Some(fuel) => fuel.saturating_add(amount),
+            None => return,
+        };
+
+        inner.set_fuel(fuel);
+        // Once resumed, the thread subtracts the fuel it needs by itself.
+        if let LocalThreadState::OutOfFuel(required) = inner.user_data().state {
+            if fuel >= required {
+                inner.user_data().state = LocalThreadState::ReadyToRun;
+                inner.resume(None);
+            }
+        }
+    }
+
     fn from_inner(
-        mut inner: processes::ProcessesCollectionThread<'a, TPud, LocalThreadUserData<TTud>>,
+        mut inner: processes::ProcessesCollectionThread<'a, TPud, LocalThreadUserData<TTud>, E>,
     ) -> Self {
         enum Ty {
             Regular,
@@ -532,6 +967,7 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThread<'a, TPud, TTud> {
 
         let ty = match inner.user_data().state {
             LocalThreadState::ReadyToRun => Ty::Regular,
+            LocalThreadState::OutOfFuel(_) => Ty::Regular,
             LocalThreadState::EmitMessage(_) => Ty::Emit,
             LocalThreadState::MessageWait(_) => Ty::Wait,
         };
@@ -544,35 +980,39 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThread<'a, TPud, TTud> {
     }
 }
 
-impl<'a, TPud, TTud> From<ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud>>
-    for ProcessesCollectionExtrinsicsThread<'a, TPud, TTud>
+impl<'a, TPud, TTud, E: vm::Engine>
+    From<ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud, E>>
+    for ProcessesCollectionExtrinsicsThread<'a, TPud, TTud, E>
 {
-    fn from(thread: ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud>) -> Self {
+    fn from(thread: ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud, E>) -> Self {
         ProcessesCollectionExtrinsicsThread::Regular(thread)
     }
 }
 
-impl<'a, TPud, TTud> From<ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TTud>>
-    for ProcessesCollectionExtrinsicsThread<'a, TPud, TTud>
+impl<'a, TPud, TTud, E: vm::Engine>
+    From<ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TTud, E>>
+    for ProcessesCollectionExtrinsicsThread<'a, TPud, TTud, E>
 {
-    fn from(thread: ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TTud>) -> Self {
+    fn from(thread: ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TTud, E>) -> Self {
         ProcessesCollectionExtrinsicsThread::EmitMessage(thread)
     }
 }
 
-impl<'a, TPud, TTud> From<ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TTud>>
-    for ProcessesCollectionExtrinsicsThread<'a, TPud, TTud>
+impl<'a, TPud, TTud, E: vm::Engine>
+    From<ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TTud, E>>
+    for ProcessesCollectionExtrinsicsThread<'a, TPud, TTud, E>
 {
-    fn from(thread: ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TTud>) -> Self {
+    fn from(thread: ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TTud, E>) -> Self {
         ProcessesCollectionExtrinsicsThread::WaitMessage(thread)
     }
 }
 
-impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadAccess<'a>
-    for ProcessesCollectionExtrinsicsThread<'a, TPud, TTud>
+impl<'a, TPud, TTud, E: vm::Engine> ProcessesCollectionExtrinsicsThreadAccess<'a>
+    for ProcessesCollectionExtrinsicsThread<'a, TPud, TTud, E>
 {
     type ProcessUserData = TPud;
     type ThreadUserData = TTud;
+    type Engine = E;
 
     fn tid(&mut self) -> ThreadId {
         match self {
@@ -590,7 +1030,7 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadAccess<'a>
         }
     }
 
-    fn next_thread(self) -> Option<ProcessesCollectionExtrinsicsThread<'a, TPud, TTud>> {
+    fn next_thread(self) -> Option<ProcessesCollectionExtrinsicsThread<'a, TPud, TTud, E>> {
         match self {
             ProcessesCollectionExtrinsicsThread::Regular(t) => t.next_thread(),
             ProcessesCollectionExtrinsicsThread::EmitMessage(t) => t.next_thread(),
@@ -615,7 +1055,8 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadAccess<'a>
     }
 }
 
-impl<'a, TPud, TTud> fmt::Debug for ProcessesCollectionExtrinsicsThread<'a, TPud, TTud>
+impl<'a, TPud, TTud, E: vm::Engine> fmt::Debug
+    for ProcessesCollectionExtrinsicsThread<'a, TPud, TTud, E>
 where
     TPud: fmt::Debug,
     TTud: fmt::Debug,
@@ -629,11 +1070,12 @@ where
     }
 }
 
-impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadAccess<'a>
-    for ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud>
+impl<'a, TPud, TTud, E: vm::Engine> ProcessesCollectionExtrinsicsThreadAccess<'a>
+    for ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud, E>
 {
     type ProcessUserData = TPud;
     type ThreadUserData = TTud;
+    type Engine = E;
 
     fn tid(&mut self) -> ThreadId {
         self.inner.tid()
@@ -643,7 +1085,7 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadAccess<'a>
         self.inner.pid()
     }
 
-    fn next_thread(self) -> Option<ProcessesCollectionExtrinsicsThread<'a, TPud, TTud>> {
+    fn next_thread(self) -> Option<ProcessesCollectionExtrinsicsThread<'a, TPud, TTud, E>> {
         self.inner
             .next_thread()
             .map(ProcessesCollectionExtrinsicsThread::from_inner)
@@ -658,7 +1100,8 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadAccess<'a>
     }
 }
 
-impl<'a, TPud, TTud> fmt::Debug for ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud>
+impl<'a, TPud, TTud, E: vm::Engine> fmt::Debug
+    for ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud, E>
 where
     TPud: fmt::Debug,
     TTud: fmt::Debug,
@@ -668,7 +1111,9 @@ where
     }
 }
 
-impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TTud> {
+impl<'a, TPud, TTud, E: vm::Engine>
+    ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TTud, E>
+{
     /// Returns true if the caller wants an answer to the message.
     pub fn needs_answer(&mut self) -> bool {
         if let LocalThreadState::EmitMessage(ref emit) = self.inner.user_data().state {
@@ -687,6 +1132,15 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TT
         }
     }
 
+    /// Returns the message to emit.
+    pub fn emit_message(&mut self) -> &EncodedMessage {
+        if let LocalThreadState::EmitMessage(ref emit) = self.inner.user_data().state {
+            &emit.message
+        } else {
+            unreachable!()
+        }
+    }
+
     /// True if the caller allows delays.
     pub fn allow_delay(&mut self) -> bool {
         if let LocalThreadState::EmitMessage(ref emit) = self.inner.user_data().state {
@@ -696,8 +1150,38 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TT
         }
     }
 
+    /// Returns the priority of the message to emit.
+    pub fn priority(&mut self) -> u8 {
+        if let LocalThreadState::EmitMessage(ref emit) = self.inner.user_data().state {
+            emit.priority
+        } else {
+            unreachable!()
+        }
+    }
+
+    /// Returns the number of nanoseconds after which the message must be answered with an
+    /// error, if any.
+    ///
+    /// Always returns `None` if
+    /// [`needs_answer`](ProcessesCollectionExtrinsicsThreadEmitMessage::needs_answer) returns
+    /// `false`.
+    pub fn answer_timeout_nanos(&mut self) -> Option<u64> {
+        if let LocalThreadState::EmitMessage(ref emit) = self.inner.user_data().state {
+            if emit.message_id_write.is_some() {
+                emit.answer_timeout_nanos
+            } else {
+                None
+            }
+        } else {
+            unreachable!()
+        }
+    }
+
     /// Returns the message to emit and resumes the thread.
     ///
+    /// If the message ID can't be written in the memory of the process, the process is instead
+    /// killed the next time the thread runs, as if it had accessed this memory itself.
+    ///
     /// # Panic
     ///
     /// - Panics if `message_id.is_some() != thread.needs_answer()`. In other words, if
@@ -720,28 +1204,58 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TT
                 None => panic!(),
             };
 
+            // The location has been checked when parsing the extrinsic call, and the memory of a
+            // process can't shrink. This is therefore not supposed to fail.
             let mut buf = [0; 8];
             LittleEndian::write_u64(&mut buf, From::from(message_id));
-            self.inner.write_memory(message_id_write, &buf).unwrap();
+            if self.inner.write_memory(message_id_write, &buf).is_err() {
+                self.inner
+                    .resume_with_trap(vm::Trap::MemoryAccessOutOfBounds);
+                return emit.message;
+            }
         } else {
             assert!(message_id.is_none());
         }
 
-        self.inner.resume(Some(wasmi::RuntimeValue::I32(0)));
+        self.inner.resume(Some(WasmValue::I32(0)));
         emit.message
     }
 
     /// Resumes the thread, signalling an error in the emission.
     pub fn refuse_emit(mut self) {
-        self.inner.resume(Some(wasmi::RuntimeValue::I32(1)));
+        self.inner.resume(Some(WasmValue::I32(1)));
+    }
+
+    /// Resumes the thread, signalling that the message doesn't conform to the schema of the
+    /// interface.
+    pub fn refuse_emit_invalid_message(mut self) {
+        self.inner.resume(Some(WasmValue::I32(2)));
+    }
+
+    /// Resumes the thread, signalling that the memory limit of the kernel has been reached.
+    pub fn refuse_emit_memory_limit(mut self) {
+        self.inner.resume(Some(WasmValue::I32(5)));
+    }
+
+    /// Resumes the thread, signalling that the queue of messages of the handler of the interface
+    /// is full.
+    pub fn refuse_emit_inbox_full(mut self) {
+        self.inner.resume(Some(WasmValue::I32(4)));
+    }
+
+    /// Resumes the thread, signalling that emitting the message would exceed the maximum depth
+    /// of a chain of messages waiting for an answer.
+    pub fn refuse_emit_wait_depth_exceeded(mut self) {
+        self.inner.resume(Some(WasmValue::I32(3)));
     }
 }
 
-impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadAccess<'a>
-    for ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TTud>
+impl<'a, TPud, TTud, E: vm::Engine> ProcessesCollectionExtrinsicsThreadAccess<'a>
+    for ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TTud, E>
 {
     type ProcessUserData = TPud;
     type ThreadUserData = TTud;
+    type Engine = E;
 
     fn tid(&mut self) -> ThreadId {
         self.inner.tid()
@@ -751,7 +1265,7 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadAccess<'a>
         self.inner.pid()
     }
 
-    fn next_thread(self) -> Option<ProcessesCollectionExtrinsicsThread<'a, TPud, TTud>> {
+    fn next_thread(self) -> Option<ProcessesCollectionExtrinsicsThread<'a, TPud, TTud, E>> {
         self.inner
             .next_thread()
             .map(ProcessesCollectionExtrinsicsThread::from_inner)
@@ -766,7 +1280,8 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadAccess<'a>
     }
 }
 
-impl<'a, TPud, TTud> fmt::Debug for ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TTud>
+impl<'a, TPud, TTud, E: vm::Engine> fmt::Debug
+    for ProcessesCollectionExtrinsicsThreadEmitMessage<'a, TPud, TTud, E>
 where
     TPud: fmt::Debug,
     TTud: fmt::Debug,
@@ -776,7 +1291,9 @@ where
     }
 }
 
-impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TTud> {
+impl<'a, TPud, TTud, E: vm::Engine>
+    ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TTud, E>
+{
     /// Returns the list of message IDs that the thread is waiting on. In order.
     pub fn message_ids_iter<'b>(&'b mut self) -> impl Iterator<Item = MessageId> + 'b {
         if let LocalThreadState::MessageWait(ref wait) = self.inner.user_data().state {
@@ -804,10 +1321,29 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TT
         }
     }
 
+    /// Returns the maximum number of nanoseconds the thread should be blocked for, if any.
+    ///
+    /// Always returns `None` if [`block`](ProcessesCollectionExtrinsicsThreadWaitMessage::block)
+    /// returns `false`.
+    pub fn timeout_nanos(&mut self) -> Option<u64> {
+        if let LocalThreadState::MessageWait(ref wait) = self.inner.user_data().state {
+            if wait.block {
+                wait.timeout_nanos
+            } else {
+                None
+            }
+        } else {
+            unreachable!()
+        }
+    }
+
     /// Resume the thread, sending back a message.
     ///
     /// `index` must be the index within the list returned by [`message_ids_iter`].
     ///
+    /// If the message can't be written in the memory of the process, the process is instead
+    /// killed the next time the thread runs, as if it had accessed this memory itself.
+    ///
     /// # Panic
     ///
     /// - Panics if the message is too large. You should make sure this is not the case before
@@ -818,7 +1354,7 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TT
         mut self,
         index: usize,
         message: EncodedMessage,
-    ) -> ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud> {
+    ) -> ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud, E> {
         let wait = {
             match mem::replace(
                 &mut self.inner.user_data().state,
@@ -833,25 +1369,27 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TT
         let message_size_u32 = u32::try_from(message.0.len()).unwrap();
         assert!(wait.out_size >= message_size_u32);
 
-        // Write the message in the process's memory.
-        match self.inner.write_memory(wait.out_pointer, &message.0) {
-            Ok(()) => {}
-            Err(_) => panic!(), // TODO: can legit happen
-        };
-
-        // Zero the corresponding entry in the messages to wait upon.
-        match self.inner.write_memory(
-            wait.msg_ids_ptr + u32::try_from(index).unwrap() * 8,
-            &[0; 8],
-        ) {
-            Ok(()) => {}
-            Err(_) => panic!(), // TODO: can legit happen
-        };
+        // Write the message in the process's memory, and zero the corresponding entry in the
+        // messages to wait upon. Both locations have been checked when parsing the extrinsic
+        // call, and the memory of a process can't shrink. This is therefore not supposed to fail.
+        let msg_id_ptr = u32::try_from(index)
+            .ok()
+            .and_then(|index| index.checked_mul(8))
+            .and_then(|offset| wait.msg_ids_ptr.checked_add(offset));
+        let written = self
+            .inner
+            .write_memory(wait.out_pointer, &message.0)
+            .and_then(|()| self.inner.write_memory(msg_id_ptr.ok_or(())?, &[0; 8]));
 
         self.inner.user_data().state = LocalThreadState::ReadyToRun;
-        self.inner.resume(Some(wasmi::RuntimeValue::I32(
-            i32::try_from(message_size_u32).unwrap(),
-        )));
+        if written.is_ok() {
+            self.inner.resume(Some(WasmValue::I32(
+                i32::try_from(message_size_u32).unwrap(),
+            )));
+        } else {
+            self.inner
+                .resume_with_trap(vm::Trap::MemoryAccessOutOfBounds);
+        }
 
         ProcessesCollectionExtrinsicsThreadRegular { inner: self.inner }
     }
@@ -860,11 +1398,10 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TT
     pub fn resume_message_too_big(
         mut self,
         message_size: usize,
-    ) -> ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud> {
+    ) -> ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud, E> {
         self.inner.user_data().state = LocalThreadState::ReadyToRun;
-        self.inner.resume(Some(wasmi::RuntimeValue::I32(
-            i32::try_from(message_size).unwrap(),
-        )));
+        self.inner
+            .resume(Some(WasmValue::I32(i32::try_from(message_size).unwrap())));
 
         ProcessesCollectionExtrinsicsThreadRegular { inner: self.inner }
     }
@@ -874,29 +1411,31 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TT
     /// # Panic
     ///
     /// - Panics if [`block`](ProcessesCollectionExtrinsicsThreadWaitMessage::block) would
-    /// return `true`.
+    /// return `true` and [`timeout_nanos`](ProcessesCollectionExtrinsicsThreadWaitMessage::timeout_nanos)
+    /// would return `None`.
     ///
     pub fn resume_no_message(
         mut self,
-    ) -> ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud> {
+    ) -> ProcessesCollectionExtrinsicsThreadRegular<'a, TPud, TTud, E> {
         if let LocalThreadState::MessageWait(ref wait) = self.inner.user_data().state {
-            assert!(!wait.block);
+            assert!(!wait.block || wait.timeout_nanos.is_some());
         } else {
             unreachable!()
         }
 
         self.inner.user_data().state = LocalThreadState::ReadyToRun;
-        self.inner.resume(Some(wasmi::RuntimeValue::I32(0)));
+        self.inner.resume(Some(WasmValue::I32(0)));
 
         ProcessesCollectionExtrinsicsThreadRegular { inner: self.inner }
     }
 }
 
-impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadAccess<'a>
-    for ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TTud>
+impl<'a, TPud, TTud, E: vm::Engine> ProcessesCollectionExtrinsicsThreadAccess<'a>
+    for ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TTud, E>
 {
     type ProcessUserData = TPud;
     type ThreadUserData = TTud;
+    type Engine = E;
 
     fn tid(&mut self) -> ThreadId {
         self.inner.tid()
@@ -906,7 +1445,7 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadAccess<'a>
         self.inner.pid()
     }
 
-    fn next_thread(self) -> Option<ProcessesCollectionExtrinsicsThread<'a, TPud, TTud>> {
+    fn next_thread(self) -> Option<ProcessesCollectionExtrinsicsThread<'a, TPud, TTud, E>> {
         self.inner
             .next_thread()
             .map(ProcessesCollectionExtrinsicsThread::from_inner)
@@ -921,7 +1460,8 @@ impl<'a, TPud, TTud> ProcessesCollectionExtrinsicsThreadAccess<'a>
     }
 }
 
-impl<'a, TPud, TTud> fmt::Debug for ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TTud>
+impl<'a, TPud, TTud, E: vm::Engine> fmt::Debug
+    for ProcessesCollectionExtrinsicsThreadWaitMessage<'a, TPud, TTud, E>
 where
     TPud: fmt::Debug,
     TTud: fmt::Debug,
@@ -941,29 +1481,49 @@ impl LocalThreadState {
     }
 }
 
+/// Kills the process of the given thread, which has made an invalid call to an extrinsic.
+///
+/// An invalid call most commonly means that the thread has passed a buffer that isn't within the
+/// memory of its process. The process is reported as having crashed because of an out of bounds
+/// memory access, as if it had accessed this buffer itself.
+fn invalid_extrinsic_call<'a, TPud, TTud, E: vm::Engine>(
+    thread: processes::ProcessesCollectionThread<TPud, LocalThreadUserData<TTud>, E>,
+) -> RunOneOutcome<'a, TPud, TTud, E> {
+    let (pid, user_data, dead_threads) = thread.abort_process();
+    RunOneOutcome::ProcessFinished {
+        pid,
+        user_data,
+        dead_threads: dead_threads
+            .into_iter()
+            .map(|(id, state)| (id, state.external_user_data))
+            .collect(),
+        outcome: Err(vm::Trap::MemoryAccessOutOfBounds),
+    }
+}
+
 /// Analyzes a call to `next_message` made by the given thread.
 ///
 /// The `thread` parameter is only used in order to read memory from the process. This function
 /// has no side effect.
 ///
 /// Returns an error if the call is invalid.
-fn parse_extrinsic_next_message<TPud, TTud>(
-    thread: &mut processes::ProcessesCollectionThread<TPud, LocalThreadUserData<TTud>>,
-    params: Vec<wasmi::RuntimeValue>,
+fn parse_extrinsic_next_message<TPud, TTud, E: vm::Engine>(
+    thread: &mut processes::ProcessesCollectionThread<TPud, LocalThreadUserData<TTud>, E>,
+    params: Vec<WasmValue>,
 ) -> Result<MessageWait, ()> {
     // We use an assert here rather than a runtime check because the WASM VM (rather than us) is
     // supposed to check the function signature.
-    assert_eq!(params.len(), 5);
+    assert_eq!(params.len(), 6);
 
-    let msg_ids_ptr = u32::try_from(params[0].try_into::<i32>().ok_or(())?).map_err(|_| ())?;
+    let msg_ids_ptr = u32::try_from(params[0].into_i32().ok_or(())?).map_err(|_| ())?;
     // TODO: consider not copying the message ids and read memory on demand instead
     let msg_ids = {
-        let len = u32::try_from(params[1].try_into::<i32>().ok_or(())?).map_err(|_| ())?;
+        let len = u32::try_from(params[1].into_i32().ok_or(())?).map_err(|_| ())?;
         if len >= 512 {
             // TODO: arbitrary limit in order to not allocate too much memory below; a bit crappy
             return Err(());
         }
-        let mem = thread.read_memory(msg_ids_ptr, len * 8)?;
+        let mem = thread.read_memory(msg_ids_ptr, len.checked_mul(8).ok_or(())?)?;
         let mut out = vec![MessageId::from(0u64); usize::try_from(len).map_err(|_| ())?];
         for (o, i) in out.iter_mut().zip(mem.chunks(8)) {
             let val = byteorder::LittleEndian::read_u64(i);
@@ -972,9 +1532,16 @@ fn parse_extrinsic_next_message<TPud, TTud>(
         out
     };
 
-    let out_pointer = u32::try_from(params[2].try_into::<i32>().ok_or(())?).map_err(|_| ())?;
-    let out_size = u32::try_from(params[3].try_into::<i32>().ok_or(())?).map_err(|_| ())?;
-    let block = params[4].try_into::<i32>().ok_or(())? != 0;
+    let out_pointer = u32::try_from(params[2].into_i32().ok_or(())?).map_err(|_| ())?;
+    let out_size = u32::try_from(params[3].into_i32().ok_or(())?).map_err(|_| ())?;
+    // The message is written later, when it is available. We check now that the buffer is valid,
+    // so that the thread can't make the delivery fail.
+    thread.check_memory_range(out_pointer, out_size)?;
+    let block = params[4].into_i32().ok_or(())? != 0;
+    let timeout_nanos = match params[5].into_i64().ok_or(())? as u64 {
+        0 => None,
+        n => Some(n),
+    };
 
     Ok(MessageWait {
         msg_ids,
@@ -982,6 +1549,7 @@ fn parse_extrinsic_next_message<TPud, TTud>(
         out_pointer,
         out_size,
         block,
+        timeout_nanos,
     })
 }
 
@@ -991,59 +1559,75 @@ fn parse_extrinsic_next_message<TPud, TTud>(
 /// has no side effect.
 ///
 /// Returns an error if the call is invalid.
-fn parse_extrinsic_emit_message<TPud, TTud>(
-    thread: &mut processes::ProcessesCollectionThread<TPud, LocalThreadUserData<TTud>>,
-    params: Vec<wasmi::RuntimeValue>,
+fn parse_extrinsic_emit_message<TPud, TTud, E: vm::Engine>(
+    thread: &mut processes::ProcessesCollectionThread<TPud, LocalThreadUserData<TTud>, E>,
+    params: Vec<WasmValue>,
 ) -> Result<EmitMessage, ()> {
     // We use an assert here rather than a runtime check because the WASM VM (rather than us) is
-    // supposed to check the function signature.
-    assert_eq!(params.len(), 6);
+    // supposed to check the function signature. `emit_message_with_timeout` has an additional
+    // parameter compared to `emit_message`.
+    assert!(params.len() == 7 || params.len() == 8);
 
     let interface: InterfaceHash = {
-        let addr = u32::try_from(params[0].try_into::<i32>().ok_or(())?).map_err(|_| ())?;
+        let addr = u32::try_from(params[0].into_i32().ok_or(())?).map_err(|_| ())?;
         InterfaceHash::from(
             <[u8; 32]>::try_from(&thread.read_memory(addr, 32)?[..]).map_err(|_| ())?,
         )
     };
 
     let message = {
-        let addr = u32::try_from(params[1].try_into::<i32>().ok_or(())?).map_err(|_| ())?;
-        let num_bufs = u32::try_from(params[2].try_into::<i32>().ok_or(())?).map_err(|_| ())?;
-        let mut out_msg = Vec::new();
-        for buf_n in 0..num_bufs {
-            let sub_buf_ptr = thread.read_memory(addr + 8 * buf_n, 4).map_err(|_| ())?;
-            let sub_buf_ptr = LittleEndian::read_u32(&sub_buf_ptr);
-            let sub_buf_sz = thread
-                .read_memory(addr + 8 * buf_n + 4, 4)
-                .map_err(|_| ())?;
-            let sub_buf_sz = LittleEndian::read_u32(&sub_buf_sz);
-            if out_msg.len() + usize::try_from(sub_buf_sz).map_err(|_| ())? >= 16 * 1024 * 1024 {
-                // TODO: arbitrary maximum message length
-                panic!("Max message length reached");
-                //return Err(());
+        let addr = u32::try_from(params[1].into_i32().ok_or(())?).map_err(|_| ())?;
+        let num_bufs = u32::try_from(params[2].into_i32().ok_or(())?).map_err(|_| ())?;
+
+        // Read the list of buffers first, in order to know the total size of the message.
+        let sub_bufs = thread.read_memory_buffers_list(addr, num_bufs)?;
+        let mut total_sz = 0usize;
+        for (_, sub_buf_sz) in &sub_bufs {
+            total_sz = total_sz
+                .checked_add(usize::try_from(*sub_buf_sz).map_err(|_| ())?)
+                .ok_or(())?;
+            if total_sz >= MAX_MESSAGE_SIZE {
+                return Err(());
             }
-            out_msg.extend_from_slice(
-                &thread
-                    .read_memory(sub_buf_ptr, sub_buf_sz)
-                    .map_err(|_| ())?,
-            );
         }
-        EncodedMessage(out_msg)
+
+        // The message has to be copied out of the memory of the process anyway. If it consists
+        // of a single buffer, we use that copy as it is rather than copying it a second time.
+        if let [(sub_buf_ptr, sub_buf_sz)] = sub_bufs[..] {
+            EncodedMessage(thread.read_memory(sub_buf_ptr, sub_buf_sz)?)
+        } else {
+            let mut out_msg = Vec::with_capacity(total_sz);
+            for (sub_buf_ptr, sub_buf_sz) in sub_bufs {
+                out_msg.extend_from_slice(&thread.read_memory(sub_buf_ptr, sub_buf_sz)?);
+            }
+            EncodedMessage(out_msg)
+        }
     };
 
-    let needs_answer = params[3].try_into::<i32>().ok_or(())? != 0;
-    let allow_delay = params[4].try_into::<i32>().ok_or(())? != 0;
+    let needs_answer = params[3].into_i32().ok_or(())? != 0;
+    let allow_delay = params[4].into_i32().ok_or(())? != 0;
+    let priority = u8::try_from(params[5].into_i32().ok_or(())?).map_err(|_| ())?;
     let message_id_write = if needs_answer {
-        Some(u32::try_from(params[5].try_into::<i32>().ok_or(())?).map_err(|_| ())?)
+        let addr = u32::try_from(params[6].into_i32().ok_or(())?).map_err(|_| ())?;
+        // The message ID is written later, when the message is accepted. We check now that the
+        // location is valid, so that the thread can't make the emission fail.
+        thread.check_memory_range(addr, 8)?;
+        Some(addr)
     } else {
         None
     };
+    let answer_timeout_nanos = match params.get(7) {
+        Some(timeout) => Some(u64::try_from(timeout.into_i64().ok_or(())?).map_err(|_| ())?),
+        None => None,
+    };
 
     Ok(EmitMessage {
         interface,
         message_id_write,
         message,
         allow_delay,
+        priority,
+        answer_timeout_nanos,
     })
 }
 
@@ -1053,23 +1637,23 @@ fn parse_extrinsic_emit_message<TPud, TTud>(
 /// has no side effect.
 ///
 /// Returns an error if the call is invalid.
-fn parse_extrinsic_emit_answer<TPud, TTud>(
-    thread: &mut processes::ProcessesCollectionThread<TPud, LocalThreadUserData<TTud>>,
-    params: Vec<wasmi::RuntimeValue>,
+fn parse_extrinsic_emit_answer<TPud, TTud, E: vm::Engine>(
+    thread: &mut processes::ProcessesCollectionThread<TPud, LocalThreadUserData<TTud>, E>,
+    params: Vec<WasmValue>,
 ) -> Result<EmitAnswer, ()> {
     // We use an assert here rather than a runtime check because the WASM VM (rather than us) is
     // supposed to check the function signature.
     assert_eq!(params.len(), 3);
 
     let message_id = {
-        let addr = u32::try_from(params[0].try_into::<i32>().ok_or(())?).map_err(|_| ())?;
+        let addr = u32::try_from(params[0].into_i32().ok_or(())?).map_err(|_| ())?;
         let buf = thread.read_memory(addr, 8)?;
         MessageId::from(byteorder::LittleEndian::read_u64(&buf))
     };
 
     let response = {
-        let addr = u32::try_from(params[1].try_into::<i32>().ok_or(())?).map_err(|_| ())?;
-        let sz = u32::try_from(params[2].try_into::<i32>().ok_or(())?).map_err(|_| ())?;
+        let addr = u32::try_from(params[1].into_i32().ok_or(())?).map_err(|_| ())?;
+        let sz = u32::try_from(params[2].into_i32().ok_or(())?).map_err(|_| ())?;
         EncodedMessage(thread.read_memory(addr, sz)?)
     };
 
@@ -1079,6 +1663,41 @@ fn parse_extrinsic_emit_answer<TPud, TTud>(
     })
 }
 
+/// Analyzes a call to `emit_answer_chunk` made by the given thread.
+///
+/// The `thread` parameter is only used in order to read memory from the process. This function
+/// has no side effect.
+///
+/// Returns an error if the call is invalid.
+fn parse_extrinsic_emit_answer_chunk<TPud, TTud, E: vm::Engine>(
+    thread: &mut processes::ProcessesCollectionThread<TPud, LocalThreadUserData<TTud>, E>,
+    params: Vec<WasmValue>,
+) -> Result<EmitAnswerChunk, ()> {
+    // We use an assert here rather than a runtime check because the WASM VM (rather than us) is
+    // supposed to check the function signature.
+    assert_eq!(params.len(), 4);
+
+    let message_id = {
+        let addr = u32::try_from(params[0].into_i32().ok_or(())?).map_err(|_| ())?;
+        let buf = thread.read_memory(addr, 8)?;
+        MessageId::from(byteorder::LittleEndian::read_u64(&buf))
+    };
+
+    let chunk = {
+        let addr = u32::try_from(params[1].into_i32().ok_or(())?).map_err(|_| ())?;
+        let sz = u32::try_from(params[2].into_i32().ok_or(())?).map_err(|_| ())?;
+        thread.read_memory(addr, sz)?
+    };
+
+    let is_final = params[3].into_i32().ok_or(())? != 0;
+
+    Ok(EmitAnswerChunk {
+        message_id,
+        chunk,
+        is_final,
+    })
+}
+
 /// Analyzes a call to `emit_message_error` made by the given thread.
 /// Returns the message for which to notify of an error.
 ///
@@ -1086,16 +1705,40 @@ fn parse_extrinsic_emit_answer<TPud, TTud>(
 /// has no side effect.
 ///
 /// Returns an error if the call is invalid.
-fn parse_extrinsic_emit_message_error<TPud, TTud>(
-    thread: &mut processes::ProcessesCollectionThread<TPud, LocalThreadUserData<TTud>>,
-    params: Vec<wasmi::RuntimeValue>,
+fn parse_extrinsic_emit_message_error<TPud, TTud, E: vm::Engine>(
+    thread: &mut processes::ProcessesCollectionThread<TPud, LocalThreadUserData<TTud>, E>,
+    params: Vec<WasmValue>,
+) -> Result<MessageId, ()> {
+    // We use an assert here rather than a runtime check because the WASM VM (rather than us) is
+    // supposed to check the function signature.
+    assert_eq!(params.len(), 1);
+
+    let msg_id = {
+        let addr = u32::try_from(params[0].into_i32().ok_or(())?).map_err(|_| ())?;
+        let buf = thread.read_memory(addr, 8)?;
+        MessageId::from(byteorder::LittleEndian::read_u64(&buf))
+    };
+
+    Ok(msg_id)
+}
+
+/// Analyzes a call to `cancel_message` made by the given thread.
+/// Returns the message whose answer is no longer expected.
+///
+/// The `thread` parameter is only used in order to read memory from the process. This function
+/// has no side effect.
+///
+/// Returns an error if the call is invalid.
+fn parse_extrinsic_cancel_message<TPud, TTud, E: vm::Engine>(
+    thread: &mut processes::ProcessesCollectionThread<TPud, LocalThreadUserData<TTud>, E>,
+    params: Vec<WasmValue>,
 ) -> Result<MessageId, ()> {
     // We use an assert here rather than a runtime check because the WASM VM (rather than us) is
     // supposed to check the function signature.
     assert_eq!(params.len(), 1);
 
     let msg_id = {
-        let addr = u32::try_from(params[0].try_into::<i32>().ok_or(())?).map_err(|_| ())?;
+        let addr = u32::try_from(params[0].into_i32().ok_or(())?).map_err(|_| ())?;
         let buf = thread.read_memory(addr, 8)?;
         MessageId::from(byteorder::LittleEndian::read_u64(&buf))
     };
@@ -19,22 +19,28 @@ use crate::scheduler::{
     extrinsics::{self, ProcessesCollectionExtrinsicsThreadAccess as _},
     vm,
 };
+use crate::schema::MessageSchema;
+use crate::signature::WasmValue;
+use crate::time::{Deadline, TimeSource};
 use crate::InterfaceHash;
 
-use alloc::{collections::VecDeque, vec::Vec};
-use core::{convert::TryFrom, iter, mem};
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+use core::{cmp, convert::TryFrom, iter, mem, ops, time::Duration};
 use crossbeam_queue::SegQueue;
 use hashbrown::{hash_map::Entry, HashMap, HashSet};
-use redshirt_syscalls_interface::{Encode, EncodedMessage, MessageId, Pid, ThreadId};
+use redshirt_syscalls_interface::{AnswerError, Encode, EncodedMessage, MessageId, Pid, ThreadId};
 use smallvec::SmallVec;
 
 /// Handles scheduling processes and inter-process communications.
-pub struct Core {
+///
+/// The code of the processes is executed by the [`Engine`](vm::Engine) passed as the generic
+/// parameter `E`. See [`CoreBuilder::build_with_engine`].
+pub struct Core<E: vm::Engine = vm::WasmiEngine> {
     /// Queue of events to return in priority when `run` is called.
     pending_events: SegQueue<CoreRunOutcomeInner>,
 
     /// List of running processes.
-    processes: extrinsics::ProcessesCollectionExtrinsics<Process, ()>,
+    processes: extrinsics::ProcessesCollectionExtrinsics<Process, (), E>,
 
     /// List of `Pid`s that have been reserved during the construction.
     ///
@@ -44,6 +50,18 @@ pub struct Core {
     /// For each interface, which program is fulfilling it.
     interfaces: HashMap<InterfaceHash, InterfaceState>,
 
+    /// Schemas that the messages emitted on interfaces must conform to. Interfaces that aren't
+    /// in this list accept any message.
+    interface_schemas: HashMap<InterfaceHash, MessageSchema>,
+
+    /// Interfaces whose messages are coalesced. See [`Core::set_interface_coalescing`].
+    coalescing_interfaces: HashSet<InterfaceHash>,
+
+    /// Messages to answer as soon as a handler is registered for the given interface. See
+    /// [`Core::answer_on_interface_registration`].
+    // TODO: call shrink_to from time to time
+    interface_waiters: HashMap<InterfaceHash, SmallVec<[MessageId; 4]>>,
+
     /// Pool of identifiers for messages.
     message_id_pool: IdPool,
 
@@ -51,6 +69,114 @@ pub struct Core {
     // TODO: doc about hash safety
     // TODO: call shrink_to from time to time
     messages_to_answer: HashMap<MessageId, Pid>,
+
+    /// For each message in `messages_to_answer`, depth of the chain of messages waiting for an
+    /// answer it belongs to. See [`Core::message_wait_depth`].
+    // TODO: call shrink_to from time to time
+    message_wait_depths: HashMap<MessageId, u32>,
+
+    /// For each message in `messages_to_answer` whose handler has started emitting the answer
+    /// piece by piece with `emit_answer_chunk`, concatenation of the pieces emitted so far.
+    // TODO: call shrink_to from time to time
+    answer_chunks: HashMap<MessageId, Vec<u8>>,
+
+    /// If `Some`, emitting a message whose depth in the chain of messages waiting for an answer
+    /// would be above this value fails. See [`CoreBuilder::with_max_wait_depth`].
+    max_wait_depth: Option<u32>,
+
+    /// If `Some`, maximum total size of the linear memories of the processes above which
+    /// starting processes and emitting messages fails. See [`CoreBuilder::with_memory_limit`].
+    memory_limit: Option<usize>,
+
+    /// If `Some`, interface messages are queued by order of priority of their emitter. See
+    /// [`CoreBuilder::with_priority_inbox`].
+    ///
+    /// Contains the priority of each process, as set with [`Core::set_process_priority`].
+    /// Processes that aren't in this list have a priority of `0`.
+    // TODO: call shrink_to from time to time
+    process_priorities: Option<HashMap<Pid, u8>>,
+
+    /// If `Some`, maximum number of messages that can be waiting in the queue of a process
+    /// before emitting messages towards it fails. See [`CoreBuilder::with_inbox_limit`].
+    inbox_limit: Option<usize>,
+
+    /// What to do with the notifications generated by the `Core` towards a process whose queue
+    /// of messages is full.
+    inbox_overflow_policy: InboxOverflowPolicy,
+
+    /// For each process whose queue of messages has reached `inbox_limit`, threads that have
+    /// emitted a message with `allow_delay` towards it and that wait for the queue to have room.
+    // TODO: call shrink_to from time to time
+    full_inbox_waiters: HashMap<Pid, VecDeque<ThreadId>>,
+
+    /// Processes that have terminated and whose cleanup hasn't been fully performed yet.
+    ///
+    /// The cleanup is split in steps, and at most
+    /// [`teardown_batch_size`](Core::teardown_batch_size) steps are performed every time
+    /// [`run`](Core::run) is called.
+    pending_teardowns: VecDeque<ProcessTeardown>,
+
+    /// Maximum number of cleanup steps performed at once. See
+    /// [`CoreBuilder::with_teardown_batch_size`].
+    teardown_batch_size: usize,
+
+    /// Source of time used to enforce timeouts. See [`CoreBuilder::with_time_source`].
+    time_source: Option<Box<dyn TimeSource>>,
+
+    /// Function called whenever a process traps. See [`CoreBuilder::with_trap_hook`].
+    trap_hook: Option<Box<dyn Fn(Pid, &TrapInfo) + Send + Sync>>,
+
+    /// For threads that are blocked in `next_message` with a timeout, the moment when they must
+    /// be resumed.
+    ///
+    /// Entries aren't removed when a thread is woken up by a message. Instead, we check the state
+    /// of the thread when the deadline is reached.
+    // TODO: call shrink_to from time to time
+    message_wait_deadlines: HashMap<ThreadId, Deadline>,
+
+    /// For messages in `messages_to_answer` that have been emitted with a timeout, the moment
+    /// when they must be answered with [`AnswerError::TimedOut`].
+    // TODO: call shrink_to from time to time
+    answer_deadlines: HashMap<MessageId, Deadline>,
+}
+
+/// Estimation of the memory used by a [`Core`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MemoryFootprint {
+    /// Total size, in bytes, of the linear memories of all the processes.
+    pub processes_memory: usize,
+    /// Total size, in bytes, of the messages that are waiting to be delivered.
+    pub queued_messages: usize,
+    /// Approximate size, in bytes, of the internal data structures.
+    pub internal_structures: usize,
+}
+
+/// Information about a trap, passed to the hook set with [`CoreBuilder::with_trap_hook`].
+#[derive(Debug)]
+pub struct TrapInfo<'a> {
+    /// The trap that has happened.
+    ///
+    /// > **Note**: The interpreter doesn't expose the function that was being executed nor the
+    /// >           state of the stack at the time of the trap.
+    pub trap: &'a vm::Trap,
+}
+
+/// What to do when the `Core` generates a notification, such as a
+/// [`ProcessDestroyed`](redshirt_syscalls_interface::ffi::Message::ProcessDestroyed) message,
+/// towards a process whose queue of messages is full. See [`CoreBuilder::with_inbox_limit`].
+///
+/// Notifications are never discarded. Only interface messages that don't need an answer can be
+/// discarded in order to make room for them. If there isn't any, the queue grows above the limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InboxOverflowPolicy {
+    /// The notification is queued anyway, and the queue grows above the limit.
+    ForceGrow,
+    /// The oldest interface message that doesn't need an answer is discarded in favour of the
+    /// notification.
+    DropOldest,
+    /// The most recently queued interface message that doesn't need an answer is discarded in
+    /// favour of the notification.
+    DropNewest,
 }
 
 /// Which way an interface is handled.
@@ -74,11 +200,27 @@ pub struct CoreBuilder {
     reserved_pids: HashSet<Pid>,
     /// Builder for the [`processes`][Core::processes] field in `Core`.
     inner_builder: extrinsics::ProcessesCollectionExtrinsicsBuilder,
+    /// See the corresponding field in `Core`.
+    memory_limit: Option<usize>,
+    /// See the corresponding field in `Core`.
+    max_wait_depth: Option<u32>,
+    /// See the corresponding field in `Core`.
+    inbox_limit: Option<usize>,
+    /// See the corresponding field in `Core`.
+    inbox_overflow_policy: InboxOverflowPolicy,
+    /// See the corresponding field in `Core`.
+    priority_inbox: bool,
+    /// See the corresponding field in `Core`.
+    teardown_batch_size: usize,
+    /// See the corresponding field in `Core`.
+    time_source: Option<Box<dyn TimeSource>>,
+    /// See the corresponding field in `Core`.
+    trap_hook: Option<Box<dyn Fn(Pid, &TrapInfo) + Send + Sync>>,
 }
 
 /// Outcome of calling [`run`](Core::run).
 // TODO: #[derive(Debug)]
-pub enum CoreRunOutcome<'a> {
+pub enum CoreRunOutcome<'a, E: vm::Engine = vm::WasmiEngine> {
     /// A program has stopped, either because the main function has stopped or a problem has
     /// occurred.
     ProgramFinished {
@@ -99,20 +241,29 @@ pub enum CoreRunOutcome<'a> {
         /// How the program ended. If `Ok`, it has gracefully terminated. If `Err`, something
         /// bad happened.
         // TODO: force Ok to i32?
-        outcome: Result<Option<wasmi::RuntimeValue>, wasmi::Trap>,
+        outcome: Result<Option<WasmValue>, vm::Trap>,
     },
 
     /// Thread has tried to emit a message on an interface that isn't registered. The thread is
-    /// now in sleep mode. You can either wake it up by calling [`set_interface_handler`], or
-    /// resume the thread with an "interface not available error" by calling . // TODO
+    /// now in sleep mode, and is woken up when a handler is set by calling
+    /// [`set_interface_handler`](Core::set_interface_handler).
     ThreadWaitUnavailableInterface {
         /// Thread that emitted the message.
-        thread: CoreThread<'a>,
+        thread: CoreThread<'a, E>,
 
         /// Interface that the thread is trying to access.
         interface: InterfaceHash,
     },
 
+    /// Thread has run out of fuel and is now paused. You can either resume it by calling
+    /// [`Core::refuel`], or kill its process.
+    ///
+    /// Only happens if [`CoreBuilder::with_fuel_limit`] has been called.
+    ThreadOutOfFuel {
+        /// Thread that has run out of fuel.
+        thread: CoreThread<'a, E>,
+    },
+
     /// A process has emitted a message on an interface registered with a reserved PID.
     ReservedPidInterfaceMessage {
         pid: Pid,
@@ -124,13 +275,22 @@ pub enum CoreRunOutcome<'a> {
     /// Response to a message emitted using [`Core::emit_interface_message_answer`].
     MessageResponse {
         message_id: MessageId,
-        response: Result<EncodedMessage, ()>,
+        response: Result<EncodedMessage, AnswerError>,
     },
 
     /// Nothing to do. No thread is ready to run.
     Idle,
 }
 
+/// Outcome of calling [`step`](Core::step).
+pub enum CoreStepOutcome<'a, E: vm::Engine = vm::WasmiEngine> {
+    /// The step has produced an outcome, exactly like [`Core::run`] would have.
+    Outcome(CoreRunOutcome<'a, E>),
+    /// The step has made progress, but nothing needs to be reported. For example, a thread has
+    /// been run until it has called `next_message`.
+    Progress,
+}
+
 /// Because of lifetime issues, this is the same as `CoreRunOutcome` but that holds `Pid`s instead
 /// of `CoreProcess`es.
 // TODO: remove this enum and solve borrowing issues
@@ -140,12 +300,15 @@ enum CoreRunOutcomeInner {
         unhandled_messages: Vec<MessageId>,
         cancelled_messages: Vec<MessageId>,
         unregistered_interfaces: Vec<InterfaceHash>,
-        outcome: Result<Option<wasmi::RuntimeValue>, wasmi::Trap>,
+        outcome: Result<Option<WasmValue>, vm::Trap>,
     },
     ThreadWaitUnavailableInterface {
         thread: ThreadId,
         interface: InterfaceHash,
     },
+    ThreadOutOfFuel {
+        thread: ThreadId,
+    },
     ReservedPidInterfaceMessage {
         // TODO: `pid` is redundant with `message_id`; should just be a better API with an `Event` handle struct
         pid: Pid,
@@ -155,7 +318,7 @@ enum CoreRunOutcomeInner {
     },
     MessageResponse {
         message_id: MessageId,
-        response: Result<EncodedMessage, ()>,
+        response: Result<EncodedMessage, AnswerError>,
     },
     LoopAgain,
     Idle,
@@ -170,7 +333,7 @@ struct Process {
     /// and [`InterfaceMessage::index_in_list`](redshirt_syscalls_interface::ffi::InterfaceMessage::index_in_list) fields are
     /// set to a dummy value, and must be filled before actually delivering the message.
     // TODO: call shrink_to_fit from time to time
-    messages_queue: VecDeque<redshirt_syscalls_interface::ffi::Message>,
+    messages_queue: MessagesQueue,
 
     /// Interfaces that the process has registered.
     registered_interfaces: SmallVec<[InterfaceHash; 1]>,
@@ -182,20 +345,33 @@ struct Process {
     /// List of messages that the process has emitted and that are waiting for an answer.
     emitted_messages: SmallVec<[MessageId; 8]>,
 
-    /// List of messages that the process is expected to answer.
+    /// List of messages that the process has retrieved and is expected to answer.
     messages_to_answer: SmallVec<[MessageId; 8]>,
 }
 
+/// Cleanup remaining to be performed after a process has terminated.
+struct ProcessTeardown {
+    /// Id of the process that has terminated.
+    pid: Pid,
+
+    /// Interfaces whose handler hasn't been notified yet of the process being destroyed.
+    used_interfaces: hashbrown::hash_set::IntoIter<InterfaceHash>,
+
+    /// Messages that were queued for the process and that it never retrieved. Interface messages
+    /// that need an answer are answered with an error.
+    messages_queue: VecDeque<redshirt_syscalls_interface::ffi::Message>,
+}
+
 /// Access to a process within the core.
-pub struct CoreProcess<'a> {
+pub struct CoreProcess<'a, E: vm::Engine = vm::WasmiEngine> {
     /// Access to the process within the inner collection.
-    process: extrinsics::ProcessesCollectionExtrinsicsProc<'a, Process, ()>,
+    process: extrinsics::ProcessesCollectionExtrinsicsProc<'a, Process, (), E>,
 }
 
 /// Access to a thread within the core.
-pub struct CoreThread<'a> {
+pub struct CoreThread<'a, E: vm::Engine = vm::WasmiEngine> {
     /// Access to the thread within the inner collection.
-    thread: extrinsics::ProcessesCollectionExtrinsicsThread<'a, Process, ()>,
+    thread: extrinsics::ProcessesCollectionExtrinsicsThread<'a, Process, (), E>,
 }
 
 impl Core {
@@ -204,59 +380,138 @@ impl Core {
         CoreBuilder {
             reserved_pids: HashSet::new(),
             inner_builder: extrinsics::ProcessesCollectionExtrinsicsBuilder::default(),
+            memory_limit: None,
+            max_wait_depth: None,
+            inbox_limit: None,
+            inbox_overflow_policy: InboxOverflowPolicy::ForceGrow,
+            priority_inbox: false,
+            teardown_batch_size: 64,
+            time_source: None,
+            trap_hook: None,
         }
     }
+}
 
+impl<E: vm::Engine> Core<E> {
     /// Run the core once.
+    ///
+    /// This function isn't reentrant. Methods that resolve messages, such as
+    /// [`answer_message`](Core::answer_message), can be called while handling the outcome of a
+    /// call to `run`, but never directly produce an outcome. Instead, the outcomes they generate
+    /// are queued and returned by the next call to `run`.
     // TODO: make multithreaded
-    pub fn run(&mut self) -> CoreRunOutcome {
+    pub fn run(&mut self) -> CoreRunOutcome<E> {
         loop {
-            break match self.run_inner() {
-                CoreRunOutcomeInner::Idle => CoreRunOutcome::Idle,
+            match self.run_inner() {
                 CoreRunOutcomeInner::LoopAgain => continue,
-                CoreRunOutcomeInner::ProgramFinished {
-                    pid,
-                    unhandled_messages,
-                    cancelled_messages,
-                    unregistered_interfaces,
-                    outcome,
-                } => CoreRunOutcome::ProgramFinished {
-                    pid,
-                    unhandled_messages,
-                    cancelled_messages,
-                    unregistered_interfaces,
-                    outcome,
-                },
-                CoreRunOutcomeInner::ThreadWaitUnavailableInterface { thread, interface } => {
-                    CoreRunOutcome::ThreadWaitUnavailableInterface {
-                        thread: CoreThread {
-                            thread: match self.processes.thread_by_id(thread) {
-                                Some(t) => t,
-                                None => unreachable!(),
-                            },
+                inner => break self.outer_outcome(inner),
+            }
+        }
+    }
+
+    /// Same as [`run`](Core::run), but if nothing is ready to run, blocks the current thread
+    /// until `deadline` is reached then returns [`CoreRunOutcome::Idle`].
+    ///
+    /// While blocked, the `Core` also wakes up when one of its own timeouts (see
+    /// [`next_deadline`](Core::next_deadline)) elapses, and resumes running threads. Messages
+    /// answered by the embedder can't be noticed while this function is blocked, as the state
+    /// of the `Core` can only be modified by calling its methods.
+    #[cfg(feature = "std")]
+    pub fn run_until(&mut self, deadline: std::time::Instant) -> CoreRunOutcome<E> {
+        loop {
+            match self.run_inner() {
+                CoreRunOutcomeInner::LoopAgain => continue,
+                CoreRunOutcomeInner::Idle => {}
+                inner => return self.outer_outcome(inner),
+            }
+
+            let now = std::time::Instant::now();
+            if deadline <= now {
+                return CoreRunOutcome::Idle;
+            }
+
+            let mut sleep = deadline - now;
+            if let (Some(source), Some(next)) = (&self.time_source, self.next_deadline()) {
+                sleep = cmp::min(sleep, next.remaining(&**source));
+            }
+            std::thread::sleep(sleep);
+        }
+    }
+
+    /// Performs a single scheduling step.
+    ///
+    /// Contrary to [`run`](Core::run), which keeps running threads until something needs to be
+    /// reported, this function runs at most one thread until it is interrupted, then returns.
+    /// Combined with [`CoreBuilder::with_deterministic_scheduling`], this makes it possible to
+    /// observe the interleaving of the threads one step at a time.
+    pub fn step(&mut self) -> CoreStepOutcome<E> {
+        match self.run_inner() {
+            CoreRunOutcomeInner::LoopAgain => CoreStepOutcome::Progress,
+            inner => CoreStepOutcome::Outcome(self.outer_outcome(inner)),
+        }
+    }
+
+    /// Turns a [`CoreRunOutcomeInner`] returned by [`run_inner`](Core::run_inner) into a
+    /// [`CoreRunOutcome`].
+    ///
+    /// # Panic
+    ///
+    /// - Panics if `inner` is [`CoreRunOutcomeInner::LoopAgain`].
+    ///
+    fn outer_outcome(&mut self, inner: CoreRunOutcomeInner) -> CoreRunOutcome<E> {
+        match inner {
+            CoreRunOutcomeInner::LoopAgain => unreachable!(),
+            CoreRunOutcomeInner::Idle => CoreRunOutcome::Idle,
+            CoreRunOutcomeInner::ProgramFinished {
+                pid,
+                unhandled_messages,
+                cancelled_messages,
+                unregistered_interfaces,
+                outcome,
+            } => CoreRunOutcome::ProgramFinished {
+                pid,
+                unhandled_messages,
+                cancelled_messages,
+                unregistered_interfaces,
+                outcome,
+            },
+            CoreRunOutcomeInner::ThreadWaitUnavailableInterface { thread, interface } => {
+                CoreRunOutcome::ThreadWaitUnavailableInterface {
+                    thread: CoreThread {
+                        thread: match self.processes.thread_by_id(thread) {
+                            Some(t) => t,
+                            None => unreachable!(),
                         },
-                        interface,
-                    }
-                }
-                CoreRunOutcomeInner::ReservedPidInterfaceMessage {
-                    pid,
-                    message_id,
-                    interface,
-                    message,
-                } => CoreRunOutcome::ReservedPidInterfaceMessage {
-                    pid,
-                    message_id,
+                    },
                     interface,
-                    message,
-                },
-                CoreRunOutcomeInner::MessageResponse {
-                    message_id,
-                    response,
-                } => CoreRunOutcome::MessageResponse {
-                    message_id,
-                    response,
+                }
+            }
+            CoreRunOutcomeInner::ThreadOutOfFuel { thread } => CoreRunOutcome::ThreadOutOfFuel {
+                thread: CoreThread {
+                    thread: match self.processes.thread_by_id(thread) {
+                        Some(t) => t,
+                        None => unreachable!(),
+                    },
                 },
-            };
+            },
+            CoreRunOutcomeInner::ReservedPidInterfaceMessage {
+                pid,
+                message_id,
+                interface,
+                message,
+            } => CoreRunOutcome::ReservedPidInterfaceMessage {
+                pid,
+                message_id,
+                interface,
+                message,
+            },
+            CoreRunOutcomeInner::MessageResponse {
+                message_id,
+                response,
+            } => CoreRunOutcome::MessageResponse {
+                message_id,
+                response,
+            },
         }
     }
 
@@ -268,6 +523,52 @@ impl Core {
             return ev;
         }
 
+        // Cleaning up after the processes that have terminated is done in small steps, so that
+        // a process with a lot of outstanding messages doesn't stall everything else. The events
+        // generated by the cleanup are returned by the next calls to `run_inner`.
+        if !self.pending_teardowns.is_empty() {
+            self.advance_teardowns();
+            if let Ok(ev) = self.pending_events.pop() {
+                return ev;
+            }
+        }
+
+        // Resume the threads whose `next_message` timeout has elapsed.
+        if let Some(expired) = self.expired_message_wait() {
+            self.message_wait_deadlines.remove(&expired);
+            if let Some(extrinsics::ProcessesCollectionExtrinsicsThread::WaitMessage(thread)) =
+                self.processes.thread_by_id(expired)
+            {
+                thread.resume_no_message();
+            }
+            return CoreRunOutcomeInner::LoopAgain;
+        }
+
+        // Answer with an error the messages whose answer timeout has elapsed. The answer of the
+        // handler, if it ever comes, is then discarded.
+        if let Some(expired) = self.expired_answer_deadline() {
+            self.answer_deadlines.remove(&expired);
+            let emitter_alive = match self.messages_to_answer.get(&expired) {
+                Some(pid) => self.processes.process_by_id(*pid).is_some(),
+                None => false,
+            };
+            if emitter_alive {
+                let _ev = self.answer_message_inner(expired, Err(AnswerError::TimedOut));
+                debug_assert!(_ev.is_none());
+            } else {
+                // The emitter has terminated in the meanwhile.
+                self.messages_to_answer.remove(&expired);
+                self.message_wait_depths.remove(&expired);
+                self.answer_chunks.remove(&expired);
+            }
+            return CoreRunOutcomeInner::LoopAgain;
+        }
+
+        // Retry the emits that were waiting for the queue of their destination to have room.
+        if let Some(thread_id) = self.unblocked_full_inbox_waiter() {
+            return self.process_emit(thread_id);
+        }
+
         match self.processes.run() {
             extrinsics::RunOneOutcome::ProcessFinished {
                 pid,
@@ -275,183 +576,564 @@ impl Core {
                 dead_threads,
                 user_data,
             } => {
+                let mut dead_thread_ids = Vec::with_capacity(dead_threads.len());
                 for (dead_thread_id, dead_thread_state) in dead_threads {
+                    self.message_wait_deadlines.remove(&dead_thread_id);
+                    dead_thread_ids.push(dead_thread_id);
                     match dead_thread_state {
                         _ => {} // TODO:
                     }
                 }
+                self.remove_unavailable_interface_waiters(&dead_thread_ids);
 
-                // Unregister the interfaces this program had registered.
-                let mut unregistered_interfaces = Vec::new();
-                for interface in user_data.registered_interfaces {
-                    let _interface = self.interfaces.remove(&interface);
-                    debug_assert_eq!(_interface, Some(InterfaceState::Process(pid)));
-                    unregistered_interfaces.push(interface);
+                if let (Some(hook), Err(trap)) = (&self.trap_hook, &outcome) {
+                    hook(pid, &TrapInfo { trap });
                 }
 
-                // Cancelling messages that the process had emitted.
-                // TODO: this only handles messages emitted through the external API
-                let mut cancelled_messages = Vec::new();
-                for emitted_message in user_data.emitted_messages {
-                    let _emitter = self.messages_to_answer.remove(&emitted_message);
-                    debug_assert_eq!(_emitter, Some(pid));
-                    cancelled_messages.push(emitted_message);
-                }
+                self.process_finished(pid, user_data, outcome)
+            }
 
-                // Notify interface handlers about the process stopping.
-                for interface in user_data.used_interfaces {
-                    match self.interfaces.get(&interface) {
-                        Some(InterfaceState::Process(p)) => {
-                            if let Some(mut process) = self.processes.process_by_id(*p) {
-                                let message =
-                                    redshirt_syscalls_interface::ffi::Message::ProcessDestroyed(
-                                        redshirt_syscalls_interface::ffi::ProcessDestroyedMessage {
-                                            index_in_list: 0,
-                                            pid: pid.into(),
-                                        },
-                                    );
-
-                                process.user_data().messages_queue.push_back(message);
-                                try_resume_message_wait(process);
-                            } // TODO: notify externals as well?
-                        }
-                        None => unreachable!(),
-                        _ => {}
+            extrinsics::RunOneOutcome::ThreadFinished { .. } => {
+                // TODO: report?
+                CoreRunOutcomeInner::LoopAgain
+            }
+
+            extrinsics::RunOneOutcome::ThreadWaitMessage(mut thread) => {
+                let thread_id = thread.tid();
+                let deadline = match (&self.time_source, thread.timeout_nanos()) {
+                    (Some(source), Some(timeout)) => {
+                        Some(Deadline::after(&**source, Duration::from_nanos(timeout)))
+                    }
+                    _ => None,
+                };
+
+                let still_waiting = match try_resume_message_wait_thread(thread) {
+                    extrinsics::ProcessesCollectionExtrinsicsThread::WaitMessage(_) => true,
+                    _ => false,
+                };
+
+                match deadline {
+                    Some(deadline) if still_waiting => {
+                        self.message_wait_deadlines.insert(thread_id, deadline);
+                    }
+                    _ => {
+                        self.message_wait_deadlines.remove(&thread_id);
                     }
                 }
 
-                // TODO: also, what do we do with the pending messages and all?
+                CoreRunOutcomeInner::LoopAgain
+            }
+
+            extrinsics::RunOneOutcome::ThreadEmitMessage(thread) => {
+                let thread_id = thread.tid();
+                self.process_emit(thread_id)
+            }
 
-                CoreRunOutcomeInner::ProgramFinished {
-                    pid,
-                    unregistered_interfaces,
-                    // TODO: this only handles messages emitted through the external API
-                    unhandled_messages: user_data.messages_to_answer.to_vec(), // TODO: to_vec overhead
-                    cancelled_messages,
-                    outcome,
+            extrinsics::RunOneOutcome::ThreadEmitAnswer {
+                mut thread,
+                message_id,
+                response,
+            } => {
+                // Only the process that has received the message is allowed to answer it.
+                if !thread
+                    .process_user_data()
+                    .messages_to_answer
+                    .contains(&message_id)
+                {
+                    return CoreRunOutcomeInner::LoopAgain;
                 }
+
+                thread
+                    .process_user_data()
+                    .messages_to_answer
+                    .retain(|m| *m != message_id);
+                self.answer_message_inner(message_id, response)
+                    .unwrap_or(CoreRunOutcomeInner::LoopAgain)
             }
 
-            extrinsics::RunOneOutcome::ThreadFinished { .. } => {
-                // TODO: report?
-                CoreRunOutcomeInner::LoopAgain
+            extrinsics::RunOneOutcome::ThreadEmitAnswerChunk {
+                mut thread,
+                message_id,
+                chunk,
+                is_final,
+            } => {
+                // Only the process that has received the message is allowed to answer it. Pieces
+                // emitted by any other process are dropped.
+                if !thread
+                    .process_user_data()
+                    .messages_to_answer
+                    .contains(&message_id)
+                {
+                    return CoreRunOutcomeInner::LoopAgain;
+                }
+
+                if !self.messages_to_answer.contains_key(&message_id) {
+                    // The emitter of the message is no longer interested in the answer. The
+                    // pieces emitted so far, if any, are discarded.
+                    self.answer_chunks.remove(&message_id);
+                    thread
+                        .process_user_data()
+                        .messages_to_answer
+                        .retain(|m| *m != message_id);
+                    return CoreRunOutcomeInner::LoopAgain;
+                }
+
+                let mut response = self.answer_chunks.remove(&message_id).unwrap_or_default();
+                response.extend_from_slice(&chunk);
+                if !is_final {
+                    self.answer_chunks.insert(message_id, response);
+                    return CoreRunOutcomeInner::LoopAgain;
+                }
+
+                thread
+                    .process_user_data()
+                    .messages_to_answer
+                    .retain(|m| *m != message_id);
+                self.answer_message_inner(message_id, Ok(EncodedMessage(response)))
+                    .unwrap_or(CoreRunOutcomeInner::LoopAgain)
             }
 
-            extrinsics::RunOneOutcome::ThreadWaitMessage(thread) => {
-                try_resume_message_wait_thread(thread);
-                CoreRunOutcomeInner::LoopAgain
+            extrinsics::RunOneOutcome::ThreadEmitMessageError {
+                mut thread,
+                message_id,
+            } => {
+                // Only the process that has received the message is allowed to answer it.
+                if !thread
+                    .process_user_data()
+                    .messages_to_answer
+                    .contains(&message_id)
+                {
+                    return CoreRunOutcomeInner::LoopAgain;
+                }
+
+                thread
+                    .process_user_data()
+                    .messages_to_answer
+                    .retain(|m| *m != message_id);
+                self.answer_message_inner(message_id, Err(AnswerError::MalformedRequest))
+                    .unwrap_or(CoreRunOutcomeInner::LoopAgain)
             }
 
-            extrinsics::RunOneOutcome::ThreadEmitMessage(mut thread) => {
+            extrinsics::RunOneOutcome::ThreadCancelMessage {
+                mut thread,
+                message_id,
+            } => {
+                // Only the emitter of a message is allowed to cancel it.
                 let emitter_pid = thread.pid();
-                let interface = thread.emit_interface().clone();
+                if self.messages_to_answer.get(&message_id) == Some(&emitter_pid) {
+                    // Removing the message from `messages_to_answer` makes the handler's answer,
+                    // if it ever comes, be discarded, and lets the ID be assigned again.
+                    self.messages_to_answer.remove(&message_id);
+                    self.message_wait_depths.remove(&message_id);
+                    self.answer_deadlines.remove(&message_id);
+                    self.answer_chunks.remove(&message_id);
+                }
+
+                // The answer might have arrived before the cancellation, in which case it is
+                // removed from the queue in order to never be delivered.
                 thread
                     .process_user_data()
-                    .used_interfaces
-                    .insert(interface.clone());
-
-                match (self.interfaces.get_mut(&interface), thread.allow_delay()) {
-                    (Some(InterfaceState::Process(pid)), _) => {
-                        let message_id = if thread.needs_answer() {
-                            Some(loop {
-                                let id: MessageId = self.message_id_pool.assign();
-                                if u64::from(id) == 0 || u64::from(id) == 1 {
-                                    continue;
-                                }
-                                match self.messages_to_answer.entry(id) {
-                                    Entry::Occupied(_) => continue,
-                                    Entry::Vacant(e) => e.insert(emitter_pid),
-                                };
-                                break id;
-                            })
-                        } else {
-                            None
-                        };
+                    .messages_queue
+                    .retain(|msg| match msg {
+                        redshirt_syscalls_interface::ffi::Message::Response(response) => {
+                            response.message_id != message_id
+                        }
+                        _ => true,
+                    });
+                CoreRunOutcomeInner::LoopAgain
+            }
 
-                        let message = thread.accept_emit(message_id);
-
-                        if let Some(process) = self.processes.process_by_id(*pid) {
-                            let message = redshirt_syscalls_interface::ffi::Message::Interface(
-                                redshirt_syscalls_interface::ffi::InterfaceMessage {
-                                    interface: interface.into(),
-                                    index_in_list: 0,
-                                    message_id,
-                                    emitter_pid: emitter_pid.into(),
-                                    actual_data: message.0,
-                                },
-                            );
+            extrinsics::RunOneOutcome::ThreadFuelConsumed(_) => CoreRunOutcomeInner::LoopAgain,
 
-                            let mut process = match self.processes.process_by_id(*pid) {
-                                Some(p) => p,
-                                None => unreachable!(),
-                            };
-                            process.user_data().messages_queue.push_back(message);
-                            try_resume_message_wait(process);
-                            CoreRunOutcomeInner::LoopAgain
-                        } else {
-                            CoreRunOutcomeInner::ReservedPidInterfaceMessage {
-                                pid: emitter_pid,
-                                message_id,
-                                interface,
-                                message,
-                            }
-                        }
-                    }
-                    (None, false) | (Some(InterfaceState::Requested { .. }), false) => {
-                        thread.refuse_emit();
-                        CoreRunOutcomeInner::LoopAgain
-                    }
-                    (Some(InterfaceState::Requested { threads, .. }), true) => {
-                        threads.push(thread.tid());
-                        CoreRunOutcomeInner::ThreadWaitUnavailableInterface {
-                            thread: thread.tid(),
-                            interface,
+            extrinsics::RunOneOutcome::ThreadYielded(_) => CoreRunOutcomeInner::LoopAgain,
+
+            extrinsics::RunOneOutcome::ThreadOutOfFuel(mut thread) => {
+                CoreRunOutcomeInner::ThreadOutOfFuel {
+                    thread: thread.tid(),
+                }
+            }
+
+            extrinsics::RunOneOutcome::Idle if !self.pending_teardowns.is_empty() => {
+                CoreRunOutcomeInner::LoopAgain
+            }
+            extrinsics::RunOneOutcome::Idle => CoreRunOutcomeInner::Idle,
+        }
+    }
+
+    /// Handles a thread that is emitting a message.
+    ///
+    /// Called when the thread calls `emit_message`, and again if the thread has been waiting
+    /// for the queue of the handler of the interface to have room.
+    fn process_emit(&mut self, thread_id: ThreadId) -> CoreRunOutcomeInner {
+        // Because of borrowing issues, we have to check these before grabbing the thread.
+        let memory_limit_reached = self.memory_limit_reached();
+        let interface = match self.processes.thread_by_id(thread_id) {
+            Some(extrinsics::ProcessesCollectionExtrinsicsThread::EmitMessage(mut thread)) => {
+                thread.emit_interface().clone()
+            }
+            // The thread might have been killed while waiting for an inbox to have room.
+            _ => return CoreRunOutcomeInner::LoopAgain,
+        };
+        let handler_inbox_full = match (self.inbox_limit, self.interfaces.get(&interface)) {
+            (Some(limit), Some(InterfaceState::Process(pid))) => {
+                match self.processes.process_by_id(*pid) {
+                    Some(mut p) => p.user_data().messages_queue.inbox_len() >= limit,
+                    None => false,
+                }
+            }
+            _ => false,
+        };
+
+        let mut thread = match self.processes.thread_by_id(thread_id) {
+            Some(extrinsics::ProcessesCollectionExtrinsicsThread::EmitMessage(thread)) => thread,
+            _ => unreachable!(),
+        };
+
+        if memory_limit_reached {
+            thread.refuse_emit_memory_limit();
+            return CoreRunOutcomeInner::LoopAgain;
+        }
+
+        let emitter_pid = thread.pid();
+
+        if let Some(schema) = self.interface_schemas.get(&interface) {
+            if schema.validate(&thread.emit_message().0).is_err() {
+                thread.refuse_emit_invalid_message();
+                return CoreRunOutcomeInner::LoopAgain;
+            }
+        }
+
+        let wait_depth = emit_wait_depth(&self.message_wait_depths, thread.process_user_data());
+        if thread.needs_answer() && self.max_wait_depth.map_or(false, |max| wait_depth > max) {
+            thread.refuse_emit_wait_depth_exceeded();
+            return CoreRunOutcomeInner::LoopAgain;
+        }
+
+        thread
+            .process_user_data()
+            .used_interfaces
+            .insert(interface.clone());
+
+        match (self.interfaces.get_mut(&interface), thread.allow_delay()) {
+            (Some(InterfaceState::Process(pid)), true) if handler_inbox_full => {
+                self.full_inbox_waiters
+                    .entry(*pid)
+                    .or_insert_with(VecDeque::new)
+                    .push_back(thread_id);
+                CoreRunOutcomeInner::LoopAgain
+            }
+            (Some(InterfaceState::Process(_)), false) if handler_inbox_full => {
+                thread.refuse_emit_inbox_full();
+                CoreRunOutcomeInner::LoopAgain
+            }
+            (Some(InterfaceState::Process(pid)), _) => {
+                let message_id = if thread.needs_answer() {
+                    Some(loop {
+                        let id: MessageId = self.message_id_pool.assign();
+                        if u64::from(id) == 0 || u64::from(id) == 1 {
+                            continue;
                         }
-                    }
-                    (None, true) => {
-                        self.interfaces.insert(
-                            interface.clone(),
-                            InterfaceState::Requested {
-                                threads: iter::once(thread.tid()).collect(),
-                                other: Vec::new(),
-                            },
-                        );
-                        CoreRunOutcomeInner::ThreadWaitUnavailableInterface {
-                            thread: thread.tid(),
-                            interface,
+                        match self.messages_to_answer.entry(id) {
+                            Entry::Occupied(_) => continue,
+                            Entry::Vacant(e) => e.insert(emitter_pid),
+                        };
+                        self.message_wait_depths.insert(id, wait_depth);
+                        if let (Some(source), Some(timeout)) =
+                            (&self.time_source, thread.answer_timeout_nanos())
+                        {
+                            let deadline =
+                                Deadline::after(&**source, Duration::from_nanos(timeout));
+                            self.answer_deadlines.insert(id, deadline);
                         }
+                        break id;
+                    })
+                } else {
+                    None
+                };
+
+                let priority = thread.priority();
+                let message = thread.accept_emit(message_id);
+
+                if let Some(process) = self.processes.process_by_id(*pid) {
+                    let coalesce = self.coalescing_interfaces.contains(&interface);
+                    let message = redshirt_syscalls_interface::ffi::Message::Interface(
+                        redshirt_syscalls_interface::ffi::InterfaceMessage {
+                            interface: interface.into(),
+                            index_in_list: 0,
+                            message_id,
+                            emitter_pid: emitter_pid.into(),
+                            priority,
+                            actual_data: message.0,
+                        },
+                    );
+
+                    let mut process = match self.processes.process_by_id(*pid) {
+                        Some(p) => p,
+                        None => unreachable!(),
+                    };
+                    push_interface_message(
+                        &mut process.user_data().messages_queue,
+                        message,
+                        self.process_priorities.as_ref(),
+                        coalesce,
+                    );
+                    try_resume_message_wait(process);
+                    CoreRunOutcomeInner::LoopAgain
+                } else {
+                    CoreRunOutcomeInner::ReservedPidInterfaceMessage {
+                        pid: emitter_pid,
+                        message_id,
+                        interface,
+                        message,
                     }
                 }
             }
+            (None, false) | (Some(InterfaceState::Requested { .. }), false) => {
+                thread.refuse_emit();
+                CoreRunOutcomeInner::LoopAgain
+            }
+            (Some(InterfaceState::Requested { threads, .. }), true) => {
+                threads.push(thread.tid());
+                CoreRunOutcomeInner::ThreadWaitUnavailableInterface {
+                    thread: thread.tid(),
+                    interface,
+                }
+            }
+            (None, true) => {
+                self.interfaces.insert(
+                    interface.clone(),
+                    InterfaceState::Requested {
+                        threads: iter::once(thread.tid()).collect(),
+                        other: Vec::new(),
+                    },
+                );
+                CoreRunOutcomeInner::ThreadWaitUnavailableInterface {
+                    thread: thread.tid(),
+                    interface,
+                }
+            }
+        }
+    }
 
-            extrinsics::RunOneOutcome::ThreadEmitAnswer {
-                message_id,
-                response,
-                ..
-            } => {
-                // TODO: check ownership of the message
-                self.answer_message_inner(message_id, Ok(response))
-                    .unwrap_or(CoreRunOutcomeInner::LoopAgain)
+    /// If a process in `full_inbox_waiters` has room in its queue of messages, or doesn't exist
+    /// anymore, removes from the list and returns the first thread that was waiting for it.
+    fn unblocked_full_inbox_waiter(&mut self) -> Option<ThreadId> {
+        if self.full_inbox_waiters.is_empty() {
+            return None;
+        }
+
+        let limit = self.inbox_limit?;
+        let mut unblocked = None;
+        for pid in self.full_inbox_waiters.keys() {
+            let has_room = match self.processes.process_by_id(*pid) {
+                Some(mut p) => p.user_data().messages_queue.inbox_len() < limit,
+                None => true,
+            };
+            if has_room {
+                unblocked = Some(*pid);
+                break;
             }
+        }
 
-            extrinsics::RunOneOutcome::ThreadEmitMessageError { message_id, .. } => {
-                // TODO: check ownership of the message
-                self.answer_message_inner(message_id, Err(()))
-                    .unwrap_or(CoreRunOutcomeInner::LoopAgain)
+        let mut waiters = match self.full_inbox_waiters.entry(unblocked?) {
+            Entry::Occupied(e) => e,
+            Entry::Vacant(_) => unreachable!(),
+        };
+        let thread_id = waiters.get_mut().pop_front();
+        if waiters.get().is_empty() {
+            waiters.remove();
+        }
+        thread_id
+    }
+
+    /// Removes the given threads, which no longer exist, from the lists of threads waiting for
+    /// an interface to be registered. Interfaces that nobody is waiting for anymore are
+    /// forgotten.
+    fn remove_unavailable_interface_waiters(&mut self, dead_threads: &[ThreadId]) {
+        if dead_threads.is_empty() {
+            return;
+        }
+
+        self.interfaces.retain(|_, state| match state {
+            InterfaceState::Requested { threads, other } => {
+                threads.retain(|thread_id| !dead_threads.contains(thread_id));
+                !threads.is_empty() || !other.is_empty()
             }
+            InterfaceState::Process(_) => true,
+        });
+    }
 
-            extrinsics::RunOneOutcome::Idle => CoreRunOutcomeInner::Idle,
+    /// Cleans up after a process that has terminated, and returns the event to report.
+    fn process_finished(
+        &mut self,
+        pid: Pid,
+        user_data: Process,
+        outcome: Result<Option<WasmValue>, vm::Trap>,
+    ) -> CoreRunOutcomeInner {
+        // Unregister the interfaces this program had registered.
+        let mut unregistered_interfaces = Vec::new();
+        for interface in user_data.registered_interfaces {
+            let _interface = self.interfaces.remove(&interface);
+            debug_assert_eq!(_interface, Some(InterfaceState::Process(pid)));
+            self.interface_schemas.remove(&interface);
+            self.coalescing_interfaces.remove(&interface);
+            unregistered_interfaces.push(interface);
+        }
+
+        if let Some(process_priorities) = &mut self.process_priorities {
+            process_priorities.remove(&pid);
+        }
+
+        // Cancelling messages that the process had emitted.
+        // TODO: this only handles messages emitted through the external API
+        let mut cancelled_messages = Vec::new();
+        for emitted_message in user_data.emitted_messages {
+            self.message_wait_depths.remove(&emitted_message);
+            self.answer_deadlines.remove(&emitted_message);
+            self.answer_chunks.remove(&emitted_message);
+            let _emitter = self.messages_to_answer.remove(&emitted_message);
+            debug_assert_eq!(_emitter, Some(pid));
+            cancelled_messages.push(emitted_message);
+        }
+
+        // Answers that the process was in the middle of emitting will never be complete.
+        for message_id in &user_data.messages_to_answer {
+            self.answer_chunks.remove(message_id);
+        }
+
+        // Notifying the interface handlers and failing the messages that the process
+        // hasn't retrieved is done later, in steps.
+        self.pending_teardowns.push_back(ProcessTeardown {
+            pid,
+            used_interfaces: user_data.used_interfaces.into_iter(),
+            messages_queue: user_data.messages_queue.into_messages(),
+        });
+
+        CoreRunOutcomeInner::ProgramFinished {
+            pid,
+            unregistered_interfaces,
+            // TODO: this only handles messages emitted through the external API
+            unhandled_messages: user_data.messages_to_answer.to_vec(), // TODO: to_vec overhead
+            cancelled_messages,
+            outcome,
+        }
+    }
+
+    /// Performs at most [`teardown_batch_size`](Core::teardown_batch_size) cleanup steps of the
+    /// processes that have terminated.
+    ///
+    /// Events generated by the cleanup are pushed to `pending_events`.
+    fn advance_teardowns(&mut self) {
+        let mut remaining_steps = self.teardown_batch_size;
+
+        while remaining_steps != 0 {
+            let teardown = match self.pending_teardowns.front_mut() {
+                Some(t) => t,
+                None => break,
+            };
+            remaining_steps -= 1;
+
+            // Notify interface handlers about the process stopping.
+            if let Some(interface) = teardown.used_interfaces.next() {
+                match self.interfaces.get(&interface) {
+                    Some(InterfaceState::Process(p)) => {
+                        if let Some(mut process) = self.processes.process_by_id(*p) {
+                            let message =
+                                redshirt_syscalls_interface::ffi::Message::ProcessDestroyed(
+                                    redshirt_syscalls_interface::ffi::ProcessDestroyedMessage {
+                                        index_in_list: 0,
+                                        pid: teardown.pid.into(),
+                                    },
+                                );
+
+                            push_notification(
+                                &mut process.user_data().messages_queue,
+                                message,
+                                self.inbox_limit,
+                                self.inbox_overflow_policy,
+                            );
+                            try_resume_message_wait(process);
+                        } // TODO: notify externals as well?
+                    }
+                    // The process might have tried to emit a message on an interface that
+                    // has never had any handler, in which case the emit has been refused.
+                    None => {}
+                    _ => {}
+                }
+                continue;
+            }
+
+            // Answer with an error the messages that the process will never handle.
+            if let Some(message) = teardown.messages_queue.pop_front() {
+                if let redshirt_syscalls_interface::ffi::Message::Interface(msg) = message {
+                    // The emitter might have been destroyed in the meanwhile.
+                    let message_id = msg
+                        .message_id
+                        .filter(|id| self.messages_to_answer.contains_key(id));
+                    if let Some(message_id) = message_id {
+                        let answer = Err(AnswerError::HandlerCrashed);
+                        if let Some(ev) = self.answer_message_inner(message_id, answer) {
+                            self.pending_events.push(ev);
+                        }
+                    }
+                }
+                continue;
+            }
+
+            self.pending_teardowns.pop_front();
         }
     }
 
+    /// Returns the number of cleanup steps that remain to be performed for processes that have
+    /// terminated.
+    ///
+    /// A step consists in either notifying an interface handler of the destruction of a process,
+    /// or discarding a message that a process had never retrieved.
+    pub fn pending_teardown_steps(&self) -> usize {
+        self.pending_teardowns
+            .iter()
+            .map(|t| t.used_interfaces.len() + t.messages_queue.len())
+            .sum()
+    }
+
+    /// Returns the list of processes that are running.
+    pub fn pids<'a>(&'a self) -> impl ExactSizeIterator<Item = Pid> + 'a {
+        self.processes.pids()
+    }
+
     /// Returns an object granting access to a process, if it exists.
-    pub fn process_by_id(&mut self, pid: Pid) -> Option<CoreProcess> {
+    pub fn process_by_id(&mut self, pid: Pid) -> Option<CoreProcess<E>> {
         let p = self.processes.process_by_id(pid)?;
         Some(CoreProcess { process: p })
     }
 
+    /// Kills the given process immediately, as if it had crashed.
+    ///
+    /// All its threads are destroyed, including the ones waiting for the outcome of an
+    /// extrinsic. The next call to [`run`](Core::run) reports a
+    /// [`CoreRunOutcome::ProgramFinished`] whose outcome is
+    /// [`Trap::Aborted`](vm::Trap::Aborted).
+    ///
+    /// Returns an error if the process doesn't exist.
+    pub fn abort_process(&mut self, pid: Pid) -> Result<(), ()> {
+        let (user_data, dead_threads) = self.processes.process_by_id(pid).ok_or(())?.abort();
+        let dead_thread_ids = dead_threads
+            .into_iter()
+            .map(|(thread_id, _)| thread_id)
+            .collect::<Vec<_>>();
+        for thread_id in &dead_thread_ids {
+            self.message_wait_deadlines.remove(thread_id);
+        }
+        self.remove_unavailable_interface_waiters(&dead_thread_ids);
+
+        let outcome = Err(vm::Trap::Aborted);
+        let event = self.process_finished(pid, user_data, outcome);
+        self.pending_events.push(event);
+        Ok(())
+    }
+
     /// Returns an object granting access to a thread, if it exists.
-    pub fn thread_by_id(&mut self, thread: ThreadId) -> Option<CoreThread> {
+    pub fn thread_by_id(&mut self, thread: ThreadId) -> Option<CoreThread<E>> {
         let thread = self.processes.thread_by_id(thread)?;
         Some(CoreThread { thread })
     }
@@ -473,6 +1155,10 @@ impl Core {
         let (thread_ids, other_messages) = match self.interfaces.entry(interface.clone()) {
             Entry::Vacant(e) => {
                 e.insert(InterfaceState::Process(process));
+                if let Some(mut p) = self.processes.process_by_id(process) {
+                    p.user_data().registered_interfaces.push(interface.clone());
+                }
+                self.answer_interface_waiters(&interface);
                 return Ok(());
             }
             Entry::Occupied(mut e) => {
@@ -488,6 +1174,10 @@ impl Core {
             }
         };
 
+        if let Some(mut p) = self.processes.process_by_id(process) {
+            p.user_data().registered_interfaces.push(interface.clone());
+        }
+
         // Send the `other_messages`.
         // TODO: should we preserve the order w.r.t. `threads`?
         for (emitter_pid, message_id, message_data) in other_messages {
@@ -497,75 +1187,387 @@ impl Core {
                     index_in_list: 0,
                     message_id,
                     emitter_pid,
+                    priority: 0,
                     actual_data: message_data.0,
                 },
             );
 
             match self.processes.process_by_id(process) {
-                Some(mut p) => p.user_data().messages_queue.push_back(message),
+                Some(mut p) => push_interface_message(
+                    &mut p.user_data().messages_queue,
+                    message,
+                    self.process_priorities.as_ref(),
+                    self.coalescing_interfaces.contains(&interface),
+                ),
                 None => unreachable!(),
             }
         }
 
-        // Now process the threads that were waiting for this interface to be registered.
-        for thread_id in thread_ids {
-            let mut thread = match self.processes.thread_by_id(thread_id) {
-                Some(extrinsics::ProcessesCollectionExtrinsicsThread::EmitMessage(t)) => t,
-                _ => unreachable!(),
-            };
+        // Now process the threads that were waiting for this interface to be registered.
+        for thread_id in thread_ids {
+            let mut thread = match self.processes.thread_by_id(thread_id) {
+                Some(extrinsics::ProcessesCollectionExtrinsicsThread::EmitMessage(t)) => t,
+                _ => unreachable!(),
+            };
+
+            debug_assert_eq!(*thread.emit_interface(), interface);
+            let emitter_pid = thread.pid().into();
+            let wait_depth = emit_wait_depth(&self.message_wait_depths, thread.process_user_data());
+
+            let message_id = if thread.needs_answer() {
+                Some(loop {
+                    let id: MessageId = self.message_id_pool.assign();
+                    if u64::from(id) == 0 || u64::from(id) == 1 {
+                        continue;
+                    }
+                    match self.messages_to_answer.entry(id) {
+                        Entry::Occupied(_) => continue,
+                        Entry::Vacant(e) => e.insert(emitter_pid),
+                    };
+                    self.message_wait_depths.insert(id, wait_depth);
+                    if let (Some(source), Some(timeout)) =
+                        (&self.time_source, thread.answer_timeout_nanos())
+                    {
+                        let deadline = Deadline::after(&**source, Duration::from_nanos(timeout));
+                        self.answer_deadlines.insert(id, deadline);
+                    }
+                    break id;
+                })
+            } else {
+                None
+            };
+
+            let priority = thread.priority();
+            let message = thread.accept_emit(message_id);
+
+            if let Some(mut interface_handler_proc) = self.processes.process_by_id(process) {
+                let message = redshirt_syscalls_interface::ffi::Message::Interface(
+                    redshirt_syscalls_interface::ffi::InterfaceMessage {
+                        interface: interface.clone().into(),
+                        index_in_list: 0,
+                        message_id,
+                        emitter_pid,
+                        priority,
+                        actual_data: message.0,
+                    },
+                );
+
+                push_interface_message(
+                    &mut interface_handler_proc.user_data().messages_queue,
+                    message,
+                    self.process_priorities.as_ref(),
+                    self.coalescing_interfaces.contains(&interface),
+                );
+            } else {
+                self.pending_events
+                    .push(CoreRunOutcomeInner::ReservedPidInterfaceMessage {
+                        pid: emitter_pid,
+                        message_id,
+                        interface: interface.clone(),
+                        message,
+                    });
+            }
+        }
+
+        if let Some(interface_handler_proc) = self.processes.process_by_id(process) {
+            try_resume_message_wait(interface_handler_proc);
+        }
+
+        self.answer_interface_waiters(&interface);
+        Ok(())
+    }
+
+    /// Unregisters the given process as the handler of the given interface. The schema and
+    /// coalescing setting of the interface are reset.
+    ///
+    /// Messages that have already been queued for the process are left untouched. Messages
+    /// emitted afterwards wait for a new handler to be registered.
+    ///
+    /// Returns an error if `process` isn't the handler of this interface.
+    pub fn unset_interface_handler(
+        &mut self,
+        interface: &InterfaceHash,
+        process: Pid,
+    ) -> Result<(), ()> {
+        if self.interface_handler(interface) != Some(process) {
+            return Err(());
+        }
+
+        self.interfaces.remove(interface);
+        self.interface_schemas.remove(interface);
+        self.coalescing_interfaces.remove(interface);
+        if let Some(mut p) = self.processes.process_by_id(process) {
+            p.user_data()
+                .registered_interfaces
+                .retain(|i| i != interface);
+        }
+
+        Ok(())
+    }
+
+    /// Answers the given message with an empty response as soon as a handler is registered for
+    /// the given interface, or immediately if there already is one.
+    ///
+    /// This lets a process wait for one of its dependencies to be available, rather than
+    /// emitting a message with `allow_delay` set and hoping for a handler to show up.
+    ///
+    /// Nothing happens if the message has been cancelled or its emitter has terminated by the
+    /// time the interface is registered.
+    pub fn answer_on_interface_registration(
+        &mut self,
+        interface: InterfaceHash,
+        message_id: MessageId,
+    ) {
+        if self.interface_handler(&interface).is_some() {
+            self.answer_message(message_id, Ok(EncodedMessage(Vec::new())));
+        } else {
+            self.interface_waiters
+                .entry(interface)
+                .or_default()
+                .push(message_id);
+        }
+    }
+
+    /// Answers the messages passed to
+    /// [`answer_on_interface_registration`](Core::answer_on_interface_registration) for the
+    /// given interface.
+    fn answer_interface_waiters(&mut self, interface: &InterfaceHash) {
+        let waiters = match self.interface_waiters.remove(interface) {
+            Some(w) => w,
+            None => return,
+        };
+
+        for message_id in waiters {
+            if self.messages_to_answer.contains_key(&message_id) {
+                self.answer_message(message_id, Ok(EncodedMessage(Vec::new())));
+            }
+        }
+    }
+
+    /// Transfers the registration of an interface from its current handler to the given process.
+    ///
+    /// The interface messages that have been queued for the current handler but not retrieved
+    /// yet are moved to the queue of the new handler, in the same order. Messages that the
+    /// current handler has already retrieved can still be answered by it.
+    ///
+    /// Returns an error if the interface isn't registered or if `new_handler` isn't a running
+    /// process.
+    pub fn reassign_interface(
+        &mut self,
+        interface: InterfaceHash,
+        new_handler: Pid,
+    ) -> Result<(), ()> {
+        let old_handler = match self.interfaces.get(&interface) {
+            Some(InterfaceState::Process(pid)) => *pid,
+            _ => return Err(()),
+        };
+
+        if self.processes.process_by_id(new_handler).is_none() {
+            return Err(());
+        }
+
+        if old_handler == new_handler {
+            return Ok(());
+        }
+
+        let mut moved_messages = VecDeque::new();
+        if let Some(mut old_handler_proc) = self.processes.process_by_id(old_handler) {
+            let queue = &mut old_handler_proc.user_data().messages_queue;
+            let mut index = 0;
+            while index < queue.len() {
+                match &queue[index] {
+                    redshirt_syscalls_interface::ffi::Message::Interface(msg)
+                        if InterfaceHash::from(msg.interface) == interface =>
+                    {
+                        moved_messages.push_back(queue.remove(index).unwrap());
+                    }
+                    _ => index += 1,
+                }
+            }
+
+            old_handler_proc
+                .user_data()
+                .registered_interfaces
+                .retain(|i| *i != interface);
+        }
+
+        self.interfaces
+            .insert(interface.clone(), InterfaceState::Process(new_handler));
+
+        let mut new_handler_proc = match self.processes.process_by_id(new_handler) {
+            Some(p) => p,
+            None => unreachable!(),
+        };
+        let coalesce = self.coalescing_interfaces.contains(&interface);
+        new_handler_proc
+            .user_data()
+            .registered_interfaces
+            .push(interface);
+        for message in moved_messages {
+            push_interface_message(
+                &mut new_handler_proc.user_data().messages_queue,
+                message,
+                self.process_priorities.as_ref(),
+                coalesce,
+            );
+        }
+        try_resume_message_wait(new_handler_proc);
+
+        Ok(())
+    }
+
+    /// Gives more fuel to the given thread. If the thread has run out of fuel and now has enough
+    /// to continue, it is resumed.
+    ///
+    /// Has no effect if [`CoreBuilder::with_fuel_limit`] hasn't been called. Returns an error if
+    /// the thread doesn't exist.
+    pub fn refuel(&mut self, thread: ThreadId, amount: u64) -> Result<(), ()> {
+        let mut thread = self.processes.thread_by_id(thread).ok_or(())?;
+        thread.refuel(amount);
+        Ok(())
+    }
 
-            debug_assert_eq!(*thread.emit_interface(), interface);
-            let emitter_pid = thread.pid().into();
+    /// Returns the list of threads of the given process, alongside with their state.
+    ///
+    /// Threads whose call to `emit_message` is delayed because the inbox of the handler is full
+    /// are reported as
+    /// [`ThreadState::BlockedOnExtrinsic`](extrinsics::ThreadState::BlockedOnExtrinsic).
+    ///
+    /// Returns an empty list if the process doesn't exist.
+    pub fn threads(&mut self, pid: Pid) -> Vec<(ThreadId, extrinsics::ThreadState)> {
+        let process = match self.processes.process_by_id(pid) {
+            Some(p) => p,
+            None => return Vec::new(),
+        };
 
-            let message_id = if thread.needs_answer() {
-                Some(loop {
-                    let id: MessageId = self.message_id_pool.assign();
-                    if u64::from(id) == 0 || u64::from(id) == 1 {
-                        continue;
-                    }
-                    match self.messages_to_answer.entry(id) {
-                        Entry::Occupied(_) => continue,
-                        Entry::Vacant(e) => e.insert(emitter_pid),
-                    };
-                    break id;
-                })
+        let mut out = Vec::new();
+        let mut thread = process.main_thread();
+        loop {
+            let tid = thread.tid();
+            let state = if self
+                .full_inbox_waiters
+                .values()
+                .any(|waiters| waiters.contains(&tid))
+            {
+                extrinsics::ThreadState::BlockedOnExtrinsic(extrinsics::Extrinsic::EmitMessage)
             } else {
-                None
+                thread.state()
             };
+            out.push((tid, state));
+            match thread.next_thread() {
+                Some(t) => thread = t,
+                None => break,
+            }
+        }
+        out
+    }
 
-            let message = thread.accept_emit(message_id);
+    /// Returns the size, in bytes, of the linear memory of the given process, or `None` if the
+    /// process doesn't exist.
+    pub fn process_memory_size(&self, pid: Pid) -> Option<usize> {
+        self.processes.process_memory_size(pid)
+    }
 
-            if let Some(mut interface_handler_proc) = self.processes.process_by_id(process) {
-                let message = redshirt_syscalls_interface::ffi::Message::Interface(
-                    redshirt_syscalls_interface::ffi::InterfaceMessage {
-                        interface: interface.clone().into(),
-                        index_in_list: 0,
-                        message_id,
-                        emitter_pid,
-                        actual_data: message.0,
-                    },
-                );
+    /// Calls `f` with a view of the given memory range of the given process, without copying
+    /// it.
+    ///
+    /// Returns an error if the process doesn't exist, or if the range is invalid or out of range.
+    pub fn with_process_memory<R>(
+        &self,
+        pid: Pid,
+        offset: u32,
+        size: u32,
+        f: impl FnOnce(&[u8]) -> R,
+    ) -> Result<R, ()> {
+        self.processes.with_process_memory(pid, offset, size, f)
+    }
 
-                interface_handler_proc
-                    .user_data()
-                    .messages_queue
-                    .push_back(message);
-            } else {
-                self.pending_events
-                    .push(CoreRunOutcomeInner::ReservedPidInterfaceMessage {
-                        pid: emitter_pid,
-                        message_id,
-                        interface: interface.clone(),
-                        message,
-                    });
+    /// Returns the [`Pid`] of the handler of the given interface, if any.
+    pub fn interface_handler(&self, interface: &InterfaceHash) -> Option<Pid> {
+        match self.interfaces.get(interface) {
+            Some(InterfaceState::Process(pid)) => Some(*pid),
+            _ => None,
+        }
+    }
+
+    /// Sets the schema that the messages emitted by processes on the given interface must
+    /// conform to. Passing `None` removes the schema.
+    ///
+    /// Messages that don't conform to the schema are refused, and the emitting thread is resumed
+    /// with an error.
+    ///
+    /// The schema is automatically removed if the process handling the interface terminates.
+    pub fn set_interface_schema(
+        &mut self,
+        interface: InterfaceHash,
+        schema: Option<MessageSchema>,
+    ) {
+        match schema {
+            Some(schema) => {
+                self.interface_schemas.insert(interface, schema);
+            }
+            None => {
+                self.interface_schemas.remove(&interface);
             }
         }
+    }
 
-        if let Some(interface_handler_proc) = self.processes.process_by_id(process) {
-            try_resume_message_wait(interface_handler_proc);
+    /// Enables or disables coalescing of the messages emitted on the given interface.
+    ///
+    /// When enabled, a message that doesn't expect an answer replaces the messages on this
+    /// interface from the same emitter that haven't been retrieved by the handler yet and that
+    /// don't expect an answer either. Messages that expect an answer are never coalesced.
+    ///
+    /// This is appropriate for interfaces carrying state updates, where only the latest value
+    /// matters and a slow handler would otherwise be flooded with stale updates.
+    ///
+    /// The setting is automatically removed if the process handling the interface terminates.
+    pub fn set_interface_coalescing(&mut self, interface: InterfaceHash, enabled: bool) {
+        if enabled {
+            self.coalescing_interfaces.insert(interface);
+        } else {
+            self.coalescing_interfaces.remove(&interface);
         }
+    }
 
-        Ok(())
+    /// Returns the depth of the chain of messages waiting for an answer that the given message
+    /// belongs to, or `None` if the message isn't waiting for an answer.
+    ///
+    /// A message emitted while the emitter isn't handling any message has a depth of 1. A message
+    /// emitted by a process that has retrieved, and not answered yet, a message of depth `N` has
+    /// a depth of `N + 1`.
+    pub fn message_wait_depth(&self, message_id: MessageId) -> Option<u32> {
+        self.message_wait_depths.get(&message_id).cloned()
+    }
+
+    /// Returns the depth of the deepest chain of messages waiting for an answer, or 0 if no
+    /// message is waiting for an answer.
+    ///
+    /// See [`Core::message_wait_depth`].
+    pub fn max_message_wait_depth(&self) -> u32 {
+        self.message_wait_depths
+            .values()
+            .cloned()
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Sets the priority of the given process or reserved `Pid`. Processes have a priority of
+    /// `0` by default.
+    ///
+    /// If the [`Core`] has been built with [`CoreBuilder::with_priority_inbox`], messages emitted
+    /// by processes with a higher priority are delivered before the ones emitted by processes
+    /// with a lower priority.
+    ///
+    /// Has no effect otherwise.
+    pub fn set_process_priority(&mut self, pid: Pid, priority: u8) {
+        if let Some(process_priorities) = &mut self.process_priorities {
+            if priority == 0 {
+                process_priorities.remove(&pid);
+            } else {
+                process_priorities.insert(pid, priority);
+            }
+        }
     }
 
     /// Emits a message for the handler of the given interface.
@@ -637,24 +1639,31 @@ impl Core {
         };
 
         if let Some(mut process) = self.processes.process_by_id(pid) {
+            let coalesce = self.coalescing_interfaces.contains(&interface);
             let message = redshirt_syscalls_interface::ffi::Message::Interface(
                 redshirt_syscalls_interface::ffi::InterfaceMessage {
                     interface: interface.into(),
                     message_id,
                     emitter_pid,
+                    priority: 0,
                     index_in_list: 0,
                     actual_data: message.encode().0.to_vec(),
                 },
             );
 
-            process.user_data().messages_queue.push_back(message);
+            push_interface_message(
+                &mut process.user_data().messages_queue,
+                message,
+                self.process_priorities.as_ref(),
+                coalesce,
+            );
             try_resume_message_wait(process);
         } else {
             assert!(self.reserved_pids.contains(&emitter_pid));
             self.pending_events
                 .push(CoreRunOutcomeInner::ReservedPidInterfaceMessage {
                     pid: emitter_pid,
-                    message_id: None,
+                    message_id,
                     interface,
                     message: message.encode(),
                 });
@@ -662,28 +1671,64 @@ impl Core {
 
         if let Some(messages_to_answer_entry) = messages_to_answer_entry {
             messages_to_answer_entry.insert(emitter_pid);
+            // Reserved `Pid`s never handle messages, and are thus always at the start of a chain.
+            self.message_wait_depths.insert(message_id.unwrap(), 1);
         }
         message_id
     }
 
+    /// Sets the answer to a message that has been emitted and that needs an answer.
     ///
+    /// If the message has been emitted by a process, the answer is delivered to it. If the message
+    /// has been emitted using [`emit_interface_message_answer`](Core::emit_interface_message_answer),
+    /// a [`MessageResponse`](CoreRunOutcome::MessageResponse) will be returned by the next call to
+    /// [`run`](Core::run).
     ///
-    /// It is forbidden to answer messages created using [`emit_interface_message_answer`] or
-    /// [`emit_interface_message_no_answer`]. Only messages generated by processes can be answered
-    /// through this method.
+    /// Nothing happens if the message has been cancelled or its emitter has terminated.
     // TODO: better API
-    pub fn answer_message(&mut self, message_id: MessageId, response: Result<EncodedMessage, ()>) {
-        let ret = self.answer_message_inner(message_id, response);
-        assert!(ret.is_none());
+    pub fn answer_message(
+        &mut self,
+        message_id: MessageId,
+        response: Result<EncodedMessage, AnswerError>,
+    ) {
+        // If the emitter isn't a process, the response must be reported back to the user. We
+        // queue it in order for it to be returned at the next call to `run`.
+        if let Some(ev) = self.answer_message_inner(message_id, response) {
+            self.pending_events.push(ev);
+        }
+    }
+
+    /// Cancels a message that has been emitted using
+    /// [`emit_interface_message_answer`](Core::emit_interface_message_answer).
+    ///
+    /// The answer of the handler, if it ever comes, is discarded, and no
+    /// [`MessageResponse`](CoreRunOutcome::MessageResponse) is produced for this message.
+    ///
+    /// Nothing happens if `emitter_pid` isn't the emitter of the message, or if the message has
+    /// already been answered.
+    pub fn cancel_message(&mut self, emitter_pid: Pid, message_id: MessageId) {
+        // Only the emitter of a message is allowed to cancel it.
+        if self.messages_to_answer.get(&message_id) == Some(&emitter_pid) {
+            self.messages_to_answer.remove(&message_id);
+            self.message_wait_depths.remove(&message_id);
+            self.answer_deadlines.remove(&message_id);
+            self.answer_chunks.remove(&message_id);
+        }
     }
 
     // TODO: better API
     fn answer_message_inner(
         &mut self,
         message_id: MessageId,
-        response: Result<EncodedMessage, ()>,
+        response: Result<EncodedMessage, AnswerError>,
     ) -> Option<CoreRunOutcomeInner> {
+        // If the handler had started emitting the answer piece by piece, the pieces are
+        // superseded by this answer.
+        self.answer_chunks.remove(&message_id);
+
         if let Some(emitter_pid) = self.messages_to_answer.remove(&message_id) {
+            self.message_wait_depths.remove(&message_id);
+            self.answer_deadlines.remove(&message_id);
             if let Some(mut process) = self.processes.process_by_id(emitter_pid) {
                 let actual_message = redshirt_syscalls_interface::ffi::Message::Response(
                     redshirt_syscalls_interface::ffi::ResponseMessage {
@@ -708,30 +1753,193 @@ impl Core {
                 })
             }
         } else {
-            // TODO: what to do here?
-            panic!("no process found with that event")
+            // The message has been cancelled by its emitter, or the emitter has terminated. The
+            // answer is discarded.
+            None
         }
     }
 
     /// Start executing the module passed as parameter.
     ///
     /// Each import of the [`Module`](crate::module::Module) is resolved.
-    pub fn execute(&mut self, module: &Module) -> Result<CoreProcess, vm::NewErr> {
+    pub fn execute(&mut self, module: &Module) -> Result<CoreProcess<E>, vm::NewErr> {
+        self.execute_inner(module, vm::EntryPoint::Default)
+    }
+
+    /// Same as [`execute`](Core::execute), but the process starts by calling the exported
+    /// function named `entry` with the given parameters, rather than `_start` or `main`.
+    ///
+    /// Returns [`NewErr::StartNotFound`](vm::NewErr::StartNotFound) if there is no such export,
+    /// and [`NewErr::StartBadSignature`](vm::NewErr::StartBadSignature) if `args` don't match
+    /// the signature of the function.
+    pub fn execute_named(
+        &mut self,
+        module: &Module,
+        entry: &str,
+        args: &[WasmValue],
+    ) -> Result<CoreProcess<E>, vm::NewErr> {
+        let entry_point = vm::EntryPoint::Named {
+            symbol: entry,
+            params: args.to_vec(),
+        };
+        self.execute_inner(module, entry_point)
+    }
+
+    /// Common implementation of [`execute`](Core::execute) and
+    /// [`execute_named`](Core::execute_named).
+    fn execute_inner(
+        &mut self,
+        module: &Module,
+        entry_point: vm::EntryPoint,
+    ) -> Result<CoreProcess<E>, vm::NewErr> {
         let proc_metadata = Process {
-            messages_queue: VecDeque::new(),
+            messages_queue: MessagesQueue::default(),
             registered_interfaces: SmallVec::new(),
             used_interfaces: HashSet::new(),
             emitted_messages: SmallVec::new(),
             messages_to_answer: SmallVec::new(),
         };
 
-        let process = self.processes.execute(module, proc_metadata, ())?;
+        let pid = self
+            .processes
+            .execute(module, entry_point, proc_metadata, ())?
+            .pid();
+
+        if self.memory_limit_reached() {
+            match self.processes.process_by_id(pid) {
+                Some(p) => p.abort(),
+                None => unreachable!(),
+            };
+            return Err(vm::NewErr::MemoryLimitReached);
+        }
 
+        let process = match self.processes.process_by_id(pid) {
+            Some(p) => p,
+            None => unreachable!(),
+        };
         Ok(CoreProcess { process })
     }
+
+    /// Returns the earliest moment when a thread blocked in `next_message` must be woken up, or
+    /// when a message emitted with a timeout must be answered with an error, if any.
+    ///
+    /// Always returns `None` if no [`TimeSource`] has been passed to
+    /// [`CoreBuilder::with_time_source`].
+    pub fn next_deadline(&self) -> Option<Deadline> {
+        self.message_wait_deadlines
+            .values()
+            .chain(self.answer_deadlines.values())
+            .min()
+            .cloned()
+    }
+
+    /// Returns a message whose answer deadline has been reached, if any.
+    fn expired_answer_deadline(&self) -> Option<MessageId> {
+        if self.answer_deadlines.is_empty() {
+            return None;
+        }
+
+        let source = self.time_source.as_ref()?;
+        self.answer_deadlines
+            .iter()
+            .find(|(_, deadline)| deadline.is_expired(&**source))
+            .map(|(message_id, _)| *message_id)
+    }
+
+    /// Returns a thread whose `next_message` deadline has been reached, if any.
+    fn expired_message_wait(&self) -> Option<ThreadId> {
+        if self.message_wait_deadlines.is_empty() {
+            return None;
+        }
+
+        let source = self.time_source.as_ref()?;
+        self.message_wait_deadlines
+            .iter()
+            .find(|(_, deadline)| deadline.is_expired(&**source))
+            .map(|(thread_id, _)| *thread_id)
+    }
+
+    /// Returns an estimation of the memory currently used by the processes and by the `Core`.
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut footprint = MemoryFootprint {
+            processes_memory: self.processes.total_memory_size(),
+            queued_messages: 0,
+            internal_structures: self.interfaces.capacity()
+                * mem::size_of::<(InterfaceHash, InterfaceState)>()
+                + self.messages_to_answer.capacity() * mem::size_of::<(MessageId, Pid)>()
+                + self.message_wait_depths.capacity() * mem::size_of::<(MessageId, u32)>()
+                + self.answer_chunks.capacity() * mem::size_of::<(MessageId, Vec<u8>)>()
+                + self.message_wait_deadlines.capacity() * mem::size_of::<(ThreadId, Deadline)>()
+                + self.answer_deadlines.capacity() * mem::size_of::<(MessageId, Deadline)>(),
+        };
+
+        for process in self.processes.user_datas() {
+            footprint.internal_structures += mem::size_of::<Process>()
+                + process.messages_queue.capacity()
+                    * mem::size_of::<redshirt_syscalls_interface::ffi::Message>();
+
+            for message in process.messages_queue.iter() {
+                footprint.queued_messages += match message {
+                    redshirt_syscalls_interface::ffi::Message::Interface(msg) => {
+                        msg.actual_data.len()
+                    }
+                    redshirt_syscalls_interface::ffi::Message::Response(msg) => {
+                        msg.actual_data.as_ref().map(|d| d.len()).unwrap_or(0)
+                    }
+                    redshirt_syscalls_interface::ffi::Message::ProcessDestroyed(_) => 0,
+                };
+            }
+        }
+
+        for chunks in self.answer_chunks.values() {
+            footprint.queued_messages += chunks.len();
+        }
+
+        for teardown in &self.pending_teardowns {
+            for message in &teardown.messages_queue {
+                if let redshirt_syscalls_interface::ffi::Message::Interface(msg) = message {
+                    footprint.queued_messages += msg.actual_data.len();
+                }
+            }
+        }
+
+        for state in self.interfaces.values() {
+            if let InterfaceState::Requested { other, .. } = state {
+                footprint.queued_messages +=
+                    other.iter().map(|(_, _, msg)| msg.0.len()).sum::<usize>();
+            }
+        }
+
+        footprint
+    }
+
+    /// Returns the number of messages waiting to be retrieved by the given process, not
+    /// including the responses to messages it has emitted.
+    ///
+    /// Returns `None` if the process doesn't exist.
+    pub fn inbox_len(&mut self, pid: Pid) -> Option<usize> {
+        let mut process = self.processes.process_by_id(pid)?;
+        Some(process.user_data().messages_queue.inbox_len())
+    }
+
+    /// Returns true if a memory limit has been configured and the memory of the processes is
+    /// above it.
+    fn memory_limit_reached(&self) -> bool {
+        match self.memory_limit {
+            Some(limit) => self.processes.total_memory_size() > limit,
+            None => false,
+        }
+    }
+}
+
+impl MemoryFootprint {
+    /// Returns the sum of all the fields.
+    pub fn total(&self) -> usize {
+        self.processes_memory + self.queued_messages + self.internal_structures
+    }
 }
 
-impl<'a> CoreProcess<'a> {
+impl<'a, E: vm::Engine> CoreProcess<'a, E> {
     /// Returns the [`Pid`] of the process.
     pub fn pid(&self) -> Pid {
         self.process.pid()
@@ -739,12 +1947,11 @@ impl<'a> CoreProcess<'a> {
 
     /// Adds a new thread to the process, starting the function with the given index and passing
     /// the given parameters.
-    // TODO: don't expose wasmi::RuntimeValue
     pub fn start_thread(
         self,
         fn_index: u32,
-        params: Vec<wasmi::RuntimeValue>,
-    ) -> Result<CoreThread<'a>, vm::StartErr> {
+        params: Vec<WasmValue>,
+    ) -> Result<CoreThread<'a, E>, vm::StartErr> {
         let thread = self.process.start_thread(fn_index, params, ())?;
         Ok(CoreThread { thread })
     }
@@ -755,7 +1962,7 @@ impl<'a> CoreProcess<'a> {
     }
 }
 
-impl<'a> CoreThread<'a> {
+impl<'a, E: vm::Engine> CoreThread<'a, E> {
     /// Returns the [`ThreadId`] of the thread.
     pub fn tid(&mut self) -> ThreadId {
         self.thread.tid()
@@ -781,24 +1988,383 @@ impl CoreBuilder {
         pid
     }
 
+    /// Sets a limit, in bytes, to the total size of the linear memories of the processes, which
+    /// is the [`processes_memory`](MemoryFootprint::processes_memory) field of the
+    /// [`MemoryFootprint`]. This total is kept up to date as processes start, grow their memory
+    /// and stop, which makes checking the limit cheap.
+    ///
+    /// Once the limit is exceeded, starting new processes fails and messages emitted by
+    /// processes are refused with a dedicated error code.
+    ///
+    /// By default, there is no limit.
+    pub fn with_memory_limit(mut self, limit: usize) -> Self {
+        self.memory_limit = Some(limit);
+        self
+    }
+
+    /// If called, the order in which the threads of the processes are executed only depends on
+    /// the inputs of the [`Core`], making runs reproducible.
+    ///
+    /// `Pid`s, `ThreadId`s and `MessageId`s are allocated from pseudo-random pools with a fixed
+    /// seed. Given the same sequence of calls on the [`Core`], the same identifiers are thus
+    /// assigned.
+    ///
+    /// The only clock that the [`Core`] reads is the [`TimeSource`] passed to
+    /// [`CoreBuilder::with_time_source`]. Passing a source that the caller controls makes timeouts
+    /// reproducible as well.
+    pub fn with_deterministic_scheduling(mut self) -> Self {
+        self.inner_builder = self.inner_builder.with_deterministic_scheduling();
+        self
+    }
+
+    /// Sets a limit to the number of messages that can be waiting in the queue of a process.
+    /// Responses to messages that the process has emitted don't count towards this limit.
+    ///
+    /// Once the limit is reached, messages emitted by processes towards this process are
+    /// refused, unless they have been emitted with `allow_delay`, in which case the emitting
+    /// thread blocks until the queue has room. Notifications generated by the `Core` itself,
+    /// such as the destruction of a process, are handled according to `overflow_policy`.
+    ///
+    /// By default, there is no limit.
+    pub fn with_inbox_limit(mut self, limit: usize, overflow_policy: InboxOverflowPolicy) -> Self {
+        self.inbox_limit = Some(limit);
+        self.inbox_overflow_policy = overflow_policy;
+        self
+    }
+
+    /// Sets a maximum depth to the chains of messages waiting for an answer.
+    ///
+    /// When a process emits a message that needs an answer while it is itself handling a message
+    /// that needs an answer, the two messages form a chain. See [`Core::message_wait_depth`].
+    /// Once the limit is set, emitting a message that would be deeper than `max_depth` fails.
+    /// This guards against excessively nested requests.
+    ///
+    /// By default, there is no limit.
+    pub fn with_max_wait_depth(mut self, max_depth: u32) -> Self {
+        self.max_wait_depth = Some(max_depth);
+        self
+    }
+
+    /// If called, the interface messages waiting to be delivered to a process are ordered by
+    /// priority of their emitter, as set with [`Core::set_process_priority`], rather than by
+    /// order of arrival.
+    ///
+    /// This only breaks the first-in-first-out order across priorities. Messages whose emitters
+    /// have the same priority are still delivered in the order in which they have been emitted.
+    /// Responses and process destruction notifications are unaffected.
+    pub fn with_priority_inbox(mut self) -> Self {
+        self.priority_inbox = true;
+        self
+    }
+
+    /// Enables fuel metering. Each thread is given `limit` units of fuel when it starts, and
+    /// consumes one unit per WASM instruction that it executes.
+    ///
+    /// A thread that runs out of fuel is paused, and [`Core::run`] returns
+    /// [`CoreRunOutcome::ThreadOutOfFuel`]. This guarantees that a process stuck in an infinite
+    /// loop doesn't prevent the [`Core`] from regaining control.
+    ///
+    /// By default, fuel metering is disabled.
+    pub fn with_fuel_limit(mut self, limit: u64) -> Self {
+        self.inner_builder = self.inner_builder.with_fuel_limit(limit);
+        self
+    }
+
+    /// Sets the maximum number of pages of 64kiB that the memory of each process can have.
+    ///
+    /// Past this limit, the `memory.grow` instruction returns `-1` to the process, which can
+    /// continue running. Executing a module that requires more memory than that right from the
+    /// start fails with [`NewErr::MemoryLimitReached`](vm::NewErr::MemoryLimitReached).
+    ///
+    /// Contrary to [`with_memory_limit`](CoreBuilder::with_memory_limit), this limit applies to
+    /// each process individually.
+    ///
+    /// By default, there is no limit.
+    pub fn with_process_memory_limit(mut self, max_pages: u32) -> Self {
+        self.inner_builder = self.inner_builder.with_process_memory_limit(max_pages);
+        self
+    }
+
+    /// Sets the [`TimeSource`] used to determine when the `timeout_nanos` passed to
+    /// `next_message` and the answer timeouts passed to `emit_message` elapse.
+    ///
+    /// The [`Core`] checks the time every time [`Core::run`] is called, and doesn't wake itself
+    /// up. Use [`Core::next_deadline`] to know when to call [`Core::run`] again.
+    ///
+    /// By default, there is no time source and timeouts are ignored: threads block until a
+    /// message arrives.
+    pub fn with_time_source(mut self, source: impl TimeSource + 'static) -> Self {
+        self.time_source = Some(Box::new(source));
+        self
+    }
+
+    /// Sets a function called when a process has grown its linear memory, with the [`Pid`] of
+    /// the process and its number of pages of 64kiB before and after growing.
+    ///
+    /// The hook is called synchronously from within [`Core::run`], so that the sizes are
+    /// consistent with [`Core::process_memory_size`]. If a thread grows the memory multiple times
+    /// without being interrupted, the hook is only called once. It isn't called if the process
+    /// terminates.
+    pub fn with_memory_grow_hook(
+        mut self,
+        hook: impl Fn(Pid, u32, u32) + Send + Sync + 'static,
+    ) -> Self {
+        self.inner_builder = self.inner_builder.with_memory_grow_hook(hook);
+        self
+    }
+
+    /// Sets a function called whenever a process stops because of a trap, such as an
+    /// `unreachable` instruction or an out of bounds memory access.
+    ///
+    /// The hook is called synchronously from within [`Core::run`], before the corresponding
+    /// [`CoreRunOutcome::ProgramFinished`] is produced. It isn't called for processes killed
+    /// with [`Core::abort_process`].
+    pub fn with_trap_hook(mut self, hook: impl Fn(Pid, &TrapInfo) + Send + Sync + 'static) -> Self {
+        self.trap_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Sets the maximum number of cleanup steps performed every time [`Core::run`] is called, once
+    /// a process has terminated.
+    ///
+    /// When a process terminates, the handlers of the interfaces it has used must be notified and
+    /// the messages that it hasn't retrieved must be answered with an error. This is done
+    /// progressively, so that a process with thousands of outstanding messages doesn't stall the
+    /// other processes. The default is 64.
+    ///
+    /// # Panic
+    ///
+    /// - Panics if `batch_size` is 0.
+    ///
+    pub fn with_teardown_batch_size(mut self, batch_size: usize) -> Self {
+        assert_ne!(batch_size, 0);
+        self.teardown_batch_size = batch_size;
+        self
+    }
+
     /// Turns the builder into a [`Core`].
-    pub fn build(mut self) -> Core {
+    pub fn build(self) -> Core {
+        self.build_with_engine()
+    }
+
+    /// Turns the builder into a [`Core`] whose processes are executed by the engine `E`.
+    pub fn build_with_engine<E: vm::Engine>(mut self) -> Core<E> {
         self.reserved_pids.shrink_to_fit();
 
         Core {
             pending_events: SegQueue::new(),
             processes: self.inner_builder.build(),
             interfaces: Default::default(),
+            interface_schemas: Default::default(),
+            coalescing_interfaces: Default::default(),
+            interface_waiters: Default::default(),
             reserved_pids: self.reserved_pids,
             message_id_pool: IdPool::new(),
             messages_to_answer: HashMap::default(),
+            message_wait_depths: HashMap::default(),
+            answer_chunks: HashMap::default(),
+            max_wait_depth: self.max_wait_depth,
+            inbox_limit: self.inbox_limit,
+            inbox_overflow_policy: self.inbox_overflow_policy,
+            full_inbox_waiters: HashMap::default(),
+            memory_limit: self.memory_limit,
+            process_priorities: if self.priority_inbox {
+                Some(HashMap::default())
+            } else {
+                None
+            },
+            pending_teardowns: VecDeque::new(),
+            teardown_batch_size: self.teardown_batch_size,
+            time_source: self.time_source,
+            trap_hook: self.trap_hook,
+            message_wait_deadlines: HashMap::default(),
+            answer_deadlines: HashMap::default(),
+        }
+    }
+}
+
+/// Returns the depth in the chain of messages waiting for an answer that a message emitted by
+/// the given process would have. See [`Core::message_wait_depth`].
+fn emit_wait_depth(depths: &HashMap<MessageId, u32>, process: &Process) -> u32 {
+    let handled_depth = process
+        .messages_to_answer
+        .iter()
+        .filter_map(|m| depths.get(m))
+        .cloned()
+        .max()
+        .unwrap_or(0);
+    handled_depth + 1
+}
+
+/// Pushes an interface message to the given queue of messages of a process.
+///
+/// Interface messages are ordered by their [`priority`] and, if `priorities` is `Some`, then by
+/// the priority of their emitter. The message is inserted before the first interface message
+/// that has a strictly lower priority, so that messages with the same priority stay in the order
+/// in which they have been pushed.
+///
+/// [`priority`]: redshirt_syscalls_interface::ffi::InterfaceMessage::priority
+///
+/// If `coalesce` is true and the message doesn't need an answer, the messages of the queue on
+/// the same interface, from the same emitter, and that don't need an answer are removed first.
+fn push_interface_message(
+    queue: &mut MessagesQueue,
+    message: redshirt_syscalls_interface::ffi::Message,
+    priorities: Option<&HashMap<Pid, u8>>,
+    coalesce: bool,
+) {
+    if coalesce {
+        if let redshirt_syscalls_interface::ffi::Message::Interface(new) = &message {
+            if new.message_id.is_none() {
+                queue.retain(|m| match m {
+                    redshirt_syscalls_interface::ffi::Message::Interface(old) => {
+                        old.message_id.is_some()
+                            || old.interface != new.interface
+                            || old.emitter_pid != new.emitter_pid
+                    }
+                    _ => true,
+                });
+            }
+        }
+    }
+
+    let priority_of = |msg: &redshirt_syscalls_interface::ffi::Message| match msg {
+        redshirt_syscalls_interface::ffi::Message::Interface(msg) => {
+            let emitter_priority = priorities
+                .and_then(|p| p.get(&msg.emitter_pid).cloned())
+                .unwrap_or(0);
+            Some((msg.priority, emitter_priority))
+        }
+        _ => None,
+    };
+
+    let priority = match priority_of(&message) {
+        Some(p) => p,
+        None => return queue.push_back(message),
+    };
+
+    let position = queue
+        .iter()
+        .position(|m| priority_of(m).map_or(false, |p| p < priority));
+    match position {
+        Some(position) => queue.insert(position, message),
+        None => queue.push_back(message),
+    }
+}
+
+/// Pushes a notification generated by the `Core` to the given queue of messages of a process,
+/// applying `policy` if the queue has reached `limit`.
+fn push_notification(
+    queue: &mut MessagesQueue,
+    message: redshirt_syscalls_interface::ffi::Message,
+    limit: Option<usize>,
+    policy: InboxOverflowPolicy,
+) {
+    if limit.map_or(false, |limit| queue.inbox_len() >= limit) {
+        // Only interface messages that don't need an answer can be discarded. Notifications are
+        // never discarded.
+        let is_droppable = |m: &redshirt_syscalls_interface::ffi::Message| match m {
+            redshirt_syscalls_interface::ffi::Message::Interface(msg) => msg.message_id.is_none(),
+            _ => false,
+        };
+
+        let to_drop = match policy {
+            InboxOverflowPolicy::ForceGrow => None,
+            InboxOverflowPolicy::DropOldest => queue.iter().position(is_droppable),
+            InboxOverflowPolicy::DropNewest => queue.iter().rposition(is_droppable),
+        };
+
+        if let Some(to_drop) = to_drop {
+            queue.remove(to_drop);
         }
     }
+
+    queue.push_back(message);
+}
+
+/// Queue of messages of a process. Keeps track of the number of messages that count towards the
+/// limit passed to [`CoreBuilder::with_inbox_limit`], so that it doesn't have to be recomputed.
+///
+/// Dereferences to the underlying `VecDeque` for read-only access.
+#[derive(Debug, Default)]
+struct MessagesQueue {
+    messages: VecDeque<redshirt_syscalls_interface::ffi::Message>,
+    /// Number of messages in `messages` that aren't responses.
+    inbox_len: usize,
+}
+
+impl MessagesQueue {
+    /// Returns the number of messages in the queue, not including responses.
+    fn inbox_len(&self) -> usize {
+        self.inbox_len
+    }
+
+    fn push_back(&mut self, message: redshirt_syscalls_interface::ffi::Message) {
+        if is_inbox_message(&message) {
+            self.inbox_len += 1;
+        }
+        self.messages.push_back(message);
+    }
+
+    fn insert(&mut self, index: usize, message: redshirt_syscalls_interface::ffi::Message) {
+        if is_inbox_message(&message) {
+            self.inbox_len += 1;
+        }
+        self.messages.insert(index, message);
+    }
+
+    fn remove(&mut self, index: usize) -> Option<redshirt_syscalls_interface::ffi::Message> {
+        let message = self.messages.remove(index)?;
+        if is_inbox_message(&message) {
+            self.inbox_len -= 1;
+        }
+        Some(message)
+    }
+
+    fn retain(&mut self, mut f: impl FnMut(&redshirt_syscalls_interface::ffi::Message) -> bool) {
+        let inbox_len = &mut self.inbox_len;
+        self.messages.retain(|message| {
+            let keep = f(message);
+            if !keep && is_inbox_message(message) {
+                *inbox_len -= 1;
+            }
+            keep
+        });
+    }
+
+    /// Gives mutable access to a message of the queue. The kind of the message must not be
+    /// changed.
+    fn get_mut(&mut self, index: usize) -> Option<&mut redshirt_syscalls_interface::ffi::Message> {
+        self.messages.get_mut(index)
+    }
+
+    fn into_messages(self) -> VecDeque<redshirt_syscalls_interface::ffi::Message> {
+        self.messages
+    }
+}
+
+impl ops::Deref for MessagesQueue {
+    type Target = VecDeque<redshirt_syscalls_interface::ffi::Message>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.messages
+    }
+}
+
+/// Returns true if the given message counts towards the limit passed to
+/// [`CoreBuilder::with_inbox_limit`]. Responses to messages emitted by the process don't.
+fn is_inbox_message(message: &redshirt_syscalls_interface::ffi::Message) -> bool {
+    match message {
+        redshirt_syscalls_interface::ffi::Message::Response(_) => false,
+        _ => true,
+    }
 }
 
 /// If any of the threads of the given process is waiting for a message to arrive, checks the
 /// queue and tries to resume said thread.
-fn try_resume_message_wait(process: extrinsics::ProcessesCollectionExtrinsicsProc<Process, ()>) {
+fn try_resume_message_wait<E: vm::Engine>(
+    process: extrinsics::ProcessesCollectionExtrinsicsProc<Process, (), E>,
+) {
     // TODO: is it a good strategy to just go through threads in linear order? what about
     //       round-robin-ness instead?
     let mut thread = process.main_thread();
@@ -821,9 +2387,9 @@ fn try_resume_message_wait(process: extrinsics::ProcessesCollectionExtrinsicsPro
 /// said thread.
 // TODO: in order to call this function, we essentially have to put the state machine in a "bad"
 // state (message in queue and thread would accept said message); not great
-fn try_resume_message_wait_thread(
-    mut thread: extrinsics::ProcessesCollectionExtrinsicsThreadWaitMessage<Process, ()>,
-) -> extrinsics::ProcessesCollectionExtrinsicsThread<Process, ()> {
+fn try_resume_message_wait_thread<E: vm::Engine>(
+    mut thread: extrinsics::ProcessesCollectionExtrinsicsThreadWaitMessage<Process, (), E>,
+) -> extrinsics::ProcessesCollectionExtrinsicsThread<Process, (), E> {
     // Try to find a message in the queue that matches something the user is waiting for.
     let mut index_in_queue = 0;
     let index_in_msg_ids = loop {
@@ -856,16 +2422,21 @@ fn try_resume_message_wait_thread(
     // If we reach here, we have found a message that matches what the user wants.
 
     // Adjust the `index_in_list` field of the message to match what we have.
-    match thread.process_user_data().messages_queue[index_in_queue] {
-        redshirt_syscalls_interface::ffi::Message::Response(ref mut response) => {
+    match thread
+        .process_user_data()
+        .messages_queue
+        .get_mut(index_in_queue)
+    {
+        Some(redshirt_syscalls_interface::ffi::Message::Response(ref mut response)) => {
             response.index_in_list = u32::try_from(index_in_msg_ids).unwrap();
         }
-        redshirt_syscalls_interface::ffi::Message::Interface(ref mut interface) => {
+        Some(redshirt_syscalls_interface::ffi::Message::Interface(ref mut interface)) => {
             interface.index_in_list = u32::try_from(index_in_msg_ids).unwrap();
         }
-        redshirt_syscalls_interface::ffi::Message::ProcessDestroyed(ref mut proc_destr) => {
+        Some(redshirt_syscalls_interface::ffi::Message::ProcessDestroyed(ref mut proc_destr)) => {
             proc_destr.index_in_list = u32::try_from(index_in_msg_ids).unwrap();
         }
+        None => unreachable!(),
     }
 
     // Turn said message into bytes.
@@ -881,6 +2452,18 @@ fn try_resume_message_wait_thread(
             .process_user_data()
             .messages_queue
             .remove(index_in_queue);
+        if let Some(redshirt_syscalls_interface::ffi::Message::Interface(
+            redshirt_syscalls_interface::ffi::InterfaceMessage {
+                message_id: Some(message_id),
+                ..
+            },
+        )) = message
+        {
+            thread
+                .process_user_data()
+                .messages_to_answer
+                .push(message_id);
+        }
         From::from(thread.resume_message(index_in_msg_ids, msg_bytes))
     } else {
         From::from(thread.resume_message_too_big(msg_bytes.0.len()))
@@ -16,9 +16,10 @@
 use crate::id_pool::IdPool;
 use crate::module::Module;
 use crate::scheduler::vm;
-use crate::signature::Signature;
-use alloc::{borrow::Cow, vec::Vec};
-use core::fmt;
+use crate::signature::{Signature, WasmValue};
+use alloc::{borrow::Cow, borrow::ToOwned as _, boxed::Box, vec::Vec};
+use byteorder::{ByteOrder as _, LittleEndian};
+use core::{convert::TryFrom as _, fmt};
 use hashbrown::{
     hash_map::{DefaultHashBuilder, Entry, OccupiedEntry},
     HashMap,
@@ -33,7 +34,10 @@ use redshirt_syscalls_interface::{Pid, ThreadId};
 /// The generic parameters `TPud` and `TTud` are "user data"s that are stored respectively per
 /// process and per thread, and allows the user to put extra information associated to a process
 /// or a thread.
-pub struct ProcessesCollection<TExtr, TPud, TTud> {
+///
+/// The code of the processes is executed by the [`Engine`](vm::Engine) passed as the generic
+/// parameter `E`.
+pub struct ProcessesCollection<TExtr, TPud, TTud, E: vm::Engine = vm::WasmiEngine> {
     /// Allocations of process IDs.
     pid_pool: IdPool,
 
@@ -41,18 +45,34 @@ pub struct ProcessesCollection<TExtr, TPud, TTud> {
     tid_pool: IdPool,
 
     /// List of running processes.
-    processes: HashMap<Pid, Process<TPud, TTud>>,
+    processes: HashMap<Pid, Process<TPud, TTud, E>>,
+
+    /// Sum of the sizes, in bytes, of the linear memories of all the processes in `processes`.
+    /// Updated when a process starts, grows its memory, or stops.
+    total_memory_size: usize,
 
-    /// List of functions that processes can call.
+    /// List of functions that processes can call, with their signature.
     /// The key of this map is an arbitrary `usize` that we pass to the WASM interpreter.
     /// This field is never modified after the [`ProcessesCollection`] is created.
-    extrinsics: HashMap<usize, TExtr>,
+    extrinsics: HashMap<usize, (TExtr, Signature)>,
 
     /// Map used to resolve imports when starting a process.
     /// For each module and function name, stores the signature and an arbitrary usize that
     /// corresponds to the entry in `extrinsics`.
     /// This field is never modified after the [`ProcessesCollection`] is created.
     extrinsics_id_assign: HashMap<(Cow<'static, str>, Cow<'static, str>), (usize, Signature)>,
+
+    /// If true, the thread to run is chosen in a deterministic way. See
+    /// [`ProcessesCollectionBuilder::with_deterministic_scheduling`].
+    deterministic: bool,
+
+    /// If `deterministic` is true, contains the process whose thread has been run the last time
+    /// [`ProcessesCollection::run`] has been called.
+    last_run_process: Option<Pid>,
+
+    /// Function called when the memory of a process has grown. See
+    /// [`ProcessesCollectionBuilder::with_memory_grow_hook`].
+    memory_grow_hook: Option<Box<dyn Fn(Pid, u32, u32) + Send + Sync>>,
 }
 
 /// Prototype for a `ProcessesCollection` under construction.
@@ -60,18 +80,27 @@ pub struct ProcessesCollectionBuilder<TExtr> {
     /// See the corresponding field in `ProcessesCollection`.
     pid_pool: IdPool,
     /// See the corresponding field in `ProcessesCollection`.
-    extrinsics: HashMap<usize, TExtr>,
+    extrinsics: HashMap<usize, (TExtr, Signature)>,
     /// See the corresponding field in `ProcessesCollection`.
     extrinsics_id_assign: HashMap<(Cow<'static, str>, Cow<'static, str>), (usize, Signature)>,
+    /// See the corresponding field in `ProcessesCollection`.
+    deterministic: bool,
+    /// See the corresponding field in `ProcessesCollection`.
+    memory_grow_hook: Option<Box<dyn Fn(Pid, u32, u32) + Send + Sync>>,
 }
 
 /// Single running process in the list.
-struct Process<TPud, TTud> {
+struct Process<TPud, TTud, E: vm::Engine> {
     /// State of a single process.
-    state_machine: vm::ProcessStateMachine<Thread<TTud>>,
+    state_machine: vm::ProcessStateMachine<Thread<TTud>, E>,
 
     /// User-chosen data (opaque to us) that describes the process.
     user_data: TPud,
+
+    /// Index of the thread from which to start looking for a thread that is ready to run.
+    /// Modified when a thread yields, so that the other threads of the process get a chance to
+    /// run.
+    first_thread_to_run: usize,
 }
 
 /// Additional data associated to a thread.
@@ -83,31 +112,38 @@ struct Thread<TTud> {
     thread_id: ThreadId,
 
     /// Value to use when resuming. If `Some`, the process is ready for a round of running. If
-    /// `None`, then we're waiting for the user to call `resume`.
-    value_back: Option<Option<wasmi::RuntimeValue>>,
+    /// `None`, then we're waiting for the user to call `resume`. Contains an error if the
+    /// extrinsic that the thread has called has failed and the process must be killed.
+    value_back: Option<Result<Option<WasmValue>, vm::Trap>>,
 }
 
 /// Access to a process within the collection.
-pub struct ProcessesCollectionProc<'a, TPud, TTud> {
+pub struct ProcessesCollectionProc<'a, TPud, TTud, E: vm::Engine = vm::WasmiEngine> {
     /// Pointer within the hashmap.
-    process: OccupiedEntry<'a, Pid, Process<TPud, TTud>, DefaultHashBuilder>,
+    process: OccupiedEntry<'a, Pid, Process<TPud, TTud, E>, DefaultHashBuilder>,
 
     /// Reference to the same field in [`ProcessesCollection`].
     tid_pool: &'a mut IdPool,
+
+    /// Reference to the same field in [`ProcessesCollection`].
+    total_memory_size: &'a mut usize,
 }
 
 /// Access to a thread within the collection.
-pub struct ProcessesCollectionThread<'a, TPud, TTud> {
+pub struct ProcessesCollectionThread<'a, TPud, TTud, E: vm::Engine = vm::WasmiEngine> {
     /// Pointer within the hashmap.
-    process: OccupiedEntry<'a, Pid, Process<TPud, TTud>, DefaultHashBuilder>,
+    process: OccupiedEntry<'a, Pid, Process<TPud, TTud, E>, DefaultHashBuilder>,
 
     /// Index of the thread within the [`vm::ProcessStateMachine`].
     thread_index: usize,
+
+    /// Reference to the same field in [`ProcessesCollection`].
+    total_memory_size: &'a mut usize,
 }
 
 /// Outcome of the [`run`](ProcessesCollection::run) function.
 #[derive(Debug)]
-pub enum RunOneOutcome<'a, TExtr, TPud, TTud> {
+pub enum RunOneOutcome<'a, TExtr, TPud, TTud, E: vm::Engine = vm::WasmiEngine> {
     /// Either the main thread of a process has finished, or a fatal error was encountered.
     ///
     /// The process no longer exists.
@@ -124,19 +160,19 @@ pub enum RunOneOutcome<'a, TExtr, TPud, TTud> {
         dead_threads: Vec<(ThreadId, TTud)>,
 
         /// Value returned by the main thread that has finished, or error that happened.
-        outcome: Result<Option<wasmi::RuntimeValue>, wasmi::Trap>,
+        outcome: Result<Option<WasmValue>, vm::Trap>,
     },
 
     /// A thread in a process has finished.
     ThreadFinished {
         /// Process whose thread has finished.
-        process: ProcessesCollectionProc<'a, TPud, TTud>,
+        process: ProcessesCollectionProc<'a, TPud, TTud, E>,
 
         /// User data of the thread.
         user_data: TTud,
 
         /// Value returned by the function that was executed.
-        value: Option<wasmi::RuntimeValue>,
+        value: Option<WasmValue>,
     },
 
     /// The currently-executed function has been paused due to a call to an external function.
@@ -146,14 +182,14 @@ pub enum RunOneOutcome<'a, TExtr, TPud, TTud> {
     /// again, you must pass back the outcome of calling that function.
     Interrupted {
         /// Thread that has been interrupted.
-        thread: ProcessesCollectionThread<'a, TPud, TTud>,
+        thread: ProcessesCollectionThread<'a, TPud, TTud, E>,
 
         /// Identifier of the function to call. Corresponds to the value provided at
         /// initialization when resolving imports.
         id: &'a mut TExtr,
 
         /// Parameters of the function call.
-        params: Vec<wasmi::RuntimeValue>,
+        params: Vec<WasmValue>,
     },
 
     /// No thread is ready to run. Nothing was done.
@@ -166,7 +202,7 @@ pub enum RunOneOutcome<'a, TExtr, TPud, TTud> {
 /// to grow again in the future. We therefore avoid that situation.
 const PROCESSES_MIN_CAPACITY: usize = 128;
 
-impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
+impl<TExtr, TPud, TTud, E: vm::Engine> ProcessesCollection<TExtr, TPud, TTud, E> {
     /// Creates a new process state machine from the given module.
     ///
     /// The closure is called for each import that the module has. It must assign a number to each
@@ -175,48 +211,72 @@ impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
     /// the call.
     ///
     /// A single main thread (whose user data is passed by parameter) is automatically created and
-    /// is paused at the start of the "_start" function of the module.
+    /// is paused at the start of the given entry point of the module.
     pub fn execute(
         &mut self,
         module: &Module,
+        entry_point: vm::EntryPoint,
         proc_user_data: TPud,
         main_thread_user_data: TTud,
-    ) -> Result<ProcessesCollectionProc<TPud, TTud>, vm::NewErr> {
+    ) -> Result<ProcessesCollectionProc<TPud, TTud, E>, vm::NewErr> {
         let main_thread_id = self.tid_pool.assign(); // TODO: check for duplicates
         let main_thread_data = Thread {
             user_data: main_thread_user_data,
             thread_id: main_thread_id,
-            value_back: Some(None),
+            value_back: Some(Ok(None)),
         };
 
+        // If an import fails to resolve, we store its details here in order to report them.
+        let mut missing_import = None;
+
         let state_machine = {
             let extrinsics_id_assign = &mut self.extrinsics_id_assign;
-            vm::ProcessStateMachine::new(
+            let missing_import_ref = &mut missing_import;
+            let result = vm::ProcessStateMachine::with_engine(
                 module,
+                entry_point,
                 main_thread_data,
                 move |interface, function, obtained_signature| {
-                    if let Some((index, expected_signature)) =
-                        extrinsics_id_assign.get(&(interface.into(), function.into()))
-                    {
-                        if expected_signature.matches_wasmi(obtained_signature) {
-                            return Ok(*index);
-                        } else {
-                            // TODO: way to report the signature mismatch?
-                        }
+                    let expected_signature =
+                        match extrinsics_id_assign.get(&(interface.into(), function.into())) {
+                            Some((index, expected_signature))
+                                if expected_signature == obtained_signature =>
+                            {
+                                return Ok(*index)
+                            }
+                            Some((_, expected_signature)) => Some(expected_signature.clone()),
+                            None => None,
+                        };
+
+                    if missing_import_ref.is_none() {
+                        *missing_import_ref = Some(vm::NewErr::MissingImport {
+                            module: interface.to_owned(),
+                            field: function.to_owned(),
+                            signature: obtained_signature.clone(),
+                            expected_signature,
+                        });
                     }
 
                     Err(())
                 },
-            )?
+            );
+
+            match (result, missing_import.take()) {
+                (Ok(state_machine), _) => state_machine,
+                (Err(vm::NewErr::Instantiation(_)), Some(err)) => return Err(err),
+                (Err(err), _) => return Err(err),
+            }
         };
 
         // We only modify `self` at the very end.
         let new_pid = self.pid_pool.assign();
+        self.total_memory_size += state_machine.memory_size();
         self.processes.insert(
             new_pid,
             Process {
                 state_machine,
                 user_data: proc_user_data,
+                first_thread_to_run: 0,
             },
         );
 
@@ -234,21 +294,37 @@ impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
     /// Runs one thread amongst the collection.
     ///
     /// Which thread is run is implementation-defined and no guarantee is made.
-    pub fn run(&mut self) -> RunOneOutcome<TExtr, TPud, TTud> {
+    pub fn run(&mut self) -> RunOneOutcome<TExtr, TPud, TTud, E> {
         // We start by finding a thread in `self.processes` that is ready to run.
         let (mut process, inner_thread_index): (OccupiedEntry<_, _, _>, usize) = {
+            let deterministic = self.deterministic;
+            let last_run_process = self.last_run_process.map(u64::from);
             let entries = self.processes.iter_mut().collect::<Vec<_>>();
             // TODO: entries.shuffle(&mut rand::thread_rng());
-            let entry = entries
-                .into_iter()
-                .filter_map(|(k, p)| {
+            let entry = {
+                let mut ready = entries.into_iter().filter_map(|(k, p)| {
                     if let Some(i) = p.ready_to_run_thread_index() {
                         Some((*k, i))
                     } else {
                         None
                     }
-                })
-                .next();
+                });
+                // The iteration order of `self.processes` isn't deterministic. Instead, we go
+                // through the processes in the order of their `Pid`, starting after the one that
+                // has been run last, so that a busy process can't starve the others.
+                if deterministic {
+                    ready.min_by_key(|(pid, _)| {
+                        let pid = u64::from(*pid);
+                        let wrapped = last_run_process.map_or(false, |last| pid <= last);
+                        (wrapped, pid)
+                    })
+                } else {
+                    ready.next()
+                }
+            };
+            if deterministic {
+                self.last_run_process = entry.map(|(pid, _)| pid);
+            }
             match entry {
                 Some((pid, inner_thread_index)) => match self.processes.entry(pid) {
                     Entry::Occupied(p) => (p, inner_thread_index),
@@ -259,16 +335,39 @@ impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
         };
 
         // Now run the thread until something happens.
+        let memory_size_before = process.get().state_machine.memory_size();
         let run_outcome = {
             let mut thread = match process.get_mut().state_machine.thread(inner_thread_index) {
                 Some(t) => t,
                 None => unreachable!(),
             };
-            let value_back = match thread.user_data().value_back.take() {
-                Some(vb) => vb,
+            match thread.user_data().value_back.take() {
+                Some(Ok(value_back)) => Ok(thread.run(value_back)),
+                Some(Err(trap)) => Err(trap),
                 None => unreachable!(),
-            };
-            thread.run(value_back)
+            }
+        };
+
+        // The memory of a process never shrinks.
+        self.total_memory_size += process.get().state_machine.memory_size() - memory_size_before;
+
+        let run_outcome = match run_outcome {
+            Ok(outcome) => outcome,
+            // The extrinsic that the thread has called has failed. We kill the entire process.
+            Err(trap) => {
+                let (pid, proc) = remove_process(&mut self.total_memory_size, process);
+                let dead_threads = proc
+                    .state_machine
+                    .into_user_datas()
+                    .map(|t| (t.thread_id, t.user_data))
+                    .collect::<Vec<_>>();
+                return RunOneOutcome::ProcessFinished {
+                    pid,
+                    user_data: proc.user_data,
+                    dead_threads,
+                    outcome: Err(trap),
+                };
+            }
         };
 
         match run_outcome {
@@ -281,7 +380,7 @@ impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
                 return_value,
                 user_data: main_thread_user_data,
             }) => {
-                let (pid, proc) = process.remove_entry();
+                let (pid, proc) = remove_process(&mut self.total_memory_size, process);
                 let other_threads_ud = proc.state_machine.into_user_datas();
                 let mut dead_threads = Vec::with_capacity(1 + other_threads_ud.len());
                 dead_threads.push((
@@ -305,26 +404,51 @@ impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
                 return_value,
                 user_data,
                 ..
-            }) => RunOneOutcome::ThreadFinished {
-                process: ProcessesCollectionProc {
-                    process,
-                    tid_pool: &mut self.tid_pool,
-                },
-                user_data: user_data.user_data,
-                value: return_value,
-            },
+            }) => {
+                report_memory_growth(&self.memory_grow_hook, &process, memory_size_before);
+                RunOneOutcome::ThreadFinished {
+                    process: ProcessesCollectionProc {
+                        process,
+                        tid_pool: &mut self.tid_pool,
+                        total_memory_size: &mut self.total_memory_size,
+                    },
+                    user_data: user_data.user_data,
+                    value: return_value,
+                }
+            }
 
             // Thread wants to call an extrinsic function.
             Ok(vm::ExecOutcome::Interrupted { id, params, .. }) => {
-                // TODO: check params against signature with a debug_assert
-                let extrinsic = match self.extrinsics.get_mut(&id) {
+                report_memory_growth(&self.memory_grow_hook, &process, memory_size_before);
+
+                let (extrinsic, signature) = match self.extrinsics.get_mut(&id) {
                     Some(e) => e,
                     None => unreachable!(),
                 };
+
+                // The signature has normally been enforced when resolving the imports. We check
+                // the parameters again in order to kill the process, rather than pass invalid
+                // parameters to the handler of the extrinsic, if that ever isn't the case.
+                if !signature.matches_params(&params) {
+                    let (pid, proc) = remove_process(&mut self.total_memory_size, process);
+                    let dead_threads = proc
+                        .state_machine
+                        .into_user_datas()
+                        .map(|t| (t.thread_id, t.user_data))
+                        .collect::<Vec<_>>();
+                    return RunOneOutcome::ProcessFinished {
+                        pid,
+                        user_data: proc.user_data,
+                        dead_threads,
+                        outcome: Err(vm::Trap::UnexpectedSignature),
+                    };
+                }
+
                 RunOneOutcome::Interrupted {
                     thread: ProcessesCollectionThread {
                         process,
                         thread_index: inner_thread_index,
+                        total_memory_size: &mut self.total_memory_size,
                     },
                     id: extrinsic,
                     params,
@@ -333,7 +457,7 @@ impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
 
             // An error happened during the execution. We kill the entire process.
             Ok(vm::ExecOutcome::Errored { error, .. }) => {
-                let (pid, proc) = process.remove_entry();
+                let (pid, proc) = remove_process(&mut self.total_memory_size, process);
                 let dead_threads = proc
                     .state_machine
                     .into_user_datas()
@@ -354,19 +478,62 @@ impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
         self.processes.keys().cloned()
     }
 
+    /// Returns an iterator to the user datas of all the processes that exist in the collection.
+    pub fn user_datas<'a>(&'a self) -> impl ExactSizeIterator<Item = &'a TPud> + 'a {
+        self.processes.values().map(|p| &p.user_data)
+    }
+
+    /// Returns an iterator to the `Pid`s and user datas of all the processes that exist in the
+    /// collection.
+    pub fn pids_user_datas<'a>(&'a self) -> impl ExactSizeIterator<Item = (Pid, &'a TPud)> + 'a {
+        self.processes.iter().map(|(pid, p)| (*pid, &p.user_data))
+    }
+
+    /// Returns the sum of the sizes, in bytes, of the linear memories of all the processes.
+    pub fn total_memory_size(&self) -> usize {
+        self.total_memory_size
+    }
+
+    /// Returns the size, in bytes, of the linear memory of the given process, or `None` if the
+    /// process doesn't exist.
+    pub fn process_memory_size(&self, pid: Pid) -> Option<usize> {
+        self.processes
+            .get(&pid)
+            .map(|p| p.state_machine.memory_size())
+    }
+
+    /// Calls `f` with a view of the given memory range of the given process, without copying
+    /// it.
+    ///
+    /// Returns an error if the process doesn't exist, or if the range is invalid or out of range.
+    pub fn with_process_memory<R>(
+        &self,
+        pid: Pid,
+        offset: u32,
+        size: u32,
+        f: impl FnOnce(&[u8]) -> R,
+    ) -> Result<R, ()> {
+        let process = self.processes.get(&pid).ok_or(())?;
+        process.state_machine.with_memory(offset, size, f)
+    }
+
     /// Returns a process by its [`Pid`], if it exists.
-    pub fn process_by_id(&mut self, pid: Pid) -> Option<ProcessesCollectionProc<TPud, TTud>> {
+    pub fn process_by_id(&mut self, pid: Pid) -> Option<ProcessesCollectionProc<TPud, TTud, E>> {
         match self.processes.entry(pid) {
             Entry::Vacant(_) => None,
             Entry::Occupied(e) => Some(ProcessesCollectionProc {
                 process: e,
                 tid_pool: &mut self.tid_pool,
+                total_memory_size: &mut self.total_memory_size,
             }),
         }
     }
 
     /// Returns a thread by its [`ThreadId`], if it exists.
-    pub fn thread_by_id(&mut self, id: ThreadId) -> Option<ProcessesCollectionThread<TPud, TTud>> {
+    pub fn thread_by_id(
+        &mut self,
+        id: ThreadId,
+    ) -> Option<ProcessesCollectionThread<TPud, TTud, E>> {
         // TODO: ouch that's O(n)
 
         let mut loop_out = None;
@@ -390,6 +557,7 @@ impl<TExtr, TPud, TTud> ProcessesCollection<TExtr, TPud, TTud> {
                 Entry::Occupied(e) => e,
             },
             thread_index,
+            total_memory_size: &mut self.total_memory_size,
         })
     }
 }
@@ -400,6 +568,8 @@ impl<TExtr> Default for ProcessesCollectionBuilder<TExtr> {
             pid_pool: IdPool::new(),
             extrinsics: Default::default(),
             extrinsics_id_assign: Default::default(),
+            deterministic: false,
+            memory_grow_hook: None,
         }
     }
 }
@@ -424,7 +594,8 @@ impl<TExtr> ProcessesCollectionBuilder<TExtr> {
     /// calls it, a [`RunOneOutcome::Interrupted`] event will be generated, containing the token
     /// passed as parameter.
     ///
-    /// The function signature passed as parameter is enforced when the process is created.
+    /// The function signature passed as parameter is enforced when the process is created, and
+    /// the parameters are checked against it again every time the function is called.
     ///
     /// # Panic
     ///
@@ -444,14 +615,40 @@ impl<TExtr> ProcessesCollectionBuilder<TExtr> {
         debug_assert!(!self.extrinsics.contains_key(&index));
         match self.extrinsics_id_assign.entry((interface, f_name)) {
             Entry::Occupied(_) => panic!(),
-            Entry::Vacant(e) => e.insert((index, signature)),
+            Entry::Vacant(e) => e.insert((index, signature.clone())),
         };
-        self.extrinsics.insert(index, token.into());
+        self.extrinsics.insert(index, (token.into(), signature));
+        self
+    }
+
+    /// If called, [`ProcessesCollection::run`] goes through the processes that have a thread
+    /// ready to run in a round-robin fashion, ordered by `Pid`, rather than relying on the
+    /// iteration order of an internal hash map.
+    ///
+    /// Combined with identifiers being allocated from pools with a fixed seed, this makes the
+    /// execution of the processes reproducible given the same inputs.
+    pub fn with_deterministic_scheduling(mut self) -> Self {
+        self.deterministic = true;
+        self
+    }
+
+    /// Sets a function called when the linear memory of a process has grown, with the `Pid` of
+    /// the process and its number of pages of 64kiB before and after growing.
+    ///
+    /// The hook is called from within [`ProcessesCollection::run`] once the thread that has grown
+    /// the memory is paused, so that the sizes are consistent with
+    /// [`ProcessesCollection::process_memory_size`]. If the memory grows multiple times while the
+    /// thread runs, the hook is only called once. It isn't called if the process terminates.
+    pub fn with_memory_grow_hook(
+        mut self,
+        hook: impl Fn(Pid, u32, u32) + Send + Sync + 'static,
+    ) -> Self {
+        self.memory_grow_hook = Some(Box::new(hook));
         self
     }
 
     /// Turns the builder into a [`ProcessesCollection`].
-    pub fn build<TPud, TTud>(mut self) -> ProcessesCollection<TExtr, TPud, TTud> {
+    pub fn build<TPud, TTud, E: vm::Engine>(mut self) -> ProcessesCollection<TExtr, TPud, TTud, E> {
         // We're not going to modify these fields ever again, so let's free some memory.
         self.extrinsics.shrink_to_fit();
         self.extrinsics_id_assign.shrink_to_fit();
@@ -461,16 +658,56 @@ impl<TExtr> ProcessesCollectionBuilder<TExtr> {
             pid_pool: self.pid_pool,
             tid_pool: IdPool::new(),
             processes: HashMap::with_capacity(PROCESSES_MIN_CAPACITY),
+            total_memory_size: 0,
             extrinsics: self.extrinsics,
             extrinsics_id_assign: self.extrinsics_id_assign,
+            deterministic: self.deterministic,
+            last_run_process: None,
+            memory_grow_hook: self.memory_grow_hook,
         }
     }
 }
 
-impl<TPud, TTud> Process<TPud, TTud> {
+/// Removes the given process from the collection, and subtracts the size of its memory from
+/// `total_memory_size`.
+fn remove_process<TPud, TTud, E: vm::Engine>(
+    total_memory_size: &mut usize,
+    process: OccupiedEntry<Pid, Process<TPud, TTud, E>, DefaultHashBuilder>,
+) -> (Pid, Process<TPud, TTud, E>) {
+    let (pid, process) = process.remove_entry();
+    *total_memory_size -= process.state_machine.memory_size();
+    (pid, process)
+}
+
+/// Calls `hook`, if any, if the memory of the given process is now larger than
+/// `memory_size_before` bytes.
+fn report_memory_growth<TPud, TTud, E: vm::Engine>(
+    hook: &Option<Box<dyn Fn(Pid, u32, u32) + Send + Sync>>,
+    process: &OccupiedEntry<Pid, Process<TPud, TTud, E>, DefaultHashBuilder>,
+    memory_size_before: usize,
+) {
+    let hook = match hook {
+        Some(h) => h,
+        None => return,
+    };
+
+    let memory_size_after = process.get().state_machine.memory_size();
+    if memory_size_after > memory_size_before {
+        let to_pages = |bytes: usize| u32::try_from(bytes / 65536).unwrap();
+        hook(
+            *process.key(),
+            to_pages(memory_size_before),
+            to_pages(memory_size_after),
+        );
+    }
+}
+
+impl<TPud, TTud, E: vm::Engine> Process<TPud, TTud, E> {
     /// Finds a thread in this process that is ready to be executed.
     fn ready_to_run_thread_index(&mut self) -> Option<usize> {
-        for thread_n in 0..self.state_machine.num_threads() {
+        let num_threads = self.state_machine.num_threads();
+        for offset in 0..num_threads {
+            let thread_n = (self.first_thread_to_run + offset) % num_threads;
             let mut thread = match self.state_machine.thread(thread_n) {
                 Some(t) => t,
                 None => unreachable!(),
@@ -484,7 +721,7 @@ impl<TPud, TTud> Process<TPud, TTud> {
     }
 }
 
-impl<'a, TPud, TTud> ProcessesCollectionProc<'a, TPud, TTud> {
+impl<'a, TPud, TTud, E: vm::Engine> ProcessesCollectionProc<'a, TPud, TTud, E> {
     /// Returns the [`Pid`] of the process. Allows later retrieval by calling
     /// [`process_by_id`](ProcessesCollection::process_by_id).
     pub fn pid(&self) -> Pid {
@@ -502,18 +739,17 @@ impl<'a, TPud, TTud> ProcessesCollectionProc<'a, TPud, TTud> {
     /// > **Note**: The "function ID" is the index of the function in the WASM module. WASM
     /// >           doesn't have function pointers. Instead, all the functions are part of a single
     /// >           global array of functions.
-    // TODO: don't expose wasmi::RuntimeValue in the API
     pub fn start_thread(
         mut self,
         fn_index: u32,
-        params: Vec<wasmi::RuntimeValue>,
+        params: Vec<WasmValue>,
         user_data: TTud,
-    ) -> Result<ProcessesCollectionThread<'a, TPud, TTud>, vm::StartErr> {
+    ) -> Result<ProcessesCollectionThread<'a, TPud, TTud, E>, vm::StartErr> {
         let thread_id = self.tid_pool.assign(); // TODO: check for duplicates
         let thread_data = Thread {
             user_data,
             thread_id,
-            value_back: Some(None),
+            value_back: Some(Ok(None)),
         };
 
         self.process
@@ -521,10 +757,11 @@ impl<'a, TPud, TTud> ProcessesCollectionProc<'a, TPud, TTud> {
             .state_machine
             .start_thread_by_id(fn_index, params, thread_data)?;
 
-        let thread_index = self.process.get_mut().state_machine.num_threads();
+        let thread_index = self.process.get_mut().state_machine.num_threads() - 1;
         Ok(ProcessesCollectionThread {
             process: self.process,
             thread_index,
+            total_memory_size: self.total_memory_size,
         })
     }
 
@@ -532,10 +769,11 @@ impl<'a, TPud, TTud> ProcessesCollectionProc<'a, TPud, TTud> {
     ///
     /// The "main thread" of a process is created automatically when you call
     /// [`ProcessesCollection::execute`]. If it stops, the entire process stops.
-    pub fn main_thread(self) -> ProcessesCollectionThread<'a, TPud, TTud> {
+    pub fn main_thread(self) -> ProcessesCollectionThread<'a, TPud, TTud, E> {
         ProcessesCollectionThread {
             process: self.process,
             thread_index: 0,
+            total_memory_size: self.total_memory_size,
         }
     }
 
@@ -558,7 +796,7 @@ impl<'a, TPud, TTud> ProcessesCollectionProc<'a, TPud, TTud> {
 
     /// Aborts the process and returns the associated user data.
     pub fn abort(self) -> (TPud, Vec<(ThreadId, TTud)>) {
-        let (_, proc) = self.process.remove_entry();
+        let (_, proc) = remove_process(self.total_memory_size, self.process);
         let dead_threads = proc
             .state_machine
             .into_user_datas()
@@ -568,7 +806,7 @@ impl<'a, TPud, TTud> ProcessesCollectionProc<'a, TPud, TTud> {
     }
 }
 
-impl<'a, TPud, TTud> fmt::Debug for ProcessesCollectionProc<'a, TPud, TTud>
+impl<'a, TPud, TTud, E: vm::Engine> fmt::Debug for ProcessesCollectionProc<'a, TPud, TTud, E>
 where
     TPud: fmt::Debug,
     TTud: fmt::Debug,
@@ -582,8 +820,8 @@ where
     }
 }
 
-impl<'a, TPud, TTud> ProcessesCollectionThread<'a, TPud, TTud> {
-    fn inner(&mut self) -> vm::Thread<Thread<TTud>> {
+impl<'a, TPud, TTud, E: vm::Engine> ProcessesCollectionThread<'a, TPud, TTud, E> {
+    fn inner(&mut self) -> vm::Thread<Thread<TTud>, E> {
         match self
             .process
             .get_mut()
@@ -612,7 +850,7 @@ impl<'a, TPud, TTud> ProcessesCollectionThread<'a, TPud, TTud> {
     /// Returns the following thread within the next process, or `None` if this is the last thread.
     ///
     /// Threads are ordered arbitrarily. In particular, they are **not** ordered by [`ThreadId`].
-    pub fn next_thread(mut self) -> Option<ProcessesCollectionThread<'a, TPud, TTud>> {
+    pub fn next_thread(mut self) -> Option<ProcessesCollectionThread<'a, TPud, TTud, E>> {
         self.thread_index += 1;
         if self.thread_index >= self.process.get_mut().state_machine.num_threads() {
             return None;
@@ -631,9 +869,19 @@ impl<'a, TPud, TTud> ProcessesCollectionThread<'a, TPud, TTud> {
         &mut self.inner().into_user_data().user_data
     }
 
+    /// Returns the fuel remaining for this thread, or `None` if its process isn't metered.
+    pub fn fuel(&mut self) -> Option<u64> {
+        self.inner().fuel()
+    }
+
+    /// Sets the fuel remaining for this thread. Has no effect if its process isn't metered.
+    pub fn set_fuel(&mut self, fuel: u64) {
+        self.inner().set_fuel(fuel)
+    }
+
     /// After [`RunOneOutcome::Interrupted`] is returned, use this function to feed back the value
     /// to use as the return type of the function that has been called.
-    pub fn resume(&mut self, value: Option<wasmi::RuntimeValue>) {
+    pub fn resume(&mut self, value: Option<WasmValue>) {
         let user_data = self.inner().into_user_data();
 
         // TODO: check type of the value?
@@ -641,7 +889,32 @@ impl<'a, TPud, TTud> ProcessesCollectionThread<'a, TPud, TTud> {
             panic!()
         }
 
-        user_data.value_back = Some(value);
+        user_data.value_back = Some(Ok(value));
+    }
+
+    /// After [`RunOneOutcome::Interrupted`] is returned, use this function to indicate that the
+    /// function that has been called has failed with the given trap.
+    ///
+    /// The entire process is killed the next time this thread is picked by
+    /// [`run`](ProcessesCollection::run), which then returns a
+    /// [`RunOneOutcome::ProcessFinished`] containing this trap.
+    pub fn resume_with_trap(&mut self, trap: vm::Trap) {
+        let user_data = self.inner().into_user_data();
+        if user_data.value_back.is_some() {
+            panic!()
+        }
+
+        user_data.value_back = Some(Err(trap));
+    }
+
+    /// Lowers the priority of this thread compared to the other threads of the same process,
+    /// until another thread of the process yields as well.
+    ///
+    /// The next time a thread of this process is run, the other threads that are ready to run
+    /// are picked before this one.
+    pub fn yield_to_other_threads(&mut self) {
+        let num_threads = self.process.get_mut().state_machine.num_threads();
+        self.process.get_mut().first_thread_to_run = (self.thread_index + 1) % num_threads;
     }
 
     pub fn read_memory(&mut self, offset: u32, size: u32) -> Result<Vec<u8>, ()> {
@@ -651,6 +924,50 @@ impl<'a, TPud, TTud> ProcessesCollectionThread<'a, TPud, TTud> {
             .read_memory(offset, size)
     }
 
+    /// Reads a little-endian `u32` at the given memory location.
+    ///
+    /// Returns an error if the range is invalid or out of range.
+    pub fn read_memory_u32(&mut self, offset: u32) -> Result<u32, ()> {
+        let buf = self.read_memory(offset, 4)?;
+        Ok(LittleEndian::read_u32(&buf))
+    }
+
+    /// Reads an array of `count` buffer descriptions at the given memory location. Each
+    /// description is made of a little-endian `u32` pointer followed with a little-endian `u32`
+    /// length.
+    ///
+    /// The whole array is read at once. Returns an error if the range is invalid or out of range.
+    pub fn read_memory_buffers_list(
+        &mut self,
+        offset: u32,
+        count: u32,
+    ) -> Result<Vec<(u32, u32)>, ()> {
+        let size = count.checked_mul(8).ok_or(())?;
+        let buf = self.read_memory(offset, size)?;
+        Ok(buf
+            .chunks_exact(8)
+            .map(|desc| {
+                (
+                    LittleEndian::read_u32(&desc[..4]),
+                    LittleEndian::read_u32(&desc[4..]),
+                )
+            })
+            .collect())
+    }
+
+    /// Checks that the given memory range is within the memory of the process, without copying
+    /// it.
+    ///
+    /// Returns an error if the range is invalid or out of range.
+    pub fn check_memory_range(&mut self, offset: u32, size: u32) -> Result<(), ()> {
+        let end = usize::try_from(offset.checked_add(size).ok_or(())?).map_err(|_| ())?;
+        if end <= self.process.get().state_machine.memory_size() {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+
     /// Write the data at the given memory location.
     ///
     /// Returns an error if the range is invalid or out of range.
@@ -660,9 +977,21 @@ impl<'a, TPud, TTud> ProcessesCollectionThread<'a, TPud, TTud> {
             .state_machine
             .write_memory(offset, value)
     }
+
+    /// Aborts the process this thread belongs to. Returns its [`Pid`] and the associated user
+    /// data.
+    pub fn abort_process(self) -> (Pid, TPud, Vec<(ThreadId, TTud)>) {
+        let (pid, proc) = remove_process(self.total_memory_size, self.process);
+        let dead_threads = proc
+            .state_machine
+            .into_user_datas()
+            .map(|t| (t.thread_id, t.user_data))
+            .collect::<Vec<_>>();
+        (pid, proc.user_data, dead_threads)
+    }
 }
 
-impl<'a, TPud, TTud> fmt::Debug for ProcessesCollectionThread<'a, TPud, TTud>
+impl<'a, TPud, TTud, E: vm::Engine> fmt::Debug for ProcessesCollectionThread<'a, TPud, TTud, E>
 where
     TPud: fmt::Debug,
     TTud: fmt::Debug,
@@ -684,8 +1013,11 @@ where
 
 #[cfg(test)]
 mod tests {
-    use super::ProcessesCollectionBuilder;
-    use crate::sig;
+    use super::{ProcessesCollectionBuilder, RunOneOutcome};
+    use crate::scheduler::vm::{self, EntryPoint};
+    use crate::{module::Module, sig, signature::WasmValue};
+    use alloc::{sync::Arc, vec, vec::Vec};
+    use spin::Mutex;
 
     #[test]
     #[should_panic]
@@ -694,4 +1026,148 @@ mod tests {
             .with_extrinsic("foo", "test", sig!(()), ())
             .with_extrinsic("foo", "test", sig!(()), ());
     }
+
+    #[test]
+    fn extrinsic_signature_mismatch_refused() {
+        let module = Module::from_wat(
+            r#"(module
+            (import "foo" "test" (func $test (param i64 i64)))
+            (func $_start
+                (call $test (i64.const 1) (i64.const 2)))
+            (export "_start" (func $_start)))
+        "#,
+        )
+        .unwrap();
+
+        let mut collection = ProcessesCollectionBuilder::<()>::default()
+            .with_extrinsic("foo", "test", sig!((I32)), ())
+            .build::<(), (), vm::WasmiEngine>();
+        assert!(collection
+            .execute(&module, EntryPoint::Default, (), ())
+            .is_err());
+    }
+
+    #[test]
+    fn float_extrinsic() {
+        let module = Module::from_wat(
+            r#"(module
+            (import "foo" "test" (func $test (param f32) (result f64)))
+            (func $_start (result f64)
+                (call $test (f32.const 1.5)))
+            (export "_start" (func $_start)))
+        "#,
+        )
+        .unwrap();
+
+        let mut collection = ProcessesCollectionBuilder::<()>::default()
+            .with_extrinsic("foo", "test", sig!((F32) -> F64), ())
+            .build::<(), (), vm::WasmiEngine>();
+        let pid = collection
+            .execute(&module, EntryPoint::Default, (), ())
+            .unwrap()
+            .pid();
+
+        match collection.run() {
+            RunOneOutcome::Interrupted {
+                mut thread, params, ..
+            } => {
+                assert_eq!(params, vec![WasmValue::F32(1.5)]);
+                thread.resume(Some(WasmValue::F64(3.0)));
+            }
+            _ => panic!(),
+        }
+
+        match collection.run() {
+            RunOneOutcome::ProcessFinished {
+                pid: finished,
+                outcome: Ok(ret_val),
+                ..
+            } => {
+                assert_eq!(finished, pid);
+                assert_eq!(ret_val, Some(WasmValue::F64(3.0)));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn memory_grow_hook_called() {
+        let module = Module::from_wat(
+            r#"(module
+            (import "foo" "test" (func $test))
+            (memory (export "memory") 1)
+            (func $_start
+                (drop (memory.grow (i32.const 2)))
+                (call $test)
+                (drop (memory.grow (i32.const 1)))
+                (call $test))
+            (export "_start" (func $_start)))
+        "#,
+        )
+        .unwrap();
+
+        let grown = Arc::new(Mutex::new(Vec::new()));
+        let mut collection = ProcessesCollectionBuilder::<()>::default()
+            .with_extrinsic("foo", "test", sig!(()), ())
+            .with_memory_grow_hook({
+                let grown = grown.clone();
+                move |pid, before, after| grown.lock().push((pid, before, after))
+            })
+            .build::<(), (), vm::WasmiEngine>();
+        let pid = collection
+            .execute(&module, EntryPoint::Default, (), ())
+            .unwrap()
+            .pid();
+
+        match collection.run() {
+            RunOneOutcome::Interrupted { mut thread, .. } => thread.resume(None),
+            _ => panic!(),
+        }
+        assert_eq!(*grown.lock(), vec![(pid, 1, 3)]);
+        assert_eq!(collection.process_memory_size(pid), Some(3 * 65536));
+
+        match collection.run() {
+            RunOneOutcome::Interrupted { mut thread, .. } => thread.resume(None),
+            _ => panic!(),
+        }
+        assert_eq!(*grown.lock(), vec![(pid, 1, 3), (pid, 3, 4)]);
+        assert_eq!(collection.process_memory_size(pid), Some(4 * 65536));
+    }
+
+    #[test]
+    fn read_memory_helpers() {
+        let module = Module::from_wat(
+            r#"(module
+            (import "foo" "test" (func $test))
+            (memory (export "memory") 1)
+            (data (i32.const 8) "\78\56\34\12\10\00\00\00\05\00\00\00\00\01\00\00\03\00\00\00")
+            (func $_start
+                (call $test))
+            (export "_start" (func $_start)))
+        "#,
+        )
+        .unwrap();
+
+        let mut collection = ProcessesCollectionBuilder::<()>::default()
+            .with_extrinsic("foo", "test", sig!(()), ())
+            .build::<(), (), vm::WasmiEngine>();
+        collection
+            .execute(&module, EntryPoint::Default, (), ())
+            .unwrap();
+
+        match collection.run() {
+            RunOneOutcome::Interrupted { mut thread, .. } => {
+                assert_eq!(thread.read_memory_u32(8), Ok(0x12345678));
+                assert_eq!(
+                    thread.read_memory_buffers_list(12, 2),
+                    Ok(vec![(16, 5), (256, 3)])
+                );
+                assert_eq!(thread.read_memory_buffers_list(12, 0), Ok(Vec::new()));
+                assert!(thread.read_memory_u32(65534).is_err());
+                assert!(thread.read_memory_buffers_list(12, 0x2000_0000).is_err());
+                assert!(thread.read_memory_buffers_list(65530, 2).is_err());
+            }
+            _ => panic!(),
+        }
+    }
 }
@@ -15,12 +15,20 @@
 
 #![cfg(test)]
 
-use super::{Core, CoreRunOutcome};
+use super::{
+    Core, CoreRunOutcome, CoreStepOutcome, Extrinsic, InboxOverflowPolicy, NewErr, ThreadState,
+    Trap,
+};
 use crate::{
     module::Module,
-    signature::{Signature, ValueType},
+    schema::{MessageSchema, SchemaField},
+    signature::{Signature, ValueType, WasmValue},
+    time::{Deadline, MockTimeSource},
 };
+use alloc::{format, string::String, sync::Arc, vec, vec::Vec};
 use core::iter;
+use redshirt_syscalls_interface::{ffi::Message, AnswerError, Decode as _};
+use spin::Mutex;
 
 #[test]
 fn basic_module() {
@@ -43,14 +51,51 @@ fn basic_module() {
             ..
         } => {
             assert_eq!(pid, expected_pid);
-            assert_eq!(ret_val, Some(wasmi::RuntimeValue::I32(5)));
+            assert_eq!(ret_val, Some(WasmValue::I32(5)));
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn execute_named_entry_point() {
+    let module = Module::from_wat(
+        r#"(module
+        (func $add (param $p0 i32) (param $p1 i32) (result i32)
+            (i32.add (local.get $p0) (local.get $p1)))
+        (export "add" (func $add)))
+    "#,
+    )
+    .unwrap();
+
+    let mut core = Core::new().build();
+
+    match core.execute_named(&module, "sub", &[]) {
+        Err(NewErr::StartNotFound) => {}
+        _ => panic!(),
+    }
+    match core.execute_named(&module, "add", &[WasmValue::I64(2)]) {
+        Err(NewErr::StartBadSignature) => {}
+        _ => panic!(),
+    }
+
+    let args = [WasmValue::I32(2), WasmValue::I32(3)];
+    let expected_pid = core.execute_named(&module, "add", &args).unwrap().pid();
+
+    match core.run() {
+        CoreRunOutcome::ProgramFinished {
+            pid,
+            outcome: Ok(ret_val),
+            ..
+        } => {
+            assert_eq!(pid, expected_pid);
+            assert_eq!(ret_val, Some(WasmValue::I32(5)));
         }
         _ => panic!(),
     }
 }
 
 #[test]
-#[ignore] // TODO: test fails
 fn trapping_module() {
     let module = Module::from_wat(
         r#"(module
@@ -61,17 +106,1599 @@ fn trapping_module() {
     )
     .unwrap();
 
+    let trapped = Arc::new(Mutex::new(Vec::new()));
+    let mut core = Core::new()
+        .with_trap_hook({
+            let trapped = trapped.clone();
+            move |pid, _| trapped.lock().push(pid)
+        })
+        .build();
+    let expected_pid = core.execute(&module).unwrap().pid();
+
+    match core.run() {
+        CoreRunOutcome::ProgramFinished {
+            pid,
+            outcome: Err(trap),
+            ..
+        } => {
+            assert_eq!(pid, expected_pid);
+            assert_eq!(trap, Trap::Unreachable);
+            assert_eq!(*trapped.lock(), vec![expected_pid]);
+        }
+        _ => panic!(),
+    }
+
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+    assert_eq!(*trapped.lock(), vec![expected_pid]);
+}
+
+/// Module that emits a message on interface `[5; 32]` without needing an answer and without
+/// allowing delays, then returns the value returned by `emit_message`.
+const EMIT_NO_DELAY_MODULE: &str = r#"(module
+    (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+    (memory (export "memory") 1)
+    (data (i32.const 0) "\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05")
+    (data (i32.const 32) "\40\00\00\00\03\00\00\00")
+    (data (i32.const 64) "abc")
+    (func $_start (result i32)
+        (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)))
+    (export "_start" (func $_start)))
+"#;
+
+#[test]
+fn emit_no_delay_delivered() {
+    let module = Module::from_wat(EMIT_NO_DELAY_MODULE).unwrap();
+
+    let mut builder = Core::new();
+    let handler_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    core.set_interface_handler(From::from([5; 32]), handler_pid)
+        .unwrap();
+    let expected_pid = core.execute(&module).unwrap().pid();
+
+    match core.run() {
+        CoreRunOutcome::ReservedPidInterfaceMessage {
+            pid,
+            message_id,
+            message,
+            ..
+        } => {
+            assert_eq!(pid, expected_pid);
+            assert!(message_id.is_none());
+            assert_eq!(message.0, b"abc");
+        }
+        _ => panic!(),
+    }
+
+    match core.run() {
+        CoreRunOutcome::ProgramFinished {
+            pid,
+            outcome: Ok(ret_val),
+            ..
+        } => {
+            assert_eq!(pid, expected_pid);
+            assert_eq!(ret_val, Some(WasmValue::I32(0)));
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn emit_no_delay_no_handler() {
+    let module = Module::from_wat(EMIT_NO_DELAY_MODULE).unwrap();
+
     let mut core = Core::new().build();
     let expected_pid = core.execute(&module).unwrap().pid();
 
+    match core.run() {
+        CoreRunOutcome::ProgramFinished {
+            pid,
+            outcome: Ok(ret_val),
+            ..
+        } => {
+            assert_eq!(pid, expected_pid);
+            assert_eq!(ret_val, Some(WasmValue::I32(1)));
+        }
+        _ => panic!(),
+    }
+}
+
+/// Same as [`EMIT_NO_DELAY_MODULE`], except that delays are allowed. The thread waits for a
+/// handler of the interface to be registered.
+const EMIT_DELAY_MODULE: &str = r#"(module
+    (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+    (memory (export "memory") 1)
+    (data (i32.const 0) "\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05")
+    (data (i32.const 32) "\40\00\00\00\03\00\00\00")
+    (data (i32.const 64) "abc")
+    (func $_start (result i32)
+        (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 0) (i32.const 0)))
+    (export "_start" (func $_start)))
+"#;
+
+#[test]
+fn emit_delay_delivered_once_handler_registered() {
+    let module = Module::from_wat(EMIT_DELAY_MODULE).unwrap();
+
+    let mut builder = Core::new();
+    let handler_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    let expected_pid = core.execute(&module).unwrap().pid();
+
+    match core.run() {
+        CoreRunOutcome::ThreadWaitUnavailableInterface { interface, .. } => {
+            assert_eq!(interface, crate::InterfaceHash::from([5; 32]));
+        }
+        _ => panic!(),
+    }
+
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+
+    core.set_interface_handler(From::from([5; 32]), handler_pid)
+        .unwrap();
+
+    match core.run() {
+        CoreRunOutcome::ReservedPidInterfaceMessage {
+            pid,
+            message_id,
+            message,
+            ..
+        } => {
+            assert_eq!(pid, expected_pid);
+            assert!(message_id.is_none());
+            assert_eq!(message.0, b"abc");
+        }
+        _ => panic!(),
+    }
+
+    match core.run() {
+        CoreRunOutcome::ProgramFinished {
+            pid,
+            outcome: Ok(ret_val),
+            ..
+        } => {
+            assert_eq!(pid, expected_pid);
+            assert_eq!(ret_val, Some(WasmValue::I32(0)));
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn emit_delay_emitter_killed_before_handler_registered() {
+    let module = Module::from_wat(EMIT_DELAY_MODULE).unwrap();
+
+    let mut builder = Core::new();
+    let handler_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    let emitter_pid = core.execute(&module).unwrap().pid();
+
+    match core.run() {
+        CoreRunOutcome::ThreadWaitUnavailableInterface { .. } => {}
+        _ => panic!(),
+    }
+
+    core.abort_process(emitter_pid).unwrap();
     match core.run() {
         CoreRunOutcome::ProgramFinished {
             pid,
             outcome: Err(_),
             ..
+        } => assert_eq!(pid, emitter_pid),
+        _ => panic!(),
+    }
+
+    core.set_interface_handler(From::from([5; 32]), handler_pid)
+        .unwrap();
+
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn emit_past_memory_limit_refused() {
+    // Grows its memory by one page, then emits a message on interface `[5; 32]`.
+    let module = Module::from_wat(
+        r#"(module
+        (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05")
+        (data (i32.const 32) "\40\00\00\00\03\00\00\00")
+        (data (i32.const 64) "abc")
+        (func $_start (result i32)
+            (drop (memory.grow (i32.const 1)))
+            (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 0)))
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    let mut builder = Core::new().with_memory_limit(100_000);
+    let handler_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    core.set_interface_handler(From::from([5; 32]), handler_pid)
+        .unwrap();
+    let expected_pid = core.execute(&module).unwrap().pid();
+    assert_eq!(core.memory_footprint().processes_memory, 65536);
+
+    match core.run() {
+        CoreRunOutcome::ProgramFinished {
+            pid,
+            outcome: Ok(ret_val),
+            ..
         } => {
             assert_eq!(pid, expected_pid);
+            assert_eq!(ret_val, Some(WasmValue::I32(5)));
+        }
+        _ => panic!(),
+    }
+    assert_eq!(core.memory_footprint().processes_memory, 0);
+}
+
+#[test]
+fn answer_from_event_handler_applied_next_run() {
+    let interface = crate::InterfaceHash::from([7; 32]);
+
+    let mut builder = Core::new();
+    let emitter_pid = builder.reserve_pid();
+    let handler_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    core.set_interface_handler(interface.clone(), handler_pid)
+        .unwrap();
+
+    let emitted_id = core.emit_interface_message_answer(emitter_pid, interface, ());
+
+    match core.run() {
+        CoreRunOutcome::ReservedPidInterfaceMessage {
+            pid, message_id, ..
+        } => {
+            assert_eq!(pid, emitter_pid);
+            assert_eq!(message_id, Some(emitted_id));
+            // Answering while handling the outcome of `run`.
+            core.answer_message(emitted_id, Ok(crate::EncodedMessage(vec![1, 2, 3])));
+        }
+        _ => panic!(),
+    }
+
+    match core.run() {
+        CoreRunOutcome::MessageResponse {
+            message_id,
+            response,
+        } => {
+            assert_eq!(message_id, emitted_id);
+            assert_eq!(response.unwrap().0, vec![1, 2, 3]);
+        }
+        _ => panic!(),
+    }
+
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn reserved_pid_cancels_message() {
+    let interface = crate::InterfaceHash::from([7; 32]);
+
+    let mut builder = Core::new();
+    let emitter_pid = builder.reserve_pid();
+    let handler_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    core.set_interface_handler(interface.clone(), handler_pid)
+        .unwrap();
+
+    let emitted_id = core.emit_interface_message_answer(emitter_pid, interface, ());
+    match core.run() {
+        CoreRunOutcome::ReservedPidInterfaceMessage { message_id, .. } => {
+            assert_eq!(message_id, Some(emitted_id))
+        }
+        _ => panic!(),
+    }
+
+    core.cancel_message(emitter_pid, emitted_id);
+
+    // The answer of the handler, arriving after the cancellation, is discarded.
+    core.answer_message(emitted_id, Ok(crate::EncodedMessage(vec![1, 2, 3])));
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn answer_emitted_in_chunks_reassembled() {
+    // Waits for an interface message, then answers it in three pieces. The ID of the message
+    // is at offset 290, after the enum tag, the interface hash, and the `Option` tag.
+    let module = Module::from_wat(
+        r#"(module
+        (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+        (import "redshirt" "emit_answer_chunk" (func $emit_answer_chunk (param i32 i32 i32 i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\01\00\00\00\00\00\00\00")
+        (data (i32.const 16) "\01\02\03\04\05")
+        (func $_start (result i32)
+            (drop (call $next_message (i32.const 0) (i32.const 1) (i32.const 256) (i32.const 256) (i32.const 1) (i64.const 0)))
+            (call $emit_answer_chunk (i32.const 290) (i32.const 16) (i32.const 2) (i32.const 0))
+            (call $emit_answer_chunk (i32.const 290) (i32.const 18) (i32.const 2) (i32.const 0))
+            (call $emit_answer_chunk (i32.const 290) (i32.const 20) (i32.const 1) (i32.const 1))
+            i32.const 0)
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    let interface = crate::InterfaceHash::from([7; 32]);
+
+    let mut builder = Core::new();
+    let emitter_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    let handler_pid = core.execute(&module).unwrap().pid();
+    core.set_interface_handler(interface.clone(), handler_pid)
+        .unwrap();
+
+    let emitted_id = core.emit_interface_message_answer(emitter_pid, interface, ());
+
+    match core.run() {
+        CoreRunOutcome::MessageResponse {
+            message_id,
+            response,
+        } => {
+            assert_eq!(message_id, emitted_id);
+            assert_eq!(response.unwrap().0, vec![1, 2, 3, 4, 5]);
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn answer_chunk_from_other_process_dropped() {
+    // Waits for an interface message, answers it with a first piece, then waits forever. The ID
+    // of the message is at offset 290, after the enum tag, the interface hash, and the `Option`
+    // tag.
+    let handler = Module::from_wat(
+        r#"(module
+        (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+        (import "redshirt" "emit_answer_chunk" (func $emit_answer_chunk (param i32 i32 i32 i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\01\00\00\00\00\00\00\00")
+        (data (i32.const 16) "\01\02")
+        (func $_start (result i32)
+            (drop (call $next_message (i32.const 0) (i32.const 1) (i32.const 256) (i32.const 256) (i32.const 1) (i64.const 0)))
+            (call $emit_answer_chunk (i32.const 290) (i32.const 16) (i32.const 2) (i32.const 0))
+            (drop (call $next_message (i32.const 0) (i32.const 1) (i32.const 256) (i32.const 256) (i32.const 1) (i64.const 0)))
+            i32.const 0)
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    // Waits for an interface message containing the ID of a message that it hasn't received,
+    // and tries to finish the answer to it. The body of the message is at offset 304, after the
+    // enum tag, the interface hash, the `Option` tag, the emitter, the priority, the index in
+    // the list, and the length of the body.
+    let injector = Module::from_wat(
+        r#"(module
+        (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+        (import "redshirt" "emit_answer_chunk" (func $emit_answer_chunk (param i32 i32 i32 i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\01\00\00\00\00\00\00\00")
+        (data (i32.const 16) "\09")
+        (func $_start (result i32)
+            (drop (call $next_message (i32.const 0) (i32.const 1) (i32.const 256) (i32.const 256) (i32.const 1) (i64.const 0)))
+            (call $emit_answer_chunk (i32.const 304) (i32.const 16) (i32.const 1) (i32.const 1))
+            i32.const 0)
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    let interface = crate::InterfaceHash::from([7; 32]);
+    let injector_interface = crate::InterfaceHash::from([8; 32]);
+
+    let mut builder = Core::new();
+    let emitter_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    let handler_pid = core.execute(&handler).unwrap().pid();
+    core.set_interface_handler(interface.clone(), handler_pid)
+        .unwrap();
+    let injector_pid = core.execute(&injector).unwrap().pid();
+    core.set_interface_handler(injector_interface.clone(), injector_pid)
+        .unwrap();
+
+    let emitted_id = core.emit_interface_message_answer(emitter_pid, interface, ());
+    core.emit_interface_message_no_answer(
+        emitter_pid,
+        injector_interface,
+        crate::EncodedMessage(u64::from(emitted_id).to_le_bytes().to_vec()),
+    );
+
+    // The piece emitted by the injector doesn't finish the answer.
+    match core.run() {
+        CoreRunOutcome::ProgramFinished {
+            pid,
+            outcome: Ok(_),
+            ..
+        } => assert_eq!(pid, injector_pid),
+        _ => panic!(),
+    }
+
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn answer_error_reported_to_emitter() {
+    // Waits for an interface message, then answers it with an error. The ID of the message is
+    // at offset 290, after the enum tag, the interface hash, and the `Option` tag.
+    let module = Module::from_wat(
+        r#"(module
+        (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+        (import "redshirt" "emit_answer_error" (func $emit_answer_error (param i32 i32 i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\01\00\00\00\00\00\00\00")
+        (data (i32.const 16) "\2a\2b")
+        (func $_start (result i32)
+            (drop (call $next_message (i32.const 0) (i32.const 1) (i32.const 256) (i32.const 256) (i32.const 1) (i64.const 0)))
+            (call $emit_answer_error (i32.const 290) (i32.const 16) (i32.const 2))
+            i32.const 0)
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    let interface = crate::InterfaceHash::from([7; 32]);
+
+    let mut builder = Core::new();
+    let emitter_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    let handler_pid = core.execute(&module).unwrap().pid();
+    core.set_interface_handler(interface.clone(), handler_pid)
+        .unwrap();
+
+    let emitted_id = core.emit_interface_message_answer(emitter_pid, interface, ());
+
+    match core.run() {
+        CoreRunOutcome::MessageResponse {
+            message_id,
+            response,
+        } => {
+            assert_eq!(message_id, emitted_id);
+            assert_eq!(
+                response.err(),
+                Some(AnswerError::HandlerError(vec![0x2a, 0x2b]))
+            );
         }
         _ => panic!(),
     }
 }
+
+#[test]
+fn answer_to_cancelled_message_discarded() {
+    // Emits a message that needs an answer, cancels it, then waits for its answer. The ID of
+    // the message is written at offset 96.
+    let module = Module::from_wat(
+        r#"(module
+        (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+        (import "redshirt" "cancel_message" (func $cancel_message (param i32)))
+        (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05")
+        (data (i32.const 32) "\40\00\00\00\03\00\00\00")
+        (data (i32.const 64) "abc")
+        (func $_start (result i32)
+            (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 96)))
+            (call $cancel_message (i32.const 96))
+            (drop (call $next_message (i32.const 96) (i32.const 1) (i32.const 256) (i32.const 256) (i32.const 1) (i64.const 0)))
+            i32.const 0)
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    let interface = crate::InterfaceHash::from([5; 32]);
+
+    let mut builder = Core::new();
+    let handler_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    core.set_interface_handler(interface.clone(), handler_pid)
+        .unwrap();
+    let emitter_pid = core.execute(&module).unwrap().pid();
+
+    let emitted_id = match core.run() {
+        CoreRunOutcome::ReservedPidInterfaceMessage {
+            pid, message_id, ..
+        } => {
+            assert_eq!(pid, emitter_pid);
+            message_id.unwrap()
+        }
+        _ => panic!(),
+    };
+
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+
+    // The emitter has cancelled the message before the answer arrives. It must never receive it.
+    core.answer_message(emitted_id, Ok(crate::EncodedMessage(vec![1, 2, 3])));
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+    assert!(core.process_by_id(emitter_pid).is_some());
+}
+
+#[test]
+fn answer_timeout_reported_to_emitter() {
+    // Emits a message that needs an answer with a timeout of 1000 nanoseconds, then waits for
+    // its answer and stores the length of the answer at offset 200. The ID of the message is
+    // written at offset 96.
+    let module = Module::from_wat(
+        r#"(module
+        (import "redshirt" "emit_message_with_timeout" (func $emit_message_with_timeout (param i32 i32 i32 i32 i32 i32 i32 i64) (result i32)))
+        (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05")
+        (data (i32.const 32) "\40\00\00\00\03\00\00\00")
+        (data (i32.const 64) "abc")
+        (data (i32.const 128) "\01\00\00\00\00\00\00\00")
+        (func $_start (result i32)
+            (drop (call $emit_message_with_timeout (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 96) (i64.const 1000)))
+            (i32.store (i32.const 200)
+                (call $next_message (i32.const 96) (i32.const 1) (i32.const 256) (i32.const 256) (i32.const 1) (i64.const 0)))
+            (drop (call $next_message (i32.const 128) (i32.const 1) (i32.const 512) (i32.const 256) (i32.const 1) (i64.const 0)))
+            i32.const 0)
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    let interface = crate::InterfaceHash::from([5; 32]);
+    let clock = MockTimeSource::default();
+
+    let mut builder = Core::new().with_time_source(clock.clone());
+    let handler_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    core.set_interface_handler(interface.clone(), handler_pid)
+        .unwrap();
+    let emitter_pid = core.execute(&module).unwrap().pid();
+
+    let emitted_id = match core.run() {
+        CoreRunOutcome::ReservedPidInterfaceMessage { message_id, .. } => message_id.unwrap(),
+        _ => panic!(),
+    };
+
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+    assert_eq!(
+        core.next_deadline(),
+        Some(Deadline::from_monotonic_nanos(1000))
+    );
+
+    clock.set(999);
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+
+    clock.set(1000);
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+    assert_eq!(core.next_deadline(), None);
+
+    let response_len = core
+        .with_process_memory(emitter_pid, 200, 4, |mem| {
+            let mut len = [0; 4];
+            len.copy_from_slice(mem);
+            u32::from_le_bytes(len)
+        })
+        .unwrap();
+    let response = core
+        .with_process_memory(emitter_pid, 256, response_len, |mem| mem.to_vec())
+        .unwrap();
+    match Message::decode(crate::EncodedMessage(response)).unwrap() {
+        Message::Response(response) => {
+            assert_eq!(response.message_id, emitted_id);
+            assert_eq!(response.actual_data, Err(AnswerError::TimedOut));
+        }
+        _ => panic!(),
+    }
+
+    // The answer of the handler, arriving too late, is discarded.
+    core.answer_message(emitted_id, Ok(crate::EncodedMessage(vec![1, 2, 3])));
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn negative_answer_timeout_kills_process() {
+    assert_invalid_extrinsic_call(
+        r#"(module
+        (import "redshirt" "emit_message_with_timeout" (func $emit_message_with_timeout (param i32 i32 i32 i32 i32 i32 i32 i64) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05")
+        (func $_start (result i32)
+            (call $emit_message_with_timeout (i32.const 0) (i32.const 32) (i32.const 0) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 96) (i64.const -1)))
+        (export "_start" (func $_start)))
+    "#,
+    );
+}
+
+#[test]
+fn emit_violating_schema_refused() {
+    let module = Module::from_wat(EMIT_NO_DELAY_MODULE).unwrap();
+
+    let mut builder = Core::new();
+    let handler_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    core.set_interface_handler(From::from([5; 32]), handler_pid)
+        .unwrap();
+    // The module emits 3 bytes, while the schema expects 4.
+    core.set_interface_schema(
+        From::from([5; 32]),
+        Some(MessageSchema::new(iter::once(SchemaField::U32))),
+    );
+    let expected_pid = core.execute(&module).unwrap().pid();
+
+    match core.run() {
+        CoreRunOutcome::ProgramFinished {
+            pid,
+            outcome: Ok(ret_val),
+            ..
+        } => {
+            assert_eq!(pid, expected_pid);
+            assert_eq!(ret_val, Some(WasmValue::I32(2)));
+        }
+        _ => panic!(),
+    }
+}
+
+/// Module that waits for an interface message, then forwards it as it is on interface `[2; 32]`
+/// and returns the value returned by `emit_message`.
+const FORWARD_MODULE: &str = r#"(module
+    (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+    (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+    (memory (export "memory") 1)
+    (data (i32.const 0) "\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02")
+    (data (i32.const 32) "\01\00\00\00\00\00\00\00")
+    (data (i32.const 64) "\00\01\00\00")
+    (func $_start (result i32)
+        (i32.store (i32.const 68)
+            (call $next_message (i32.const 32) (i32.const 1) (i32.const 256) (i32.const 512) (i32.const 1) (i64.const 0)))
+        (call $emit_message (i32.const 0) (i32.const 64) (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 0) (i32.const 0)))
+    (export "_start" (func $_start)))
+"#;
+
+#[test]
+fn priority_inbox_orders_by_emitter_priority() {
+    let module = Module::from_wat(FORWARD_MODULE).unwrap();
+
+    let interface = crate::InterfaceHash::from([7; 32]);
+
+    let mut builder = Core::new().with_priority_inbox();
+    let low_pid = builder.reserve_pid();
+    let high_pid = builder.reserve_pid();
+    let observer_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    core.set_interface_handler(From::from([2; 32]), observer_pid)
+        .unwrap();
+    core.set_process_priority(high_pid, 5);
+
+    let handler_pid = core.execute(&module).unwrap().pid();
+    core.set_interface_handler(interface.clone(), handler_pid)
+        .unwrap();
+
+    // The low priority message is emitted first.
+    core.emit_interface_message_no_answer(low_pid, interface.clone(), 1u8);
+    core.emit_interface_message_no_answer(high_pid, interface, 2u8);
+
+    match core.run() {
+        CoreRunOutcome::ReservedPidInterfaceMessage { pid, message, .. } => {
+            assert_eq!(pid, handler_pid);
+            match Message::decode(message).unwrap() {
+                Message::Interface(msg) => {
+                    assert_eq!(msg.emitter_pid, high_pid);
+                    assert_eq!(msg.actual_data, vec![2]);
+                }
+                _ => panic!(),
+            }
+        }
+        _ => panic!(),
+    }
+}
+
+/// Module that waits for the response to a message that is never emitted, and thus never
+/// retrieves any interface message.
+const STUCK_MODULE: &str = r#"(module
+    (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+    (memory (export "memory") 1)
+    (data (i32.const 0) "\39\30\00\00\00\00\00\00")
+    (func $_start (result i32)
+        (drop (call $next_message (i32.const 0) (i32.const 1) (i32.const 256) (i32.const 512) (i32.const 1) (i64.const 0)))
+        i32.const 0)
+    (export "_start" (func $_start)))
+"#;
+
+#[test]
+fn high_priority_message_delivered_first() {
+    // Emits `1` with priority 0, then `2` with priority 5, on interface `[7; 32]`.
+    let emitter_module = Module::from_wat(
+        r#"(module
+        (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07")
+        (data (i32.const 32) "\40\00\00\00\01\00\00\00\41\00\00\00\01\00\00\00")
+        (data (i32.const 64) "\01\02")
+        (func $_start (result i32)
+            (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)))
+            (call $emit_message (i32.const 0) (i32.const 40) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 5) (i32.const 0)))
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    let interface = crate::InterfaceHash::from([7; 32]);
+
+    let mut builder = Core::new();
+    let observer_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    core.set_interface_handler(From::from([2; 32]), observer_pid)
+        .unwrap();
+
+    // Both messages are queued by a handler that never retrieves them, then moved to a handler
+    // that forwards the first message it receives.
+    let stuck_pid = core
+        .execute(&Module::from_wat(STUCK_MODULE).unwrap())
+        .unwrap()
+        .pid();
+    let forward_pid = core
+        .execute(&Module::from_wat(FORWARD_MODULE).unwrap())
+        .unwrap()
+        .pid();
+    core.set_interface_handler(interface.clone(), stuck_pid)
+        .unwrap();
+
+    let emitter_pid = core.execute(&emitter_module).unwrap().pid();
+    match core.run() {
+        CoreRunOutcome::ProgramFinished {
+            pid,
+            outcome: Ok(ret_val),
+            ..
+        } => {
+            assert_eq!(pid, emitter_pid);
+            assert_eq!(ret_val, Some(WasmValue::I32(0)));
+        }
+        _ => panic!(),
+    }
+
+    core.reassign_interface(interface, forward_pid).unwrap();
+
+    match core.run() {
+        CoreRunOutcome::ReservedPidInterfaceMessage { pid, message, .. } => {
+            assert_eq!(pid, forward_pid);
+            match Message::decode(message).unwrap() {
+                Message::Interface(msg) => {
+                    assert_eq!(msg.emitter_pid, emitter_pid);
+                    assert_eq!(msg.priority, 5);
+                    assert_eq!(msg.actual_data, vec![2]);
+                }
+                _ => panic!(),
+            }
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn reassign_interface_moves_queued_messages() {
+    let stuck_module = Module::from_wat(STUCK_MODULE).unwrap();
+    let forward_module = Module::from_wat(FORWARD_MODULE).unwrap();
+
+    let interface = crate::InterfaceHash::from([7; 32]);
+
+    let mut builder = Core::new();
+    let emitter_pid = builder.reserve_pid();
+    let observer_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    core.set_interface_handler(From::from([2; 32]), observer_pid)
+        .unwrap();
+
+    let old_handler_pid = core.execute(&stuck_module).unwrap().pid();
+    let new_handler_pid = core.execute(&forward_module).unwrap().pid();
+    core.set_interface_handler(interface.clone(), old_handler_pid)
+        .unwrap();
+
+    core.emit_interface_message_no_answer(emitter_pid, interface.clone(), 1u8);
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+
+    core.reassign_interface(interface.clone(), new_handler_pid)
+        .unwrap();
+    assert_eq!(core.interface_handler(&interface), Some(new_handler_pid));
+
+    match core.run() {
+        CoreRunOutcome::ReservedPidInterfaceMessage { pid, message, .. } => {
+            assert_eq!(pid, new_handler_pid);
+            match Message::decode(message).unwrap() {
+                Message::Interface(msg) => {
+                    assert_eq!(msg.emitter_pid, emitter_pid);
+                    assert_eq!(msg.actual_data, vec![1]);
+                }
+                _ => panic!(),
+            }
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn process_teardown_is_progressive() {
+    let module = Module::from_wat(
+        r#"(module
+        (func $_start (result i32)
+            i32.const 0)
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    let interface = crate::InterfaceHash::from([7; 32]);
+
+    let mut builder = Core::new().with_teardown_batch_size(16);
+    let emitter_pid = builder.reserve_pid();
+    let mut core = builder.build();
+
+    let handler_pid = core.execute(&module).unwrap().pid();
+    core.set_interface_handler(interface.clone(), handler_pid)
+        .unwrap();
+
+    // The handler terminates without ever retrieving these messages.
+    let emitted = (0..1000u32)
+        .map(|n| core.emit_interface_message_answer(emitter_pid, interface.clone(), n))
+        .collect::<Vec<_>>();
+
+    match core.run() {
+        CoreRunOutcome::ProgramFinished { pid, .. } => assert_eq!(pid, handler_pid),
+        _ => panic!(),
+    }
+
+    let mut answered = Vec::new();
+    loop {
+        let steps_before = core.pending_teardown_steps();
+        match core.run() {
+            CoreRunOutcome::MessageResponse {
+                message_id,
+                response,
+            } => {
+                assert_eq!(response.err(), Some(AnswerError::HandlerCrashed));
+                answered.push(message_id);
+            }
+            CoreRunOutcome::Idle => break,
+            _ => panic!(),
+        }
+        assert!(steps_before - core.pending_teardown_steps() <= 16);
+    }
+
+    assert_eq!(core.pending_teardown_steps(), 0);
+    assert_eq!(answered, emitted);
+}
+
+#[test]
+fn coalescing_interface_keeps_latest_update() {
+    let module = Module::from_wat(FORWARD_MODULE).unwrap();
+
+    let interface = crate::InterfaceHash::from([7; 32]);
+
+    let mut builder = Core::new();
+    let emitter_pid = builder.reserve_pid();
+    let observer_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    core.set_interface_handler(From::from([2; 32]), observer_pid)
+        .unwrap();
+
+    let handler_pid = core.execute(&module).unwrap().pid();
+    core.set_interface_handler(interface.clone(), handler_pid)
+        .unwrap();
+    core.set_interface_coalescing(interface.clone(), true);
+
+    // Three updates are emitted before the handler gets a chance to run.
+    core.emit_interface_message_no_answer(emitter_pid, interface.clone(), 1u8);
+    core.emit_interface_message_no_answer(emitter_pid, interface.clone(), 2u8);
+    core.emit_interface_message_no_answer(emitter_pid, interface, 3u8);
+
+    match core.run() {
+        CoreRunOutcome::ReservedPidInterfaceMessage { pid, message, .. } => {
+            assert_eq!(pid, handler_pid);
+            match Message::decode(message).unwrap() {
+                Message::Interface(msg) => {
+                    assert_eq!(msg.emitter_pid, emitter_pid);
+                    assert_eq!(msg.actual_data, vec![3]);
+                }
+                _ => panic!(),
+            }
+        }
+        _ => panic!(),
+    }
+
+    match core.run() {
+        CoreRunOutcome::ProgramFinished { pid, .. } => assert_eq!(pid, handler_pid),
+        _ => panic!(),
+    }
+
+    // No stale update remains to be cleaned up.
+    assert_eq!(core.pending_teardown_steps(), 0);
+}
+
+/// Returns a module that waits for an interface message, then forwards it as it is on interface
+/// `[target; 32]` as a message that needs an answer, and returns the value returned by
+/// `emit_message`.
+fn relay_module(target: u8) -> Module {
+    let hash = iter::repeat(format!("\\{:02x}", target))
+        .take(32)
+        .collect::<String>();
+    Module::from_wat(format!(
+        r#"(module
+        (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+        (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "{hash}")
+        (data (i32.const 32) "\01\00\00\00\00\00\00\00")
+        (data (i32.const 64) "\00\01\00\00")
+        (func $_start (result i32)
+            (i32.store (i32.const 68)
+                (call $next_message (i32.const 32) (i32.const 1) (i32.const 256) (i32.const 512) (i32.const 1) (i64.const 0)))
+            (call $emit_message (i32.const 0) (i32.const 64) (i32.const 1) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 128)))
+        (export "_start" (func $_start)))
+    "#,
+        hash = hash
+    ))
+    .unwrap()
+}
+
+#[test]
+fn wait_chain_deeper_than_limit_refused() {
+    let mut builder = Core::new().with_max_wait_depth(2);
+    let emitter_pid = builder.reserve_pid();
+    let end_pid = builder.reserve_pid();
+    let mut core = builder.build();
+
+    // The chain is: emitter -> first relay -> second relay -> end.
+    let first_relay_pid = core.execute(&relay_module(4)).unwrap().pid();
+    let second_relay_pid = core.execute(&relay_module(5)).unwrap().pid();
+    core.set_interface_handler(From::from([3; 32]), first_relay_pid)
+        .unwrap();
+    core.set_interface_handler(From::from([4; 32]), second_relay_pid)
+        .unwrap();
+    core.set_interface_handler(From::from([5; 32]), end_pid)
+        .unwrap();
+
+    let emitted_id = core.emit_interface_message_answer(emitter_pid, From::from([3; 32]), ());
+    assert_eq!(core.message_wait_depth(emitted_id), Some(1));
+
+    let mut outcomes = Vec::new();
+    loop {
+        match core.run() {
+            CoreRunOutcome::ProgramFinished {
+                pid,
+                outcome: Ok(ret_val),
+                ..
+            } => outcomes.push((pid, ret_val)),
+            CoreRunOutcome::Idle => break,
+            _ => panic!(),
+        }
+    }
+
+    // The first relay succeeds, while the second relay would create a chain of depth 3.
+    assert_eq!(
+        outcomes,
+        vec![
+            (first_relay_pid, Some(WasmValue::I32(0))),
+            (second_relay_pid, Some(WasmValue::I32(3))),
+        ]
+    );
+    assert_eq!(core.max_message_wait_depth(), 2);
+}
+
+#[test]
+fn process_destroyed_delivered_to_full_inbox() {
+    let stuck_module = Module::from_wat(STUCK_MODULE).unwrap();
+    let emit_module = Module::from_wat(EMIT_NO_DELAY_MODULE).unwrap();
+
+    let mut core = Core::new()
+        .with_inbox_limit(1, InboxOverflowPolicy::ForceGrow)
+        .build();
+
+    let handler_pid = core.execute(&stuck_module).unwrap().pid();
+    core.set_interface_handler(From::from([5; 32]), handler_pid)
+        .unwrap();
+    let emitter_pid = core.execute(&emit_module).unwrap().pid();
+
+    // The message of the emitter fills the inbox of the handler.
+    match core.run() {
+        CoreRunOutcome::ProgramFinished {
+            pid,
+            outcome: Ok(ret_val),
+            ..
+        } => {
+            assert_eq!(pid, emitter_pid);
+            assert_eq!(ret_val, Some(WasmValue::I32(0)));
+        }
+        _ => panic!(),
+    }
+    assert_eq!(core.inbox_len(handler_pid), Some(1));
+
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+
+    // The `ProcessDestroyed` notification has been queued despite the limit.
+    assert_eq!(core.inbox_len(handler_pid), Some(2));
+}
+
+#[test]
+fn process_destroyed_replaces_oldest_message_in_full_inbox() {
+    let stuck_module = Module::from_wat(STUCK_MODULE).unwrap();
+    let emit_module = Module::from_wat(EMIT_NO_DELAY_MODULE).unwrap();
+
+    let mut core = Core::new()
+        .with_inbox_limit(1, InboxOverflowPolicy::DropOldest)
+        .build();
+
+    let handler_pid = core.execute(&stuck_module).unwrap().pid();
+    core.set_interface_handler(From::from([5; 32]), handler_pid)
+        .unwrap();
+    let emitter_pid = core.execute(&emit_module).unwrap().pid();
+
+    match core.run() {
+        CoreRunOutcome::ProgramFinished { pid, .. } => assert_eq!(pid, emitter_pid),
+        _ => panic!(),
+    }
+    assert_eq!(core.inbox_len(handler_pid), Some(1));
+
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+
+    // The message of the emitter, which doesn't need an answer, has been discarded in favour
+    // of the `ProcessDestroyed` notification.
+    assert_eq!(core.inbox_len(handler_pid), Some(1));
+}
+
+#[test]
+fn emit_delay_blocks_on_full_inbox() {
+    // Emits a message on interface `[9; 32]` and waits for the answer, then retrieves one
+    // interface message, then waits forever.
+    let handler_module = Module::from_wat(
+        r#"(module
+        (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32) (result i32)))
+        (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09")
+        (data (i32.const 32) "\40\00\00\00\01\00\00\00")
+        (data (i32.const 128) "\01\00\00\00\00\00\00\00")
+        (data (i32.const 160) "\39\30\00\00\00\00\00\00")
+        (func $_start (result i32)
+            (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 1) (i32.const 0) (i32.const 96)))
+            (drop (call $next_message (i32.const 96) (i32.const 1) (i32.const 256) (i32.const 512) (i32.const 1)))
+            (drop (call $next_message (i32.const 128) (i32.const 1) (i32.const 256) (i32.const 512) (i32.const 1)))
+            (drop (call $next_message (i32.const 160) (i32.const 1) (i32.const 256) (i32.const 512) (i32.const 1)))
+            i32.const 0)
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    // Emits two messages on interface `[5; 32]` with `allow_delay`, and returns the result of
+    // the second emission.
+    let emitter_module = Module::from_wat(
+        r#"(module
+        (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05")
+        (data (i32.const 32) "\40\00\00\00\03\00\00\00")
+        (data (i32.const 64) "abc")
+        (func $_start (result i32)
+            (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 0)))
+            (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 0)))
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    let mut builder = Core::new().with_inbox_limit(1, InboxOverflowPolicy::ForceGrow);
+    let reserved_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    core.set_interface_handler(From::from([9; 32]), reserved_pid)
+        .unwrap();
+
+    let handler_pid = core.execute(&handler_module).unwrap().pid();
+    core.set_interface_handler(From::from([5; 32]), handler_pid)
+        .unwrap();
+    let emitter_pid = core.execute(&emitter_module).unwrap().pid();
+
+    // The first message fills the inbox of the handler, and the second one blocks.
+    let mut handler_message = None;
+    loop {
+        match core.run() {
+            CoreRunOutcome::ReservedPidInterfaceMessage { message_id, .. } => {
+                handler_message = message_id;
+            }
+            CoreRunOutcome::Idle => break,
+            _ => panic!(),
+        }
+    }
+    assert_eq!(core.inbox_len(handler_pid), Some(1));
+
+    // Once the handler retrieves the first message, the second one is delivered.
+    core.answer_message(
+        handler_message.unwrap(),
+        Ok(crate::EncodedMessage(Vec::new())),
+    );
+    match core.run() {
+        CoreRunOutcome::ProgramFinished {
+            pid,
+            outcome: Ok(ret_val),
+            ..
+        } => {
+            assert_eq!(pid, emitter_pid);
+            assert_eq!(ret_val, Some(WasmValue::I32(0)));
+        }
+        _ => panic!(),
+    }
+    assert_eq!(core.inbox_len(handler_pid), Some(1));
+}
+
+#[test]
+fn threads_blocked_on_full_inbox() {
+    // Waits forever for the answer to a message that was never emitted.
+    let handler_module = Module::from_wat(
+        r#"(module
+        (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 160) "\39\30\00\00\00\00\00\00")
+        (func $_start (result i32)
+            (drop (call $next_message (i32.const 160) (i32.const 1) (i32.const 256) (i32.const 512) (i32.const 1)))
+            i32.const 0)
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    // Emits two messages on interface `[5; 32]` with `allow_delay`.
+    let emitter_module = Module::from_wat(
+        r#"(module
+        (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05")
+        (data (i32.const 32) "\40\00\00\00\03\00\00\00")
+        (data (i32.const 64) "abc")
+        (func $_start (result i32)
+            (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 0)))
+            (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 0)))
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    let mut core = Core::new()
+        .with_inbox_limit(1, InboxOverflowPolicy::ForceGrow)
+        .build();
+    let handler_pid = core.execute(&handler_module).unwrap().pid();
+    core.set_interface_handler(From::from([5; 32]), handler_pid)
+        .unwrap();
+    let emitter_pid = core.execute(&emitter_module).unwrap().pid();
+
+    // The first message fills the inbox of the handler, and the second one blocks.
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+
+    let emitter_threads = core.threads(emitter_pid);
+    assert_eq!(emitter_threads.len(), 1);
+    assert_eq!(
+        emitter_threads[0].1,
+        ThreadState::BlockedOnExtrinsic(Extrinsic::EmitMessage)
+    );
+    let handler_threads = core.threads(handler_pid);
+    assert_eq!(handler_threads.len(), 1);
+    assert_eq!(handler_threads[0].1, ThreadState::WaitingForMessage);
+}
+
+#[test]
+fn deterministic_scheduling_round_robin() {
+    // Each process emits three messages on interface `[5; 32]`, then finishes.
+    let module = Module::from_wat(
+        r#"(module
+        (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05")
+        (data (i32.const 32) "\40\00\00\00\03\00\00\00")
+        (data (i32.const 64) "abc")
+        (func $_start
+            (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 0)))
+            (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 0)))
+            (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 0))))
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    let mut builder = Core::new()
+        .with_deterministic_scheduling()
+        .with_time_source(MockTimeSource::default());
+    let handler_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    core.set_interface_handler(From::from([5; 32]), handler_pid)
+        .unwrap();
+
+    let mut pids = Vec::new();
+    for _ in 0..3 {
+        pids.push(core.execute(&module).unwrap().pid());
+    }
+    pids.sort_by_key(|pid| u64::from(*pid));
+
+    // The processes take turns in the order of their `Pid`, rather than the first one running
+    // until it finishes.
+    let mut expected = Vec::new();
+    for _ in 0..3 {
+        expected.extend(pids.iter().map(|pid| (*pid, false)));
+    }
+    expected.extend(pids.iter().map(|pid| (*pid, true)));
+
+    let mut events = Vec::new();
+    loop {
+        match core.run() {
+            CoreRunOutcome::ReservedPidInterfaceMessage { pid, .. } => events.push((pid, false)),
+            CoreRunOutcome::ProgramFinished { pid, .. } => events.push((pid, true)),
+            CoreRunOutcome::Idle => break,
+            _ => panic!(),
+        }
+    }
+
+    assert_eq!(events, expected);
+}
+
+#[test]
+fn emit_multiple_buffers_delivered_intact() {
+    let module = Module::from_wat(
+        r#"(module
+        (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05")
+        (data (i32.const 32) "\40\00\00\00\02\00\00\00\50\00\00\00\01\00\00\00\60\00\00\00\03\00\00\00")
+        (data (i32.const 64) "ab")
+        (data (i32.const 80) "c")
+        (data (i32.const 96) "def")
+        (func $_start (result i32)
+            (call $emit_message (i32.const 0) (i32.const 32) (i32.const 3) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)))
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    let mut builder = Core::new();
+    let handler_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    core.set_interface_handler(From::from([5; 32]), handler_pid)
+        .unwrap();
+    let expected_pid = core.execute(&module).unwrap().pid();
+
+    match core.run() {
+        CoreRunOutcome::ReservedPidInterfaceMessage { pid, message, .. } => {
+            assert_eq!(pid, expected_pid);
+            assert_eq!(message.0, b"abcdef");
+        }
+        _ => panic!(),
+    }
+}
+
+/// Executes the given module and checks that it is killed because of an out of bounds memory
+/// access, which is how invalid calls to extrinsics are reported.
+fn assert_invalid_extrinsic_call(wat: &str) {
+    let module = Module::from_wat(wat).unwrap();
+
+    let mut core = Core::new().build();
+    let expected_pid = core.execute(&module).unwrap().pid();
+
+    match core.run() {
+        CoreRunOutcome::ProgramFinished {
+            pid,
+            outcome: Err(trap),
+            ..
+        } => {
+            assert_eq!(pid, expected_pid);
+            assert_eq!(trap, Trap::MemoryAccessOutOfBounds);
+        }
+        _ => panic!(),
+    }
+
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn emit_bad_message_id_pointer_kills_process() {
+    // The message ID would be written at the very end of the memory, past its last byte.
+    assert_invalid_extrinsic_call(
+        r#"(module
+        (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05")
+        (func $_start (result i32)
+            (call $emit_message (i32.const 0) (i32.const 32) (i32.const 0) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 65532)))
+        (export "_start" (func $_start)))
+    "#,
+    );
+}
+
+#[test]
+fn emit_too_large_message_kills_process() {
+    // The buffers add up to 16MiB, which is above the maximum size of a message.
+    assert_invalid_extrinsic_call(
+        r#"(module
+        (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05")
+        (data (i32.const 32) "\40\00\00\00\00\00\80\00\40\00\00\00\00\00\80\00")
+        (func $_start (result i32)
+            (call $emit_message (i32.const 0) (i32.const 32) (i32.const 2) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)))
+        (export "_start" (func $_start)))
+    "#,
+    );
+}
+
+#[test]
+fn next_message_bad_out_buffer_kills_process() {
+    assert_invalid_extrinsic_call(
+        r#"(module
+        (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+        (memory (export "memory") 1)
+        (func $_start (result i32)
+            (call $next_message (i32.const 0) (i32.const 1) (i32.const 65000) (i32.const 1024) (i32.const 1) (i64.const 0)))
+        (export "_start" (func $_start)))
+    "#,
+    );
+}
+
+#[test]
+fn next_message_bad_message_ids_pointer_kills_process() {
+    assert_invalid_extrinsic_call(
+        r#"(module
+        (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+        (memory (export "memory") 1)
+        (func $_start (result i32)
+            (call $next_message (i32.const 65532) (i32.const 1) (i32.const 0) (i32.const 1024) (i32.const 1) (i64.const 0)))
+        (export "_start" (func $_start)))
+    "#,
+    );
+}
+
+/// Module that emits an empty message on interface `[5; 32]`, waits for the answer, then
+/// returns `0`.
+const EMIT_AND_WAIT_MODULE: &str = r#"(module
+    (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+    (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+    (memory (export "memory") 1)
+    (data (i32.const 0) "\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05\05")
+    (func $_start (result i32)
+        (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 0) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 128)))
+        (drop (call $next_message (i32.const 128) (i32.const 1) (i32.const 256) (i32.const 512) (i32.const 1) (i64.const 0)))
+        i32.const 0)
+    (export "_start" (func $_start)))
+"#;
+
+#[test]
+fn interface_waiter_unblocked_by_registration() {
+    let waiter_module = Module::from_wat(EMIT_AND_WAIT_MODULE).unwrap();
+    let stuck_module = Module::from_wat(STUCK_MODULE).unwrap();
+
+    let awaited = crate::InterfaceHash::from([7; 32]);
+
+    let mut builder = Core::new();
+    let handler_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    core.set_interface_handler(From::from([5; 32]), handler_pid)
+        .unwrap();
+    let waiter_pid = core.execute(&waiter_module).unwrap().pid();
+
+    match core.run() {
+        CoreRunOutcome::ReservedPidInterfaceMessage {
+            pid, message_id, ..
+        } => {
+            assert_eq!(pid, waiter_pid);
+            core.answer_on_interface_registration(awaited.clone(), message_id.unwrap());
+        }
+        _ => panic!(),
+    }
+
+    match core.run() {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+
+    let registering_pid = core.execute(&stuck_module).unwrap().pid();
+    core.set_interface_handler(awaited, registering_pid)
+        .unwrap();
+
+    match core.run() {
+        CoreRunOutcome::ProgramFinished { pid, outcome, .. } => {
+            assert_eq!(pid, waiter_pid);
+            assert!(outcome.is_ok());
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn interface_waiter_answered_if_already_registered() {
+    let awaited = crate::InterfaceHash::from([7; 32]);
+
+    let mut builder = Core::new();
+    let waiter_pid = builder.reserve_pid();
+    let handler_pid = builder.reserve_pid();
+    let registered_pid = builder.reserve_pid();
+    let mut core = builder.build();
+    core.set_interface_handler(From::from([5; 32]), handler_pid)
+        .unwrap();
+    core.set_interface_handler(awaited.clone(), registered_pid)
+        .unwrap();
+
+    let emitted_id =
+        core.emit_interface_message_answer(waiter_pid, crate::InterfaceHash::from([5; 32]), ());
+    match core.run() {
+        CoreRunOutcome::ReservedPidInterfaceMessage { message_id, .. } => {
+            assert_eq!(message_id, Some(emitted_id));
+            core.answer_on_interface_registration(awaited, emitted_id);
+        }
+        _ => panic!(),
+    }
+
+    match core.run() {
+        CoreRunOutcome::MessageResponse {
+            message_id,
+            response,
+        } => {
+            assert_eq!(message_id, emitted_id);
+            assert!(response.unwrap().0.is_empty());
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn step_stops_at_each_interruption() {
+    // Calls `next_message` three times without blocking, then finishes.
+    let module = Module::from_wat(
+        r#"(module
+        (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\01\00\00\00\00\00\00\00")
+        (func $_start (result i32)
+            (drop (call $next_message (i32.const 0) (i32.const 1) (i32.const 256) (i32.const 256) (i32.const 0) (i64.const 0)))
+            (drop (call $next_message (i32.const 0) (i32.const 1) (i32.const 256) (i32.const 256) (i32.const 0) (i64.const 0)))
+            (drop (call $next_message (i32.const 0) (i32.const 1) (i32.const 256) (i32.const 256) (i32.const 0) (i64.const 0)))
+            i32.const 0)
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    let mut core = Core::new().with_deterministic_scheduling().build();
+    let expected_pid = core.execute(&module).unwrap().pid();
+
+    for _ in 0..3 {
+        match core.step() {
+            CoreStepOutcome::Progress => {}
+            _ => panic!(),
+        }
+    }
+
+    match core.step() {
+        CoreStepOutcome::Outcome(CoreRunOutcome::ProgramFinished { pid, .. }) => {
+            assert_eq!(pid, expected_pid)
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn run_until_idle_waits_for_deadline() {
+    let mut core = Core::new().build();
+
+    let start = std::time::Instant::now();
+    let deadline = start + std::time::Duration::from_millis(50);
+    match core.run_until(deadline) {
+        CoreRunOutcome::Idle => {}
+        _ => panic!(),
+    }
+    assert!(std::time::Instant::now() >= deadline);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn run_until_returns_without_waiting_when_ready() {
+    let module = Module::from_wat(
+        r#"(module
+        (func $_start (result i32)
+            i32.const 5)
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    let mut core = Core::new().build();
+    let expected_pid = core.execute(&module).unwrap().pid();
+
+    let start = std::time::Instant::now();
+    match core.run_until(start + std::time::Duration::from_secs(30)) {
+        CoreRunOutcome::ProgramFinished { pid, .. } => assert_eq!(pid, expected_pid),
+        _ => panic!(),
+    }
+    assert!(start.elapsed() < std::time::Duration::from_secs(30));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn run_until_wakes_up_for_next_message_timeout() {
+    // Waits for an interface message with a timeout of 10ms, then finishes.
+    let module = Module::from_wat(
+        r#"(module
+        (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\01\00\00\00\00\00\00\00")
+        (func $_start (result i32)
+            (drop (call $next_message (i32.const 0) (i32.const 1) (i32.const 256) (i32.const 256) (i32.const 1) (i64.const 10000000)))
+            i32.const 0)
+        (export "_start" (func $_start)))
+    "#,
+    )
+    .unwrap();
+
+    struct HostTimeSource(std::time::Instant);
+    impl crate::time::TimeSource for HostTimeSource {
+        fn monotonic_nanos(&self) -> u128 {
+            self.0.elapsed().as_nanos()
+        }
+    }
+
+    let mut core = Core::new()
+        .with_time_source(HostTimeSource(std::time::Instant::now()))
+        .build();
+    let pid = core.execute(&module).unwrap().pid();
+
+    let start = std::time::Instant::now();
+    match core.run_until(start + std::time::Duration::from_secs(30)) {
+        CoreRunOutcome::ProgramFinished {
+            pid: finished,
+            outcome: Ok(_),
+            ..
+        } => assert_eq!(finished, pid),
+        _ => panic!(),
+    }
+    assert!(start.elapsed() < std::time::Duration::from_secs(30));
+}
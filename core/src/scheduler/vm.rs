@@ -13,17 +13,21 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::module::Module;
-use alloc::{
-    borrow::{Cow, ToOwned as _},
-    boxed::Box,
-    format,
-    vec::Vec,
+use crate::{
+    module::{Module, FUEL_GLOBAL},
+    signature::{Signature, ValueType, WasmValue},
 };
-use core::{cell::RefCell, convert::TryInto, fmt};
+use alloc::{string::String, vec, vec::Vec};
+use core::fmt;
 use smallvec::SmallVec;
 
-/// WASMI state machine dedicated to a process.
+pub use self::engine::{Engine, EngineRunOutcome, Trap};
+pub use self::interpreter::WasmiEngine;
+
+mod engine;
+mod interpreter;
+
+/// State machine dedicated to a process.
 ///
 /// # Initialization
 ///
@@ -54,10 +58,15 @@ use smallvec::SmallVec;
 /// then interrupted by a call to an imported function, you must execute the imported function and
 /// pass its return value the next time you call [`run`](Thread::run).
 ///
-/// The generic parameter of this struct is some userdata that is associated with each thread.
+/// The generic parameter `T` of this struct is some userdata that is associated with each thread.
 /// You must pass a value when creating a thread, and can retreive it later by calling
 /// [`user_data`](Thread::user_data) or [`into_user_data`](Thread::into_user_data).
 ///
+/// # Engine
+///
+/// The code is executed by the [`Engine`] passed as the generic parameter `E`. By default, this
+/// is the [`WasmiEngine`].
+///
 /// # Poisoning
 ///
 /// If the main thread stops, or if any thread encounters an error, then the VM moves into a
@@ -70,51 +79,41 @@ use smallvec::SmallVec;
 /// The [`ProcessStateMachine`] is single-threaded. In other words, the VM can only ever run one
 /// thread simultaneously. This might change in the future.
 ///
-pub struct ProcessStateMachine<T> {
-    /// Original module, with resolved imports.
-    module: wasmi::ModuleRef,
-
-    /// Memory of the module instantiation.
-    ///
-    /// Right now we only support one unique `Memory` object per process. This is it.
-    /// Contains `None` if the process doesn't export any memory object, which means it doesn't use
-    /// any memory.
-    memory: Option<wasmi::MemoryRef>,
-
-    /// Table of the indirect function calls.
-    ///
-    /// In WASM, function pointers are in reality indices in a table called
-    /// `__indirect_function_table`. This is this table, if it exists.
-    indirect_table: Option<wasmi::TableRef>,
+pub struct ProcessStateMachine<T, E: Engine = WasmiEngine> {
+    /// Instance of the module, with resolved imports.
+    instance: E::Instance,
 
     /// List of threads that this process is running.
-    threads: SmallVec<[ThreadState<T>; 4]>,
+    threads: SmallVec<[ThreadState<T, E>; 4]>,
 
     /// If true, the state machine is in a poisoned state and cannot run any code anymore.
     is_poisoned: bool,
+
+    /// True if the module has been instrumented for fuel metering, in which case it exports the
+    /// global named [`FUEL_GLOBAL`].
+    is_metered: bool,
 }
 
 /// State of a single thread within the VM.
-struct ThreadState<T> {
+struct ThreadState<T, E: Engine> {
     /// Execution context of this thread. This notably holds the program counter, state of the
     /// stack, and so on.
-    ///
-    /// This field is an `Option` because we need to be able to temporarily extract it. It must
-    /// always be `Some`.
-    execution: Option<wasmi::FuncInvocation<'static>>,
+    execution: E::Execution,
 
-    /// If false, then one must call `execution.start_execution()` instead of `resume_execution()`.
-    /// This is a particularity of the WASM interpreter that we don't want to expose in our API.
-    interrupted: bool,
+    /// Fuel remaining for this thread. Only relevant if the module is metered.
+    ///
+    /// All the threads share the same global, and the fuel of each thread is swapped in and out
+    /// of it around each call to [`Thread::run`].
+    fuel: u64,
 
     /// Opaque user data associated with the thread.
     user_data: T,
 }
 
 /// Access to a thread within the virtual machine.
-pub struct Thread<'a, T> {
+pub struct Thread<'a, T, E: Engine = WasmiEngine> {
     /// Reference to the parent object.
-    vm: &'a mut ProcessStateMachine<T>,
+    vm: &'a mut ProcessStateMachine<T, E>,
 
     // Index within [`ProcessStateMachine::threads`] of the thread we are referencing.
     index: usize,
@@ -122,7 +121,7 @@ pub struct Thread<'a, T> {
 
 /// Outcome of the [`run`](Thread::run) function.
 #[derive(Debug)]
-pub enum ExecOutcome<'a, T> {
+pub enum ExecOutcome<'a, T, E: Engine = WasmiEngine> {
     /// A thread has finished. The thread no longer exists in the list.
     ///
     /// If this was the main thread (i.e. `thread_index` is 0), then the state machine is now in
@@ -138,7 +137,7 @@ pub enum ExecOutcome<'a, T> {
         thread_index: usize,
 
         /// Return value of the thread function.
-        return_value: Option<wasmi::RuntimeValue>,
+        return_value: Option<WasmValue>,
 
         /// User data that was stored within the thread.
         user_data: T,
@@ -155,14 +154,14 @@ pub enum ExecOutcome<'a, T> {
     /// >           [`run`](Thread::run) with a value of the wrong type.
     Interrupted {
         /// Thread that was interrupted.
-        thread: Thread<'a, T>,
+        thread: Thread<'a, T, E>,
 
         /// Identifier of the function to call. Corresponds to the value provided at
         /// initialization when resolving imports.
         id: usize,
 
         /// Parameters of the function call.
-        params: Vec<wasmi::RuntimeValue>,
+        params: Vec<WasmValue>,
     },
 
     /// The currently-executed function has finished with an error. The state machine is now in a
@@ -171,27 +170,60 @@ pub enum ExecOutcome<'a, T> {
     /// Calling [`is_poisoned`](ProcessStateMachine::is_poisoned) will return true.
     Errored {
         /// Thread that error'd.
-        thread: Thread<'a, T>,
+        thread: Thread<'a, T, E>,
 
         /// Error that happened.
-        // TODO: error type should change here
-        error: wasmi::Trap,
+        error: Trap,
     },
 }
 
 /// Error that can happen when initializing a VM.
 #[derive(Debug)]
 pub enum NewErr {
-    /// Error in the interpreter.
-    Interpreter(wasmi::Error),
+    /// The engine has failed to instantiate the module. Contains a description of the problem.
+    Instantiation(String),
     /// The "start" symbol doesn't exist.
     StartNotFound,
     /// The "start" symbol must be a function.
     StartIsntAFunction,
+    /// The "start" symbol doesn't accept the parameters it is called with. `_start` must not
+    /// accept any parameter, and `main` must accept two `i32`s. A named entry point must accept
+    /// the parameters passed alongside with it.
+    StartBadSignature,
     /// If a "memory" symbol is provided, it must be a memory.
     MemoryIsntMemory,
     /// If a "__indirect_function_table" symbol is provided, it must be a table.
     IndirectTableIsntTable,
+    /// Starting the process would exceed the limit to the total memory usage, or the process
+    /// requires more memory than the per-process limit.
+    MemoryLimitReached,
+    /// The module imports a function that isn't available.
+    MissingImport {
+        /// Name of the module the function is imported from.
+        module: String,
+        /// Name of the imported function.
+        field: String,
+        /// Signature of the function, as declared by the module.
+        signature: Signature,
+        /// If a function with this name is available but has a different signature, contains
+        /// the signature of that function.
+        expected_signature: Option<Signature>,
+    },
+}
+
+/// Function that the main thread of a [`ProcessStateMachine`] starts executing.
+#[derive(Debug, Clone)]
+pub enum EntryPoint<'a> {
+    /// The `_start` function, or, if it doesn't exist, the `main` function with `argc` and `argv`
+    /// set to 0.
+    Default,
+    /// The exported function with the given name, called with the given parameters.
+    Named {
+        /// Name of the exported function.
+        symbol: &'a str,
+        /// Parameters passed to the function.
+        params: Vec<WasmValue>,
+    },
 }
 
 /// Error that can happen when starting a new thread.
@@ -203,6 +235,8 @@ pub enum StartErr {
     FunctionNotFound,
     /// The requested function has been found in the list of exports, but it is not a function.
     NotAFunction,
+    /// The parameters don't match the signature of the requested function.
+    BadSignature,
 }
 
 /// Error that can happen when resuming the execution of a function.
@@ -213,14 +247,14 @@ pub enum RunErr {
     /// Passed a wrong value back.
     BadValueTy {
         /// Type of the value that was expected.
-        expected: Option<wasmi::ValueType>,
+        expected: Option<ValueType>,
         /// Type of the value that was actually passed.
-        obtained: Option<wasmi::ValueType>,
+        obtained: Option<ValueType>,
     },
 }
 
 impl<T> ProcessStateMachine<T> {
-    /// Creates a new process state machine from the given module.
+    /// Creates a new process state machine from the given module, using the [`WasmiEngine`].
     ///
     /// The closure is called for each import that the module has. It must assign a number to each
     /// import, or return an error if the import can't be resolved. When the VM calls one of these
@@ -232,124 +266,72 @@ impl<T> ProcessStateMachine<T> {
     pub fn new(
         module: &Module,
         main_thread_user_data: T,
-        mut symbols: impl FnMut(&str, &str, &wasmi::Signature) -> Result<usize, ()>,
+        symbols: impl FnMut(&str, &str, &Signature) -> Result<usize, ()>,
     ) -> Result<Self, NewErr> {
-        struct ImportResolve<'a>(
-            RefCell<&'a mut dyn FnMut(&str, &str, &wasmi::Signature) -> Result<usize, ()>>,
-        );
-        impl<'a> wasmi::ImportResolver for ImportResolve<'a> {
-            fn resolve_func(
-                &self,
-                module_name: &str,
-                field_name: &str,
-                signature: &wasmi::Signature,
-            ) -> Result<wasmi::FuncRef, wasmi::Error> {
-                let closure = &mut **self.0.borrow_mut();
-                let index = match closure(module_name, field_name, signature) {
-                    Ok(i) => i,
-                    Err(_) => {
-                        return Err(wasmi::Error::Instantiation(format!(
-                            "Couldn't resolve `{}`:`{}`",
-                            module_name, field_name
-                        )))
-                    }
-                };
-
-                Ok(wasmi::FuncInstance::alloc_host(signature.clone(), index))
-            }
-
-            fn resolve_global(
-                &self,
-                _module_name: &str,
-                _field_name: &str,
-                _global_type: &wasmi::GlobalDescriptor,
-            ) -> Result<wasmi::GlobalRef, wasmi::Error> {
-                Err(wasmi::Error::Instantiation(
-                    "Importing globals is not supported yet".to_owned(),
-                ))
-            }
-
-            fn resolve_memory(
-                &self,
-                _module_name: &str,
-                _field_name: &str,
-                _memory_type: &wasmi::MemoryDescriptor,
-            ) -> Result<wasmi::MemoryRef, wasmi::Error> {
-                Err(wasmi::Error::Instantiation(
-                    "Importing memory is not supported yet".to_owned(),
-                ))
-            }
-
-            fn resolve_table(
-                &self,
-                _module_name: &str,
-                _field_name: &str,
-                _table_type: &wasmi::TableDescriptor,
-            ) -> Result<wasmi::TableRef, wasmi::Error> {
-                Err(wasmi::Error::Instantiation(
-                    "Importing tables is not supported yet".to_owned(),
-                ))
-            }
-        }
+        Self::with_entry_point(module, EntryPoint::Default, main_thread_user_data, symbols)
+    }
 
-        let not_started =
-            wasmi::ModuleInstance::new(module.as_ref(), &ImportResolve(RefCell::new(&mut symbols)))
-                .map_err(NewErr::Interpreter)?;
-
-        // TODO: WASM has a special "start" instruction that can be used to designate a function
-        // that must be executed before the module is considered initialized. It is unclear whether
-        // this is intended to be a function that for example initializes global variables, or if
-        // this is an equivalent of "_start". In practice, Rust never seems to generate such as
-        // "start" instruction, so for now we ignore it. The code below panics if there is such
-        // a "start" item, so we will fortunately not blindly run into troubles.
-        let module = not_started.assert_no_start();
-
-        let memory = if let Some(mem) = module.export_by_name("memory") {
-            if let Some(mem) = mem.as_memory() {
-                Some(mem.clone())
-            } else {
-                return Err(NewErr::MemoryIsntMemory);
-            }
-        } else {
-            None
-        };
+    /// Same as [`new`](ProcessStateMachine::new), but the main thread is paused at the start of
+    /// the given entry point.
+    pub fn with_entry_point(
+        module: &Module,
+        entry_point: EntryPoint,
+        main_thread_user_data: T,
+        symbols: impl FnMut(&str, &str, &Signature) -> Result<usize, ()>,
+    ) -> Result<Self, NewErr> {
+        Self::with_engine(module, entry_point, main_thread_user_data, symbols)
+    }
+}
 
-        let indirect_table = if let Some(tbl) = module.export_by_name("__indirect_function_table") {
-            if let Some(tbl) = tbl.as_table() {
-                Some(tbl.clone())
-            } else {
-                return Err(NewErr::IndirectTableIsntTable);
-            }
-        } else {
-            None
-        };
+impl<T, E: Engine> ProcessStateMachine<T, E> {
+    /// Same as [`with_entry_point`](ProcessStateMachine::with_entry_point), but executes the
+    /// module with the engine `E`.
+    pub fn with_engine(
+        module: &Module,
+        entry_point: EntryPoint,
+        main_thread_user_data: T,
+        mut symbols: impl FnMut(&str, &str, &Signature) -> Result<usize, ()>,
+    ) -> Result<Self, NewErr> {
+        let instance = E::instantiate(module, &mut symbols)?;
+        let is_metered = E::global_i64(&instance, FUEL_GLOBAL).is_some();
 
         let mut state_machine = ProcessStateMachine {
-            module,
-            memory,
-            indirect_table,
+            instance,
             is_poisoned: false,
+            is_metered,
             threads: SmallVec::new(),
         };
 
-        // Try to start executing `_start` or `main`.
-        // TODO: executing `main` is a hack right now in order to support wasm32-unknown-unknown which doesn't have
-        // a `_start` function
-        match state_machine.start_thread_by_name("_start", &[][..], main_thread_user_data) {
-            Ok(_) => {}
-            Err((StartErr::FunctionNotFound, user_data)) => {
-                static ARGC_ARGV: [wasmi::RuntimeValue; 2] =
-                    [wasmi::RuntimeValue::I32(0), wasmi::RuntimeValue::I32(0)];
-                match state_machine.start_thread_by_name("main", &ARGC_ARGV[..], user_data) {
-                    Ok(_) => {}
-                    Err((StartErr::FunctionNotFound, _)) => return Err(NewErr::StartNotFound),
-                    Err((StartErr::Poisoned, _)) => unreachable!(),
-                    Err((StartErr::NotAFunction, _)) => return Err(NewErr::StartIsntAFunction),
+        let result = match entry_point {
+            // Try to start executing `_start` or `main`.
+            // TODO: executing `main` is a hack right now in order to support wasm32-unknown-unknown which doesn't have
+            // a `_start` function
+            EntryPoint::Default => {
+                match state_machine
+                    .start_thread_by_name("_start", Vec::new(), main_thread_user_data)
+                    .map(|_| ())
+                {
+                    Err((StartErr::FunctionNotFound, user_data)) => {
+                        let argc_argv = vec![WasmValue::I32(0), WasmValue::I32(0)];
+                        state_machine
+                            .start_thread_by_name("main", argc_argv, user_data)
+                            .map(|_| ())
+                    }
+                    result => result,
                 }
             }
+            EntryPoint::Named { symbol, params } => state_machine
+                .start_thread_by_name(symbol, params, main_thread_user_data)
+                .map(|_| ()),
+        };
+
+        match result {
+            Ok(()) => {}
+            Err((StartErr::FunctionNotFound, _)) => return Err(NewErr::StartNotFound),
             Err((StartErr::Poisoned, _)) => unreachable!(),
             Err((StartErr::NotAFunction, _)) => return Err(NewErr::StartIsntAFunction),
-        };
+            Err((StartErr::BadSignature, _)) => return Err(NewErr::StartBadSignature),
+        }
 
         Ok(state_machine)
     }
@@ -370,29 +352,17 @@ impl<T> ProcessStateMachine<T> {
     pub fn start_thread_by_id(
         &mut self,
         function_id: u32,
-        params: impl Into<Cow<'static, [wasmi::RuntimeValue]>>,
+        params: Vec<WasmValue>,
         user_data: T,
-    ) -> Result<Thread<T>, StartErr> {
+    ) -> Result<Thread<T, E>, StartErr> {
         if self.is_poisoned {
             return Err(StartErr::Poisoned);
         }
 
-        // Find the function within the process.
-        let function = self
-            .indirect_table
-            .as_ref()
-            .and_then(|t| t.get(function_id).ok())
-            .and_then(|f| f)
-            .ok_or(StartErr::FunctionNotFound)?;
-
-        let execution = match wasmi::FuncInstance::invoke_resumable(&function, params) {
-            Ok(e) => e,
-            Err(err) => unreachable!("{:?}", err),
-        };
-
+        let execution = E::start_by_id(&self.instance, function_id, params)?;
         self.threads.push(ThreadState {
-            execution: Some(execution),
-            interrupted: false,
+            execution,
+            fuel: 0,
             user_data,
         });
 
@@ -408,27 +378,20 @@ impl<T> ProcessStateMachine<T> {
     fn start_thread_by_name(
         &mut self,
         symbol_name: &str,
-        params: impl Into<Cow<'static, [wasmi::RuntimeValue]>>,
+        params: Vec<WasmValue>,
         user_data: T,
-    ) -> Result<Thread<T>, (StartErr, T)> {
+    ) -> Result<Thread<T, E>, (StartErr, T)> {
         if self.is_poisoned {
             return Err((StartErr::Poisoned, user_data));
         }
 
-        match self.module.export_by_name(symbol_name) {
-            Some(wasmi::ExternVal::Func(f)) => {
-                let execution = match wasmi::FuncInstance::invoke_resumable(&f, params) {
-                    Ok(e) => e,
-                    Err(err) => unreachable!("{:?}", err),
-                };
-                self.threads.push(ThreadState {
-                    execution: Some(execution),
-                    interrupted: false,
-                    user_data,
-                });
-            }
-            None => return Err((StartErr::FunctionNotFound, user_data)),
-            _ => return Err((StartErr::NotAFunction, user_data)),
+        match E::start_by_name(&self.instance, symbol_name, params) {
+            Ok(execution) => self.threads.push(ThreadState {
+                execution,
+                fuel: 0,
+                user_data,
+            }),
+            Err(err) => return Err((err, user_data)),
         }
 
         let thread_id = self.threads.len() - 1;
@@ -452,7 +415,7 @@ impl<T> ProcessStateMachine<T> {
     ///
     /// Returns `None` if the index is superior or equal to what
     /// [`num_threads`](ProcessStateMachine::num_threads) would return.
-    pub fn thread(&mut self, index: usize) -> Option<Thread<T>> {
+    pub fn thread(&mut self, index: usize) -> Option<Thread<T, E>> {
         if index < self.threads.len() {
             Some(Thread { vm: self, index })
         } else {
@@ -465,33 +428,41 @@ impl<T> ProcessStateMachine<T> {
         self.threads.into_iter().map(|thread| thread.user_data)
     }
 
+    /// Returns the size, in bytes, of the linear memory of the process.
+    ///
+    /// Returns 0 if the process doesn't have any memory.
+    pub fn memory_size(&self) -> usize {
+        E::memory_size(&self.instance)
+    }
+
     /// Copies the given memory range into a `Vec<u8>`.
     ///
     /// Returns an error if the range is invalid or out of range.
     pub fn read_memory(&self, offset: u32, size: u32) -> Result<Vec<u8>, ()> {
-        let mem = match self.memory.as_ref() {
-            Some(m) => m,
-            None => unreachable!(),
-        };
+        E::read_memory(&self.instance, offset, size)
+    }
 
-        mem.get(offset, size.try_into().map_err(|_| ())?)
-            .map_err(|_| ())
+    /// Calls `f` with a view of the given memory range, without copying it.
+    ///
+    /// Returns an error if the range is invalid or out of range.
+    pub fn with_memory<R>(
+        &self,
+        offset: u32,
+        size: u32,
+        f: impl FnOnce(&[u8]) -> R,
+    ) -> Result<R, ()> {
+        E::with_memory(&self.instance, offset, size, f)
     }
 
     /// Write the data at the given memory location.
     ///
     /// Returns an error if the range is invalid or out of range.
     pub fn write_memory(&mut self, offset: u32, value: &[u8]) -> Result<(), ()> {
-        let mem = match self.memory.as_ref() {
-            Some(m) => m,
-            None => unreachable!(),
-        };
-
-        mem.set(offset, value).map_err(|_| ())
+        E::write_memory(&self.instance, offset, value)
     }
 }
 
-impl<T> fmt::Debug for ProcessStateMachine<T>
+impl<T, E: Engine> fmt::Debug for ProcessStateMachine<T, E>
 where
     T: fmt::Debug,
 {
@@ -500,7 +471,7 @@ where
     }
 }
 
-impl<T> fmt::Debug for ThreadState<T>
+impl<T, E: Engine> fmt::Debug for ThreadState<T, E>
 where
     T: fmt::Debug,
 {
@@ -509,74 +480,33 @@ where
     }
 }
 
-impl<'a, T> Thread<'a, T> {
+impl<'a, T, E: Engine> Thread<'a, T, E> {
     /// Starts or continues execution of this thread.
     ///
     /// If this is the first call you call [`run`](Thread::run) for this thread, then you must pass
     /// a value of `None`.
     /// If, however, you call this function after a previous call to [`run`](Thread::run) that was
     /// interrupted by an external function call, then you must pass back the outcome of that call.
-    pub fn run(mut self, value: Option<wasmi::RuntimeValue>) -> Result<ExecOutcome<'a, T>, RunErr> {
-        struct DummyExternals;
-        impl wasmi::Externals for DummyExternals {
-            fn invoke_index(
-                &mut self,
-                index: usize,
-                args: wasmi::RuntimeArgs,
-            ) -> Result<Option<wasmi::RuntimeValue>, wasmi::Trap> {
-                Err(wasmi::TrapKind::Host(Box::new(Interrupt {
-                    index,
-                    args: args.as_ref().to_vec(),
-                }))
-                .into())
-            }
-        }
-
-        #[derive(Debug)]
-        struct Interrupt {
-            index: usize,
-            args: Vec<wasmi::RuntimeValue>,
-        }
-        impl fmt::Display for Interrupt {
-            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-                write!(f, "Interrupt")
-            }
-        }
-        impl wasmi::HostError for Interrupt {}
-
+    pub fn run(self, value: Option<WasmValue>) -> Result<ExecOutcome<'a, T, E>, RunErr> {
         if self.vm.is_poisoned {
             return Err(RunErr::Poisoned);
         }
 
-        let thread_state = &mut self.vm.threads[self.index];
+        if self.vm.is_metered {
+            let fuel = self.vm.threads[self.index].fuel as i64;
+            let _ = E::set_global_i64(&self.vm.instance, FUEL_GLOBAL, fuel);
+        }
 
-        let mut execution = match thread_state.execution.take() {
-            Some(e) => e,
-            None => unreachable!(),
-        };
-        let result = if thread_state.interrupted {
-            let expected_ty = execution.resumable_value_type();
-            let obtained_ty = value.as_ref().map(|v| v.value_type());
-            if expected_ty != obtained_ty {
-                return Err(RunErr::BadValueTy {
-                    expected: expected_ty,
-                    obtained: obtained_ty,
-                });
-            }
-            execution.resume_execution(value, &mut DummyExternals)
-        } else {
-            if value.is_some() {
-                return Err(RunErr::BadValueTy {
-                    expected: None,
-                    obtained: value.as_ref().map(|v| v.value_type()),
-                });
+        let outcome = E::run(&mut self.vm.threads[self.index].execution, value)?;
+
+        if self.vm.is_metered {
+            if let Some(fuel) = E::global_i64(&self.vm.instance, FUEL_GLOBAL) {
+                self.vm.threads[self.index].fuel = fuel as u64;
             }
-            thread_state.interrupted = true;
-            execution.start_execution(&mut DummyExternals)
-        };
+        }
 
-        match result {
-            Ok(return_value) => {
+        match outcome {
+            EngineRunOutcome::Finished(return_value) => {
                 let user_data = self.vm.threads.remove(self.index).user_data;
                 // If this is the "main" function, the state machine is now poisoned.
                 if self.index == 0 {
@@ -588,28 +518,16 @@ impl<'a, T> Thread<'a, T> {
                     user_data,
                 })
             }
-            Err(wasmi::ResumableError::AlreadyStarted) => unreachable!(),
-            Err(wasmi::ResumableError::NotResumable) => unreachable!(),
-            Err(wasmi::ResumableError::Trap(ref trap)) if trap.kind().is_host() => {
-                let interrupt: &Interrupt = match trap.kind() {
-                    wasmi::TrapKind::Host(err) => match err.downcast_ref() {
-                        Some(e) => e,
-                        None => unreachable!(),
-                    },
-                    _ => unreachable!(),
-                };
-                thread_state.execution = Some(execution);
-                Ok(ExecOutcome::Interrupted {
-                    thread: self,
-                    id: interrupt.index,
-                    params: interrupt.args.clone(),
-                })
-            }
-            Err(wasmi::ResumableError::Trap(trap)) => {
+            EngineRunOutcome::Interrupted { id, params } => Ok(ExecOutcome::Interrupted {
+                thread: self,
+                id,
+                params,
+            }),
+            EngineRunOutcome::Trapped(error) => {
                 self.vm.is_poisoned = true;
                 Ok(ExecOutcome::Errored {
                     thread: self,
-                    error: trap,
+                    error,
                 })
             }
         }
@@ -623,6 +541,22 @@ impl<'a, T> Thread<'a, T> {
         self.index
     }
 
+    /// Returns the fuel remaining for this thread, or `None` if the module hasn't been
+    /// instrumented for fuel metering.
+    pub fn fuel(&self) -> Option<u64> {
+        if self.vm.is_metered {
+            Some(self.vm.threads[self.index].fuel)
+        } else {
+            None
+        }
+    }
+
+    /// Sets the fuel remaining for this thread. Has no effect if the module hasn't been
+    /// instrumented for fuel metering.
+    pub fn set_fuel(&mut self, fuel: u64) {
+        self.vm.threads[self.index].fuel = fuel;
+    }
+
     /// Returns the user data associated to that thread.
     pub fn user_data(&mut self) -> &mut T {
         &mut self.vm.threads[self.index].user_data
@@ -634,7 +568,7 @@ impl<'a, T> Thread<'a, T> {
     }
 }
 
-impl<'a, T> fmt::Debug for Thread<'a, T>
+impl<'a, T, E: Engine> fmt::Debug for Thread<'a, T, E>
 where
     T: fmt::Debug,
 {
@@ -646,9 +580,12 @@ where
 impl fmt::Display for NewErr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            NewErr::Interpreter(_) => write!(f, "Error in the interpreter"),
+            NewErr::Instantiation(err) => write!(f, "Failed to instantiate the module: {}", err),
             NewErr::StartNotFound => write!(f, "The \"start\" symbol doesn't exist"),
             NewErr::StartIsntAFunction => write!(f, "The \"start\" symbol must be a function"),
+            NewErr::StartBadSignature => {
+                write!(f, "The \"start\" symbol has an unexpected signature")
+            }
             NewErr::MemoryIsntMemory => {
                 write!(f, "If a \"memory\" symbol is provided, it must be a memory")
             }
@@ -656,6 +593,20 @@ impl fmt::Display for NewErr {
                 f,
                 "If a \"__indirect_function_table\" symbol is provided, it must be a table"
             ),
+            NewErr::MemoryLimitReached => write!(f, "Limit to the total memory usage reached"),
+            NewErr::MissingImport {
+                module,
+                field,
+                expected_signature: Some(_),
+                ..
+            } => write!(
+                f,
+                "Signature mismatch for imported function {}::{}",
+                module, field
+            ),
+            NewErr::MissingImport { module, field, .. } => {
+                write!(f, "Imported function {}::{} doesn't exist", module, field)
+            }
         }
     }
 }
@@ -666,6 +617,7 @@ impl fmt::Display for StartErr {
             StartErr::Poisoned => write!(f, "State machine is in a poisoned state"),
             StartErr::FunctionNotFound => write!(f, "Function to start was not found"),
             StartErr::NotAFunction => write!(f, "Symbol to start is not a function"),
+            StartErr::BadSignature => write!(f, "Parameters don't match the function signature"),
         }
     }
 }
@@ -686,7 +638,7 @@ impl fmt::Display for RunErr {
 #[cfg(test)]
 mod tests {
     use super::{ExecOutcome, NewErr, ProcessStateMachine};
-    use crate::module::Module;
+    use crate::{module::Module, signature::WasmValue};
 
     #[test]
     fn starts_if_main() {
@@ -720,6 +672,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn main_with_arguments_executes() {
+        let module = Module::from_wat(
+            r#"(module
+            (func $main (param $p0 i32) (param $p1 i32) (result i32)
+                (i32.add (local.get $p0) (i32.const 3)))
+            (export "main" (func $main)))
+        "#,
+        )
+        .unwrap();
+
+        let mut state_machine =
+            ProcessStateMachine::new(&module, (), |_, _, _| unreachable!()).unwrap();
+        match state_machine.thread(0).unwrap().run(None) {
+            Ok(ExecOutcome::ThreadFinished {
+                return_value: Some(WasmValue::I32(3)),
+                ..
+            }) => {}
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn error_if_main_bad_signature() {
+        let module = Module::from_wat(
+            r#"(module
+            (func $main (param $p0 i64) (result i32)
+                i32.const 5)
+            (export "main" (func $main)))
+        "#,
+        )
+        .unwrap();
+
+        match ProcessStateMachine::new(&module, (), |_, _, _| unreachable!()) {
+            Err(NewErr::StartBadSignature) => {}
+            _ => panic!(),
+        }
+    }
+
     #[test]
     fn main_executes() {
         let module = Module::from_wat(
@@ -735,7 +726,7 @@ mod tests {
             ProcessStateMachine::new(&module, (), |_, _, _| unreachable!()).unwrap();
         match state_machine.thread(0).unwrap().run(None) {
             Ok(ExecOutcome::ThreadFinished {
-                return_value: Some(wasmi::RuntimeValue::I32(5)),
+                return_value: Some(WasmValue::I32(5)),
                 ..
             }) => {}
             _ => panic!(),
@@ -768,10 +759,10 @@ mod tests {
         match state_machine
             .thread(0)
             .unwrap()
-            .run(Some(wasmi::RuntimeValue::I32(2227)))
+            .run(Some(WasmValue::I32(2227)))
         {
             Ok(ExecOutcome::ThreadFinished {
-                return_value: Some(wasmi::RuntimeValue::I32(2227)),
+                return_value: Some(WasmValue::I32(2227)),
                 ..
             }) => {}
             _ => panic!(),
@@ -0,0 +1,160 @@
+// Copyright (C) 2019  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{NewErr, RunErr, StartErr};
+use crate::{
+    module::Module,
+    signature::{Signature, WasmValue},
+};
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+/// Execution engine capable of running WASM code.
+///
+/// The [`ProcessStateMachine`](super::ProcessStateMachine) is built on top of an engine, and
+/// handles everything that isn't directly related to executing code, such as keeping track of
+/// threads and of poisoning. This makes it possible to swap the interpreter for a faster backend
+/// on hosts that support it.
+///
+/// All the methods of this trait are static. The engine itself is only used as a type-level
+/// marker.
+pub trait Engine {
+    /// Instance of a module, with its imports resolved.
+    type Instance;
+
+    /// Execution of a function within an [`Instance`](Engine::Instance). Holds the program
+    /// counter, state of the stack, and so on.
+    type Execution;
+
+    /// Instantiates the given module.
+    ///
+    /// The closure is called for each function import of the module. It must assign a number to
+    /// each import, or return an error if the import can't be resolved. This number is later
+    /// reported through [`EngineRunOutcome::Interrupted`] when the function is called.
+    ///
+    /// Instances must expose their memory and their indirect function table under the names
+    /// `memory` and `__indirect_function_table`.
+    fn instantiate(
+        module: &Module,
+        symbols: &mut dyn FnMut(&str, &str, &Signature) -> Result<usize, ()>,
+    ) -> Result<Self::Instance, NewErr>;
+
+    /// Returns the size, in bytes, of the linear memory of the instance, or 0 if it doesn't
+    /// have any memory.
+    fn memory_size(instance: &Self::Instance) -> usize;
+
+    /// Copies the given memory range into a `Vec<u8>`.
+    ///
+    /// Returns an error if the range is invalid or out of range.
+    fn read_memory(instance: &Self::Instance, offset: u32, size: u32) -> Result<Vec<u8>, ()>;
+
+    /// Calls `f` with a view of the given memory range, without copying it.
+    ///
+    /// Returns an error if the instance doesn't have any memory, or if the range is invalid or
+    /// out of range.
+    fn with_memory<R>(
+        instance: &Self::Instance,
+        offset: u32,
+        size: u32,
+        f: impl FnOnce(&[u8]) -> R,
+    ) -> Result<R, ()>;
+
+    /// Write the data at the given memory location.
+    ///
+    /// Returns an error if the range is invalid or out of range.
+    fn write_memory(instance: &Self::Instance, offset: u32, value: &[u8]) -> Result<(), ()>;
+
+    /// Returns the value of the exported `i64` global with the given name, or `None` if the
+    /// instance doesn't export such a global.
+    fn global_i64(instance: &Self::Instance, name: &str) -> Option<i64>;
+
+    /// Sets the value of the exported mutable `i64` global with the given name.
+    ///
+    /// Returns an error if the instance doesn't export such a global.
+    fn set_global_i64(instance: &Self::Instance, name: &str, value: i64) -> Result<(), ()>;
+
+    /// Prepares the execution of the exported function with the given name. The function
+    /// doesn't start executing before [`run`](Engine::run) is called.
+    fn start_by_name(
+        instance: &Self::Instance,
+        symbol_name: &str,
+        params: Vec<WasmValue>,
+    ) -> Result<Self::Execution, StartErr>;
+
+    /// Prepares the execution of the function with the given index in the indirect function
+    /// table. The function doesn't start executing before [`run`](Engine::run) is called.
+    fn start_by_id(
+        instance: &Self::Instance,
+        function_id: u32,
+        params: Vec<WasmValue>,
+    ) -> Result<Self::Execution, StartErr>;
+
+    /// Starts or continues the given execution.
+    ///
+    /// The first time this is called for an execution, `value` must be `None`. Afterwards, it
+    /// must be the return value of the imported function that has interrupted the execution.
+    fn run(
+        execution: &mut Self::Execution,
+        value: Option<WasmValue>,
+    ) -> Result<EngineRunOutcome, RunErr>;
+}
+
+/// Outcome of [`Engine::run`].
+#[derive(Debug)]
+pub enum EngineRunOutcome {
+    /// The function has finished executing. The execution can no longer be used.
+    Finished(Option<WasmValue>),
+
+    /// The execution has been paused due to a call to an imported function.
+    Interrupted {
+        /// Identifier of the function, as returned by the closure passed to
+        /// [`Engine::instantiate`].
+        id: usize,
+        /// Parameters of the function call.
+        params: Vec<WasmValue>,
+    },
+
+    /// The function has finished with an error. The execution can no longer be used.
+    Trapped(Trap),
+}
+
+/// Reason why the execution of a function has failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trap {
+    /// An `unreachable` instruction has been executed.
+    Unreachable,
+    /// The code has accessed memory outside of the bounds of the linear memory.
+    MemoryAccessOutOfBounds,
+    /// A function has been called with parameters that don't match its signature.
+    UnexpectedSignature,
+    /// The process has been killed from the outside rather than because of its code.
+    Aborted,
+    /// Any other error, such as a division by zero or a stack overflow, with a description
+    /// provided by the engine.
+    Other(String),
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Trap::Unreachable => write!(f, "Unreachable instruction executed"),
+            Trap::MemoryAccessOutOfBounds => write!(f, "Memory access out of bounds"),
+            Trap::UnexpectedSignature => write!(f, "Function called with an unexpected signature"),
+            Trap::Aborted => write!(f, "Process aborted"),
+            Trap::Other(err) => write!(f, "{}", err),
+        }
+    }
+}
@@ -0,0 +1,349 @@
+// Copyright (C) 2019  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{Engine, EngineRunOutcome, NewErr, RunErr, StartErr, Trap};
+use crate::{
+    module::Module,
+    signature::{Signature, ValueType, WasmValue},
+};
+
+use alloc::{borrow::ToOwned as _, boxed::Box, format, string::ToString as _, vec::Vec};
+use core::{cell::RefCell, convert::TryInto, fmt};
+
+/// [`Engine`] based on the `wasmi` interpreter.
+///
+/// This is the default engine. It is portable and doesn't require any support from the host.
+#[derive(Debug)]
+pub enum WasmiEngine {}
+
+/// Instance of a module within the [`WasmiEngine`].
+pub struct WasmiInstance {
+    /// Original module, with resolved imports.
+    module: wasmi::ModuleRef,
+
+    /// Memory of the module instantiation.
+    ///
+    /// Right now we only support one unique `Memory` object per process. This is it.
+    /// Contains `None` if the process doesn't export any memory object, which means it doesn't use
+    /// any memory.
+    memory: Option<wasmi::MemoryRef>,
+
+    /// Table of the indirect function calls.
+    ///
+    /// In WASM, function pointers are in reality indices in a table called
+    /// `__indirect_function_table`. This is this table, if it exists.
+    indirect_table: Option<wasmi::TableRef>,
+}
+
+/// Execution of a function within the [`WasmiEngine`].
+pub struct WasmiExecution {
+    /// Execution context. This notably holds the program counter, state of the stack, and so on.
+    execution: wasmi::FuncInvocation<'static>,
+
+    /// If false, then one must call `execution.start_execution()` instead of `resume_execution()`.
+    /// This is a particularity of the WASM interpreter that we don't want to expose in our API.
+    interrupted: bool,
+}
+
+impl Engine for WasmiEngine {
+    type Instance = WasmiInstance;
+    type Execution = WasmiExecution;
+
+    fn instantiate(
+        module: &Module,
+        symbols: &mut dyn FnMut(&str, &str, &Signature) -> Result<usize, ()>,
+    ) -> Result<WasmiInstance, NewErr> {
+        struct ImportResolve<'a>(
+            RefCell<&'a mut dyn FnMut(&str, &str, &Signature) -> Result<usize, ()>>,
+        );
+        impl<'a> wasmi::ImportResolver for ImportResolve<'a> {
+            fn resolve_func(
+                &self,
+                module_name: &str,
+                field_name: &str,
+                signature: &wasmi::Signature,
+            ) -> Result<wasmi::FuncRef, wasmi::Error> {
+                let closure = &mut **self.0.borrow_mut();
+                let index = match closure(module_name, field_name, &From::from(signature)) {
+                    Ok(i) => i,
+                    Err(_) => {
+                        return Err(wasmi::Error::Instantiation(format!(
+                            "Couldn't resolve `{}`:`{}`",
+                            module_name, field_name
+                        )))
+                    }
+                };
+
+                Ok(wasmi::FuncInstance::alloc_host(signature.clone(), index))
+            }
+
+            fn resolve_global(
+                &self,
+                _module_name: &str,
+                _field_name: &str,
+                _global_type: &wasmi::GlobalDescriptor,
+            ) -> Result<wasmi::GlobalRef, wasmi::Error> {
+                Err(wasmi::Error::Instantiation(
+                    "Importing globals is not supported yet".to_owned(),
+                ))
+            }
+
+            fn resolve_memory(
+                &self,
+                _module_name: &str,
+                _field_name: &str,
+                _memory_type: &wasmi::MemoryDescriptor,
+            ) -> Result<wasmi::MemoryRef, wasmi::Error> {
+                Err(wasmi::Error::Instantiation(
+                    "Importing memory is not supported yet".to_owned(),
+                ))
+            }
+
+            fn resolve_table(
+                &self,
+                _module_name: &str,
+                _field_name: &str,
+                _table_type: &wasmi::TableDescriptor,
+            ) -> Result<wasmi::TableRef, wasmi::Error> {
+                Err(wasmi::Error::Instantiation(
+                    "Importing tables is not supported yet".to_owned(),
+                ))
+            }
+        }
+
+        let compiled = wasmi::Module::from_parity_wasm_module(module.source().clone())
+            .map_err(|err| NewErr::Instantiation(err.to_string()))?;
+        let not_started =
+            wasmi::ModuleInstance::new(&compiled, &ImportResolve(RefCell::new(symbols)))
+                .map_err(|err| NewErr::Instantiation(err.to_string()))?;
+
+        // TODO: WASM has a special "start" instruction that can be used to designate a function
+        // that must be executed before the module is considered initialized. It is unclear whether
+        // this is intended to be a function that for example initializes global variables, or if
+        // this is an equivalent of "_start". In practice, Rust never seems to generate such as
+        // "start" instruction, so for now we ignore it. The code below panics if there is such
+        // a "start" item, so we will fortunately not blindly run into troubles.
+        let module = not_started.assert_no_start();
+
+        let memory = if let Some(mem) = module.export_by_name("memory") {
+            if let Some(mem) = mem.as_memory() {
+                Some(mem.clone())
+            } else {
+                return Err(NewErr::MemoryIsntMemory);
+            }
+        } else {
+            None
+        };
+
+        let indirect_table = if let Some(tbl) = module.export_by_name("__indirect_function_table") {
+            if let Some(tbl) = tbl.as_table() {
+                Some(tbl.clone())
+            } else {
+                return Err(NewErr::IndirectTableIsntTable);
+            }
+        } else {
+            None
+        };
+
+        Ok(WasmiInstance {
+            module,
+            memory,
+            indirect_table,
+        })
+    }
+
+    fn memory_size(instance: &WasmiInstance) -> usize {
+        match instance.memory.as_ref() {
+            Some(mem) => wasmi::memory_units::Bytes::from(mem.current_size()).0,
+            None => 0,
+        }
+    }
+
+    fn read_memory(instance: &WasmiInstance, offset: u32, size: u32) -> Result<Vec<u8>, ()> {
+        let mem = instance.memory.as_ref().ok_or(())?;
+
+        mem.get(offset, size.try_into().map_err(|_| ())?)
+            .map_err(|_| ())
+    }
+
+    fn with_memory<R>(
+        instance: &WasmiInstance,
+        offset: u32,
+        size: u32,
+        f: impl FnOnce(&[u8]) -> R,
+    ) -> Result<R, ()> {
+        let mem = instance.memory.as_ref().ok_or(())?;
+        let start: usize = offset.try_into().map_err(|_| ())?;
+        let size: usize = size.try_into().map_err(|_| ())?;
+        let end = start.checked_add(size).ok_or(())?;
+        mem.with_direct_access(|memory| memory.get(start..end).map(f))
+            .ok_or(())
+    }
+
+    fn write_memory(instance: &WasmiInstance, offset: u32, value: &[u8]) -> Result<(), ()> {
+        let mem = instance.memory.as_ref().ok_or(())?;
+
+        mem.set(offset, value).map_err(|_| ())
+    }
+
+    fn global_i64(instance: &WasmiInstance, name: &str) -> Option<i64> {
+        match instance.module.export_by_name(name) {
+            Some(wasmi::ExternVal::Global(global)) => global.get().try_into::<i64>(),
+            _ => None,
+        }
+    }
+
+    fn set_global_i64(instance: &WasmiInstance, name: &str, value: i64) -> Result<(), ()> {
+        match instance.module.export_by_name(name) {
+            Some(wasmi::ExternVal::Global(global)) => {
+                global.set(wasmi::RuntimeValue::I64(value)).map_err(|_| ())
+            }
+            _ => Err(()),
+        }
+    }
+
+    fn start_by_name(
+        instance: &WasmiInstance,
+        symbol_name: &str,
+        params: Vec<WasmValue>,
+    ) -> Result<WasmiExecution, StartErr> {
+        match instance.module.export_by_name(symbol_name) {
+            Some(wasmi::ExternVal::Func(f)) => invoke(&f, params),
+            None => Err(StartErr::FunctionNotFound),
+            _ => Err(StartErr::NotAFunction),
+        }
+    }
+
+    fn start_by_id(
+        instance: &WasmiInstance,
+        function_id: u32,
+        params: Vec<WasmValue>,
+    ) -> Result<WasmiExecution, StartErr> {
+        // Find the function within the process.
+        let function = instance
+            .indirect_table
+            .as_ref()
+            .and_then(|t| t.get(function_id).ok())
+            .and_then(|f| f)
+            .ok_or(StartErr::FunctionNotFound)?;
+
+        invoke(&function, params)
+    }
+
+    fn run(
+        execution: &mut WasmiExecution,
+        value: Option<WasmValue>,
+    ) -> Result<EngineRunOutcome, RunErr> {
+        struct DummyExternals;
+        impl wasmi::Externals for DummyExternals {
+            fn invoke_index(
+                &mut self,
+                index: usize,
+                args: wasmi::RuntimeArgs,
+            ) -> Result<Option<wasmi::RuntimeValue>, wasmi::Trap> {
+                Err(wasmi::TrapKind::Host(Box::new(Interrupt {
+                    index,
+                    args: args.as_ref().to_vec(),
+                }))
+                .into())
+            }
+        }
+
+        #[derive(Debug)]
+        struct Interrupt {
+            index: usize,
+            args: Vec<wasmi::RuntimeValue>,
+        }
+        impl fmt::Display for Interrupt {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "Interrupt")
+            }
+        }
+        impl wasmi::HostError for Interrupt {}
+
+        let result = if execution.interrupted {
+            let expected_ty = execution
+                .execution
+                .resumable_value_type()
+                .map(ValueType::from);
+            let obtained_ty = value.as_ref().map(|v| v.ty());
+            if expected_ty != obtained_ty {
+                return Err(RunErr::BadValueTy {
+                    expected: expected_ty,
+                    obtained: obtained_ty,
+                });
+            }
+            execution
+                .execution
+                .resume_execution(value.map(From::from), &mut DummyExternals)
+        } else {
+            if value.is_some() {
+                return Err(RunErr::BadValueTy {
+                    expected: None,
+                    obtained: value.as_ref().map(|v| v.ty()),
+                });
+            }
+            execution.interrupted = true;
+            execution.execution.start_execution(&mut DummyExternals)
+        };
+
+        match result {
+            Ok(return_value) => Ok(EngineRunOutcome::Finished(return_value.map(From::from))),
+            Err(wasmi::ResumableError::AlreadyStarted) => unreachable!(),
+            Err(wasmi::ResumableError::NotResumable) => unreachable!(),
+            Err(wasmi::ResumableError::Trap(ref trap)) if trap.kind().is_host() => {
+                let interrupt: &Interrupt = match trap.kind() {
+                    wasmi::TrapKind::Host(err) => match err.downcast_ref() {
+                        Some(e) => e,
+                        None => unreachable!(),
+                    },
+                    _ => unreachable!(),
+                };
+                Ok(EngineRunOutcome::Interrupted {
+                    id: interrupt.index,
+                    params: interrupt.args.iter().cloned().map(From::from).collect(),
+                })
+            }
+            Err(wasmi::ResumableError::Trap(trap)) => {
+                let trap = match trap.kind() {
+                    wasmi::TrapKind::Unreachable => Trap::Unreachable,
+                    wasmi::TrapKind::MemoryAccessOutOfBounds => Trap::MemoryAccessOutOfBounds,
+                    wasmi::TrapKind::UnexpectedSignature => Trap::UnexpectedSignature,
+                    kind => Trap::Other(format!("{:?}", kind)),
+                };
+                Ok(EngineRunOutcome::Trapped(trap))
+            }
+        }
+    }
+}
+
+/// Builds a [`WasmiExecution`] that will call the given function.
+///
+/// Returns an error if `params` don't match the signature of the function.
+fn invoke(function: &wasmi::FuncRef, params: Vec<WasmValue>) -> Result<WasmiExecution, StartErr> {
+    let params = params
+        .into_iter()
+        .map(From::from)
+        .collect::<Vec<wasmi::RuntimeValue>>();
+    let execution = match wasmi::FuncInstance::invoke_resumable(function, params) {
+        Ok(e) => e,
+        Err(_) => return Err(StartErr::BadSignature),
+    };
+
+    Ok(WasmiExecution {
+        execution,
+        interrupted: false,
+    })
+}
@@ -0,0 +1,157 @@
+// Copyright (C) 2019  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Description of the format of the messages of an interface.
+//!
+//! An interface can optionally be associated with a [`MessageSchema`]. Messages emitted on an
+//! interface that don't conform to its schema are refused before reaching the handler.
+
+use alloc::vec::Vec;
+use core::{convert::TryFrom, fmt};
+
+pub use redshirt_interface_interface::ffi::SchemaField;
+
+/// Format of a message, as a list of fields.
+///
+/// A message conforms to a schema if it consists of the SCALE encoding of each field, one after
+/// the other, and nothing else.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageSchema {
+    fields: Vec<SchemaField>,
+}
+
+/// Error that can happen when validating a message against a schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidateErr {
+    /// The message ended in the middle of the field with the given index.
+    Truncated { field: usize },
+    /// The length prefix of the field with the given index is invalid.
+    InvalidLength { field: usize },
+    /// All the fields have been read, but the message contains more data.
+    TrailingData,
+}
+
+impl MessageSchema {
+    /// Builds a schema from its list of fields.
+    pub fn new(fields: impl IntoIterator<Item = SchemaField>) -> Self {
+        MessageSchema {
+            fields: fields.into_iter().collect(),
+        }
+    }
+
+    /// Returns the list of fields of the schema.
+    pub fn fields(&self) -> &[SchemaField] {
+        &self.fields
+    }
+
+    /// Checks whether the given message conforms to the schema.
+    pub fn validate(&self, mut message: &[u8]) -> Result<(), ValidateErr> {
+        for (field_index, field) in self.fields.iter().enumerate() {
+            let len = match field {
+                SchemaField::U8 => 1,
+                SchemaField::U16 => 2,
+                SchemaField::U32 => 4,
+                SchemaField::U64 => 8,
+                SchemaField::Bytes(len) => usize::try_from(*len).unwrap(),
+                SchemaField::VarBytes => {
+                    let (prefix_len, len) = decode_compact_len(message, field_index)?;
+                    message = &message[prefix_len..];
+                    len
+                }
+            };
+
+            if message.len() < len {
+                return Err(ValidateErr::Truncated { field: field_index });
+            }
+            message = &message[len..];
+        }
+
+        if message.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidateErr::TrailingData)
+        }
+    }
+}
+
+/// Decodes a SCALE compact-encoded length at the start of `message`. Returns the number of bytes
+/// of the prefix and the decoded length.
+fn decode_compact_len(message: &[u8], field: usize) -> Result<(usize, usize), ValidateErr> {
+    let first = *message.get(0).ok_or(ValidateErr::Truncated { field })?;
+    let prefix_len = match first & 0b11 {
+        0b00 => 1,
+        0b01 => 2,
+        0b10 => 4,
+        // Lengths that don't fit in 30 bits are never valid in practice.
+        _ => return Err(ValidateErr::InvalidLength { field }),
+    };
+
+    if message.len() < prefix_len {
+        return Err(ValidateErr::Truncated { field });
+    }
+
+    let mut buf = [0; 4];
+    buf[..prefix_len].copy_from_slice(&message[..prefix_len]);
+    let len = u32::from_le_bytes(buf) >> 2;
+    let len = usize::try_from(len).map_err(|_| ValidateErr::InvalidLength { field })?;
+    Ok((prefix_len, len))
+}
+
+impl fmt::Display for ValidateErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidateErr::Truncated { field } => {
+                write!(f, "Message ended in the middle of field {}", field)
+            }
+            ValidateErr::InvalidLength { field } => {
+                write!(f, "Invalid length prefix for field {}", field)
+            }
+            ValidateErr::TrailingData => write!(f, "Message contains trailing data"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MessageSchema, SchemaField, ValidateErr};
+    use alloc::vec::Vec;
+    use redshirt_syscalls_interface::Encode as _;
+
+    #[test]
+    fn valid_message() {
+        let schema = MessageSchema::new([SchemaField::U32, SchemaField::VarBytes].iter().cloned());
+        let message = (5u32, b"hello".to_vec()).encode();
+        assert_eq!(schema.validate(&message.0), Ok(()));
+    }
+
+    #[test]
+    fn invalid_messages() {
+        let schema = MessageSchema::new([SchemaField::U32, SchemaField::Bytes(2)].iter().cloned());
+        assert_eq!(
+            schema.validate(&[0, 0, 0, 0, 1]),
+            Err(ValidateErr::Truncated { field: 1 })
+        );
+        assert_eq!(
+            schema.validate(&[0, 0, 0, 0, 1, 2, 3]),
+            Err(ValidateErr::TrailingData)
+        );
+
+        let schema = MessageSchema::new(core::iter::once(SchemaField::VarBytes));
+        assert_eq!(
+            schema.validate(&(Vec::<u8>::new(), 8u8).encode().0),
+            Err(ValidateErr::TrailingData)
+        );
+    }
+}
@@ -28,6 +28,7 @@ pub struct Signature {
 ///
 /// ```
 /// let _sig: redshirt_core::signature::Signature = redshirt_core::sig!((I32, I64) -> I32);
+/// let _float_sig: redshirt_core::signature::Signature = redshirt_core::sig!((F32) -> F64);
 /// ```
 #[macro_export]
 macro_rules! sig {
@@ -52,6 +53,15 @@ pub enum ValueType {
     F64,
 }
 
+/// Value passed to or returned by a WASM function.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WasmValue {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+}
+
 impl Signature {
     pub fn new(
         params: impl Iterator<Item = ValueType>,
@@ -63,8 +73,52 @@ impl Signature {
         }
     }
 
-    pub(crate) fn matches_wasmi(&self, sig: &wasmi::Signature) -> bool {
-        wasmi::Signature::from(self) == *sig
+    /// Returns true if the given parameters have the number and types expected by this
+    /// signature.
+    pub fn matches_params(&self, params: &[WasmValue]) -> bool {
+        self.params.len() == params.len()
+            && self
+                .params
+                .iter()
+                .zip(params.iter())
+                .all(|(ty, param)| *ty == param.ty())
+    }
+}
+
+impl WasmValue {
+    /// Returns the type of the value.
+    pub fn ty(&self) -> ValueType {
+        match self {
+            WasmValue::I32(_) => ValueType::I32,
+            WasmValue::I64(_) => ValueType::I64,
+            WasmValue::F32(_) => ValueType::F32,
+            WasmValue::F64(_) => ValueType::F64,
+        }
+    }
+
+    /// Returns the value if it is an `i32`.
+    pub fn into_i32(self) -> Option<i32> {
+        match self {
+            WasmValue::I32(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is an `i64`.
+    pub fn into_i64(self) -> Option<i64> {
+        match self {
+            WasmValue::I64(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> From<&'a wasmi::Signature> for Signature {
+    fn from(sig: &'a wasmi::Signature) -> Signature {
+        Signature::new(
+            sig.params().iter().cloned().map(ValueType::from),
+            sig.return_type().map(ValueType::from),
+        )
     }
 }
 
@@ -87,6 +141,28 @@ impl From<Signature> for wasmi::Signature {
     }
 }
 
+impl From<wasmi::ValueType> for ValueType {
+    fn from(ty: wasmi::ValueType) -> ValueType {
+        match ty {
+            wasmi::ValueType::I32 => ValueType::I32,
+            wasmi::ValueType::I64 => ValueType::I64,
+            wasmi::ValueType::F32 => ValueType::F32,
+            wasmi::ValueType::F64 => ValueType::F64,
+        }
+    }
+}
+
+impl From<parity_wasm::elements::ValueType> for ValueType {
+    fn from(ty: parity_wasm::elements::ValueType) -> ValueType {
+        match ty {
+            parity_wasm::elements::ValueType::I32 => ValueType::I32,
+            parity_wasm::elements::ValueType::I64 => ValueType::I64,
+            parity_wasm::elements::ValueType::F32 => ValueType::F32,
+            parity_wasm::elements::ValueType::F64 => ValueType::F64,
+        }
+    }
+}
+
 impl From<ValueType> for wasmi::ValueType {
     fn from(ty: ValueType) -> wasmi::ValueType {
         match ty {
@@ -97,3 +173,25 @@ impl From<ValueType> for wasmi::ValueType {
         }
     }
 }
+
+impl From<WasmValue> for wasmi::RuntimeValue {
+    fn from(value: WasmValue) -> wasmi::RuntimeValue {
+        match value {
+            WasmValue::I32(v) => wasmi::RuntimeValue::I32(v),
+            WasmValue::I64(v) => wasmi::RuntimeValue::I64(v),
+            WasmValue::F32(v) => wasmi::RuntimeValue::F32(v.into()),
+            WasmValue::F64(v) => wasmi::RuntimeValue::F64(v.into()),
+        }
+    }
+}
+
+impl From<wasmi::RuntimeValue> for WasmValue {
+    fn from(value: wasmi::RuntimeValue) -> WasmValue {
+        match value {
+            wasmi::RuntimeValue::I32(v) => WasmValue::I32(v),
+            wasmi::RuntimeValue::I64(v) => WasmValue::I64(v),
+            wasmi::RuntimeValue::F32(v) => WasmValue::F32(v.into()),
+            wasmi::RuntimeValue::F64(v) => WasmValue::F64(v.into()),
+        }
+    }
+}
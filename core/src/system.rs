@@ -13,23 +13,35 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use crate::error::Error;
 use crate::module::Module;
 use crate::native::{self, NativeProgramMessageIdWrite as _};
-use crate::scheduler::{Core, CoreBuilder, CoreRunOutcome};
-use alloc::{vec, vec::Vec};
-use core::task::Poll;
-use futures::prelude::*;
+use crate::scheduler::{
+    Core, CoreBuilder, CoreRunOutcome, Engine, MemoryFootprint, NewErr, ThreadState, Trap,
+    WasmiEngine,
+};
+use crate::schema::MessageSchema;
+use crate::signature::WasmValue;
+use alloc::{sync::Arc, vec, vec::Vec};
+use core::{fmt, mem, task::Poll};
+use crossbeam_queue::SegQueue;
+use futures::{prelude::*, task::AtomicWaker};
 use hashbrown::{hash_map::Entry, HashMap, HashSet};
-use redshirt_syscalls_interface::{Decode, Encode, EncodedMessage, MessageId, Pid};
+use redshirt_syscalls_interface::{
+    AnswerError, Decode, Encode, EncodedMessage, InterfaceHash, MessageId, Pid, ThreadId,
+};
 use smallvec::SmallVec;
 
 /// Main struct that handles a system, including the scheduler, program loader,
 /// inter-process communication, and so on.
 ///
 /// Natively handles the "interface" and "threads" interfaces.  TODO: indicate hashes
-pub struct System {
+///
+/// The programs are executed by the [`Engine`] passed as the generic parameter `E`. See
+/// [`SystemBuilder::build_with_engine`].
+pub struct System<E: Engine = WasmiEngine> {
     /// Inner system with inter-process communications.
-    core: Core,
+    core: Core<E>,
 
     /// List of active futexes. The keys of this hashmap are process IDs and memory addresses, and
     /// the values of this hashmap are a list of "wait" messages to answer once the corresponding
@@ -61,6 +73,31 @@ pub struct System {
     /// All these messages expect a `redshirt_loader_interface::ffi::LoadResponse` as answer.
     // TODO: call shink_to_fit from time to time
     loading_programs: HashSet<MessageId>,
+
+    /// Queue of answers submitted through [`AnswerSender`]s, and that must be delivered.
+    answers: Arc<AnswersQueue>,
+
+    /// True if [`System::shutdown`] has been called.
+    shut_down: bool,
+}
+
+/// Handle that can be used to answer messages from outside of the [`System`], for example from
+/// a background task.
+///
+/// Answers submitted through this handle are delivered the next time [`System::run`] is polled.
+/// Polling the `Future` returned by [`System::run`] is woken up when an answer is submitted.
+#[derive(Clone)]
+pub struct AnswerSender {
+    queue: Arc<AnswersQueue>,
+}
+
+/// Shared between the [`System`] and the [`AnswerSender`]s.
+#[derive(Default)]
+struct AnswersQueue {
+    /// Answers waiting to be delivered.
+    answers: SegQueue<(MessageId, Result<EncodedMessage, AnswerError>)>,
+    /// Waker to wake up when an answer is pushed to the queue.
+    waker: AtomicWaker,
 }
 
 /// Prototype for a [`System`].
@@ -91,20 +128,169 @@ pub enum SystemRunOutcome {
     ProgramFinished {
         /// Identifier of the process that has stopped.
         pid: Pid,
-        /// Either `Ok(())` if the main thread has ended, or the error that happened in the
-        /// process.
-        // TODO: change error type
-        outcome: Result<(), wasmi::Error>,
+        /// Either `Ok(())` if the main thread has ended, or the reason why the process has
+        /// crashed.
+        outcome: Result<(), CrashReason>,
+    },
+
+    /// A thread has run out of fuel and is now paused. It can be resumed by calling
+    /// [`System::refuel`].
+    ///
+    /// Only happens if [`SystemBuilder::with_fuel_limit`] has been called.
+    ThreadOutOfFuel {
+        /// Identifier of the process the thread belongs to.
+        pid: Pid,
+        /// Identifier of the thread that has run out of fuel.
+        thread_id: ThreadId,
     },
+
+    /// [`System::shutdown`] has been called. The [`System`] no longer does anything.
+    ShutDown,
+}
+
+/// Reason why a program has ended with an error.
+#[derive(Debug)]
+pub enum CrashReason {
+    /// The program has been killed with [`System::abort_process`].
+    Aborted,
+    /// The program has tried to access memory outside of the bounds of its linear memory.
+    MemoryAccessOutOfBounds,
+    /// The program has trapped for another reason, for example by executing an `unreachable`
+    /// instruction or by dividing by zero.
+    Trap(Trap),
 }
 
-impl System {
+impl<E: Engine> System<E> {
     /// Start executing a program.
-    pub fn execute(&mut self, program: &Module) -> Pid {
-        self.core
-            .execute(program)
-            .expect("failed to start startup program")
-            .pid() // TODO: don't unwrap
+    ///
+    /// Returns an error if the program couldn't be started, for example if the memory limit
+    /// passed to [`SystemBuilder::with_memory_limit`] has been reached.
+    pub fn execute(&mut self, program: &Module) -> Result<Pid, NewErr> {
+        Ok(self.core.execute(program)?.pid())
+    }
+
+    /// Kills the given program immediately.
+    ///
+    /// The next call to [`run`](System::run) reports a [`SystemRunOutcome::ProgramFinished`]
+    /// whose outcome is [`CrashReason::Aborted`], and the native programs are notified of the
+    /// destruction.
+    ///
+    /// Returns an error if the program doesn't exist.
+    pub fn abort_process(&mut self, pid: Pid) -> Result<(), ()> {
+        self.core.abort_process(pid)
+    }
+
+    /// Stops the [`System`].
+    ///
+    /// All the programs are killed immediately, including the ones whose threads are waiting for
+    /// the outcome of an extrinsic, and the native programs are notified of their destruction.
+    /// The messages that were waiting to be answered by these programs are answered with
+    /// [`AnswerError::HandlerCrashed`].
+    ///
+    /// Afterwards, [`run`](System::run) always returns [`SystemRunOutcome::ShutDown`].
+    pub fn shutdown(&mut self) {
+        self.shut_down = true;
+        self.main_programs.clear();
+        // Responses from the loader are no longer turned into programs.
+        self.loading_programs.clear();
+
+        let pids = self.core.pids().collect::<Vec<_>>();
+        for pid in pids {
+            let _ = self.core.abort_process(pid);
+        }
+
+        // Process the termination of the programs until there is nothing left to do. This
+        // notifies the native programs and answers the pending messages.
+        while self.run_once().is_some() {}
+        debug_assert_eq!(self.core.pids().len(), 0);
+        debug_assert!(self.futex_waits.is_empty());
+    }
+
+    /// Transfers the registration of an interface from its current handler to the given process,
+    /// without any gap during which the interface is unregistered.
+    ///
+    /// See [`Core::reassign_interface`](crate::scheduler::Core::reassign_interface).
+    pub fn reassign_interface(
+        &mut self,
+        interface: InterfaceHash,
+        new_handler: Pid,
+    ) -> Result<(), ()> {
+        self.core.reassign_interface(interface, new_handler)
+    }
+
+    /// Returns the depth of the deepest chain of messages waiting for an answer.
+    ///
+    /// See [`Core::message_wait_depth`](crate::scheduler::Core::message_wait_depth).
+    pub fn max_message_wait_depth(&self) -> u32 {
+        self.core.max_message_wait_depth()
+    }
+
+    /// Returns a handle that can be used to answer messages from outside of the [`System`].
+    pub fn answer_sender(&self) -> AnswerSender {
+        AnswerSender {
+            queue: self.answers.clone(),
+        }
+    }
+
+    /// Gives more fuel to the given thread, resuming it if it had run out of fuel.
+    ///
+    /// See [`Core::refuel`](crate::scheduler::Core::refuel).
+    pub fn refuel(&mut self, thread_id: ThreadId, amount: u64) -> Result<(), ()> {
+        self.core.refuel(thread_id, amount)
+    }
+
+    /// Returns the list of threads of the given process, alongside with their state.
+    ///
+    /// Returns an empty list if the process doesn't exist.
+    pub fn threads(&mut self, pid: Pid) -> Vec<(ThreadId, ThreadState)> {
+        self.core.threads(pid)
+    }
+
+    /// Returns the size, in bytes, of the linear memory of the given process, or `None` if the
+    /// process doesn't exist.
+    ///
+    /// This is always a multiple of the WASM page size of 64kiB.
+    pub fn process_memory_size(&self, pid: Pid) -> Option<usize> {
+        self.core.process_memory_size(pid)
+    }
+
+    /// Calls `f` with a view of the given memory range of the given process.
+    ///
+    /// Contrary to [`read_memory`](System::read_memory), the memory isn't copied. The
+    /// [`System`] can't be accessed while `f` is running.
+    ///
+    /// Returns an error if the process doesn't exist, or if the range is invalid or out of range.
+    pub fn with_memory_ref<R>(
+        &self,
+        pid: Pid,
+        offset: u32,
+        size: u32,
+        f: impl FnOnce(&[u8]) -> R,
+    ) -> Result<R, ()> {
+        self.core.with_process_memory(pid, offset, size, f)
+    }
+
+    /// Copies the given memory range of the given process into a `Vec<u8>`.
+    ///
+    /// Returns an error if the process doesn't exist, or if the range is invalid or out of range.
+    pub fn read_memory(&self, pid: Pid, offset: u32, size: u32) -> Result<Vec<u8>, ()> {
+        self.with_memory_ref(pid, offset, size, |memory| memory.to_vec())
+    }
+
+    /// Returns an estimation of the memory currently used by the [`System`].
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut footprint = self.core.memory_footprint();
+        footprint.internal_structures += self.futex_waits.capacity()
+            * mem::size_of::<((Pid, u32), SmallVec<[MessageId; 4]>)>()
+            + self.loading_programs.capacity() * mem::size_of::<MessageId>()
+            + self.main_programs.capacity() * mem::size_of::<[u8; 32]>();
+        footprint
+    }
+
+    /// Returns the earliest moment when a timeout passed to `next_message` or `emit_message`
+    /// elapses, if any. See [`run`](System::run).
+    pub fn next_deadline(&self) -> Option<crate::time::Deadline> {
+        self.core.next_deadline()
     }
 
     /// Runs the [`System`] once and returns the outcome.
@@ -113,9 +299,26 @@ impl System {
     /// >           produce events in case there's nothing to do. In other words, this function
     /// >           can be seen as a generator that returns only when something needs to be
     /// >           notified.
-    pub fn run<'b>(&'b mut self) -> impl Future<Output = SystemRunOutcome> + 'b {
+    ///
+    /// The returned future doesn't wake itself up when a timeout passed to `next_message` or
+    /// `emit_message` elapses. If a [`TimeSource`](crate::time::TimeSource) has been passed to
+    /// [`SystemBuilder::with_time_source`], the embedder should arm a timer that fires at
+    /// [`next_deadline`](System::next_deadline) and poll the future again (or drop it and call
+    /// `run` again) when it fires.
+    ///
+    /// Returns an error if something wrong happened but the [`System`] can continue running.
+    pub fn run<'b>(&'b mut self) -> impl Future<Output = Result<SystemRunOutcome, Error>> + 'b {
         // TODO: We use a `poll_fn` because async/await don't work in no_std yet.
         future::poll_fn(move |cx| loop {
+            if self.shut_down {
+                return Poll::Ready(Ok(SystemRunOutcome::ShutDown));
+            }
+
+            self.answers.waker.register(cx.waker());
+            while let Ok((message_id, answer)) = self.answers.answers.pop() {
+                self.core.answer_message(message_id, answer);
+            }
+
             if let Some(out) = self.run_once() {
                 return Poll::Ready(out);
             }
@@ -124,7 +327,13 @@ impl System {
             futures::pin_mut!(next_event);
             let event = match next_event.poll(cx) {
                 Poll::Ready(ev) => ev,
-                Poll::Pending => return Poll::Pending,
+                Poll::Pending => {
+                    // An answer might have been submitted in the meanwhile.
+                    if !self.answers.answers.is_empty() {
+                        continue;
+                    }
+                    return Poll::Pending;
+                }
             };
 
             match event {
@@ -146,26 +355,47 @@ impl System {
                             .emit_interface_message_no_answer(emitter_pid, interface, message);
                     }
                 }
-                native::NativeProgramsCollectionEvent::CancelMessage { .. } => unimplemented!(),
+                native::NativeProgramsCollectionEvent::CancelMessage {
+                    emitter_pid,
+                    message_id,
+                } => {
+                    self.core.cancel_message(emitter_pid, message_id);
+                }
                 native::NativeProgramsCollectionEvent::Answer { message_id, answer } => {
                     self.core.answer_message(message_id, answer);
                 }
+                native::NativeProgramsCollectionEvent::InterfaceRegistrationRejected { .. } => {
+                    // The registration message has been passed to the `Core`, which refuses
+                    // it as well and answers the program if it has asked for an answer.
+                }
             }
         })
     }
 
-    fn run_once(&mut self) -> Option<SystemRunOutcome> {
+    fn run_once(&mut self) -> Option<Result<SystemRunOutcome, Error>> {
         // TODO: remove loop?
         loop {
             match self.core.run() {
                 CoreRunOutcome::ProgramFinished { pid, outcome, .. } => {
                     self.native_programs.process_destroyed(pid);
-                    return Some(SystemRunOutcome::ProgramFinished {
+                    // All the threads of the process are gone, including the ones that were
+                    // waiting on a futex.
+                    self.futex_waits.retain(|(p, _), _| *p != pid);
+                    return Some(Ok(SystemRunOutcome::ProgramFinished {
                         pid,
                         outcome: outcome.map(|_| ()).map_err(|err| err.into()),
-                    });
+                    }));
+                }
+                // The `Core` delivers the message and resumes the thread once a program
+                // registers the interface.
+                CoreRunOutcome::ThreadWaitUnavailableInterface { .. } => {}
+
+                CoreRunOutcome::ThreadOutOfFuel { mut thread } => {
+                    return Some(Ok(SystemRunOutcome::ThreadOutOfFuel {
+                        pid: thread.pid(),
+                        thread_id: thread.tid(),
+                    }));
                 }
-                CoreRunOutcome::ThreadWaitUnavailableInterface { .. } => {} // TODO: lazy-loading
 
                 CoreRunOutcome::MessageResponse {
                     message_id,
@@ -173,15 +403,27 @@ impl System {
                     ..
                 } => {
                     if self.loading_programs.remove(&message_id) {
-                        let redshirt_loader_interface::ffi::LoadResponse { result } =
-                            Decode::decode(response.unwrap()).unwrap();
-                        let module = Module::from_bytes(&result.unwrap()).unwrap();
-                        match self.core.execute(&module) {
-                            Ok(_) => {}
-                            Err(_) => panic!(),
+                        let result = response
+                            .ok()
+                            .and_then(|r| {
+                                redshirt_loader_interface::ffi::LoadResponse::decode(r).ok()
+                            })
+                            .and_then(|r| r.result.ok());
+                        let module = match result {
+                            Some(bytes) => Module::from_bytes(&bytes),
+                            None => return Some(Err(Error::ProgramLoadFailed)),
+                        };
+                        match module {
+                            Ok(module) => {
+                                if let Err(err) = self.core.execute(&module) {
+                                    return Some(Err(Error::ProgramStartFailed(err)));
+                                }
+                            }
+                            Err(err) => return Some(Err(Error::InvalidProgram(err))),
                         }
                     } else {
-                        self.native_programs.message_response(message_id, response);
+                        // Responses that nobody waits for anymore are discarded.
+                        let _ = self.native_programs.message_response(message_id, response);
                     }
                 }
 
@@ -192,21 +434,32 @@ impl System {
                     message,
                 } if interface == redshirt_threads_interface::ffi::INTERFACE => {
                     let msg: redshirt_threads_interface::ffi::ThreadsMessage =
-                        Decode::decode(message).unwrap();
+                        match Decode::decode(message) {
+                            Ok(m) => m,
+                            Err(_) => {
+                                return Some(
+                                    Err(self.malformed_message(pid, message_id, interface)),
+                                )
+                            }
+                        };
                     match msg {
+                        // `New` and `FutexWake` never expect an answer.
+                        redshirt_threads_interface::ffi::ThreadsMessage::New(_)
+                        | redshirt_threads_interface::ffi::ThreadsMessage::FutexWake(_)
+                            if message_id.is_some() =>
+                        {
+                            return Some(Err(self.malformed_message(pid, message_id, interface)));
+                        }
                         redshirt_threads_interface::ffi::ThreadsMessage::New(new_thread) => {
-                            assert!(message_id.is_none());
-                            self.core
-                                .process_by_id(pid)
-                                .unwrap()
-                                .start_thread(
-                                    new_thread.fn_ptr,
-                                    vec![wasmi::RuntimeValue::I32(new_thread.user_data as i32)],
-                                )
-                                .unwrap();
+                            let result = self.core.process_by_id(pid).unwrap().start_thread(
+                                new_thread.fn_ptr,
+                                vec![WasmValue::I32(new_thread.user_data as i32)],
+                            );
+                            if result.is_err() {
+                                return Some(Err(Error::ThreadStartFailed { pid }));
+                            }
                         }
                         redshirt_threads_interface::ffi::ThreadsMessage::FutexWake(mut wake) => {
-                            assert!(message_id.is_none());
                             if let Some(list) = self.futex_waits.get_mut(&(pid, wake.addr)) {
                                 while wake.nwake > 0 && !list.is_empty() {
                                     wake.nwake -= 1;
@@ -249,7 +502,9 @@ impl System {
                         message,
                     ) {
                         Ok(m) => m,
-                        Err(_) => panic!(), // TODO:
+                        Err(_) => {
+                            return Some(Err(self.malformed_message(pid, message_id, interface)))
+                        }
                     };
                     match msg {
                         redshirt_interface_interface::ffi::InterfaceMessage::Register(
@@ -279,6 +534,72 @@ impl System {
                                 }
                             }
                         }
+                        redshirt_interface_interface::ffi::InterfaceMessage::Unregister(
+                            interface_hash,
+                        ) => {
+                            let result = self
+                                .core
+                                .unset_interface_handler(&interface_hash, pid)
+                                .map_err(|()| redshirt_interface_interface::ffi::InterfaceUnregisterError::NotHandler);
+                            let response =
+                                redshirt_interface_interface::ffi::InterfaceUnregisterResponse {
+                                    result,
+                                };
+                            if let Some(message_id) = message_id {
+                                self.core.answer_message(message_id, Ok(response.encode()));
+                            }
+                        }
+                        redshirt_interface_interface::ffi::InterfaceMessage::SetSchema(
+                            interface_hash,
+                            fields,
+                        ) => {
+                            let result = if self.core.interface_handler(&interface_hash)
+                                == Some(pid)
+                            {
+                                self.core.set_interface_schema(
+                                    interface_hash,
+                                    Some(MessageSchema::new(fields)),
+                                );
+                                Ok(())
+                            } else {
+                                Err(redshirt_interface_interface::ffi::InterfaceSetSchemaError::NotHandler)
+                            };
+                            let response =
+                                redshirt_interface_interface::ffi::InterfaceSetSchemaResponse {
+                                    result,
+                                };
+                            if let Some(message_id) = message_id {
+                                self.core.answer_message(message_id, Ok(response.encode()));
+                            }
+                        }
+                        redshirt_interface_interface::ffi::InterfaceMessage::SetCoalescing(
+                            interface_hash,
+                            enabled,
+                        ) => {
+                            let result = if self.core.interface_handler(&interface_hash)
+                                == Some(pid)
+                            {
+                                self.core.set_interface_coalescing(interface_hash, enabled);
+                                Ok(())
+                            } else {
+                                Err(redshirt_interface_interface::ffi::InterfaceSetCoalescingError::NotHandler)
+                            };
+                            let response =
+                                redshirt_interface_interface::ffi::InterfaceSetCoalescingResponse {
+                                    result,
+                                };
+                            if let Some(message_id) = message_id {
+                                self.core.answer_message(message_id, Ok(response.encode()));
+                            }
+                        }
+                        redshirt_interface_interface::ffi::InterfaceMessage::Await(
+                            interface_hash,
+                        ) => {
+                            if let Some(message_id) = message_id {
+                                self.core
+                                    .answer_on_interface_registration(interface_hash, message_id);
+                            }
+                        }
                     }
                 }
 
@@ -288,14 +609,35 @@ impl System {
                     interface,
                     message,
                 } => {
-                    self.native_programs
+                    let result = self
+                        .native_programs
                         .interface_message(interface, message_id, pid, message);
+                    // No native program has registered this interface anymore.
+                    if let (Err(_), Some(message_id)) = (result, message_id) {
+                        self.core
+                            .answer_message(message_id, Err(AnswerError::HandlerCrashed));
+                    }
                 }
 
                 CoreRunOutcome::Idle => return None,
             }
         }
     }
+
+    /// Answers a message that couldn't be decoded on one of the interfaces that the [`System`]
+    /// handles, and returns the corresponding error.
+    fn malformed_message(
+        &mut self,
+        pid: Pid,
+        message_id: Option<MessageId>,
+        interface: InterfaceHash,
+    ) -> Error {
+        if let Some(message_id) = message_id {
+            self.core
+                .answer_message(message_id, Err(AnswerError::MalformedRequest));
+        }
+        Error::MalformedMessage { pid, interface }
+    }
 }
 
 impl SystemBuilder {
@@ -319,7 +661,7 @@ impl SystemBuilder {
     /// Registers native code that can communicate with the WASM programs.
     pub fn with_native_program<T>(mut self, program: T) -> Self
     where
-        T: Send + 'static,
+        T: Send + Sync + 'static,
         for<'r> &'r T: native::NativeProgramRef<'r>,
     {
         self.native_programs.push(self.core.reserve_pid(), program);
@@ -350,9 +692,98 @@ impl SystemBuilder {
         self
     }
 
+    /// Sets a limit, in bytes, to the total size of the memories of the programs, as reported in
+    /// the `processes_memory` field of [`System::memory_footprint`].
+    ///
+    /// Once the limit is exceeded, starting new programs fails and messages emitted by programs
+    /// are refused.
+    ///
+    /// See [`CoreBuilder::with_memory_limit`](crate::scheduler::CoreBuilder::with_memory_limit).
+    ///
+    /// By default, there is no limit.
+    pub fn with_memory_limit(mut self, limit: usize) -> Self {
+        self.core = self.core.with_memory_limit(limit);
+        self
+    }
+
+    /// Sets the maximum number of pages of 64kiB that the memory of each program can have.
+    ///
+    /// See [`CoreBuilder::with_process_memory_limit`](crate::scheduler::CoreBuilder::with_process_memory_limit).
+    ///
+    /// By default, there is no limit.
+    pub fn with_process_memory_limit(mut self, max_pages: u32) -> Self {
+        self.core = self.core.with_process_memory_limit(max_pages);
+        self
+    }
+
+    /// If called, the execution of the programs is reproducible given the same inputs. This
+    /// includes timeouts if the source passed to
+    /// [`with_time_source`](SystemBuilder::with_time_source) is controlled by the caller.
+    ///
+    /// See [`CoreBuilder::with_deterministic_scheduling`](crate::scheduler::CoreBuilder::with_deterministic_scheduling).
+    pub fn with_deterministic_scheduling(mut self) -> Self {
+        self.core = self.core.with_deterministic_scheduling();
+        self
+    }
+
+    /// Sets a maximum depth to the chains of messages waiting for an answer. Emitting a message
+    /// that would exceed this depth fails.
+    ///
+    /// See [`CoreBuilder::with_max_wait_depth`](crate::scheduler::CoreBuilder::with_max_wait_depth).
+    ///
+    /// By default, there is no limit.
+    pub fn with_max_wait_depth(mut self, max_depth: u32) -> Self {
+        self.core = self.core.with_max_wait_depth(max_depth);
+        self
+    }
+
+    /// Sets the [`TimeSource`](crate::time::TimeSource) used to determine when the timeouts
+    /// passed to `next_message` and `emit_message` elapse.
+    ///
+    /// See [`CoreBuilder::with_time_source`](crate::scheduler::CoreBuilder::with_time_source).
+    ///
+    /// By default, there is no time source and timeouts are ignored.
+    pub fn with_time_source(mut self, source: impl crate::time::TimeSource + 'static) -> Self {
+        self.core = self.core.with_time_source(source);
+        self
+    }
+
+    /// Enables fuel metering. Each thread is given `limit` units of fuel, roughly corresponding
+    /// to a number of WASM instructions, and is paused once it has consumed all of it.
+    ///
+    /// See [`CoreBuilder::with_fuel_limit`](crate::scheduler::CoreBuilder::with_fuel_limit).
+    ///
+    /// By default, fuel metering is disabled.
+    pub fn with_fuel_limit(mut self, limit: u64) -> Self {
+        self.core = self.core.with_fuel_limit(limit);
+        self
+    }
+
+    /// Sets a function called when a program has grown its linear memory, with the [`Pid`] of
+    /// the program and its number of pages of 64kiB before and after growing.
+    ///
+    /// See [`CoreBuilder::with_memory_grow_hook`](crate::scheduler::CoreBuilder::with_memory_grow_hook).
+    pub fn with_memory_grow_hook(
+        mut self,
+        hook: impl Fn(Pid, u32, u32) + Send + Sync + 'static,
+    ) -> Self {
+        self.core = self.core.with_memory_grow_hook(hook);
+        self
+    }
+
     /// Builds the [`System`].
-    pub fn build(mut self) -> System {
-        let mut core = self.core.build();
+    ///
+    /// Returns [`Error::ProgramStartFailed`] if one of the programs passed to
+    /// [`with_startup_process`](SystemBuilder::with_startup_process) couldn't be started.
+    pub fn build(self) -> Result<System, Error> {
+        self.build_with_engine()
+    }
+
+    /// Builds a [`System`] whose programs are executed by the engine `E`.
+    ///
+    /// See [`build`](SystemBuilder::build).
+    pub fn build_with_engine<E: Engine>(mut self) -> Result<System<E>, Error> {
+        let mut core = self.core.build_with_engine();
 
         // We ask the core to redirect messages for the `interface` and `threads` interfaces
         // towards our "virtual" `Pid`s.
@@ -372,18 +803,50 @@ impl SystemBuilder {
         };
 
         for program in self.startup_processes {
-            core.execute(&program)
-                .expect("failed to start startup program"); // TODO:
+            core.execute(&program).map_err(Error::ProgramStartFailed)?;
         }
 
         self.main_programs.shrink_to_fit();
 
-        System {
+        Ok(System {
             core,
             native_programs: self.native_programs,
             futex_waits: Default::default(),
             loading_programs: Default::default(),
             main_programs: self.main_programs,
+            answers: Default::default(),
+            shut_down: false,
+        })
+    }
+}
+
+impl AnswerSender {
+    /// Submits the answer to the given message.
+    ///
+    /// The message must have been delivered to a native program, and must not have been answered
+    /// yet.
+    pub fn send(&self, message_id: MessageId, answer: Result<EncodedMessage, AnswerError>) {
+        self.queue.answers.push((message_id, answer));
+        self.queue.waker.wake();
+    }
+}
+
+impl From<Trap> for CrashReason {
+    fn from(trap: Trap) -> Self {
+        match trap {
+            Trap::MemoryAccessOutOfBounds => CrashReason::MemoryAccessOutOfBounds,
+            Trap::Aborted => CrashReason::Aborted,
+            trap => CrashReason::Trap(trap),
+        }
+    }
+}
+
+impl fmt::Display for CrashReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CrashReason::Aborted => write!(f, "Process aborted"),
+            CrashReason::MemoryAccessOutOfBounds => write!(f, "Memory access out of bounds"),
+            CrashReason::Trap(trap) => write!(f, "Trap: {}", trap),
         }
     }
 }
@@ -393,3 +856,888 @@ impl Default for SystemBuilder {
         SystemBuilder::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{CrashReason, SystemBuilder, SystemRunOutcome};
+    use crate::{
+        module::Module,
+        native::{test_program::TestProgram, NativeProgramEvent},
+        scheduler::{NewErr, ThreadState, Trap},
+        time::MockTimeSource,
+        EncodedMessage, Error,
+    };
+    use alloc::{format, string::String, vec, vec::Vec};
+    use futures::prelude::*;
+    use redshirt_syscalls_interface::Encode as _;
+
+    /// Returns a WAT data segment that writes the hash of the "threads" interface at offset 0.
+    fn threads_interface_data() -> String {
+        let hash = <[u8; 32]>::from(redshirt_threads_interface::ffi::INTERFACE)
+            .iter()
+            .map(|b| format!("\\{:02x}", b))
+            .collect::<String>();
+        format!(r#"(data (i32.const 0) "{}")"#, hash)
+    }
+
+    /// Returns a WAT instruction that waits for an interface message without any timeout, which
+    /// in practice means forever if nobody sends one. The list of message IDs to wait for, which
+    /// must contain `1`, is read at `list_offset`, and the message is written at offset 256.
+    fn wait_message_wat(list_offset: u32) -> String {
+        format!(
+            "(drop (call $next_message (i32.const {}) (i32.const 1) (i32.const 256) (i32.const 256) (i32.const 1) (i64.const 0)))",
+            list_offset
+        )
+    }
+
+    #[test]
+    fn execute_past_memory_limit() {
+        // Module with a single page (64kiB) of memory.
+        let module = Module::from_wat(
+            r#"(module
+            (memory (export "memory") 1)
+            (func $_start (result i32)
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+        )
+        .unwrap();
+
+        let mut system = SystemBuilder::new()
+            .with_memory_limit(100_000)
+            .build()
+            .unwrap();
+
+        assert!(system.execute(&module).is_ok());
+        assert_eq!(system.memory_footprint().processes_memory, 65536);
+
+        match system.execute(&module) {
+            Err(NewErr::MemoryLimitReached) => {}
+            _ => panic!(),
+        }
+        assert_eq!(system.memory_footprint().processes_memory, 65536);
+    }
+
+    #[test]
+    fn process_memory_size_after_grow() {
+        // Grows the memory by two pages, then waits for a message that never comes.
+        let module = Module::from_wat(format!(
+            r#"(module
+            (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "\01\00\00\00\00\00\00\00")
+            (func $_start (result i32)
+                (drop (memory.grow (i32.const 2)))
+                {wait}
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+            wait = wait_message_wat(0),
+        ))
+        .unwrap();
+
+        let mut system = SystemBuilder::new().build().unwrap();
+        let pid = system.execute(&module).unwrap();
+        assert_eq!(system.process_memory_size(pid), Some(65536));
+
+        assert!(system.run().now_or_never().is_none());
+        assert_eq!(system.process_memory_size(pid), Some(3 * 65536));
+    }
+
+    #[test]
+    fn memory_readable_without_copy() {
+        // Waits for a message that never comes.
+        let module = Module::from_wat(format!(
+            r#"(module
+            (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "\01\00\00\00\00\00\00\00")
+            (data (i32.const 16) "hello")
+            (func $_start (result i32)
+                {wait}
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+            wait = wait_message_wat(0),
+        ))
+        .unwrap();
+
+        let mut system = SystemBuilder::new().build().unwrap();
+        let pid = system.execute(&module).unwrap();
+        assert!(system.run().now_or_never().is_none());
+
+        assert_eq!(
+            system.with_memory_ref(pid, 16, 5, |mem| mem == b"hello"),
+            Ok(true)
+        );
+        assert_eq!(system.read_memory(pid, 16, 5), Ok(b"hello".to_vec()));
+
+        // Reading the whole memory twice gives a view of the same buffer, rather than a copy.
+        let first = system.with_memory_ref(pid, 0, 65536, |mem| mem.as_ptr());
+        let second = system.with_memory_ref(pid, 0, 65536, |mem| mem.as_ptr());
+        assert!(first.is_ok());
+        assert_eq!(first, second);
+
+        assert!(system.with_memory_ref(pid, 65530, 7, |_| ()).is_err());
+        assert!(system
+            .with_memory_ref(pid, u32::max_value(), 2, |_| ())
+            .is_err());
+        assert!(system
+            .with_memory_ref(From::from(0xdeadbeef), 0, 1, |_| ())
+            .is_err());
+    }
+
+    #[test]
+    fn memory_grow_past_process_limit() {
+        // Grows the memory from one to two pages, then tries to grow it to three pages.
+        let module = Module::from_wat(
+            r#"(module
+            (memory (export "memory") 1)
+            (func $_start (result i32)
+                (if (i32.ne (memory.grow (i32.const 1)) (i32.const 1))
+                    (then unreachable))
+                (if (i32.ne (memory.grow (i32.const 1)) (i32.const -1))
+                    (then unreachable))
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+        )
+        .unwrap();
+
+        let mut system = SystemBuilder::new()
+            .with_process_memory_limit(2)
+            .build()
+            .unwrap();
+        let pid = system.execute(&module).unwrap();
+
+        match system.run().now_or_never() {
+            Some(Ok(SystemRunOutcome::ProgramFinished {
+                pid: finished,
+                outcome: Ok(()),
+            })) => assert_eq!(finished, pid),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn execute_missing_import() {
+        let module = Module::from_wat(
+            r#"(module
+            (import "wasi_unstable" "nonexistent" (func $nonexistent (param i32) (result i32)))
+            (func $_start (result i32)
+                i32.const 0
+                call $nonexistent)
+            (export "_start" (func $_start)))
+        "#,
+        )
+        .unwrap();
+
+        let mut system = SystemBuilder::new().build().unwrap();
+
+        match system.execute(&module) {
+            Err(NewErr::MissingImport {
+                module,
+                field,
+                signature,
+                expected_signature: None,
+            }) => {
+                assert_eq!(module, "wasi_unstable");
+                assert_eq!(field, "nonexistent");
+                assert_eq!(signature, crate::sig!((I32) -> I32));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn startup_process_failure_reported() {
+        let module = Module::from_wat(
+            r#"(module
+            (import "wasi_unstable" "nonexistent" (func $nonexistent (param i32) (result i32)))
+            (func $_start (result i32)
+                i32.const 0
+                call $nonexistent)
+            (export "_start" (func $_start)))
+        "#,
+        )
+        .unwrap();
+
+        match SystemBuilder::new().with_startup_process(module).build() {
+            Err(Error::ProgramStartFailed(NewErr::MissingImport { .. })) => {}
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn execute_import_signature_mismatch() {
+        let module = Module::from_wat(
+            r#"(module
+            (import "redshirt" "next_message" (func $next_message (param i32) (result i32)))
+            (func $_start (result i32)
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+        )
+        .unwrap();
+
+        let mut system = SystemBuilder::new().build().unwrap();
+
+        match system.execute(&module) {
+            Err(NewErr::MissingImport {
+                module,
+                field,
+                expected_signature: Some(_),
+                ..
+            }) => {
+                assert_eq!(module, "redshirt");
+                assert_eq!(field, "next_message");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn enumerate_threads() {
+        // The main thread asks for a new thread to be spawned, then both threads wait for a
+        // message that never comes.
+        let module = Module::from_wat(format!(
+            r#"(module
+            (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+            (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (table (export "__indirect_function_table") 1 funcref)
+            (elem (i32.const 0) $thread)
+            {threads_interface}
+            (data (i32.const 32) "\40\00\00\00\09\00\00\00")
+            (data (i32.const 64) "\00\00\00\00\00\00\00\00\00")
+            (data (i32.const 128) "\01\00\00\00\00\00\00\00")
+            (func $wait
+                {wait})
+            (func $thread (param i32)
+                (call $wait))
+            (func $_start (result i32)
+                (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)))
+                (call $wait)
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+            threads_interface = threads_interface_data(),
+            wait = wait_message_wat(128),
+        ))
+        .unwrap();
+
+        let mut system = SystemBuilder::new().build().unwrap();
+        let pid = system.execute(&module).unwrap();
+        assert!(system.run().now_or_never().is_none());
+
+        let threads = system.threads(pid);
+        assert_eq!(threads.len(), 2);
+        assert_ne!(threads[0].0, threads[1].0);
+        for (_, state) in threads {
+            assert_eq!(state, ThreadState::WaitingForMessage);
+        }
+    }
+
+    #[test]
+    fn threads_share_memory() {
+        // The main thread spawns a second thread then waits on a futex. The second thread writes
+        // to memory and wakes up the main thread, which checks the value that has been written.
+        let module = Module::from_wat(format!(
+            r#"(module
+            (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+            (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (table (export "__indirect_function_table") 1 funcref)
+            (elem (i32.const 0) $thread)
+            {threads_interface}
+            (data (i32.const 32) "\40\00\00\00\09\00\00\00")
+            (data (i32.const 40) "\50\00\00\00\09\00\00\00")
+            (data (i32.const 48) "\60\00\00\00\09\00\00\00")
+            (data (i32.const 64) "\00\00\00\00\00\00\02\00\00")
+            (data (i32.const 80) "\01\00\02\00\00\00\00\00\00")
+            (data (i32.const 96) "\02\00\02\00\00\01\00\00\00")
+            (func $thread (param i32)
+                (i32.store (local.get 0) (i32.const 42))
+                (drop (call $emit_message (i32.const 0) (i32.const 48) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0))))
+            (func $_start (result i32)
+                (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)))
+                (drop (call $emit_message (i32.const 0) (i32.const 40) (i32.const 1) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 128)))
+                (drop (call $next_message (i32.const 128) (i32.const 1) (i32.const 256) (i32.const 128) (i32.const 1) (i64.const 0)))
+                (if (i32.ne (i32.load (i32.const 512)) (i32.const 42))
+                    (then unreachable))
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+            threads_interface = threads_interface_data(),
+        ))
+        .unwrap();
+
+        let mut system = SystemBuilder::new().build().unwrap();
+        let pid = system.execute(&module).unwrap();
+
+        match system.run().now_or_never() {
+            Some(Ok(SystemRunOutcome::ProgramFinished {
+                pid: finished,
+                outcome: Ok(()),
+            })) => assert_eq!(finished, pid),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn sched_yield_alternates_threads() {
+        // The main thread spawns a second thread, then both threads increment their own counter
+        // and yield in a loop. The main thread traps if it runs a hundred times in a row, and
+        // stops once the second thread has run three times.
+        let module = Module::from_wat(format!(
+            r#"(module
+            (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+            (import "redshirt" "sched_yield" (func $sched_yield (result i32)))
+            (memory (export "memory") 1)
+            (table (export "__indirect_function_table") 1 funcref)
+            (elem (i32.const 0) $thread)
+            {threads_interface}
+            (data (i32.const 32) "\40\00\00\00\09\00\00\00")
+            (data (i32.const 64) "\00\00\00\00\00\00\00\00\00")
+            (func $thread (param i32)
+                (block $done
+                    (loop $again
+                        (i32.store (i32.const 516) (i32.add (i32.load (i32.const 516)) (i32.const 1)))
+                        (br_if $done (i32.ge_u (i32.load (i32.const 516)) (i32.const 3)))
+                        (drop (call $sched_yield))
+                        (br $again))))
+            (func $_start (result i32)
+                (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)))
+                (block $done
+                    (loop $again
+                        (i32.store (i32.const 512) (i32.add (i32.load (i32.const 512)) (i32.const 1)))
+                        (if (i32.gt_u (i32.load (i32.const 512)) (i32.const 100))
+                            (then unreachable))
+                        (br_if $done (i32.ge_u (i32.load (i32.const 516)) (i32.const 3)))
+                        (if (i32.ne (call $sched_yield) (i32.const 0))
+                            (then unreachable))
+                        (br $again)))
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+            threads_interface = threads_interface_data(),
+        ))
+        .unwrap();
+
+        let mut system = SystemBuilder::new().build().unwrap();
+        let pid = system.execute(&module).unwrap();
+
+        match system.run().now_or_never() {
+            Some(Ok(SystemRunOutcome::ProgramFinished {
+                pid: finished,
+                outcome: Ok(()),
+            })) => assert_eq!(finished, pid),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn main_thread_end_kills_other_threads() {
+        // The main thread spawns a second thread that waits for a message that never comes, then
+        // finishes.
+        let module = Module::from_wat(format!(
+            r#"(module
+            (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+            (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (table (export "__indirect_function_table") 1 funcref)
+            (elem (i32.const 0) $thread)
+            {threads_interface}
+            (data (i32.const 32) "\40\00\00\00\09\00\00\00")
+            (data (i32.const 64) "\00\00\00\00\00\00\00\00\00")
+            (data (i32.const 128) "\01\00\00\00\00\00\00\00")
+            (func $thread (param i32)
+                {wait})
+            (func $_start (result i32)
+                (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)))
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+            threads_interface = threads_interface_data(),
+            wait = wait_message_wat(128),
+        ))
+        .unwrap();
+
+        let mut system = SystemBuilder::new().build().unwrap();
+        let pid = system.execute(&module).unwrap();
+
+        match system.run().now_or_never() {
+            Some(Ok(SystemRunOutcome::ProgramFinished {
+                pid: finished,
+                outcome: Ok(()),
+            })) => assert_eq!(finished, pid),
+            _ => panic!(),
+        }
+        assert!(system.threads(pid).is_empty());
+    }
+
+    #[test]
+    fn next_message_timeout() {
+        // Waits for an interface message with a timeout of 1000 nanoseconds, and traps if the
+        // `next_message` function doesn't return 0.
+        let module = Module::from_wat(
+            r#"(module
+            (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "\01\00\00\00\00\00\00\00")
+            (func $_start (result i32)
+                (if (i32.ne (call $next_message (i32.const 0) (i32.const 1) (i32.const 256) (i32.const 256) (i32.const 1) (i64.const 1000)) (i32.const 0))
+                    (then unreachable))
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+        )
+        .unwrap();
+
+        let clock = MockTimeSource::default();
+        let mut system = SystemBuilder::new()
+            .with_time_source(clock.clone())
+            .build()
+            .unwrap();
+        let pid = system.execute(&module).unwrap();
+
+        assert!(system.run().now_or_never().is_none());
+        clock.set(999);
+        assert!(system.run().now_or_never().is_none());
+
+        clock.set(1000);
+        match system.run().now_or_never() {
+            Some(Ok(SystemRunOutcome::ProgramFinished {
+                pid: finished,
+                outcome: Ok(()),
+            })) => assert_eq!(finished, pid),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn refuel_after_out_of_fuel() {
+        // Loops 1000 times, then finishes.
+        let module = Module::from_wat(
+            r#"(module
+            (func $_start (result i32)
+                (local $i i32)
+                (loop $again
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br_if $again (i32.lt_u (local.get $i) (i32.const 1000))))
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+        )
+        .unwrap();
+
+        let mut system = SystemBuilder::new().with_fuel_limit(100).build().unwrap();
+        let pid = system.execute(&module).unwrap();
+
+        let thread_id = match system.run().now_or_never() {
+            Some(Ok(SystemRunOutcome::ThreadOutOfFuel {
+                pid: paused,
+                thread_id,
+            })) => {
+                assert_eq!(paused, pid);
+                thread_id
+            }
+            _ => panic!(),
+        };
+        assert_eq!(
+            system.threads(pid),
+            vec![(thread_id, ThreadState::OutOfFuel)]
+        );
+
+        system.refuel(thread_id, 1_000_000).unwrap();
+        match system.run().now_or_never() {
+            Some(Ok(SystemRunOutcome::ProgramFinished {
+                pid: finished,
+                outcome: Ok(()),
+            })) => assert_eq!(finished, pid),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn unreachable_reported_as_trap() {
+        let module = Module::from_wat(
+            r#"(module
+            (func $_start (result i32)
+                unreachable)
+            (export "_start" (func $_start)))
+        "#,
+        )
+        .unwrap();
+
+        let mut system = SystemBuilder::new().build().unwrap();
+        let pid = system.execute(&module).unwrap();
+        match system.run().now_or_never() {
+            Some(Ok(SystemRunOutcome::ProgramFinished {
+                pid: finished,
+                outcome: Err(CrashReason::Trap(trap)),
+            })) => {
+                assert_eq!(finished, pid);
+                assert_eq!(trap, Trap::Unreachable);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn extrinsic_buffer_out_of_bounds() {
+        // Emits a message whose interface hash is supposedly located outside of the memory.
+        let module = Module::from_wat(
+            r#"(module
+            (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (func $_start (result i32)
+                (drop (call $emit_message (i32.const 70000) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)))
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+        )
+        .unwrap();
+
+        let mut system = SystemBuilder::new().build().unwrap();
+        let pid = system.execute(&module).unwrap();
+        match system.run().now_or_never() {
+            Some(Ok(SystemRunOutcome::ProgramFinished {
+                pid: finished,
+                outcome: Err(CrashReason::MemoryAccessOutOfBounds),
+            })) => assert_eq!(finished, pid),
+            _ => panic!(),
+        }
+
+        assert!(system.threads(pid).is_empty());
+        assert!(system.run().now_or_never().is_none());
+    }
+
+    #[test]
+    fn abort_looping_process() {
+        let module = Module::from_wat(
+            r#"(module
+            (func $_start (result i32)
+                (loop $again
+                    (br $again))
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+        )
+        .unwrap();
+
+        let mut system = SystemBuilder::new().with_fuel_limit(1000).build().unwrap();
+        let pid = system.execute(&module).unwrap();
+        match system.run().now_or_never() {
+            Some(Ok(SystemRunOutcome::ThreadOutOfFuel { pid: paused, .. })) => {
+                assert_eq!(paused, pid)
+            }
+            _ => panic!(),
+        }
+
+        system.abort_process(pid).unwrap();
+        match system.run().now_or_never() {
+            Some(Ok(SystemRunOutcome::ProgramFinished {
+                pid: finished,
+                outcome: Err(CrashReason::Aborted),
+            })) => assert_eq!(finished, pid),
+            _ => panic!(),
+        }
+
+        assert!(system.threads(pid).is_empty());
+        assert!(system.abort_process(pid).is_err());
+        assert!(system.run().now_or_never().is_none());
+    }
+
+    #[test]
+    fn shutdown_destroys_all_processes() {
+        // Waits for a message that never comes.
+        let module = Module::from_wat(format!(
+            r#"(module
+            (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "\01\00\00\00\00\00\00\00")
+            (func $_start (result i32)
+                {wait}
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+            wait = wait_message_wat(0),
+        ))
+        .unwrap();
+
+        let program = TestProgram::new();
+        let mut system = SystemBuilder::new()
+            .with_native_program(program.clone())
+            .build()
+            .unwrap();
+        let pid1 = system.execute(&module).unwrap();
+        let pid2 = system.execute(&module).unwrap();
+        assert!(system.run().now_or_never().is_none());
+
+        system.shutdown();
+
+        let destroyed = program.destroyed();
+        assert_eq!(destroyed.len(), 2);
+        assert!(destroyed.contains(&pid1));
+        assert!(destroyed.contains(&pid2));
+        assert!(system.threads(pid1).is_empty());
+        assert!(system.threads(pid2).is_empty());
+
+        for _ in 0..2 {
+            match system.run().now_or_never() {
+                Some(Ok(SystemRunOutcome::ShutDown)) => {}
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn answer_from_background_thread() {
+        extern crate std;
+
+        // Emits a message on interface `[9; 32]`, then waits for the answer.
+        let module = Module::from_wat(format!(
+            r#"(module
+            (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+            (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09\09")
+            (data (i32.const 32) "\40\00\00\00\03\00\00\00")
+            (data (i32.const 64) "abc")
+            (func $_start (result i32)
+                (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 1) (i32.const 1) (i32.const 0) (i32.const 96)))
+                {wait}
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+            wait = wait_message_wat(96),
+        ))
+        .unwrap();
+
+        let program = TestProgram::new();
+        program.register(From::from([9; 32]));
+
+        let mut system = SystemBuilder::new()
+            .with_native_program(program.clone())
+            .build()
+            .unwrap();
+        let pid = system.execute(&module).unwrap();
+        assert!(system.run().now_or_never().is_none());
+
+        let messages = program.interface_messages();
+        assert_eq!(messages.len(), 1);
+        let message_id = messages[0].1.unwrap();
+
+        let sender = system.answer_sender();
+        std::thread::spawn(move || sender.send(message_id, Ok(EncodedMessage(Vec::new()))))
+            .join()
+            .unwrap();
+
+        match system.run().now_or_never() {
+            Some(Ok(SystemRunOutcome::ProgramFinished {
+                pid: finished,
+                outcome: Ok(()),
+            })) => assert_eq!(finished, pid),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn native_program_cancels_message() {
+        let handler = TestProgram::new();
+        handler.register(From::from([9; 32]));
+        let emitter = TestProgram::new();
+
+        let mut system = SystemBuilder::new()
+            .with_native_program(handler.clone())
+            .with_native_program(emitter.clone())
+            .build()
+            .unwrap();
+        assert!(system.run().now_or_never().is_none());
+
+        emitter.emit(From::from([9; 32]), EncodedMessage(vec![1]), true);
+        assert!(system.run().now_or_never().is_none());
+        let messages = handler.interface_messages();
+        assert_eq!(messages.len(), 1);
+        let message_id = messages[0].1.unwrap();
+
+        emitter.push_event(NativeProgramEvent::CancelMessage { message_id });
+        assert!(system.run().now_or_never().is_none());
+
+        // The answer of the handler, arriving after the cancellation, is discarded.
+        handler.push_event(NativeProgramEvent::Answer {
+            message_id,
+            answer: Ok(EncodedMessage(vec![2])),
+        });
+        assert!(system.run().now_or_never().is_none());
+        assert!(emitter.responses().is_empty());
+    }
+
+    #[test]
+    fn malformed_threads_message_reported() {
+        // Emits a message on the "threads" interface that can't be decoded.
+        let module = Module::from_wat(format!(
+            r#"(module
+            (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            {threads_interface}
+            (data (i32.const 32) "\40\00\00\00\01\00\00\00")
+            (data (i32.const 64) "\07")
+            (func $_start (result i32)
+                (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)))
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+            threads_interface = threads_interface_data(),
+        ))
+        .unwrap();
+
+        let mut system = SystemBuilder::new().build().unwrap();
+        let pid = system.execute(&module).unwrap();
+
+        match system.run().now_or_never() {
+            Some(Err(Error::MalformedMessage {
+                pid: emitter,
+                interface,
+            })) => {
+                assert_eq!(emitter, pid);
+                assert_eq!(interface, redshirt_threads_interface::ffi::INTERFACE);
+            }
+            _ => panic!(),
+        }
+
+        // The system continues running afterwards.
+        match system.run().now_or_never() {
+            Some(Ok(SystemRunOutcome::ProgramFinished {
+                pid: finished,
+                outcome: Ok(()),
+            })) => assert_eq!(finished, pid),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn thread_start_failure_reported() {
+        // Asks for a new thread starting at index 5 of a table that only has one element.
+        let module = Module::from_wat(format!(
+            r#"(module
+            (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (table (export "__indirect_function_table") 1 funcref)
+            (elem (i32.const 0) $thread)
+            {threads_interface}
+            (data (i32.const 32) "\40\00\00\00\09\00\00\00")
+            (data (i32.const 64) "\00\05\00\00\00\00\00\00\00")
+            (func $thread (param i32))
+            (func $_start (result i32)
+                (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)))
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+            threads_interface = threads_interface_data(),
+        ))
+        .unwrap();
+
+        let mut system = SystemBuilder::new().build().unwrap();
+        let pid = system.execute(&module).unwrap();
+
+        match system.run().now_or_never() {
+            Some(Err(Error::ThreadStartFailed { pid: emitter })) => assert_eq!(emitter, pid),
+            _ => panic!(),
+        }
+        assert_eq!(system.threads(pid).len(), 1);
+    }
+
+    #[test]
+    fn malformed_interface_message_reported() {
+        // Emits a message on the "interface" interface that can't be decoded, then waits for
+        // the answer.
+        let module = Module::from_wat(format!(
+            r#"(module
+            (import "redshirt" "next_message" (func $next_message (param i32 i32 i32 i32 i32 i64) (result i32)))
+            (import "redshirt" "emit_message" (func $emit_message (param i32 i32 i32 i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "\49\6e\56\14\8c\d4\2b\c3\9b\4e\bf\5e\b6\2c\60\4d\7d\d5\70\92\4d\4f\70\df\b3\da\f6\fe\dc\65\93\8a")
+            (data (i32.const 32) "\40\00\00\00\01\00\00\00")
+            (data (i32.const 64) "\ff")
+            (func $_start (result i32)
+                (drop (call $emit_message (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 96)))
+                {wait}
+                i32.const 0)
+            (export "_start" (func $_start)))
+        "#,
+            wait = wait_message_wat(96),
+        ))
+        .unwrap();
+
+        let mut system = SystemBuilder::new().build().unwrap();
+        let pid = system.execute(&module).unwrap();
+
+        match system.run().now_or_never() {
+            Some(Err(Error::MalformedMessage {
+                pid: emitter,
+                interface,
+            })) => {
+                assert_eq!(emitter, pid);
+                assert_eq!(interface, redshirt_interface_interface::ffi::INTERFACE);
+            }
+            _ => panic!(),
+        }
+
+        // The emitter has received an answer and can finish.
+        match system.run().now_or_never() {
+            Some(Ok(SystemRunOutcome::ProgramFinished {
+                pid: finished,
+                outcome: Ok(()),
+            })) => assert_eq!(finished, pid),
+            _ => panic!(),
+        }
+    }
+
+    /// Builds a [`System`](super::System) with a native program as the loader, and answers the
+    /// request to load the main program with `response`. Returns the outcome of running the
+    /// system afterwards.
+    fn load_main_program(
+        response: redshirt_loader_interface::ffi::LoadResponse,
+    ) -> Option<Result<SystemRunOutcome, Error>> {
+        let loader = TestProgram::new();
+        loader.register(redshirt_loader_interface::ffi::INTERFACE);
+
+        let mut system = SystemBuilder::new()
+            .with_native_program(loader.clone())
+            .with_main_program([0; 32])
+            .build()
+            .unwrap();
+        assert!(system.run().now_or_never().is_none());
+
+        let messages = loader.interface_messages();
+        assert_eq!(messages.len(), 1);
+        loader.push_event(NativeProgramEvent::Answer {
+            message_id: messages[0].1.unwrap(),
+            answer: Ok(response.encode()),
+        });
+
+        system.run().now_or_never()
+    }
+
+    #[test]
+    fn program_load_failure_reported() {
+        let response = redshirt_loader_interface::ffi::LoadResponse { result: Err(()) };
+        match load_main_program(response) {
+            Some(Err(Error::ProgramLoadFailed)) => {}
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn invalid_loaded_program_reported() {
+        let response = redshirt_loader_interface::ffi::LoadResponse {
+            result: Ok(vec![1, 2, 3]),
+        };
+        match load_main_program(response) {
+            Some(Err(Error::InvalidProgram(_))) => {}
+            _ => panic!(),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright (C) 2019  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Abstraction over the passage of time.
+//!
+//! The core doesn't have access to any clock by itself. Instead, the environment it is embedded
+//! in must provide an implementation of the [`TimeSource`] trait.
+
+use core::{convert::TryFrom as _, time::Duration};
+
+/// Source of monotonic time. Implemented by the environment the core is embedded in.
+pub trait TimeSource: Send + Sync {
+    /// Returns the number of nanoseconds that have elapsed since an arbitrary point in the past.
+    ///
+    /// The returned value must never decrease.
+    fn monotonic_nanos(&self) -> u128;
+}
+
+/// Point in time, measured by a [`TimeSource`], after which something expires.
+///
+/// A `Deadline` is only meaningful when compared to the [`TimeSource`] that was used to build it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Deadline {
+    /// Value of [`TimeSource::monotonic_nanos`] at which the deadline expires.
+    nanos: u128,
+}
+
+impl Deadline {
+    /// Builds a `Deadline` that expires once the given duration has elapsed from now.
+    pub fn after(source: &(impl TimeSource + ?Sized), duration: Duration) -> Self {
+        Deadline {
+            nanos: source.monotonic_nanos().saturating_add(duration.as_nanos()),
+        }
+    }
+
+    /// Builds a `Deadline` that expires when [`TimeSource::monotonic_nanos`] reaches the given
+    /// value.
+    pub fn from_monotonic_nanos(nanos: u128) -> Self {
+        Deadline { nanos }
+    }
+
+    /// Returns the value of [`TimeSource::monotonic_nanos`] at which the deadline expires.
+    pub fn monotonic_nanos(&self) -> u128 {
+        self.nanos
+    }
+
+    /// Returns true if the deadline has been reached.
+    pub fn is_expired(&self, source: &(impl TimeSource + ?Sized)) -> bool {
+        source.monotonic_nanos() >= self.nanos
+    }
+
+    /// Returns the time remaining before the deadline is reached, or zero if it has already been
+    /// reached.
+    pub fn remaining(&self, source: &(impl TimeSource + ?Sized)) -> Duration {
+        let nanos = self.nanos.saturating_sub(source.monotonic_nanos());
+        match u64::try_from(nanos) {
+            Ok(nanos) => Duration::from_nanos(nanos),
+            Err(_) => Duration::from_secs(u64::max_value()),
+        }
+    }
+}
+
+/// Time source whose time only moves forward when asked to. Clones share the same time.
+#[cfg(test)]
+#[derive(Debug, Default, Clone)]
+pub(crate) struct MockTimeSource(alloc::sync::Arc<core::sync::atomic::AtomicU64>);
+
+#[cfg(test)]
+impl MockTimeSource {
+    /// Sets the value returned by [`TimeSource::monotonic_nanos`].
+    pub(crate) fn set(&self, nanos: u64) {
+        self.0.store(nanos, core::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+impl TimeSource for MockTimeSource {
+    fn monotonic_nanos(&self) -> u128 {
+        u128::from(self.0.load(core::sync::atomic::Ordering::SeqCst))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Deadline, MockTimeSource, TimeSource};
+    use core::time::Duration;
+
+    #[test]
+    fn deadline_expires() {
+        let source = MockTimeSource::default();
+        source.set(1_000);
+        let deadline = Deadline::after(&source, Duration::from_nanos(500));
+        assert_eq!(deadline.monotonic_nanos(), 1_500);
+        assert!(!deadline.is_expired(&source));
+        assert_eq!(deadline.remaining(&source), Duration::from_nanos(500));
+
+        source.set(1_499);
+        assert!(!deadline.is_expired(&source));
+        assert_eq!(deadline.remaining(&source), Duration::from_nanos(1));
+
+        source.set(2_000);
+        assert!(deadline.is_expired(&source));
+        assert_eq!(deadline.remaining(&source), Duration::from_nanos(0));
+    }
+
+    #[test]
+    fn dyn_time_source() {
+        let source: &dyn TimeSource = &MockTimeSource::default();
+        let deadline = Deadline::after(source, Duration::from_secs(1));
+        assert!(!deadline.is_expired(source));
+    }
+}
@@ -108,6 +108,7 @@ impl<T> PhysicalBuffer<T> {
 
         redshirt_syscalls_interface::emit_message_with_response(&ffi::INTERFACE, msg)
             .unwrap()
+            .map(Result::unwrap)
             .map(move |mut response: Vec<ffi::HardwareAccessResponse>| {
                 debug_assert_eq!(response.len(), 1);
                 let buf = match response.remove(0) {
@@ -136,6 +137,7 @@ pub fn malloc(size: u64, alignment: u8) -> impl Future<Output = u64> {
         let msg = ffi::HardwareMessage::Malloc { size, alignment };
         redshirt_syscalls_interface::emit_message_with_response(&ffi::INTERFACE, msg)
             .unwrap()
+            .map(Result::unwrap)
             .map(move |ptr: u64| {
                 assert_ne!(ptr, 0);
                 debug_assert_eq!(ptr % u64::from(alignment), 0);
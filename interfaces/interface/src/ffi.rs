@@ -13,6 +13,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use alloc::vec::Vec;
 use parity_scale_codec::{Decode, Encode};
 use redshirt_syscalls_interface::InterfaceHash;
 
@@ -25,6 +26,39 @@ pub const INTERFACE: InterfaceHash = InterfaceHash::from_raw_hash([
 #[derive(Debug, Encode, Decode)]
 pub enum InterfaceMessage {
     Register(InterfaceHash),
+    /// Unregisters an interface previously registered with `Register`. Only the handler of the
+    /// interface can unregister it.
+    Unregister(InterfaceHash),
+    /// Sets the format that messages emitted on the given interface must conform to. Only the
+    /// handler of the interface can set its schema.
+    SetSchema(InterfaceHash, Vec<SchemaField>),
+    /// Enables or disables coalescing of the messages emitted on the given interface. Only the
+    /// handler of the interface can change this setting.
+    SetCoalescing(InterfaceHash, bool),
+    /// Waits until a handler is registered for the given interface. The answer is empty, and is
+    /// sent immediately if there already is a handler.
+    Await(InterfaceHash),
+}
+
+/// Field of a message, as part of a schema.
+///
+/// A message conforms to a schema if it consists of the SCALE encoding of each field, one after
+/// the other, and nothing else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum SchemaField {
+    /// Unsigned 8 bits integer.
+    U8,
+    /// Unsigned 16 bits integer.
+    U16,
+    /// Unsigned 32 bits integer.
+    U32,
+    /// Unsigned 64 bits integer.
+    U64,
+    /// Fixed number of bytes.
+    Bytes(u32),
+    /// Variable number of bytes, prefixed with their compact-encoded length. Corresponds to a
+    /// `Vec<u8>`.
+    VarBytes,
 }
 
 #[derive(Debug, Encode, Decode)]
@@ -37,3 +71,36 @@ pub enum InterfaceRegisterError {
     /// There already exists a process registered for this interface.
     AlreadyRegistered,
 }
+
+#[derive(Debug, Encode, Decode)]
+pub struct InterfaceUnregisterResponse {
+    pub result: Result<(), InterfaceUnregisterError>,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub enum InterfaceUnregisterError {
+    /// The emitter of the message isn't the handler of this interface.
+    NotHandler,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct InterfaceSetSchemaResponse {
+    pub result: Result<(), InterfaceSetSchemaError>,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub enum InterfaceSetSchemaError {
+    /// The emitter of the message isn't the handler of this interface.
+    NotHandler,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct InterfaceSetCoalescingResponse {
+    pub result: Result<(), InterfaceSetCoalescingError>,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub enum InterfaceSetCoalescingError {
+    /// The emitter of the message isn't the handler of this interface.
+    NotHandler,
+}
@@ -18,10 +18,16 @@
 #![deny(intra_doc_link_resolution_failure)]
 #![no_std]
 
+extern crate alloc;
+
+use alloc::vec::Vec;
 use futures::prelude::*;
 use redshirt_syscalls_interface::InterfaceHash;
 
-pub use ffi::InterfaceRegisterError;
+pub use ffi::{
+    InterfaceRegisterError, InterfaceSetCoalescingError, InterfaceSetSchemaError,
+    InterfaceUnregisterError, SchemaField,
+};
 
 pub mod ffi;
 
@@ -39,6 +45,78 @@ pub fn register_interface(
     unsafe {
         redshirt_syscalls_interface::emit_message_with_response(&ffi::INTERFACE, msg)
             .unwrap()
+            .map(Result::unwrap)
             .map(|response: ffi::InterfaceRegisterResponse| response.result)
     }
 }
+
+/// Unregisters the current program as the provider for the given interface hash.
+///
+/// Messages emitted on this interface afterwards are no longer delivered to the current program.
+///
+/// Returns an error if the current program isn't the handler of this interface.
+pub fn unregister_interface(
+    hash: InterfaceHash,
+) -> impl Future<Output = Result<(), InterfaceUnregisterError>> {
+    let msg = ffi::InterfaceMessage::Unregister(hash);
+    unsafe {
+        redshirt_syscalls_interface::emit_message_with_response(&ffi::INTERFACE, msg)
+            .unwrap()
+            .map(Result::unwrap)
+            .map(|response: ffi::InterfaceUnregisterResponse| response.result)
+    }
+}
+
+/// Sets the format that the messages emitted on the given interface must conform to.
+///
+/// Once a schema is set, messages that don't conform to it are refused by the kernel before
+/// reaching the handler, and the emitter receives an error.
+///
+/// Returns an error if the current program isn't the handler of this interface.
+pub fn set_interface_schema(
+    hash: InterfaceHash,
+    fields: Vec<SchemaField>,
+) -> impl Future<Output = Result<(), InterfaceSetSchemaError>> {
+    let msg = ffi::InterfaceMessage::SetSchema(hash, fields);
+    unsafe {
+        redshirt_syscalls_interface::emit_message_with_response(&ffi::INTERFACE, msg)
+            .unwrap()
+            .map(Result::unwrap)
+            .map(|response: ffi::InterfaceSetSchemaResponse| response.result)
+    }
+}
+
+/// Enables or disables coalescing of the messages emitted on the given interface.
+///
+/// When enabled, a message that doesn't expect an answer replaces the messages emitted by the
+/// same program on this interface that are still waiting to be retrieved and that don't expect
+/// an answer either. This is appropriate for interfaces carrying state updates, where only the
+/// latest value matters.
+///
+/// Returns an error if the current program isn't the handler of this interface.
+pub fn set_interface_coalescing(
+    hash: InterfaceHash,
+    enabled: bool,
+) -> impl Future<Output = Result<(), InterfaceSetCoalescingError>> {
+    let msg = ffi::InterfaceMessage::SetCoalescing(hash, enabled);
+    unsafe {
+        redshirt_syscalls_interface::emit_message_with_response(&ffi::INTERFACE, msg)
+            .unwrap()
+            .map(Result::unwrap)
+            .map(|response: ffi::InterfaceSetCoalescingResponse| response.result)
+    }
+}
+
+/// Waits until a program has registered itself as the provider for the given interface hash.
+///
+/// The returned future is immediately ready if there already is such a program. This can be used
+/// at startup to wait for a dependency, such as a network driver, to be available.
+pub fn await_interface(hash: InterfaceHash) -> impl Future<Output = ()> {
+    let msg = ffi::InterfaceMessage::Await(hash);
+    unsafe {
+        redshirt_syscalls_interface::emit_message_with_response(&ffi::INTERFACE, msg)
+            .unwrap()
+            .map(Result::unwrap)
+            .map(|()| ())
+    }
+}
@@ -46,6 +46,7 @@ pub async fn generate_in(out: &mut [u8]) {
             redshirt_syscalls_interface::emit_message_with_response(&ffi::INTERFACE, msg)
                 .unwrap()
                 .await
+                .unwrap()
         };
         chunk.copy_from_slice(&rep.result);
     }
@@ -80,6 +80,15 @@ pub(crate) fn peek_response(msg_id: MessageId) -> Option<ResponseMessage> {
     state.pending_messages.remove(&msg_id)
 }
 
+/// Stores a response as if it had been received from the kernel, so that the futures of this
+/// crate can be tested without a kernel.
+#[cfg(test)]
+pub(crate) fn inject_response(response: ResponseMessage) {
+    let mut state = (&*STATE).lock();
+    let _was_in = state.pending_messages.insert(response.message_id, response);
+    debug_assert!(_was_in.is_none());
+}
+
 /// Blocks the current thread until the [`Future`](core::future::Future) passed as parameter
 /// finishes.
 pub fn block_on<T>(future: impl Future<Output = T>) -> T {
@@ -222,6 +231,7 @@ pub(crate) fn next_message(to_poll: &mut [u64], block: bool) -> Option<Message>
                 out.as_mut_ptr(),
                 out.capacity() as u32,
                 block,
+                0,
             ) as usize;
             if ret == 0 {
                 return None;
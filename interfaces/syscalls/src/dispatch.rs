@@ -0,0 +1,163 @@
+// Copyright (C) 2019  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{
+    ffi::{InterfaceMessage, InterfaceOrDestroyed, ProcessDestroyedMessage},
+    InterfaceHash,
+};
+
+use alloc::boxed::Box;
+use hashbrown::HashMap;
+
+/// Dispatches the messages received on the interfaces we have registered to closures.
+///
+/// Each interface has its own closure, and a separate closure handles the notifications that a
+/// process has been destroyed.
+pub struct MessageDispatcher<'a> {
+    /// Closures to call for messages on each interface.
+    interfaces: HashMap<InterfaceHash, Box<dyn FnMut(InterfaceMessage) + 'a>>,
+    /// Closure to call when a process is destroyed.
+    process_destroyed: Option<Box<dyn FnMut(ProcessDestroyedMessage) + 'a>>,
+}
+
+impl<'a> MessageDispatcher<'a> {
+    /// Builds a new dispatcher with no closure registered.
+    pub fn new() -> Self {
+        MessageDispatcher {
+            interfaces: HashMap::new(),
+            process_destroyed: None,
+        }
+    }
+
+    /// Registers the closure to call for messages on the given interface. Replaces the previous
+    /// closure for this interface, if any.
+    pub fn on_interface(
+        &mut self,
+        interface: InterfaceHash,
+        handler: impl FnMut(InterfaceMessage) + 'a,
+    ) -> &mut Self {
+        self.interfaces.insert(interface, Box::new(handler));
+        self
+    }
+
+    /// Registers the closure to call when a process that has emitted messages on one of our
+    /// interfaces is destroyed. Replaces the previous closure, if any.
+    pub fn on_process_destroyed(
+        &mut self,
+        handler: impl FnMut(ProcessDestroyedMessage) + 'a,
+    ) -> &mut Self {
+        self.process_destroyed = Some(Box::new(handler));
+        self
+    }
+
+    /// Waits for the next message on one of our interfaces and dispatches it.
+    ///
+    /// Returns the message if no closure is registered for it.
+    pub async fn next(&mut self) -> Option<InterfaceOrDestroyed> {
+        let message = crate::next_interface_message().await;
+        self.dispatch(message)
+    }
+
+    /// Passes the given message to the corresponding closure.
+    ///
+    /// Returns the message if no closure is registered for it.
+    pub fn dispatch(&mut self, message: InterfaceOrDestroyed) -> Option<InterfaceOrDestroyed> {
+        match message {
+            InterfaceOrDestroyed::Interface(msg) => {
+                let interface = InterfaceHash::from(msg.interface);
+                if let Some(handler) = self.interfaces.get_mut(&interface) {
+                    handler(msg);
+                    None
+                } else {
+                    Some(InterfaceOrDestroyed::Interface(msg))
+                }
+            }
+            InterfaceOrDestroyed::ProcessDestroyed(msg) => {
+                if let Some(handler) = self.process_destroyed.as_mut() {
+                    handler(msg);
+                    None
+                } else {
+                    Some(InterfaceOrDestroyed::ProcessDestroyed(msg))
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Default for MessageDispatcher<'a> {
+    fn default() -> Self {
+        MessageDispatcher::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MessageDispatcher;
+    use crate::ffi::{InterfaceMessage, InterfaceOrDestroyed, ProcessDestroyedMessage};
+    use crate::{InterfaceHash, Pid};
+    use alloc::vec;
+    use core::cell::Cell;
+
+    #[test]
+    fn closures_called() {
+        const INTERFACE: InterfaceHash = InterfaceHash::from_name("test");
+
+        let interface_calls = Cell::new(0);
+        let destroyed_calls = Cell::new(0);
+
+        let mut dispatcher = MessageDispatcher::new();
+        dispatcher
+            .on_interface(INTERFACE, |msg| {
+                assert_eq!(msg.actual_data, vec![1, 2, 3]);
+                interface_calls.set(interface_calls.get() + 1);
+            })
+            .on_process_destroyed(|msg| {
+                assert_eq!(msg.pid, Pid::from(5));
+                destroyed_calls.set(destroyed_calls.get() + 1);
+            });
+
+        let interface_msg = InterfaceMessage {
+            interface: INTERFACE.clone().into(),
+            message_id: None,
+            emitter_pid: Pid::from(5),
+            priority: 0,
+            index_in_list: 0,
+            actual_data: vec![1, 2, 3],
+        };
+        assert!(dispatcher
+            .dispatch(InterfaceOrDestroyed::Interface(interface_msg.clone()))
+            .is_none());
+        assert_eq!(interface_calls.get(), 1);
+
+        let destroyed_msg = ProcessDestroyedMessage {
+            pid: Pid::from(5),
+            index_in_list: 0,
+        };
+        assert!(dispatcher
+            .dispatch(InterfaceOrDestroyed::ProcessDestroyed(destroyed_msg))
+            .is_none());
+        assert_eq!(destroyed_calls.get(), 1);
+
+        let other_msg = InterfaceMessage {
+            interface: InterfaceHash::from_name("other").into(),
+            ..interface_msg
+        };
+        assert_eq!(
+            dispatcher.dispatch(InterfaceOrDestroyed::Interface(other_msg.clone())),
+            Some(InterfaceOrDestroyed::Interface(other_msg))
+        );
+        assert_eq!(interface_calls.get(), 1);
+    }
+}
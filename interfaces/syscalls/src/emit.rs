@@ -13,9 +13,10 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::{Decode, Encode, EncodedMessage, InterfaceHash, MessageId};
+use crate::{AnswerError, Decode, Encode, EncodedMessage, InterfaceHash, MessageId};
 use byteorder::{ByteOrder as _, LittleEndian};
 use core::{
+    cmp,
     convert::TryFrom as _,
     fmt,
     marker::PhantomData,
@@ -36,6 +37,11 @@ use generic_array::{
 pub struct MessageBuilder<'a, TLen: ArrayLength<u8>> {
     /// Parameter for the FFI function.
     allow_delay: bool,
+    /// Parameter for the FFI function.
+    priority: u8,
+    /// Parameter for the FFI function. If `None`, `emit_message` is called instead of
+    /// `emit_message_with_timeout`.
+    answer_timeout_nanos: Option<u64>,
     /// Array of slices, passed to the FFI function.
     array: GenericArray<u8, TLen>,
     /// Pin the lifetime. The lifetime corresponds to the lifetime of buffers pointer to
@@ -48,6 +54,8 @@ impl<'a> MessageBuilder<'a, U0> {
     pub fn new() -> Self {
         MessageBuilder {
             allow_delay: true,
+            priority: 0,
+            answer_timeout_nanos: None,
             array: Default::default(),
             marker: PhantomData,
         }
@@ -65,6 +73,21 @@ where
         self
     }
 
+    /// Sets the priority of the message. Messages with a higher priority are delivered to the
+    /// interface handler before the ones with a lower priority. The default priority is `0`.
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// If the message needs an answer and the handler doesn't answer it within the given number
+    /// of nanoseconds, the response is an [`AnswerError::TimedOut`](crate::AnswerError::TimedOut)
+    /// error.
+    pub fn with_answer_timeout(mut self, timeout_nanos: u64) -> Self {
+        self.answer_timeout_nanos = Some(timeout_nanos);
+        self
+    }
+
     /// Append a slice of message data to the builder.
     ///
     /// > **Note**: This operation is cheap and doesn't perform any copy of the message data
@@ -83,17 +106,20 @@ where
 
         MessageBuilder {
             allow_delay: self.allow_delay,
+            priority: self.priority,
+            answer_timeout_nanos: self.answer_timeout_nanos,
             array: self.array.concat(new_pair),
             marker: self.marker,
         }
     }
 
-    /// Emit the message and returns a `Future` that will yield the response.
+    /// Emit the message and returns a `Future` that will yield the response, or the error that
+    /// the message has been answered with.
     // TODO: could we remove the error type?
     pub unsafe fn emit_with_response<T>(
         self,
         interface: &InterfaceHash,
-    ) -> Result<impl Future<Output = T>, EmitErr>
+    ) -> Result<impl Future<Output = Result<T, AnswerError>>, EmitErr>
     where
         T: Decode,
     {
@@ -135,17 +161,35 @@ where
     ) -> Result<Option<MessageId>, EmitErr> {
         let mut message_id_out = MaybeUninit::uninit();
 
-        let ret = crate::ffi::emit_message(
-            interface as *const InterfaceHash as *const _,
-            self.array.as_ptr(),
-            u32::try_from(self.array.len() / 8).unwrap(),
-            needs_answer,
-            self.allow_delay,
-            message_id_out.as_mut_ptr(),
-        );
+        let ret = if let Some(answer_timeout_nanos) = self.answer_timeout_nanos {
+            crate::ffi::emit_message_with_timeout(
+                interface as *const InterfaceHash as *const _,
+                self.array.as_ptr(),
+                u32::try_from(self.array.len() / 8).unwrap(),
+                needs_answer,
+                self.allow_delay,
+                self.priority,
+                message_id_out.as_mut_ptr(),
+                // The kernel considers timeouts that don't fit in an `i64` as invalid.
+                cmp::min(answer_timeout_nanos, i64::max_value() as u64),
+            )
+        } else {
+            crate::ffi::emit_message(
+                interface as *const InterfaceHash as *const _,
+                self.array.as_ptr(),
+                u32::try_from(self.array.len() / 8).unwrap(),
+                needs_answer,
+                self.allow_delay,
+                self.priority,
+                message_id_out.as_mut_ptr(),
+            )
+        };
 
-        if ret != 0 {
-            return Err(EmitErr::BadInterface);
+        match ret {
+            0 => {}
+            2 => return Err(EmitErr::InvalidMessage),
+            5 => return Err(EmitErr::MemoryLimitReached),
+            _ => return Err(EmitErr::BadInterface),
         }
 
         if needs_answer {
@@ -193,6 +237,28 @@ pub unsafe fn emit_message_without_response<'a>(
         .emit_without_response(interface)
 }
 
+/// Emits a message destined to the handler of the given interface, and confirms its delivery.
+///
+/// Contrary to [`emit_message_without_response`], this function never waits for a handler to
+/// be available. Returns `Ok` if the message has been delivered to the handler of the interface,
+/// or an error if no handler is available. No answer is expected from the handler.
+///
+/// # Safety
+///
+/// While the action of sending a message is totally safe, the message itself might instruct the
+/// environment to perform actions that would lead to unsafety.
+///
+pub unsafe fn emit_message_with_delivery_confirmation(
+    interface: &InterfaceHash,
+    msg: impl Encode,
+) -> Result<(), EmitErr> {
+    let msg = msg.encode();
+    MessageBuilder::new()
+        .with_no_delay()
+        .add_data(&msg)
+        .emit_without_response(interface)
+}
+
 /// Emis a message, then waits for a response to come back.
 ///
 /// Returns `Ok` if the message has been successfully dispatched. Returns an error if no handler
@@ -200,7 +266,8 @@ pub unsafe fn emit_message_without_response<'a>(
 /// Whether this function succeeds only depends on whether an interface handler is available. This
 /// function doesn't perform any validity check on the message itself.
 ///
-/// The returned future will cancel the message if it is dropped early.
+/// The returned future yields the response, or the error that the message has been answered
+/// with. It will cancel the message if it is dropped early.
 ///
 /// # Safety
 ///
@@ -210,7 +277,7 @@ pub unsafe fn emit_message_without_response<'a>(
 pub unsafe fn emit_message_with_response<'a, T: Decode>(
     interface: &InterfaceHash,
     msg: impl Encode,
-) -> Result<impl Future<Output = T>, EmitErr> {
+) -> Result<impl Future<Output = Result<T, AnswerError>>, EmitErr> {
     let msg = msg.encode();
     MessageBuilder::new()
         .add_data(&msg)
@@ -229,12 +296,23 @@ pub fn cancel_message(message_id: MessageId) {
 pub enum EmitErr {
     /// The given interface has no handler.
     BadInterface,
+    /// The message doesn't conform to the schema of the interface.
+    InvalidMessage,
+    /// The kernel has reached its memory limit.
+    MemoryLimitReached,
 }
 
 impl fmt::Display for EmitErr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             EmitErr::BadInterface => write!(f, "The given interface has no handler"),
+            EmitErr::InvalidMessage => {
+                write!(
+                    f,
+                    "The message doesn't conform to the schema of the interface"
+                )
+            }
+            EmitErr::MemoryLimitReached => write!(f, "The kernel has reached its memory limit"),
         }
     }
 }
@@ -250,7 +328,7 @@ pub struct EmitMessageWithResponse<T> {
 }
 
 impl<T: Decode> Future for EmitMessageWithResponse<T> {
-    type Output = T;
+    type Output = Result<T, AnswerError>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
         unsafe {
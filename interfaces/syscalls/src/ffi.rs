@@ -30,15 +30,25 @@ extern "C" {
     /// If `block` is true, then this function puts the thread to sleep until a message is
     /// available. If `block` is false, then this function returns as soon as possible.
     ///
+    /// If `block` is true and `timeout_nanos` is not 0, then the thread is woken up after
+    /// `timeout_nanos` nanoseconds have elapsed even if no message is available, in which case
+    /// the function returns 0. A `timeout_nanos` of 0 means "no timeout". If `block` is false,
+    /// `timeout_nanos` is ignored.
+    ///
+    /// > **Note**: The kernel might not support timeouts, in which case the thread is blocked
+    /// >           until a message is available.
+    ///
     /// If the function returns 0, then there is no message available and nothing has been written.
-    /// This function never returns 0 if `block` is `true`.
+    /// This function never returns 0 if `block` is `true` and `timeout_nanos` is 0.
     /// If the function returns a value larger than `out_len`, then a message is available whose
     /// length is the value that has been returned, but nothing has been written in `out`.
     /// If the function returns value inferior or equal to `out_len` (and different from 0), then
     /// a message has been written in `out`.
     ///
     /// Messages, amongst the set that matches `to_poll`, are always returned in the order they
-    /// have been received. In particular, this function does **not** search the queue of messages
+    /// have been received, except that interface messages with a higher
+    /// [`priority`](InterfaceMessage::priority) are returned before the ones with a lower
+    /// priority. In particular, this function does **not** search the queue of messages
     /// for a message that fits in `out_len`. It will however skip the messages in the queue that
     /// do not match any entry in `to_poll`.
     ///
@@ -53,6 +63,7 @@ extern "C" {
         out: *mut u8,
         out_len: u32,
         block: bool,
+        timeout_nanos: u64,
     ) -> u32;
 
     /// Sends a message to the process that has registered the given interface.
@@ -70,14 +81,31 @@ extern "C" {
     /// The message body is what will go into the [`actual_data`](Message::actual_data) field of
     /// the [`Message`] that the target will receive.
     ///
-    /// Returns `0` on success, and `1` in case of error.
+    /// Returns `0` on success, `1` if no handler is available for the interface, `2` if the
+    /// message doesn't conform to the schema of the interface, `3` if `needs_answer` is true
+    /// and waiting for the answer would exceed the maximum depth of a chain of messages waiting
+    /// for an answer allowed by the kernel, `4` if the queue of messages of the handler of the
+    /// interface is full and `allow_delay` is false, and `5` if the kernel has reached its
+    /// memory limit.
     ///
     /// On success, if `needs_answer` is true, will write the ID of new event into the memory
     /// pointed by `message_id_out`.
     ///
+    /// The `priority` is passed to the handler in the [`priority`](InterfaceMessage::priority)
+    /// field of the message. Messages with a higher priority are delivered to the handler before
+    /// the messages with a lower priority, even if they have been emitted later. Messages with
+    /// the same priority are delivered in the order in which they have been emitted. The
+    /// default priority is `0`.
+    ///
     /// If `allow_delay` is true, the kernel is allowed to block the thread in order to
-    /// lazily-load a handler for that interface if necessary. If `allow_delay` is false and no
-    /// interface handler is available, the function fails immediately.
+    /// lazily-load a handler for that interface if necessary, or until the queue of messages of
+    /// the handler has room. If `allow_delay` is false and no interface handler is available,
+    /// the function fails immediately.
+    ///
+    /// A success always means that the message has been delivered to the queue of the handler
+    /// of the interface. As such, emitting a message with `needs_answer` set to false and
+    /// `allow_delay` set to false can be used as a way to know whether a handler is available,
+    /// without requiring said handler to produce an answer.
     ///
     /// When this function is being called, a "lock" is being held on the memory pointed by
     /// `interface_hash`, `msg_bufs_ptrs`, `message_id_out`, and all the sub-buffers referred to
@@ -90,7 +118,26 @@ extern "C" {
         msg_bufs_num: u32,
         needs_answer: bool,
         allow_delay: bool,
+        priority: u8,
+        message_id_out: *mut u64,
+    ) -> u32;
+
+    /// Same as [`emit_message`], except that if `needs_answer` is true and the message hasn't
+    /// been answered after `answer_timeout_nanos` nanoseconds, the kernel answers it with
+    /// [`AnswerError::TimedOut`]. The answer emitted by the handler afterwards, if any, is
+    /// discarded.
+    ///
+    /// The timeout is ignored if the kernel doesn't have access to a clock. Passing a timeout
+    /// larger than `i64::max_value()` is invalid and kills the process.
+    pub(crate) fn emit_message_with_timeout(
+        interface_hash: *const u8,
+        msg_bufs_ptrs: *const u8,
+        msg_bufs_num: u32,
+        needs_answer: bool,
+        allow_delay: bool,
+        priority: u8,
         message_id_out: *mut u64,
+        answer_timeout_nanos: u64,
     ) -> u32;
 
     /// Sends an answer back to the emitter of given `message_id`.
@@ -100,6 +147,36 @@ extern "C" {
     /// function is running.
     pub(crate) fn emit_answer(message_id: *const u64, msg: *const u8, msg_len: u32);
 
+    /// Sends a piece of the answer to the emitter of given `message_id`.
+    ///
+    /// The answer received by the emitter is the concatenation of all the pieces sent for this
+    /// message, and is only delivered once this function is called with `is_final` set to true.
+    /// Calling `emit_answer` or `emit_message_error` for a message whose answer is being sent
+    /// piece by piece discards the pieces sent so far.
+    ///
+    /// If the emitter is no longer interested in the answer, the pieces are silently discarded.
+    ///
+    /// When this function is being called, a "lock" is being held on the memory pointed by
+    /// `message_id` and `data`. In particular, it is invalid to modify these buffers while the
+    /// function is running.
+    pub(crate) fn emit_answer_chunk(
+        message_id: *const u64,
+        data: *const u8,
+        data_len: u32,
+        is_final: bool,
+    );
+
+    /// Sends back to the emitter of given `message_id` an answer indicating that the operation
+    /// requested by the message has failed. `error` is a payload describing the error, whose
+    /// format is specific to the interface.
+    ///
+    /// Contrary to `emit_message_error`, this doesn't indicate any misbehaviour by the sender.
+    ///
+    /// When this function is being called, a "lock" is being held on the memory pointed by
+    /// `message_id` and `error`. In particular, it is invalid to modify these buffers while the
+    /// function is running.
+    pub(crate) fn emit_answer_error(message_id: *const u64, error: *const u8, error_len: u32);
+
     /// Notifies the kernel that the given message is invalid and cannot reasonably be answered.
     ///
     /// This should be used in situations where a message we receive fails to parse or is generally
@@ -122,6 +199,14 @@ extern "C" {
     /// `message_id`. In particular, it is invalid to modify this buffer while the function is
     /// running.
     pub(crate) fn cancel_message(message_id: *const u64);
+
+    /// Lets the other threads of the current process run before the current thread continues.
+    ///
+    /// Always returns `0`.
+    ///
+    /// > **Note**: This function is meant for threads that busy-wait. Threads that wait for a
+    /// >           message should instead call `next_message` with `block` set to true.
+    pub(crate) fn sched_yield() -> u32;
 }
 
 #[derive(Debug, Clone, Encode, Decode)]
@@ -144,6 +229,9 @@ pub struct InterfaceMessage {
     /// This should be used for security purposes, so that a process can't modify another process'
     /// resources.
     pub emitter_pid: Pid,
+    /// Priority of the message, as passed by the emitter. Always `0` for messages emitted by
+    /// the kernel.
+    pub priority: u8,
     /// Index within the list to poll where this message was.
     pub index_in_list: u32,
     pub actual_data: Vec<u8>,
@@ -171,10 +259,21 @@ pub struct ResponseMessage {
     /// Index within the list to poll where this message was.
     pub index_in_list: u32,
 
-    /// The response, or `Err` if:
-    ///
-    /// - The interface handler has crashed.
-    /// - The interface handler marked our message as invalid.
-    ///
-    pub actual_data: Result<Vec<u8>, ()>,
+    /// The response, or an error if the message couldn't be answered.
+    pub actual_data: Result<Vec<u8>, AnswerError>,
+}
+
+/// Reason why a message hasn't been answered with a response.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub enum AnswerError {
+    /// The interface handler has marked our message as invalid.
+    MalformedRequest,
+    /// The interface handler has crashed or has stopped handling the interface before answering.
+    HandlerCrashed,
+    /// The interface handler has processed the message, but the requested operation has failed.
+    /// Contains a payload describing the error, whose format is specific to the interface.
+    HandlerError(Vec<u8>),
+    /// The message has been emitted with a timeout, and the interface handler hasn't answered
+    /// it in time.
+    TimedOut,
 }
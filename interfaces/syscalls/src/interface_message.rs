@@ -39,6 +39,34 @@ pub fn emit_answer(message_id: MessageId, msg: impl Encode) {
     }
 }
 
+/// Sends a piece of the answer to the given message.
+///
+/// The answer is the concatenation of all the pieces, and is delivered once this function is
+/// called with `is_final` set to true.
+// TODO: move to interface interface?
+pub fn emit_answer_chunk(message_id: MessageId, data: &[u8], is_final: bool) {
+    unsafe {
+        crate::ffi::emit_answer_chunk(
+            &u64::from(message_id),
+            data.as_ptr(),
+            data.len() as u32,
+            is_final,
+        );
+    }
+}
+
+/// Answers the given message by notifying that the operation it requests has failed.
+///
+/// The emitter receives an [`AnswerError::HandlerError`](crate::ffi::AnswerError::HandlerError)
+/// containing the encoded `error`.
+// TODO: move to interface interface?
+pub fn emit_answer_error(message_id: MessageId, error: impl Encode) {
+    unsafe {
+        let buf = error.encode();
+        crate::ffi::emit_answer_error(&u64::from(message_id), buf.0.as_ptr(), buf.0.len() as u32);
+    }
+}
+
 /// Answers the given message by notifying of an error in the message.
 // TODO: move to interface interface?
 pub fn emit_message_error(message_id: MessageId) {
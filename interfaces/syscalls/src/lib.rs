@@ -43,7 +43,8 @@
 //! function.
 //!
 //! The message can later be optionally be answered using the [`emit_answer`] function. If the
-//! mesage is malformed, you can also use the [`emit_message_error`] function.
+//! mesage is malformed, you can also use the [`emit_message_error`] function. If the message is
+//! valid but the operation it requests has failed, use the [`emit_answer_error`] function.
 //!
 //! There is no way for an interface handler to pro-actively send data to a process. Communication
 //! can only be done as a response to a message. This must be taken into account when designing
@@ -85,12 +86,15 @@
 extern crate alloc;
 
 pub use block_on::block_on;
+pub use dispatch::MessageDispatcher;
 pub use emit::{
-    cancel_message, emit_message_with_response, emit_message_without_response, MessageBuilder,
+    cancel_message, emit_message_with_delivery_confirmation, emit_message_with_response,
+    emit_message_without_response, MessageBuilder,
 };
-pub use ffi::{InterfaceMessage, InterfaceOrDestroyed, Message, ResponseMessage};
+pub use ffi::{AnswerError, InterfaceMessage, InterfaceOrDestroyed, Message, ResponseMessage};
 pub use interface_message::{
-    emit_answer, emit_message_error, next_interface_message, InterfaceMessageFuture,
+    emit_answer, emit_answer_chunk, emit_answer_error, emit_message_error, next_interface_message,
+    InterfaceMessageFuture,
 };
 pub use response::{message_response, message_response_sync_raw, MessageResponseFuture};
 pub use traits::{Decode, Encode, EncodedMessage};
@@ -98,6 +102,7 @@ pub use traits::{Decode, Encode, EncodedMessage};
 use core::{cmp::PartialEq, fmt};
 
 mod block_on;
+mod dispatch;
 mod emit;
 mod interface_message;
 mod response;
@@ -105,6 +110,13 @@ mod traits;
 
 pub mod ffi;
 
+/// Lets the other threads of the current process run before the current thread continues.
+pub fn yield_now() {
+    unsafe {
+        crate::ffi::sched_yield();
+    }
+}
+
 /// Identifier of a running process within a core.
 // TODO: move to a Pid module?
 #[derive(
@@ -189,6 +201,41 @@ impl InterfaceHash {
     pub const fn from_raw_hash(hash: [u8; 32]) -> Self {
         InterfaceHash(hash)
     }
+
+    /// Derives an [`InterfaceHash`] from the name of an interface.
+    ///
+    /// The same name always produces the same hash. Each group of 8 bytes of the hash is the
+    /// 64-bits FNV-1a hash of the name prefixed with the index of the group.
+    ///
+    /// > **Note**: This hash isn't cryptographically secure. It is meant to avoid writing hashes
+    /// >           by hand, not to prevent two names from producing the same hash on purpose.
+    pub const fn from_name(name: &str) -> Self {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+        const FNV_PRIME: u64 = 0x100000001b3;
+
+        let name = name.as_bytes();
+        let mut hash = [0; 32];
+
+        let mut group = 0;
+        while group < 4 {
+            let mut state = (FNV_OFFSET_BASIS ^ group as u64).wrapping_mul(FNV_PRIME);
+            let mut n = 0;
+            while n < name.len() {
+                state = (state ^ name[n] as u64).wrapping_mul(FNV_PRIME);
+                n += 1;
+            }
+
+            let mut n = 0;
+            while n < 8 {
+                hash[group * 8 + n] = (state >> (n * 8)) as u8;
+                n += 1;
+            }
+
+            group += 1;
+        }
+
+        InterfaceHash(hash)
+    }
 }
 
 impl From<InterfaceHash> for [u8; 32] {
@@ -217,11 +264,32 @@ impl PartialEq<InterfaceHash> for [u8; 32] {
 
 impl fmt::Debug for InterfaceHash {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "InterfaceHash(0x")?;
+        write!(f, "InterfaceHash(0x{})", self)
+    }
+}
+
+impl fmt::Display for InterfaceHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for byte in &self.0 {
-            write!(f, "{:x}", *byte)?
+            write!(f, "{:02x}", *byte)?
         }
-        write!(f, ")")?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::InterfaceHash;
+    use alloc::format;
+
+    #[test]
+    fn interface_hash_from_name() {
+        const TCP: InterfaceHash = InterfaceHash::from_name("tcp");
+        assert_eq!(
+            format!("{}", TCP),
+            "f286c69f7e30028c13a4952078309c2b3011b4e78ff065df29fdc38f89484cc4"
+        );
+        assert_eq!(TCP, InterfaceHash::from_name("tcp"));
+        assert_ne!(TCP, InterfaceHash::from_name("udp"));
+    }
+}
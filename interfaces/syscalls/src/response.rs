@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::{ffi::Message, Decode, EncodedMessage, MessageId};
+use crate::{ffi::Message, AnswerError, Decode, EncodedMessage, MessageId};
 
 use core::{
     marker::PhantomData,
@@ -24,17 +24,18 @@ use futures::prelude::*;
 
 /// Waits until a response to the given message comes back.
 ///
-/// Returns the undecoded response.
-pub fn message_response_sync_raw(msg_id: MessageId) -> EncodedMessage {
+/// Returns the undecoded response, or the error that the message has been answered with.
+pub fn message_response_sync_raw(msg_id: MessageId) -> Result<EncodedMessage, AnswerError> {
     match crate::block_on::next_message(&mut [msg_id.into()], true).unwrap() {
-        Message::Response(m) => EncodedMessage(m.actual_data.unwrap()),
+        Message::Response(m) => m.actual_data.map(EncodedMessage),
         _ => panic!(),
     }
 }
 
 /// Returns a future that is ready when a response to the given message comes back.
 ///
-/// The return value is the type the message decodes to.
+/// The return value is the type the message decodes to, or the error that the message has been
+/// answered with.
 pub fn message_response<T: Decode>(msg_id: MessageId) -> MessageResponseFuture<T> {
     MessageResponseFuture {
         finished: false,
@@ -57,13 +58,17 @@ impl<T> Future for MessageResponseFuture<T>
 where
     T: Decode,
 {
-    type Output = T;
+    type Output = Result<T, AnswerError>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
         assert!(!self.finished);
         if let Some(message) = crate::block_on::peek_response(self.msg_id) {
             self.finished = true;
-            Poll::Ready(Decode::decode(EncodedMessage(message.actual_data.unwrap())).unwrap())
+            Poll::Ready(
+                message
+                    .actual_data
+                    .map(|data| Decode::decode(EncodedMessage(data)).unwrap()),
+            )
         } else {
             crate::block_on::register_message_waker(self.msg_id, cx.waker().clone());
             Poll::Pending
@@ -72,3 +77,53 @@ where
 }
 
 impl<T> Unpin for MessageResponseFuture<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::message_response;
+    use crate::{AnswerError, Encode as _, MessageId, ResponseMessage};
+    use alloc::vec;
+    use futures::prelude::*;
+
+    #[test]
+    fn response_decoded() {
+        let msg_id = MessageId::from(0x1000);
+        crate::block_on::inject_response(ResponseMessage {
+            message_id: msg_id,
+            index_in_list: 0,
+            actual_data: Ok(12u32.encode().0),
+        });
+
+        let response = message_response::<u32>(msg_id).now_or_never();
+        assert_eq!(response, Some(Ok(12)));
+    }
+
+    #[test]
+    fn handler_error_returned() {
+        let msg_id = MessageId::from(0x1001);
+        crate::block_on::inject_response(ResponseMessage {
+            message_id: msg_id,
+            index_in_list: 0,
+            actual_data: Err(AnswerError::HandlerError(vec![1, 2, 3])),
+        });
+
+        let response = message_response::<u32>(msg_id).now_or_never();
+        assert_eq!(
+            response,
+            Some(Err(AnswerError::HandlerError(vec![1, 2, 3])))
+        );
+    }
+
+    #[test]
+    fn timed_out_returned() {
+        let msg_id = MessageId::from(0x1002);
+        crate::block_on::inject_response(ResponseMessage {
+            message_id: msg_id,
+            index_in_list: 0,
+            actual_data: Err(AnswerError::TimedOut),
+        });
+
+        let response = message_response::<u32>(msg_id).now_or_never();
+        assert_eq!(response, Some(Err(AnswerError::TimedOut)));
+    }
+}
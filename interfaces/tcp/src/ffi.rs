@@ -34,8 +34,19 @@ pub enum TcpMessage {
     /// Ask to write data to a socket. A response is sent back once written. For each socket, only
     /// one write can exist at any given point in time.
     Write(TcpWrite),
+    /// Ask to open a UDP socket bound to a local address. UDP sockets are closed with
+    /// [`TcpMessage::Close`].
+    UdpOpen(UdpOpen),
+    /// Ask to send a datagram from a UDP socket. A response is sent back once sent.
+    UdpSend(UdpSend),
+    /// Ask to receive a datagram on a UDP socket. The response contains the datagram. For each
+    /// socket, only one receive can exist at any given point in time.
+    UdpRecv(UdpRecv),
 }
 
+// All the IP addresses are IPv6 addresses, as the eight 16-bit segments of the address. IPv4
+// addresses are passed as IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`).
+
 #[derive(Debug, Encode, Decode)]
 pub struct TcpListen {
     pub local_ip: [u16; 8],
@@ -67,6 +78,17 @@ pub struct TcpAccept {
 
 #[derive(Debug, Encode, Decode)]
 pub struct TcpAcceptResponse {
+    /// Contains an error if the listening socket has been closed before a connection could be
+    /// accepted. Accepts that are pending when the listening socket is closed must be answered
+    /// with an error.
+    pub result: Result<TcpAccepted, ()>,
+}
+
+/// Inbound connection accepted by a listening socket.
+#[derive(Debug, Encode, Decode)]
+pub struct TcpAccepted {
+    /// Socket ID of the new connection. Can be used with [`TcpMessage::Read`],
+    /// [`TcpMessage::Write`] and [`TcpMessage::Close`].
     pub accepted_socket_id: u32,
     pub remote_ip: [u16; 8],
     pub remote_port: u16,
@@ -97,3 +119,48 @@ pub struct TcpWrite {
 pub struct TcpWriteResponse {
     pub result: Result<(), ()>,
 }
+
+#[derive(Debug, Encode, Decode)]
+pub struct UdpOpen {
+    pub local_ip: [u16; 8],
+    /// Can be 0 for auto-assign.
+    pub port: u16,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct UdpOpenResponse {
+    /// On success, the socket ID and the port it's bound to.
+    pub result: Result<(u32, u16), ()>,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct UdpSend {
+    pub socket_id: u32,
+    pub remote_ip: [u16; 8],
+    pub remote_port: u16,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct UdpSendResponse {
+    pub result: Result<(), ()>,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct UdpRecv {
+    pub socket_id: u32,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct UdpRecvResponse {
+    pub result: Result<UdpDatagram, ()>,
+}
+
+/// Datagram received on a UDP socket.
+#[derive(Debug, Encode, Decode)]
+pub struct UdpDatagram {
+    /// Address of the sender of the datagram.
+    pub remote_ip: [u16; 8],
+    pub remote_port: u16,
+    pub data: Vec<u8>,
+}
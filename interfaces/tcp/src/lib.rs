@@ -21,50 +21,46 @@
 
 use futures::{prelude::*, ready};
 use parity_scale_codec::DecodeAll;
-use redshirt_syscalls_interface::{Encode as _, MessageId};
+use redshirt_syscalls_interface::{AnswerError, Encode as _, MessageId};
 use std::{
-    cmp, io, mem, net::Ipv6Addr, net::SocketAddr, pin::Pin, sync::Arc, task::Context, task::Poll,
-    task::Waker,
+    cmp, io, mem, net::Ipv4Addr, net::Ipv6Addr, net::SocketAddr, pin::Pin, sync::Arc,
+    task::Context, task::Poll, task::Waker,
 };
 
 pub mod ffi;
 
+/// TCP connection to a remote.
+///
+/// Use [`TcpStream::connect`] to open a connection, then either [`TcpStream::read`] and
+/// [`TcpStream::write`], or the [`AsyncRead`] and [`AsyncWrite`] traits, in order to
+/// communicate with the remote. The connection is closed when the `TcpStream` is dropped.
 pub struct TcpStream {
     handle: u32,
     /// Buffer of data that has been read from the socket but not transmitted to the user yet.
     read_buffer: Vec<u8>,
     /// If Some, we have sent out a "read" message and are waiting for a response.
     // TODO: use strongly typed Future here
-    pending_read: Option<Pin<Box<dyn Future<Output = ffi::TcpReadResponse> + Send>>>,
+    pending_read:
+        Option<Pin<Box<dyn Future<Output = Result<ffi::TcpReadResponse, AnswerError>> + Send>>>,
     /// If Some, we have sent out a "write" message and are waiting for a response.
     // TODO: use strongly typed Future here
-    pending_write: Option<Pin<Box<dyn Future<Output = ffi::TcpWriteResponse> + Send>>>,
+    pending_write:
+        Option<Pin<Box<dyn Future<Output = Result<ffi::TcpWriteResponse, AnswerError>> + Send>>>,
 }
 
 impl TcpStream {
+    /// Opens a TCP connection to the given address.
     pub fn connect(socket_addr: &SocketAddr) -> impl Future<Output = Result<TcpStream, ()>> {
-        let tcp_open = ffi::TcpMessage::Open(match socket_addr {
-            SocketAddr::V4(addr) => ffi::TcpOpen {
-                ip: addr.ip().to_ipv6_mapped().segments(),
-                port: addr.port(),
-            },
-            SocketAddr::V6(addr) => ffi::TcpOpen {
-                ip: addr.ip().segments(),
-                port: addr.port(),
-            },
-        });
+        let (ip, port) = ipv6_segments(socket_addr);
+        let tcp_open = ffi::TcpMessage::Open(ffi::TcpOpen { ip, port });
 
-        let msg_id = unsafe {
-            let msg = tcp_open.encode();
-            redshirt_syscalls_interface::MessageBuilder::new()
-                .add_data(&msg)
-                .emit_with_response_raw(&ffi::INTERFACE)
-                .unwrap()
-        };
+        let msg_id = emit_with_response(&tcp_open);
 
         async move {
             let message: ffi::TcpOpenResponse =
-                redshirt_syscalls_interface::message_response(msg_id).await;
+                redshirt_syscalls_interface::message_response(msg_id)
+                    .await
+                    .map_err(|_| ())?;
             let handle = message.result?;
 
             Ok(TcpStream {
@@ -75,6 +71,33 @@ impl TcpStream {
             })
         }
     }
+
+    /// Reads data from the socket into `buf`. Returns the number of bytes that have been read.
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        future::poll_fn(|cx| AsyncRead::poll_read(Pin::new(&mut *self), cx, buf)).await
+    }
+
+    /// Writes the entirety of `data` to the socket.
+    ///
+    /// Returns once the remote has accepted the data.
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), io::Error> {
+        // Only one write can exist at any given point in time.
+        if let Some(pending_write) = self.pending_write.take() {
+            if pending_write.await.map_or(true, |rep| rep.result.is_err()) {
+                return Err(io::ErrorKind::Other.into()); // TODO:
+            }
+        }
+
+        let tcp_write = ffi::TcpMessage::Write(ffi::TcpWrite {
+            socket_id: self.handle,
+            data: data.to_vec(),
+        });
+        let msg_id = emit_with_response(&tcp_write);
+        let response: ffi::TcpWriteResponse = redshirt_syscalls_interface::message_response(msg_id)
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::Other))?;
+        response.result.map_err(|()| io::ErrorKind::Other.into()) // TODO:
+    }
 }
 
 impl AsyncRead for TcpStream {
@@ -85,9 +108,10 @@ impl AsyncRead for TcpStream {
     ) -> Poll<Result<usize, io::Error>> {
         loop {
             if let Some(pending_read) = self.pending_read.as_mut() {
-                self.read_buffer = match ready!(Future::poll(Pin::new(pending_read), cx)).result {
-                    Ok(d) => d,
-                    Err(_) => return Poll::Ready(Err(io::ErrorKind::Other.into())), // TODO:
+                let response = ready!(Future::poll(Pin::new(pending_read), cx));
+                self.read_buffer = match response.map(|rep| rep.result) {
+                    Ok(Ok(d)) => d,
+                    _ => return Poll::Ready(Err(io::ErrorKind::Other.into())), // TODO:
                 };
                 self.pending_read = None;
             }
@@ -103,13 +127,7 @@ impl AsyncRead for TcpStream {
             let tcp_read = ffi::TcpMessage::Read(ffi::TcpRead {
                 socket_id: self.handle,
             });
-            let msg_id = unsafe {
-                let msg = tcp_read.encode();
-                redshirt_syscalls_interface::MessageBuilder::new()
-                    .add_data(&msg)
-                    .emit_with_response_raw(&ffi::INTERFACE)
-                    .unwrap()
-            };
+            let msg_id = emit_with_response(&tcp_read);
             self.pending_read = Some(Box::pin(redshirt_syscalls_interface::message_response(
                 msg_id,
             )));
@@ -126,9 +144,9 @@ impl AsyncWrite for TcpStream {
         buf: &[u8],
     ) -> Poll<Result<usize, io::Error>> {
         if let Some(pending_write) = self.pending_write.as_mut() {
-            match ready!(Future::poll(Pin::new(pending_write), cx)).result {
-                Ok(()) => self.pending_write = None,
-                Err(_) => return Poll::Ready(Err(io::ErrorKind::Other.into())), // TODO:
+            match ready!(Future::poll(Pin::new(pending_write), cx)).map(|rep| rep.result) {
+                Ok(Ok(())) => self.pending_write = None,
+                _ => return Poll::Ready(Err(io::ErrorKind::Other.into())), // TODO:
             }
         }
 
@@ -136,13 +154,7 @@ impl AsyncWrite for TcpStream {
             socket_id: self.handle,
             data: buf.to_vec(),
         });
-        let msg_id = unsafe {
-            let msg = tcp_write.encode();
-            redshirt_syscalls_interface::MessageBuilder::new()
-                .add_data(&msg)
-                .emit_with_response_raw(&ffi::INTERFACE)
-                .unwrap()
-        };
+        let msg_id = emit_with_response(&tcp_write);
         self.pending_write = Some(Box::pin(redshirt_syscalls_interface::message_response(
             msg_id,
         )));
@@ -198,40 +210,33 @@ impl Drop for TcpStream {
     }
 }
 
+/// Socket listening for inbound TCP connections.
+///
+/// Use [`TcpListener::bind`] to start listening, then [`TcpListener::accept`] to wait for
+/// connections. The socket is closed when the `TcpListener` is dropped.
 pub struct TcpListener {
     handle: u32,
     local_addr: SocketAddr,
     /// If Some, we have sent out an "accept" message and are waiting for a response.
     // TODO: use strongly typed Future here
-    pending_accept: Option<Pin<Box<dyn Future<Output = ffi::TcpAcceptResponse> + Send>>>,
+    pending_accept:
+        Option<Pin<Box<dyn Future<Output = Result<ffi::TcpAcceptResponse, AnswerError>> + Send>>>,
 }
 
 impl TcpListener {
     pub fn bind(socket_addr: &SocketAddr) -> impl Future<Output = Result<TcpListener, ()>> {
-        let tcp_listen = ffi::TcpMessage::Listen(match socket_addr {
-            SocketAddr::V4(addr) => ffi::TcpListen {
-                local_ip: addr.ip().to_ipv6_mapped().segments(),
-                port: addr.port(),
-            },
-            SocketAddr::V6(addr) => ffi::TcpListen {
-                local_ip: addr.ip().segments(),
-                port: addr.port(),
-            },
-        });
+        let (local_ip, port) = ipv6_segments(socket_addr);
+        let tcp_listen = ffi::TcpMessage::Listen(ffi::TcpListen { local_ip, port });
 
-        let msg_id = unsafe {
-            let msg = tcp_listen.encode();
-            redshirt_syscalls_interface::MessageBuilder::new()
-                .add_data(&msg)
-                .emit_with_response_raw(&ffi::INTERFACE)
-                .unwrap()
-        };
+        let msg_id = emit_with_response(&tcp_listen);
 
         let mut local_addr = socket_addr.clone();
 
         async move {
             let message: ffi::TcpListenResponse =
-                redshirt_syscalls_interface::message_response(msg_id).await;
+                redshirt_syscalls_interface::message_response(msg_id)
+                    .await
+                    .map_err(|_| ())?;
             let (handle, local_port) = message.result?;
             local_addr.set_port(local_port);
 
@@ -248,33 +253,30 @@ impl TcpListener {
         self.local_addr
     }
 
+    /// Waits for an inbound connection. Returns the new connection and the address of the remote.
+    ///
+    /// Returns an error if the listening socket has been closed.
     // TODO: make `&self` instead
-    pub async fn accept(&mut self) -> (TcpStream, SocketAddr) {
+    pub async fn accept(&mut self) -> Result<(TcpStream, SocketAddr), ()> {
         loop {
             if let Some(pending_accept) = self.pending_accept.as_mut() {
-                let new_stream = pending_accept.await;
+                let response = pending_accept.await;
                 self.pending_accept = None;
+                let new_stream = response.map_err(|_| ())?.result?;
                 let stream = TcpStream {
                     handle: new_stream.accepted_socket_id,
                     read_buffer: Vec::new(),
                     pending_read: None,
                     pending_write: None,
                 };
-                let remote_ip = Ipv6Addr::from(new_stream.remote_ip);
-                let remote_addr = SocketAddr::from((remote_ip, new_stream.remote_port));
-                return (stream, remote_addr);
+                let remote_addr = socket_addr(new_stream.remote_ip, new_stream.remote_port);
+                return Ok((stream, remote_addr));
             }
 
             let tcp_accept = ffi::TcpMessage::Accept(ffi::TcpAccept {
                 socket_id: self.handle,
             });
-            let msg_id = unsafe {
-                let msg = tcp_accept.encode();
-                redshirt_syscalls_interface::MessageBuilder::new()
-                    .add_data(&msg)
-                    .emit_with_response_raw(&ffi::INTERFACE)
-                    .unwrap()
-            };
+            let msg_id = emit_with_response(&tcp_accept);
             self.pending_accept = Some(Box::pin(redshirt_syscalls_interface::message_response(
                 msg_id,
             )));
@@ -293,3 +295,120 @@ impl Drop for TcpListener {
         }
     }
 }
+
+/// UDP socket bound to a local address.
+///
+/// Use [`UdpSocket::bind`] to open a socket, then [`UdpSocket::send_to`] and
+/// [`UdpSocket::recv_from`] in order to exchange datagrams. The socket is closed when the
+/// `UdpSocket` is dropped.
+pub struct UdpSocket {
+    handle: u32,
+    local_addr: SocketAddr,
+}
+
+impl UdpSocket {
+    /// Opens a UDP socket bound to the given address. The port can be 0 for auto-assign.
+    pub fn bind(socket_addr: &SocketAddr) -> impl Future<Output = Result<UdpSocket, ()>> {
+        let (local_ip, port) = ipv6_segments(socket_addr);
+        let udp_open = ffi::TcpMessage::UdpOpen(ffi::UdpOpen { local_ip, port });
+
+        let msg_id = emit_with_response(&udp_open);
+
+        let mut local_addr = socket_addr.clone();
+
+        async move {
+            let message: ffi::UdpOpenResponse =
+                redshirt_syscalls_interface::message_response(msg_id)
+                    .await
+                    .map_err(|_| ())?;
+            let (handle, local_port) = message.result?;
+            local_addr.set_port(local_port);
+
+            Ok(UdpSocket { handle, local_addr })
+        }
+    }
+
+    /// Returns the local address of the socket. Useful to determine the port.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Sends `data` as a single datagram to the given address.
+    pub async fn send_to(&self, data: &[u8], target: &SocketAddr) -> Result<(), io::Error> {
+        let (remote_ip, remote_port) = ipv6_segments(target);
+        let udp_send = ffi::TcpMessage::UdpSend(ffi::UdpSend {
+            socket_id: self.handle,
+            remote_ip,
+            remote_port,
+            data: data.to_vec(),
+        });
+        let msg_id = emit_with_response(&udp_send);
+        let response: ffi::UdpSendResponse = redshirt_syscalls_interface::message_response(msg_id)
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::Other))?;
+        response.result.map_err(|()| io::ErrorKind::Other.into()) // TODO:
+    }
+
+    /// Waits for the next datagram to arrive on the socket. Returns its content and the address
+    /// of its sender.
+    // TODO: make `&self` instead
+    pub async fn recv_from(&mut self) -> Result<(Vec<u8>, SocketAddr), io::Error> {
+        let udp_recv = ffi::TcpMessage::UdpRecv(ffi::UdpRecv {
+            socket_id: self.handle,
+        });
+        let msg_id = emit_with_response(&udp_recv);
+        let response: ffi::UdpRecvResponse = redshirt_syscalls_interface::message_response(msg_id)
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::Other))?;
+        let datagram = response
+            .result
+            .map_err(|()| io::Error::from(io::ErrorKind::Other))?; // TODO:
+        let remote_addr = socket_addr(datagram.remote_ip, datagram.remote_port);
+        Ok((datagram.data, remote_addr))
+    }
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        unsafe {
+            let tcp_close = ffi::TcpMessage::Close(ffi::TcpClose {
+                socket_id: self.handle,
+            });
+
+            redshirt_syscalls_interface::emit_message_without_response(&ffi::INTERFACE, &tcp_close);
+        }
+    }
+}
+
+/// Turns a [`SocketAddr`] into the IP address and port expected by the FFI. IPv4 addresses are
+/// mapped to IPv6.
+fn ipv6_segments(socket_addr: &SocketAddr) -> ([u16; 8], u16) {
+    match socket_addr {
+        SocketAddr::V4(addr) => (addr.ip().to_ipv6_mapped().segments(), addr.port()),
+        SocketAddr::V6(addr) => (addr.ip().segments(), addr.port()),
+    }
+}
+
+/// Turns an IP address and port received from the FFI into a [`SocketAddr`]. IPv4-mapped IPv6
+/// addresses are turned back into IPv4 addresses.
+fn socket_addr(ip: [u16; 8], port: u16) -> SocketAddr {
+    match ip {
+        [0, 0, 0, 0, 0, 0xffff, hi, lo] => {
+            let ip = Ipv4Addr::new((hi >> 8) as u8, hi as u8, (lo >> 8) as u8, lo as u8);
+            SocketAddr::from((ip, port))
+        }
+        _ => SocketAddr::from((Ipv6Addr::from(ip), port)),
+    }
+}
+
+/// Emits the given message on the TCP interface, and returns the identifier of the message to
+/// pass to [`message_response`](redshirt_syscalls_interface::message_response).
+fn emit_with_response(message: &ffi::TcpMessage) -> MessageId {
+    unsafe {
+        let msg = message.encode();
+        redshirt_syscalls_interface::MessageBuilder::new()
+            .add_data(&msg)
+            .emit_with_response_raw(&ffi::INTERFACE)
+            .unwrap()
+    }
+}
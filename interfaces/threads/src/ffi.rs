@@ -29,6 +29,11 @@ pub enum ThreadsMessage {
     FutexWake(FutexWake),
 }
 
+/// Spawns a new thread within the emitting process.
+///
+/// The new thread shares the memory of the process and is scheduled independently from the
+/// other threads. When the main thread of the process finishes, all the other threads of the
+/// process are stopped as well.
 #[derive(Debug, Encode, Decode)]
 pub struct ThreadNew {
     /// Pointer to a function to start to execute in the new thread.
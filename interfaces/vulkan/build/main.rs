@@ -23,9 +23,12 @@ const VK_XML: &[u8] = include_bytes!("../vk.xml");
 
 mod fpointers;
 mod parse;
+mod types;
+
+use types::print_ty;
 
 fn main() {
-    let registry = parse::parse(Cursor::new(VK_XML));
+    let registry = parse::parse(Cursor::new(VK_XML)).unwrap();
 
     let mut out = {
         let dest_path = Path::new(&env::var("OUT_DIR").unwrap()).join("vk.rs");
@@ -33,7 +36,7 @@ fn main() {
     };
 
     for (name, typedef) in &registry.type_defs {
-        write_type_def(out.by_ref(), name, typedef);
+        write_type_def(out.by_ref(), name, typedef, &registry);
         writeln!(out, "").unwrap();
     }
 
@@ -105,36 +108,41 @@ fn write_enum_values(mut out: impl Write, registry: &parse::VkRegistry) {
     }
 }
 
-fn write_type_def(mut out: impl Write, name: &str, type_def: &parse::VkTypeDef) {
+fn write_type_def(
+    mut out: impl Write,
+    name: &str,
+    type_def: &parse::VkTypeDef,
+    registry: &parse::VkRegistry,
+) {
     match type_def {
         parse::VkTypeDef::Enum | parse::VkTypeDef::Bitmask => {
             writeln!(out, "type {} = u32;", name).unwrap();
         }
-        parse::VkTypeDef::NonDispatchableHandle => {
+        parse::VkTypeDef::NonDispatchableHandle { .. } => {
             writeln!(out, "type {} = u64;", name).unwrap();
         }
-        parse::VkTypeDef::DispatchableHandle => {
+        parse::VkTypeDef::DispatchableHandle { .. } => {
             writeln!(out, "type {} = usize;", name).unwrap();
         }
         parse::VkTypeDef::Struct { fields } => {
-            writeln!(out, "#[repr(C)]").unwrap();
-            writeln!(out, "#[allow(non_snake_case)]").unwrap();
-            writeln!(out, "#[derive(Copy, Clone)]").unwrap();
-            writeln!(out, "struct {} {{", name).unwrap();
-            for (field_ty, field_name) in fields {
-                writeln!(out, "    r#{}: {},", field_name, print_ty(&field_ty)).unwrap();
-            }
-            writeln!(out, "}}").unwrap();
+            write!(out, "{}", types::gen_struct_def(name, fields, registry)).unwrap();
         }
         parse::VkTypeDef::Union { fields } => {
-            writeln!(out, "#[repr(C)]").unwrap();
-            writeln!(out, "#[allow(non_snake_case)]").unwrap();
-            writeln!(out, "#[derive(Copy, Clone)]").unwrap();
-            writeln!(out, "union {} {{", name).unwrap();
-            for (field_ty, field_name) in fields {
-                writeln!(out, "    r#{}: {},", field_name, print_ty(&field_ty)).unwrap();
+            write!(out, "{}", types::gen_union_def(name, fields, registry)).unwrap();
+        }
+        parse::VkTypeDef::FuncPointer { .. } if name == "PFN_vkVoidFunction" => {
+            // Part of the public API of this crate, and thus defined in `lib.rs`.
+        }
+        parse::VkTypeDef::FuncPointer { ret_ty, params } => {
+            writeln!(out, "#[allow(non_camel_case_types)]").unwrap();
+            write!(out, "type {} = extern \"system\" fn(", name).unwrap();
+            for (param_off, (param_ty, _)) in params.iter().enumerate() {
+                if param_off != 0 {
+                    write!(out, ", ").unwrap();
+                }
+                write!(out, "{}", print_ty(param_ty)).unwrap();
             }
-            writeln!(out, "}}").unwrap();
+            writeln!(out, ") -> {};", print_ty(ret_ty)).unwrap();
         }
     }
 }
@@ -185,7 +193,7 @@ fn write_commands_wrappers(mut out: impl Write, registry: &parse::VkRegistry) {
         writeln!(out, "    let msg_id = redshirt_syscalls_interface::MessageBuilder::new().add_data(&redshirt_syscalls_interface::EncodedMessage(msg_buf)).emit_with_response_raw(&INTERFACE).unwrap();").unwrap();
         writeln!(
             out,
-            "    let response = redshirt_syscalls_interface::message_response_sync_raw(msg_id);"
+            "    let response = redshirt_syscalls_interface::message_response_sync_raw(msg_id).unwrap();"
         )
         .unwrap();
         writeln!(out, "    println!(\"got response: {{:?}}\", response);").unwrap();
@@ -538,7 +546,7 @@ fn write_serialize(
     };
 
     match (param_ty, type_def, skip_const) {
-        (parse::VkType::Ident(ty_name), _, _) if ty_name.starts_with("PFN_") => {
+        (parse::VkType::Ident(_), Some(parse::VkTypeDef::FuncPointer { .. }), _) => {
             // We skip serializing all function pointers.
         }
         (parse::VkType::Ident(ty_name), _, _) if ty_name == "void" => {
@@ -579,7 +587,11 @@ fn write_serialize(
         }
         (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::Enum), false)
         | (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::Bitmask), false)
-        | (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::NonDispatchableHandle), false)
+        | (
+            parse::VkType::Ident(ty_name),
+            Some(parse::VkTypeDef::NonDispatchableHandle { .. }),
+            false,
+        )
         | (parse::VkType::Ident(ty_name), None, false) => {
             writeln!(
                 out,
@@ -589,7 +601,11 @@ fn write_serialize(
             )
             .unwrap();
         }
-        (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::DispatchableHandle), false) => {
+        (
+            parse::VkType::Ident(ty_name),
+            Some(parse::VkTypeDef::DispatchableHandle { .. }),
+            false,
+        ) => {
             let serialize = serialize_handles(param_name);
             writeln!(
                 out,
@@ -600,8 +616,16 @@ fn write_serialize(
         }
         (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::Enum), true)
         | (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::Bitmask), true)
-        | (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::NonDispatchableHandle), true)
-        | (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::DispatchableHandle), true)
+        | (
+            parse::VkType::Ident(ty_name),
+            Some(parse::VkTypeDef::NonDispatchableHandle { .. }),
+            true,
+        )
+        | (
+            parse::VkType::Ident(ty_name),
+            Some(parse::VkTypeDef::DispatchableHandle { .. }),
+            true,
+        )
         | (parse::VkType::Ident(ty_name), None, true) => {}
         (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::Struct { fields }), _) => {
             for (field_ty, field_name) in fields {
@@ -804,7 +828,7 @@ fn write_deserialize(
     };
 
     match (ty, type_def) {
-        (parse::VkType::Ident(ty_name), _) if ty_name.starts_with("PFN_") => {
+        (parse::VkType::Ident(_), Some(parse::VkTypeDef::FuncPointer { .. })) => {
             format!("mem::transmute::<_, {}>(0usize)", print_ty(ty))
         }
         (parse::VkType::Ident(ty_name), _) if ty_name == "size_t" => format!(
@@ -840,11 +864,11 @@ fn write_deserialize(
         }
         (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::Enum))
         | (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::Bitmask))
-        | (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::NonDispatchableHandle))
+        | (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::NonDispatchableHandle { .. }))
         | (parse::VkType::Ident(ty_name), None) => {
             format!("<{} as Decode>::decode(&mut msg_buf)?", print_ty(ty))
         }
-        (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::DispatchableHandle)) => {
+        (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::DispatchableHandle { .. })) => {
             // We need to be tolerant on the lack on value in `handles_vm_to_host`, as the call
             // might happen before the handle is created.
             format!("if let Some(val) = state.handles_vm_to_host.get(&(emitter_pid, <u32 as Decode>::decode(&mut msg_buf)?)) {{ *val }} else {{ 0 }}")
@@ -1086,8 +1110,16 @@ fn write_deserialize_response_into(
     match (ty, type_def, force_write) {
         (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::Enum), false)
         | (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::Bitmask), false)
-        | (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::NonDispatchableHandle), false)
-        | (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::DispatchableHandle), false)
+        | (
+            parse::VkType::Ident(ty_name),
+            Some(parse::VkTypeDef::NonDispatchableHandle { .. }),
+            false,
+        )
+        | (
+            parse::VkType::Ident(ty_name),
+            Some(parse::VkTypeDef::DispatchableHandle { .. }),
+            false,
+        )
         | (parse::VkType::Ident(ty_name), None, false) => String::new(),
 
         (parse::VkType::Ident(ty_name), _, true) if ty_name == "size_t" => {
@@ -1104,14 +1136,22 @@ fn write_deserialize_response_into(
 
         (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::Enum), true)
         | (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::Bitmask), true)
-        | (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::NonDispatchableHandle), true)
+        | (
+            parse::VkType::Ident(ty_name),
+            Some(parse::VkTypeDef::NonDispatchableHandle { .. }),
+            true,
+        )
         | (parse::VkType::Ident(ty_name), None, true) => format!(
             "{} = <{} as Decode>::decode(&mut msg_buf)?;",
             out_var_name,
             print_ty(ty)
         ),
 
-        (parse::VkType::Ident(ty_name), Some(parse::VkTypeDef::DispatchableHandle), true) => {
+        (
+            parse::VkType::Ident(ty_name),
+            Some(parse::VkTypeDef::DispatchableHandle { .. }),
+            true,
+        ) => {
             format!(
                 "{} = <u32 as Decode>::decode(&mut msg_buf)? as usize;",
                 out_var_name
@@ -1304,66 +1344,3 @@ fn write_get_instance_proc_addr(mut out: impl Write, registry: &parse::VkRegistr
     writeln!(out, "}}").unwrap();
     writeln!(out, "").unwrap();
 }
-
-fn print_ty(ty: &parse::VkType) -> String {
-    match ty {
-        parse::VkType::Ident(ident) if ident == "void" => "()".to_string(),
-        parse::VkType::Ident(ident) if ident == "char" => "u8".to_string(),
-        parse::VkType::Ident(ident) if ident == "int" => "i32".to_string(),
-        parse::VkType::Ident(ident) if ident == "int32_t" => "i32".to_string(),
-        parse::VkType::Ident(ident) if ident == "int64_t" => "i64".to_string(),
-        parse::VkType::Ident(ident) if ident == "uint8_t" => "u8".to_string(),
-        parse::VkType::Ident(ident) if ident == "uint16_t" => "u16".to_string(),
-        parse::VkType::Ident(ident) if ident == "uint32_t" => "u32".to_string(),
-        parse::VkType::Ident(ident) if ident == "uint64_t" => "u64".to_string(),
-        parse::VkType::Ident(ident) if ident == "size_t" => "usize".to_string(),
-        parse::VkType::Ident(ident) if ident == "float" => "f32".to_string(),
-        parse::VkType::Ident(ident) if ident == "double" => "f64".to_string(),
-
-        parse::VkType::Ident(ident) if ident == "VkSampleMask" => "u32".to_string(),
-        parse::VkType::Ident(ident) if ident == "VkBool32" => "u32".to_string(),
-        parse::VkType::Ident(ident) if ident == "VkDeviceAddress" => "u64".to_string(),
-        parse::VkType::Ident(ident) if ident == "VkDeviceSize" => "u64".to_string(),
-
-        parse::VkType::Ident(ident) if ident == "ANativeWindow" => "c_void".to_string(),
-        parse::VkType::Ident(ident) if ident == "AHardwareBuffer" => "c_void".to_string(),
-        parse::VkType::Ident(ident) if ident == "CAMetalLayer" => "c_void".to_string(),
-        parse::VkType::Ident(ident) if ident == "wl_display" => "c_void".to_string(),
-        parse::VkType::Ident(ident) if ident == "wl_surface" => "c_void".to_string(),
-        parse::VkType::Ident(ident) if ident == "Display" => "c_void".to_string(),
-        parse::VkType::Ident(ident) if ident == "LPCWSTR" => "*const u16".to_string(),
-        parse::VkType::Ident(ident) if ident == "HANDLE" => "*mut c_void".to_string(),
-        parse::VkType::Ident(ident) if ident == "HMONITOR" => "*mut c_void".to_string(),
-        parse::VkType::Ident(ident) if ident == "HWND" => "*mut c_void".to_string(),
-        parse::VkType::Ident(ident) if ident == "HINSTANCE" => "*mut c_void".to_string(),
-        parse::VkType::Ident(ident) if ident == "DWORD" => "u32".to_string(),
-        parse::VkType::ConstPointer(t, _)
-            if **t == parse::VkType::Ident("SECURITY_ATTRIBUTES".into()) =>
-        {
-            "*const c_void".to_owned()
-        }
-
-        // FIXME: the definitions below are probably false, but we don't care because we probably won't use them
-        parse::VkType::Ident(ident) if ident == "xcb_connection_t" => "u32".to_string(),
-        parse::VkType::Ident(ident) if ident == "xcb_window_t" => "u32".to_string(),
-        parse::VkType::Ident(ident) if ident == "xcb_visualid_t" => "u32".to_string(), // TODO: definitely wrong
-        parse::VkType::Ident(ident) if ident == "zx_handle_t" => "u32".to_string(),
-        parse::VkType::Ident(ident) if ident == "Window" => "u32".to_string(),
-        parse::VkType::Ident(ident) if ident == "VisualID" => "u32".to_string(),
-        parse::VkType::Ident(ident) if ident == "RROutput" => "u32".to_string(),
-        parse::VkType::Ident(ident) if ident == "GgpFrameToken" => "u32".to_string(),
-        parse::VkType::Ident(ident) if ident == "GgpStreamDescriptor" => "u32".to_string(),
-
-        parse::VkType::Ident(ty) => ty.to_string(),
-
-        parse::VkType::Array(t, arr) => format!("[{}; {}]", print_ty(t), arr),
-        parse::VkType::MutPointer(t, _) if **t == parse::VkType::Ident("void".into()) => {
-            "*mut c_void".to_owned()
-        }
-        parse::VkType::ConstPointer(t, _) if **t == parse::VkType::Ident("void".into()) => {
-            "*const c_void".to_owned()
-        }
-        parse::VkType::MutPointer(t, _) => format!("*mut {}", print_ty(t)),
-        parse::VkType::ConstPointer(t, _) => format!("*const {}", print_ty(t)),
-    }
-}
@@ -15,7 +15,7 @@
 
 //! Parsing of the XML definitions file.
 
-use std::{collections::HashMap, io::Read};
+use std::{collections::HashMap, error, fmt, io::Read};
 use xml::{
     attribute::OwnedAttribute, name::OwnedName, reader::Events, reader::XmlEvent, EventReader,
 };
@@ -28,10 +28,63 @@ use xml::{
 pub struct VkRegistry {
     /// List of all the Vulkan commands.
     pub commands: Vec<VkCommand>,
+    /// Commands that are an alias of another command. Keys are the name of the alias, and values
+    /// the name of the command it refers to. See [`VkRegistry::resolve_command`].
+    pub command_aliases: HashMap<String, String>,
     /// Type definitions.
     pub type_defs: HashMap<String, VkTypeDef>,
-    /// Enum values.
+    /// Enum values and constants, as written in the registry. Keys are the names of the values.
+    ///
+    /// Values defined with a bit position `n` are stored as `1 << n`, and aliases are stored as
+    /// the name of the value they refer to. See [`VkRegistry::enum_value`].
     pub enums: HashMap<String, String>,
+    /// List of all the core API versions, such as `VK_VERSION_1_0`.
+    pub features: Vec<VkFeature>,
+    /// List of all the extensions. Extensions marked as disabled in the registry are skipped.
+    pub extensions: Vec<VkExtension>,
+}
+
+/// Successfully-parsed Vulkan core API version definition.
+#[derive(Debug, Clone)]
+pub struct VkFeature {
+    /// API this version belongs to, such as `vulkan`.
+    pub api: String,
+    /// Name of the version, such as `VK_VERSION_1_1`.
+    pub name: String,
+    /// Version number, such as `1.1`.
+    pub number: String,
+    /// Names of the commands added by this version.
+    pub commands: Vec<String>,
+}
+
+/// Successfully-parsed Vulkan extension definition.
+#[derive(Debug, Clone)]
+pub struct VkExtension {
+    /// Name of the extension, such as `VK_KHR_swapchain`.
+    pub name: String,
+    /// Expression describing the extensions and core versions this extension depends on, such
+    /// as `VK_KHR_surface+VK_VERSION_1_1`. `None` if the extension has no dependency.
+    ///
+    /// This is the value of the `depends` attribute. Older versions of the registry instead have
+    /// a comma-separated `requires` attribute and a `requiresCore` attribute, in which case they
+    /// are converted to the `depends` syntax.
+    ///
+    /// > **Note**: The grammar of this expression can be complex, which is why it is stored
+    /// >           unparsed. See [`VkExtension::dependencies`].
+    pub depends: Option<String>,
+    /// Whether the extension extends instances or devices.
+    pub kind: VkExtensionKind,
+    /// Names of the commands added by this extension.
+    pub required_commands: Vec<String>,
+}
+
+/// Kind of a Vulkan extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VkExtensionKind {
+    /// Extension whose commands are loaded with `vkGetInstanceProcAddr`.
+    Instance,
+    /// Extension whose commands are loaded with `vkGetDeviceProcAddr`.
+    Device,
 }
 
 /// A type definition of the Vulkan API.
@@ -39,10 +92,27 @@ pub struct VkRegistry {
 pub enum VkTypeDef {
     Enum,
     Bitmask,
-    DispatchableHandle,
-    NonDispatchableHandle,
-    Struct { fields: Vec<(VkType, String)> },
-    Union { fields: Vec<(VkType, String)> },
+    /// Handle defined with `VK_DEFINE_HANDLE`.
+    DispatchableHandle {
+        /// Names of the handles this handle is created from, if any. Most handles have a single
+        /// parent, but some (e.g. `VkDisplayModeKHR`) have several.
+        parents: Vec<String>,
+    },
+    /// Handle defined with `VK_DEFINE_NON_DISPATCHABLE_HANDLE`.
+    NonDispatchableHandle {
+        /// Same as for [`VkTypeDef::DispatchableHandle`].
+        parents: Vec<String>,
+    },
+    Struct {
+        fields: Vec<(VkType, String)>,
+    },
+    Union {
+        fields: Vec<(VkType, String)>,
+    },
+    FuncPointer {
+        ret_ty: VkType,
+        params: Vec<(VkType, String)>,
+    },
 }
 
 /// Successfully-parsed Vulkan command definition.
@@ -64,14 +134,23 @@ pub enum VkType {
     Ident(String),
 
     /// Pointer to some memory location containing a certain number of elements of the given type.
+    /// The elements can be modified through the pointer. For example `T*` or `T* const`.
     MutPointer(Box<VkType>, VkTypePtrLen),
 
     /// Pointer to some memory location containing a certain number of elements of the given type.
+    /// The elements can't be modified through the pointer. For example `const T*` or
+    /// `const T* const`.
+    ///
+    /// Whether the pointer itself is `const` isn't represented, as it only concerns the variable
+    /// that holds the pointer.
     ConstPointer(Box<VkType>, VkTypePtrLen),
 
     /// Array of fixed size. The size is given by the second parameter and can be either a
     /// constant numeric value (for example `2`), or a constant from the registry (for example
     /// `VK_MAX_DESCRIPTION_SIZE`).
+    ///
+    /// Multi-dimensional arrays are represented as arrays of arrays, the outermost array
+    /// corresponding to the first dimension.
     Array(Box<VkType>, String),
 }
 
@@ -183,6 +262,80 @@ impl VkType {
     }
 }
 
+impl VkRegistry {
+    /// Returns the definition of the command with the given name. If the name is an alias,
+    /// returns the definition of the command it refers to.
+    pub fn resolve_command(&self, name: &str) -> Option<&VkCommand> {
+        let mut name = name;
+        // Aliases can in principle point to other aliases. We bound the number of iterations in
+        // order to not loop forever on a malformed registry.
+        for _ in 0..=self.command_aliases.len() {
+            match self.command_aliases.get(name) {
+                Some(target) => name = target,
+                None => return self.commands.iter().find(|c| c.name == name),
+            }
+        }
+        None
+    }
+
+    /// Returns the value of the enum value or constant with the given name, as an integer. If the
+    /// name is an alias, returns the value of what it refers to.
+    ///
+    /// Returns `None` if there is no such value, or if it isn't a decimal or hexadecimal integer
+    /// or a bit position. For example, `1000.0f` or `(~0U)` aren't supported.
+    pub fn enum_value(&self, name: &str) -> Option<i64> {
+        let mut value = self.enums.get(name)?;
+        // Same as for commands, we bound the number of iterations.
+        for _ in 0..=self.enums.len() {
+            match self.enums.get(value) {
+                Some(target) => value = target,
+                None => return parse_integer(value),
+            }
+        }
+        None
+    }
+}
+
+/// Parses an integer written in the registry, such as `-3`, `0x7FFFFFFF` or `1 << 4`.
+fn parse_integer(value: &str) -> Option<i64> {
+    let value = value.trim();
+    if value.starts_with("1 << ") {
+        let shift = value["1 << ".len()..].parse::<u32>().ok()?;
+        1i64.checked_shl(shift).filter(|v| *v > 0)
+    } else if value.starts_with("0x") {
+        i64::from_str_radix(&value[2..], 16).ok()
+    } else {
+        value.parse().ok()
+    }
+}
+
+impl VkExtension {
+    /// Returns the names of the extensions and core versions that appear in
+    /// [`depends`](VkExtension::depends).
+    ///
+    /// Only expressions consisting of names separated with `+` (and) or `,` (or) are supported.
+    /// Since alternatives are all returned, the list is a superset of what is actually required.
+    /// Returns `None` if the expression contains parentheses.
+    pub fn dependencies(&self) -> Option<Vec<&str>> {
+        let depends = match &self.depends {
+            Some(d) => d,
+            None => return Some(Vec::new()),
+        };
+
+        if depends.contains(|c| c == '(' || c == ')') {
+            return None;
+        }
+
+        Some(
+            depends
+                .split(|c| c == '+' || c == ',')
+                .map(|name| name.trim())
+                .filter(|name| !name.is_empty())
+                .collect(),
+        )
+    }
+}
+
 impl VkTypeDef {
     /// If `self` is a `Struct`, returns the type of the given subfield.
     pub fn resolve_subfield_ty(&self, subfield: &str) -> Option<&VkType> {
@@ -199,34 +352,96 @@ impl VkTypeDef {
     }
 }
 
-/// Parses the file `vk.xml` from the given source. Assumes that everything is well-formed and
-/// that no error happens.
-pub fn parse(source: impl Read) -> VkRegistry {
+/// Error that can happen when parsing the registry.
+#[derive(Debug)]
+pub enum ParseError {
+    /// The source isn't valid XML.
+    Xml(xml::reader::Error),
+    /// Found an XML event that isn't expected at this position.
+    UnexpectedEvent {
+        /// Name of the element being parsed.
+        element: String,
+        /// What was found, in a human-readable form.
+        event: String,
+    },
+    /// The source ended in the middle of an element.
+    UnexpectedEnd {
+        /// Name of the element being parsed.
+        element: String,
+    },
+    /// An attribute is missing from an element.
+    MissingAttribute {
+        /// Name of the element.
+        element: String,
+        /// Name of the missing attribute.
+        attribute: String,
+    },
+    /// The content of an element is well-formed XML, but doesn't correspond to what we expect.
+    InvalidContent {
+        /// Name of the element.
+        element: String,
+        /// Description of the problem.
+        description: String,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Xml(err) => write!(f, "Invalid XML: {}", err),
+            ParseError::UnexpectedEvent { element, event } => {
+                write!(f, "Unexpected {} in <{}>", event, element)
+            }
+            ParseError::UnexpectedEnd { element } => {
+                write!(f, "Unexpected end of source in <{}>", element)
+            }
+            ParseError::MissingAttribute { element, attribute } => {
+                write!(f, "Missing attribute {:?} in <{}>", attribute, element)
+            }
+            ParseError::InvalidContent {
+                element,
+                description,
+            } => write!(f, "Invalid <{}>: {}", element, description),
+        }
+    }
+}
+
+impl error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ParseError::Xml(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Parses the file `vk.xml` from the given source.
+pub fn parse(source: impl Read) -> Result<VkRegistry, ParseError> {
     let mut events_source = EventReader::new(source).into_iter();
 
     match events_source.next() {
         Some(Ok(XmlEvent::StartDocument { .. })) => {}
-        ev => panic!("Unexpected: {:?}", ev),
+        ev => return Err(unexpected("document", ev)),
     }
 
     let registry = match events_source.next() {
         Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "registry") => {
-            parse_registry(&mut events_source)
+            parse_registry(&mut events_source)?
         }
-        ev => panic!("Unexpected: {:?}", ev),
+        ev => return Err(unexpected("document", ev)),
     };
 
     loop {
         match events_source.next() {
             Some(Ok(XmlEvent::EndDocument { .. })) => break,
             Some(Ok(XmlEvent::Whitespace(..))) => {}
-            ev => panic!("Unexpected: {:?}", ev),
+            ev => return Err(unexpected("document", ev)),
         }
     }
 
     match events_source.next() {
-        None => return registry,
-        ev => panic!("Unexpected: {:?}", ev),
+        None => Ok(registry),
+        ev => Err(unexpected("document", ev)),
     }
 }
 
@@ -238,70 +453,90 @@ pub fn parse(source: impl Read) -> VkRegistry {
 //
 // The content of this module accomodates this. The various functions below expect as input
 // a `&mut Events` (where `Events` is an iterator) and advance the iterator until they leave
-// the current element. If anything unexpected is encountered on the way, everything stops and a
-// panic immediately happens.
+// the current element. If anything unexpected is encountered on the way, everything stops and an
+// error is immediately returned.
 //
 
-fn parse_registry(events_source: &mut Events<impl Read>) -> VkRegistry {
+fn parse_registry(events_source: &mut Events<impl Read>) -> Result<VkRegistry, ParseError> {
     let mut out = VkRegistry {
         commands: Vec::new(),
+        command_aliases: HashMap::new(),
         type_defs: HashMap::new(),
         enums: HashMap::new(),
+        features: Vec::new(),
+        extensions: Vec::new(),
     };
 
     loop {
         match events_source.next() {
             Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "types") => {
-                let type_defs = parse_types(events_source);
-                assert!(out.type_defs.is_empty());
-                out.type_defs = type_defs;
+                if !out.type_defs.is_empty() {
+                    return Err(invalid("registry", "Multiple <types> elements"));
+                }
+                out.type_defs = parse_types(events_source)?;
             }
             Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "commands") => {
-                let commands = parse_commands(events_source);
-                assert!(out.commands.is_empty());
+                if !out.commands.is_empty() {
+                    return Err(invalid("registry", "Multiple <commands> elements"));
+                }
+                let (commands, aliases) = parse_commands(events_source)?;
                 out.commands = commands;
+                out.command_aliases = aliases;
             }
             Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "enums") => {
-                for (name, value) in parse_enums(events_source) {
-                    let _prev_val = out.enums.insert(name.clone(), value);
-                    assert!(_prev_val.is_none(), "Duplicate value for {:?}", name);
+                for (name, value) in parse_enums(events_source)? {
+                    if out.enums.insert(name.clone(), value).is_some() {
+                        return Err(invalid("enums", format!("Duplicate value for {:?}", name)));
+                    }
                 }
             }
 
+            Some(Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            })) if name_equals(&name, "feature") => {
+                out.features.push(parse_feature(events_source, attributes)?);
+            }
+
             // Other things we don't care about.
             Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "comment") => {
-                advance_until_elem_end(events_source, &name)
+                advance_until_elem_end(events_source, &name)?
             }
             Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "platforms") => {
-                advance_until_elem_end(events_source, &name)
+                advance_until_elem_end(events_source, &name)?
             }
             Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "tags") => {
-                advance_until_elem_end(events_source, &name)
-            }
-            Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "feature") => {
-                advance_until_elem_end(events_source, &name)
+                advance_until_elem_end(events_source, &name)?
             }
             Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "extensions") => {
-                advance_until_elem_end(events_source, &name)
+                if !out.extensions.is_empty() {
+                    return Err(invalid("registry", "Multiple <extensions> elements"));
+                }
+                out.extensions = parse_extensions(events_source)?;
             }
 
             Some(Ok(XmlEvent::EndElement { .. })) => {
-                assert!(!out.commands.is_empty());
-                assert!(!out.type_defs.is_empty());
-                return out;
+                if out.commands.is_empty() {
+                    return Err(invalid("registry", "No command found"));
+                }
+                if out.type_defs.is_empty() {
+                    return Err(invalid("registry", "No type found"));
+                }
+                return Ok(out);
             }
             Some(Ok(XmlEvent::CData(..)))
             | Some(Ok(XmlEvent::Comment(..)))
             | Some(Ok(XmlEvent::Characters(..)))
             | Some(Ok(XmlEvent::Whitespace(..))) => {}
-            ev => panic!("Unexpected: {:?}", ev),
+            ev => return Err(unexpected("registry", ev)),
         }
     }
 }
 
 /// Call this function right after finding a `StartElement` with the name `types`. This function
 /// parses the content of the element.
-fn parse_types(events_source: &mut Events<impl Read>) -> HashMap<String, VkTypeDef> {
+fn parse_types(
+    events_source: &mut Events<impl Read>,
+) -> Result<HashMap<String, VkTypeDef>, ParseError> {
     let mut out = HashMap::new();
 
     loop {
@@ -309,26 +544,29 @@ fn parse_types(events_source: &mut Events<impl Read>) -> HashMap<String, VkTypeD
             Some(Ok(XmlEvent::StartElement {
                 name, attributes, ..
             })) if name_equals(&name, "type") => {
-                if let Some((name, ty)) = parse_type(events_source, attributes) {
+                if let Some((name, ty)) = parse_type(events_source, attributes)? {
                     if !name.is_empty() {
                         // TODO: shouldn't be there; find the bug
-                        let _prev_val = out.insert(name.clone(), ty);
-                        assert!(_prev_val.is_none(), "Duplicate value for {:?}", name);
+                        if out.insert(name.clone(), ty).is_some() {
+                            return Err(invalid(
+                                "types",
+                                format!("Duplicate value for {:?}", name),
+                            ));
+                        }
                     }
                 }
             }
             Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "comment") => {
-                advance_until_elem_end(events_source, &name)
+                advance_until_elem_end(events_source, &name)?
             }
-            Some(Ok(XmlEvent::EndElement { name, .. })) => {
-                assert!(name_equals(&name, "types"));
-                return out;
+            Some(Ok(XmlEvent::EndElement { name, .. })) if name_equals(&name, "types") => {
+                return Ok(out)
             }
             Some(Ok(XmlEvent::CData(..)))
             | Some(Ok(XmlEvent::Comment(..)))
             | Some(Ok(XmlEvent::Characters(..)))
             | Some(Ok(XmlEvent::Whitespace(..))) => {}
-            ev => panic!("Unexpected: {:?}", ev),
+            ev => return Err(unexpected("types", ev)),
         }
     }
 }
@@ -338,110 +576,232 @@ fn parse_types(events_source: &mut Events<impl Read>) -> HashMap<String, VkTypeD
 fn parse_type(
     events_source: &mut Events<impl Read>,
     attributes: Vec<OwnedAttribute>,
-) -> Option<(String, VkTypeDef)> {
+) -> Result<Option<(String, VkTypeDef)>, ParseError> {
     match find_attr(&attributes, "category") {
         Some("enum") => {
-            let name = find_attr(&attributes, "name").unwrap().to_owned();
-            advance_until_elem_end(events_source, &"type".parse().unwrap());
-            Some((name, VkTypeDef::Enum))
+            let name = required_attr(&attributes, "type", "name")?.to_owned();
+            advance_until_elem_end(events_source, &"type".parse().unwrap())?;
+            Ok(Some((name, VkTypeDef::Enum)))
         }
         Some("bitmask") => {
-            let (_, name) = parse_ty_name(events_source, attributes);
-            Some((name, VkTypeDef::Bitmask))
+            let (_, name) = parse_ty_name(events_source, "type", attributes)?;
+            Ok(Some((name, VkTypeDef::Bitmask)))
         }
         Some("include") | Some("define") | Some("basetype") => {
-            advance_until_elem_end(events_source, &"type".parse().unwrap());
-            None
+            advance_until_elem_end(events_source, &"type".parse().unwrap())?;
+            Ok(None)
         }
         Some("handle") => {
-            let (ty, name) = parse_ty_name(events_source, attributes.clone());
+            let parents = find_attr(&attributes, "parent")
+                .map(|parents| parents.split(',').map(|p| p.to_owned()).collect())
+                .unwrap_or_default();
+            let (ty, name) = parse_ty_name(events_source, "type", attributes.clone())?;
             if ty == VkType::Ident("VK_DEFINE_HANDLE".to_owned()) {
-                Some((name, VkTypeDef::DispatchableHandle))
+                Ok(Some((name, VkTypeDef::DispatchableHandle { parents })))
             } else if ty == VkType::Ident("VK_DEFINE_NON_DISPATCHABLE_HANDLE".to_owned()) {
-                Some((name, VkTypeDef::NonDispatchableHandle))
+                Ok(Some((name, VkTypeDef::NonDispatchableHandle { parents })))
             } else if find_attr(&attributes, "alias").is_some() {
-                None
+                Ok(None)
             } else {
-                panic!("Unknown handle type: {:?} for {:?}", ty, name)
+                Err(invalid(
+                    "type",
+                    format!("Unknown handle type: {:?} for {:?}", ty, name),
+                ))
             }
         }
         Some("funcpointer") => {
-            // We deliberately ignore function pointers, and manually generate their definitions.
-            advance_until_elem_end(events_source, &"type".parse().unwrap());
-            None
+            let (name, type_def) = parse_funcpointer(events_source)?;
+            Ok(Some((name, type_def)))
         }
         Some("union") => {
-            let name = find_attr(&attributes, "name").unwrap().to_owned();
-            let mut fields = Vec::new();
-
-            loop {
-                match events_source.next() {
-                    Some(Ok(XmlEvent::StartElement {
-                        name, attributes, ..
-                    })) if name_equals(&name, "member") => {
-                        fields.push(parse_ty_name(events_source, attributes));
-                    }
-                    Some(Ok(XmlEvent::StartElement { name, .. }))
-                        if name_equals(&name, "comment") =>
-                    {
-                        advance_until_elem_end(events_source, &name)
-                    }
-                    Some(Ok(XmlEvent::EndElement { .. })) => break,
-                    Some(Ok(XmlEvent::CData(..)))
-                    | Some(Ok(XmlEvent::Comment(..)))
-                    | Some(Ok(XmlEvent::Characters(..)))
-                    | Some(Ok(XmlEvent::Whitespace(..))) => {}
-                    ev => panic!("Unexpected: {:?}", ev),
-                }
-            }
-
-            Some((name, VkTypeDef::Union { fields }))
+            let name = required_attr(&attributes, "type", "name")?.to_owned();
+            let fields = parse_members(events_source)?;
+            Ok(Some((name, VkTypeDef::Union { fields })))
         }
         Some("struct") => {
-            let name = find_attr(&attributes, "name").unwrap().to_owned();
-            let mut fields = Vec::new();
-
-            loop {
-                match events_source.next() {
-                    Some(Ok(XmlEvent::StartElement {
-                        name, attributes, ..
-                    })) if name_equals(&name, "member") => {
-                        fields.push(parse_ty_name(events_source, attributes));
-                    }
-                    Some(Ok(XmlEvent::StartElement { name, .. }))
-                        if name_equals(&name, "comment") =>
-                    {
-                        advance_until_elem_end(events_source, &name)
-                    }
-                    Some(Ok(XmlEvent::EndElement { .. })) => break,
-                    Some(Ok(XmlEvent::CData(..)))
-                    | Some(Ok(XmlEvent::Comment(..)))
-                    | Some(Ok(XmlEvent::Characters(..)))
-                    | Some(Ok(XmlEvent::Whitespace(..))) => {}
-                    ev => panic!("Unexpected: {:?}", ev),
-                }
-            }
-
-            Some((name, VkTypeDef::Struct { fields }))
+            let name = required_attr(&attributes, "type", "name")?.to_owned();
+            let fields = parse_members(events_source)?;
+            Ok(Some((name, VkTypeDef::Struct { fields })))
         }
         None if find_attr(&attributes, "requires").is_some() => {
-            advance_until_elem_end(events_source, &"type".parse().unwrap());
-            None
+            advance_until_elem_end(events_source, &"type".parse().unwrap())?;
+            Ok(None)
         }
         None if find_attr(&attributes, "name") == Some("int") => {
-            advance_until_elem_end(events_source, &"type".parse().unwrap());
-            None
+            advance_until_elem_end(events_source, &"type".parse().unwrap())?;
+            Ok(None)
+        }
+        cat => Err(invalid(
+            "type",
+            format!(
+                "Unexpected type category: {:?} with attrs {:?}",
+                cat, attributes
+            ),
+        )),
+    }
+}
+
+/// Call this function right after finding a `StartElement` with the name `type` and whose
+/// category is `funcpointer`. This function parses the content of the element and returns the
+/// name of the function pointer type and its definition.
+///
+/// The content of the element is C code of the form
+/// `typedef void* (VKAPI_PTR *<name>PFN_foo</name>)(const <type>char</type>* pMessage, ...);`.
+/// The return type and the names of the parameters aren't enclosed in any element.
+fn parse_funcpointer(
+    events_source: &mut Events<impl Read>,
+) -> Result<(String, VkTypeDef), ParseError> {
+    let mut name = None;
+    // C code before and after the `<name>` element.
+    let mut before_name = String::new();
+    let mut after_name = String::new();
+
+    loop {
+        let code = if name.is_none() {
+            &mut before_name
+        } else {
+            &mut after_name
+        };
+
+        match events_source.next() {
+            Some(Ok(XmlEvent::StartElement { name: elem, .. })) if name_equals(&elem, "name") => {
+                name = Some(expect_characters_elem(events_source, "name")?)
+            }
+            Some(Ok(XmlEvent::StartElement { name: elem, .. })) if name_equals(&elem, "type") => {
+                code.push_str(&expect_characters_elem(events_source, "type")?)
+            }
+            Some(Ok(XmlEvent::EndElement { .. })) => break,
+            Some(Ok(XmlEvent::CData(s))) => code.push_str(&s),
+            Some(Ok(XmlEvent::Characters(s))) => code.push_str(&s),
+            Some(Ok(XmlEvent::Whitespace(s))) => code.push_str(&s),
+            Some(Ok(XmlEvent::Comment(..))) => {}
+            ev => return Err(unexpected("type", ev)),
+        }
+    }
+
+    let name = match name {
+        Some(n) => n,
+        None => return Err(invalid("type", "Function pointer without a name")),
+    };
+
+    // `before_name` is of the form `typedef void* (VKAPI_PTR *`.
+    let ret_ty = {
+        let decl = before_name
+            .trim_start()
+            .trim_start_matches("typedef")
+            .split('(')
+            .next()
+            .unwrap();
+        match parse_c_decl(decl) {
+            Some((ty, None)) => ty,
+            _ => {
+                return Err(invalid(
+                    "type",
+                    format!("Invalid return type of {:?}: {:?}", name, decl),
+                ))
+            }
+        }
+    };
+
+    // `after_name` is of the form `)(void* pUserData, size_t size);`.
+    let params_list = match (after_name.find('('), after_name.rfind(')')) {
+        (Some(start), Some(end)) if start < end => after_name[start + 1..end].trim(),
+        _ => {
+            return Err(invalid(
+                "type",
+                format!("Invalid parameters of {:?}: {:?}", name, after_name),
+            ))
+        }
+    };
+
+    let mut params = Vec::new();
+    if !params_list.is_empty() && params_list != "void" {
+        for decl in params_list.split(',') {
+            match parse_c_decl(decl) {
+                Some((ty, Some(param_name))) => params.push((ty, param_name)),
+                _ => {
+                    return Err(invalid(
+                        "type",
+                        format!("Invalid parameter of {:?}: {:?}", name, decl),
+                    ))
+                }
+            }
+        }
+    }
+
+    Ok((name, VkTypeDef::FuncPointer { ret_ty, params }))
+}
+
+/// Parses a C declaration consisting of a type followed with an optional name, such as
+/// `const char* pMessage` or `void*`.
+///
+/// Returns `None` if the declaration contains anything else than identifiers, `const` and `*`.
+fn parse_c_decl(decl: &str) -> Option<(VkType, Option<String>)> {
+    let spaced = decl.replace('*', " * ");
+    let tokens = spaced.split_whitespace().collect::<Vec<_>>();
+
+    let mut idents = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| **t != "*" && **t != "const");
+    let (ty_index, ty_name) = idents.next()?;
+    let name = idents.next();
+    if idents.next().is_some() {
+        return None;
+    }
+
+    if tokens.iter().any(|t| {
+        *t != "*" && *t != "const" && !t.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }) {
+        return None;
+    }
+
+    // Everything apart from the type identifier and the name, for example `const *`.
+    let decorations = tokens
+        .iter()
+        .enumerate()
+        .filter(|(n, _)| *n != ty_index && Some(*n) != name.map(|(n, _)| n))
+        .map(|(_, t)| *t)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let ty = wrap_in_pointers(VkType::Ident((*ty_name).to_owned()), &decorations, &[]);
+    Some((ty, name.map(|(_, n)| (*n).to_owned())))
+}
+
+/// Call this function right after finding a `StartElement` with the name `type` and whose
+/// category is `struct` or `union`. This function parses the content of the element and returns
+/// the list of members.
+fn parse_members(
+    events_source: &mut Events<impl Read>,
+) -> Result<Vec<(VkType, String)>, ParseError> {
+    let mut fields = Vec::new();
+
+    loop {
+        match events_source.next() {
+            Some(Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            })) if name_equals(&name, "member") => {
+                fields.push(parse_ty_name(events_source, "member", attributes)?);
+            }
+            Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "comment") => {
+                advance_until_elem_end(events_source, &name)?
+            }
+            Some(Ok(XmlEvent::EndElement { .. })) => return Ok(fields),
+            Some(Ok(XmlEvent::CData(..)))
+            | Some(Ok(XmlEvent::Comment(..)))
+            | Some(Ok(XmlEvent::Characters(..)))
+            | Some(Ok(XmlEvent::Whitespace(..))) => {}
+            ev => return Err(unexpected("type", ev)),
         }
-        cat => panic!(
-            "Unexpected type category: {:?} with attrs {:?}",
-            cat, attributes
-        ),
     }
 }
 
 /// Call this function right after finding a `StartElement` with the name `enums`. This function
 /// parses the content of the element.
-fn parse_enums(events_source: &mut Events<impl Read>) -> HashMap<String, String> {
+fn parse_enums(
+    events_source: &mut Events<impl Read>,
+) -> Result<HashMap<String, String>, ParseError> {
     let mut out = HashMap::new();
 
     loop {
@@ -449,27 +809,26 @@ fn parse_enums(events_source: &mut Events<impl Read>) -> HashMap<String, String>
             Some(Ok(XmlEvent::StartElement {
                 name, attributes, ..
             })) if name_equals(&name, "enum") => {
-                if let Some((name, value)) = parse_enum(events_source, attributes) {
-                    let _prev_val = out.insert(name.clone(), value);
-                    assert!(_prev_val.is_none(), "Duplicate value for {:?}", name);
+                let (name, value) = parse_enum(events_source, attributes)?;
+                if out.insert(name.clone(), value).is_some() {
+                    return Err(invalid("enums", format!("Duplicate value for {:?}", name)));
                 }
             }
 
             Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "comment") => {
-                advance_until_elem_end(events_source, &name)
+                advance_until_elem_end(events_source, &name)?
             }
             Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "unused") => {
-                advance_until_elem_end(events_source, &name)
+                advance_until_elem_end(events_source, &name)?
             }
-            Some(Ok(XmlEvent::EndElement { name, .. })) => {
-                assert!(name_equals(&name, "enums"));
-                return out;
+            Some(Ok(XmlEvent::EndElement { name, .. })) if name_equals(&name, "enums") => {
+                return Ok(out)
             }
             Some(Ok(XmlEvent::CData(..)))
             | Some(Ok(XmlEvent::Comment(..)))
             | Some(Ok(XmlEvent::Characters(..)))
             | Some(Ok(XmlEvent::Whitespace(..))) => {}
-            ev => panic!("Unexpected: {:?}", ev),
+            ev => return Err(unexpected("enums", ev)),
         }
     }
 }
@@ -479,56 +838,232 @@ fn parse_enums(events_source: &mut Events<impl Read>) -> HashMap<String, String>
 fn parse_enum(
     events_source: &mut Events<impl Read>,
     attributes: Vec<OwnedAttribute>,
-) -> Option<(String, String)> {
-    let name = find_attr(&attributes, "name").unwrap().to_owned();
+) -> Result<(String, String), ParseError> {
+    let name = required_attr(&attributes, "enum", "name")?.to_owned();
 
     let value = if let Some(value) = find_attr(&attributes, "value") {
         value.to_owned()
     } else if let Some(alias) = find_attr(&attributes, "alias") {
         alias.to_owned()
     } else if let Some(bitpos) = find_attr(&attributes, "bitpos") {
-        format!("2 << {}", bitpos)
+        format!("1 << {}", bitpos)
     } else {
-        panic!("Can't figure out enum value: {:?}", attributes);
+        return Err(invalid(
+            "enum",
+            format!("Can't figure out enum value: {:?}", attributes),
+        ));
     };
 
-    advance_until_elem_end(events_source, &"enum".parse().unwrap());
-    Some((name, value))
+    advance_until_elem_end(events_source, &"enum".parse().unwrap())?;
+    Ok((name, value))
 }
 
 /// Call this function right after finding a `StartElement` with the name `commands`. This
 /// function parses the content of the element.
-fn parse_commands(events_source: &mut Events<impl Read>) -> Vec<VkCommand> {
+///
+/// Returns the list of commands, and the list of aliases.
+fn parse_commands(
+    events_source: &mut Events<impl Read>,
+) -> Result<(Vec<VkCommand>, HashMap<String, String>), ParseError> {
     let mut out = Vec::new();
+    let mut aliases = HashMap::new();
 
     loop {
         match events_source.next() {
             Some(Ok(XmlEvent::StartElement {
                 name, attributes, ..
             })) if name_equals(&name, "command") => {
-                if let Some(cmd) = parse_command(events_source, attributes) {
-                    out.push(cmd);
+                if let Some(alias_of) = find_attr(&attributes, "alias") {
+                    let alias_name = required_attr(&attributes, "command", "name")?.to_owned();
+                    if aliases
+                        .insert(alias_name.clone(), alias_of.to_owned())
+                        .is_some()
+                    {
+                        return Err(invalid(
+                            "commands",
+                            format!("Duplicate alias {:?}", alias_name),
+                        ));
+                    }
+                    advance_until_elem_end(events_source, &name)?;
+                } else {
+                    out.push(parse_command(events_source, attributes)?);
                 }
             }
             Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "comment") => {
-                advance_until_elem_end(events_source, &name)
+                advance_until_elem_end(events_source, &name)?
             }
-            Some(Ok(XmlEvent::EndElement { .. })) => return out,
+            Some(Ok(XmlEvent::EndElement { .. })) => return Ok((out, aliases)),
             Some(Ok(XmlEvent::CData(..)))
             | Some(Ok(XmlEvent::Comment(..)))
             | Some(Ok(XmlEvent::Characters(..)))
             | Some(Ok(XmlEvent::Whitespace(..))) => {}
-            ev => panic!("Unexpected: {:?}", ev),
+            ev => return Err(unexpected("commands", ev)),
         }
     }
 }
 
-/// Call this function right after finding a `StartElement` with the name `command`. This
+/// Call this function right after finding a `StartElement` with the name `extensions`. This
 /// function parses the content of the element.
+fn parse_extensions(events_source: &mut Events<impl Read>) -> Result<Vec<VkExtension>, ParseError> {
+    let mut out = Vec::new();
+
+    loop {
+        match events_source.next() {
+            Some(Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            })) if name_equals(&name, "extension") => {
+                if let Some(extension) = parse_extension(events_source, attributes)? {
+                    out.push(extension);
+                }
+            }
+            Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "comment") => {
+                advance_until_elem_end(events_source, &name)?
+            }
+            Some(Ok(XmlEvent::EndElement { name, .. })) if name_equals(&name, "extensions") => {
+                return Ok(out)
+            }
+            Some(Ok(XmlEvent::CData(..)))
+            | Some(Ok(XmlEvent::Comment(..)))
+            | Some(Ok(XmlEvent::Characters(..)))
+            | Some(Ok(XmlEvent::Whitespace(..))) => {}
+            ev => return Err(unexpected("extensions", ev)),
+        }
+    }
+}
+
+/// Call this function right after finding a `StartElement` with the name `extension`. This
+/// function parses the content of the element.
+///
+/// Returns `None` if the extension is disabled.
+fn parse_extension(
+    events_source: &mut Events<impl Read>,
+    attributes: Vec<OwnedAttribute>,
+) -> Result<Option<VkExtension>, ParseError> {
+    let name = required_attr(&attributes, "extension", "name")?.to_owned();
+
+    if find_attr(&attributes, "supported") == Some("disabled") {
+        advance_until_elem_end(events_source, &"extension".parse().unwrap())?;
+        return Ok(None);
+    }
+
+    let kind = match required_attr(&attributes, "extension", "type")? {
+        "instance" => VkExtensionKind::Instance,
+        "device" => VkExtensionKind::Device,
+        kind => {
+            return Err(invalid(
+                "extension",
+                format!("Unknown extension type: {:?} for {:?}", kind, name),
+            ))
+        }
+    };
+
+    let depends = if let Some(depends) = find_attr(&attributes, "depends") {
+        Some(depends.to_owned())
+    } else {
+        let core = find_attr(&attributes, "requiresCore")
+            .map(|version| format!("VK_VERSION_{}", version.replace('.', "_")));
+        let extensions = find_attr(&attributes, "requires").map(|r| r.replace(',', "+"));
+        match (core, extensions) {
+            (Some(core), Some(extensions)) => Some(format!("{}+{}", core, extensions)),
+            (Some(core), None) => Some(core),
+            (None, Some(extensions)) => Some(extensions),
+            (None, None) => None,
+        }
+    };
+
+    let mut required_commands = Vec::new();
+
+    loop {
+        match events_source.next() {
+            Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "require") => {
+                required_commands.extend(parse_require(events_source)?);
+            }
+            Some(Ok(XmlEvent::EndElement { name, .. })) if name_equals(&name, "extension") => break,
+            Some(Ok(XmlEvent::CData(..)))
+            | Some(Ok(XmlEvent::Comment(..)))
+            | Some(Ok(XmlEvent::Characters(..)))
+            | Some(Ok(XmlEvent::Whitespace(..))) => {}
+            ev => return Err(unexpected("extension", ev)),
+        }
+    }
+
+    Ok(Some(VkExtension {
+        name,
+        depends,
+        kind,
+        required_commands,
+    }))
+}
+
+/// Call this function right after finding a `StartElement` with the name `feature`. This
+/// function parses the content of the element.
+fn parse_feature(
+    events_source: &mut Events<impl Read>,
+    attributes: Vec<OwnedAttribute>,
+) -> Result<VkFeature, ParseError> {
+    let mut out = VkFeature {
+        api: required_attr(&attributes, "feature", "api")?.to_owned(),
+        name: required_attr(&attributes, "feature", "name")?.to_owned(),
+        number: required_attr(&attributes, "feature", "number")?.to_owned(),
+        commands: Vec::new(),
+    };
+
+    loop {
+        match events_source.next() {
+            Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "require") => {
+                out.commands.extend(parse_require(events_source)?);
+            }
+            Some(Ok(XmlEvent::EndElement { name, .. })) if name_equals(&name, "feature") => {
+                return Ok(out)
+            }
+            Some(Ok(XmlEvent::CData(..)))
+            | Some(Ok(XmlEvent::Comment(..)))
+            | Some(Ok(XmlEvent::Characters(..)))
+            | Some(Ok(XmlEvent::Whitespace(..))) => {}
+            ev => return Err(unexpected("feature", ev)),
+        }
+    }
+}
+
+/// Call this function right after finding a `StartElement` with the name `require` within a
+/// `feature` or an `extension`. This function parses the content of the element and returns the
+/// names of the commands it references. Types and enum values are ignored.
+fn parse_require(events_source: &mut Events<impl Read>) -> Result<Vec<String>, ParseError> {
+    let mut out = Vec::new();
+
+    loop {
+        match events_source.next() {
+            Some(Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            })) if name_equals(&name, "command") => {
+                out.push(required_attr(&attributes, "command", "name")?.to_owned());
+                advance_until_elem_end(events_source, &name)?
+            }
+            Some(Ok(XmlEvent::StartElement { name, .. }))
+                if name_equals(&name, "enum")
+                    || name_equals(&name, "type")
+                    || name_equals(&name, "comment") =>
+            {
+                advance_until_elem_end(events_source, &name)?
+            }
+            Some(Ok(XmlEvent::EndElement { name, .. })) if name_equals(&name, "require") => {
+                return Ok(out)
+            }
+            Some(Ok(XmlEvent::CData(..)))
+            | Some(Ok(XmlEvent::Comment(..)))
+            | Some(Ok(XmlEvent::Characters(..)))
+            | Some(Ok(XmlEvent::Whitespace(..))) => {}
+            ev => return Err(unexpected("require", ev)),
+        }
+    }
+}
+
+/// Call this function right after finding a `StartElement` with the name `command` that isn't an
+/// alias. This function parses the content of the element.
 fn parse_command(
     events_source: &mut Events<impl Read>,
     attributes: Vec<OwnedAttribute>,
-) -> Option<VkCommand> {
+) -> Result<VkCommand, ParseError> {
     let mut out = VkCommand {
         name: String::new(),
         ret_ty: VkType::Ident(String::new()),
@@ -540,7 +1075,7 @@ fn parse_command(
             Some(Ok(XmlEvent::StartElement {
                 name, attributes, ..
             })) if name_equals(&name, "proto") => {
-                let (ret_ty, f_name) = parse_ty_name(events_source, attributes);
+                let (ret_ty, f_name) = parse_ty_name(events_source, "proto", attributes)?;
                 out.name = f_name;
                 out.ret_ty = ret_ty;
             }
@@ -548,42 +1083,45 @@ fn parse_command(
             Some(Ok(XmlEvent::StartElement {
                 name, attributes, ..
             })) if name_equals(&name, "param") => {
-                out.params.push(parse_ty_name(events_source, attributes));
+                out.params
+                    .push(parse_ty_name(events_source, "param", attributes)?);
             }
 
             Some(Ok(XmlEvent::StartElement { name, .. }))
                 if name_equals(&name, "implicitexternsyncparams") =>
             {
-                advance_until_elem_end(events_source, &name)
+                advance_until_elem_end(events_source, &name)?
             }
             Some(Ok(XmlEvent::EndElement { .. })) => break,
             Some(Ok(XmlEvent::CData(..)))
             | Some(Ok(XmlEvent::Comment(..)))
             | Some(Ok(XmlEvent::Characters(..)))
             | Some(Ok(XmlEvent::Whitespace(..))) => {}
-            ev => panic!("Unexpected: {:?}", ev),
+            ev => return Err(unexpected("command", ev)),
         }
     }
 
     if out.name.is_empty() || out.ret_ty == VkType::Ident(String::new()) {
-        // TODO: aliases must also be returned somehow
-        assert!(find_attr(&attributes, "alias").is_some());
-        return None;
+        return Err(invalid(
+            "command",
+            format!("Incomplete command definition: {:?}", attributes),
+        ));
     }
 
-    Some(out)
+    Ok(out)
 }
 
-/// Call this function right after finding a `StartElement`. This function parses the content of
-/// the element and expects a single `<type>` tag and a single `<name>` tag. It returns the type
-/// and the name.
+/// Call this function right after finding a `StartElement` with the name `element`. This function
+/// parses the content of the element and expects a single `<type>` tag and a single `<name>` tag.
+/// It returns the type and the name.
 fn parse_ty_name(
     events_source: &mut Events<impl Read>,
+    element: &str,
     attributes: Vec<OwnedAttribute>,
-) -> (VkType, String) {
+) -> Result<(VkType, String), ParseError> {
     let mut ret_ty_out = String::new();
     let mut name_out = String::new();
-    let mut enum_content = String::new();
+    let mut enum_contents = Vec::new();
     let len_attr = find_attr(&attributes, "len");
 
     let mut white_spaces = String::new();
@@ -591,139 +1129,225 @@ fn parse_ty_name(
     loop {
         match events_source.next() {
             Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "name") => {
-                name_out = expect_characters_elem(events_source)
+                name_out = expect_characters_elem(events_source, "name")?
             }
             Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "type") => {
-                ret_ty_out = expect_characters_elem(events_source)
+                ret_ty_out = expect_characters_elem(events_source, "type")?
             }
             Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "enum") => {
-                enum_content = expect_characters_elem(events_source)
+                enum_contents.push(expect_characters_elem(events_source, "enum")?)
             }
             Some(Ok(XmlEvent::StartElement { name, .. })) if name_equals(&name, "comment") => {
-                advance_until_elem_end(events_source, &name)
+                advance_until_elem_end(events_source, &name)?
             }
             Some(Ok(XmlEvent::EndElement { .. })) => break,
             Some(Ok(XmlEvent::CData(s))) => white_spaces.push_str(&s),
             Some(Ok(XmlEvent::Comment(s))) => white_spaces.push_str(&s),
             Some(Ok(XmlEvent::Characters(s))) => white_spaces.push_str(&s),
             Some(Ok(XmlEvent::Whitespace(s))) => white_spaces.push_str(&s),
-            ev => panic!("Unexpected: {:?}", ev),
+            ev => return Err(unexpected(element, ev)),
         }
     }
 
     let ret_ty = if white_spaces.contains("*") {
-        // TODO: we assume that there's no weird combination such as mut pointers to const pointers
-        if let Some(len) = len_attr {
-            let mut ty_out = VkType::Ident(ret_ty_out);
-            for elem in len.split(',').rev() {
-                let len = if elem == "null-terminated" {
-                    VkTypePtrLen::NullTerminated
-                } else if elem
-                    == r#"latexmath:[\lceil{\mathit{rasterizationSamples} \over 32}\rceil]"#
-                {
-                    VkTypePtrLen::OtherField {
-                        before_other_field: "(".to_owned(),
-                        other_field: vec!["rasterizationSamples".to_owned()],
-                        after_other_field: " + 31) / 32".to_owned(),
-                    }
-                } else if elem == r#"latexmath:[\textrm{codeSize} \over 4]"# {
-                    VkTypePtrLen::OtherField {
-                        before_other_field: "".to_owned(),
-                        other_field: vec!["codeSize".to_owned()],
-                        after_other_field: " / 4".to_owned(),
-                    }
-                } else {
-                    // If `altlen` is something, then this is likely a mathematical expression
-                    // that needs to be hardcoded similar to the ones above.
-                    assert!(
-                        find_attr(&attributes, "altlen").is_none(),
-                        "Field runtime length might have to be hardcoded: {:?}",
-                        len
-                    );
-                    VkTypePtrLen::OtherField {
-                        before_other_field: "".to_owned(),
-                        other_field: elem.split("::").map(|v| v.to_owned()).collect(),
-                        after_other_field: "".to_owned(),
-                    }
-                };
+        // Each `*` adds a level of indirection.
+        let num_levels = white_spaces.matches('*').count();
 
-                if white_spaces.contains("const") {
-                    ty_out = VkType::ConstPointer(Box::new(ty_out), len);
-                } else {
-                    ty_out = VkType::MutPointer(Box::new(ty_out), len);
-                }
-            }
-            ty_out
-        } else {
-            if white_spaces.contains("const") {
-                VkType::ConstPointer(Box::new(VkType::Ident(ret_ty_out)), VkTypePtrLen::One)
-            } else {
-                VkType::MutPointer(Box::new(VkType::Ident(ret_ty_out)), VkTypePtrLen::One)
-            }
+        // The `len` attribute contains one entry per level of indirection, starting with the
+        // outermost one. Levels without an entry point to a single element.
+        let lens = match len_attr {
+            Some(len) => len
+                .split(',')
+                .map(|entry| parse_ptr_len(entry, element, &attributes))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
+        if lens.len() > num_levels {
+            return Err(invalid(
+                element,
+                format!("More lengths than levels of indirection: {:?}", attributes),
+            ));
         }
+
+        wrap_in_pointers(VkType::Ident(ret_ty_out), &white_spaces, &lens)
     } else {
-        assert!(len_attr.is_none());
+        if len_attr.is_some() {
+            return Err(invalid(element, "Length attribute on a non-pointer"));
+        }
 
         if white_spaces.contains("[") {
-            if enum_content.is_empty() {
-                // TODO: hard-coded :-/
-                if white_spaces.contains("[2]") {
-                    VkType::Array(Box::new(VkType::Ident(ret_ty_out)), "2".into())
-                } else if white_spaces.contains("[3]") {
-                    VkType::Array(Box::new(VkType::Ident(ret_ty_out)), "3".into())
-                } else if white_spaces.contains("[4]") {
-                    VkType::Array(Box::new(VkType::Ident(ret_ty_out)), "4".into())
+            // Each pair of brackets is a dimension of the array. The dimension is either written
+            // between the brackets, or is the content of an `<enum>` element found between them.
+            let mut enum_contents = enum_contents.into_iter();
+            let mut dims = Vec::new();
+            for segment in white_spaces.split('[').skip(1) {
+                let dim = match segment.find(']') {
+                    Some(end) => segment[..end].trim(),
+                    None => return Err(invalid(element, "Unclosed array bracket")),
+                };
+                if !dim.is_empty() {
+                    dims.push(dim.to_owned());
+                } else if let Some(dim) = enum_contents.next() {
+                    dims.push(dim);
                 } else {
-                    panic!()
+                    return Err(invalid(element, "Missing array dimension"));
                 }
-            } else {
-                VkType::Array(Box::new(VkType::Ident(ret_ty_out)), enum_content)
             }
+            if enum_contents.next().is_some() {
+                return Err(invalid(element, "Unused array dimension"));
+            }
+
+            // `T foo[2][4]` is an array of two arrays of four elements each.
+            dims.into_iter()
+                .rev()
+                .fold(VkType::Ident(ret_ty_out), |ty, dim| {
+                    VkType::Array(Box::new(ty), dim)
+                })
         } else {
             VkType::Ident(ret_ty_out)
         }
     };
 
-    (ret_ty, name_out)
+    Ok((ret_ty, name_out))
+}
+
+/// Wraps `ty` in one pointer for each `*` found in `decorations`, which is the C code surrounding
+/// the type identifier, such as `const *`.
+///
+/// A `const` found before a `*` applies to what this `*` points to, while a `const` after the
+/// last `*` applies to the variable itself and doesn't matter to us. For example `const char*
+/// const*` is a mutable pointer to a const pointer to const characters.
+///
+/// `lens` contains the length of each level of indirection, starting with the outermost one.
+/// Levels without an entry point to a single element.
+fn wrap_in_pointers(ty: VkType, decorations: &str, lens: &[VkTypePtrLen]) -> VkType {
+    let num_levels = decorations.matches('*').count();
+    let pointee_is_const = decorations
+        .split('*')
+        .take(num_levels)
+        .map(|segment| segment.contains("const"));
+
+    let mut ty_out = ty;
+    for (level, is_const) in pointee_is_const.enumerate() {
+        let len = lens
+            .get(num_levels - level - 1)
+            .cloned()
+            .unwrap_or(VkTypePtrLen::One);
+        ty_out = if is_const {
+            VkType::ConstPointer(Box::new(ty_out), len)
+        } else {
+            VkType::MutPointer(Box::new(ty_out), len)
+        };
+    }
+    ty_out
+}
+
+/// Turns an entry of the `len` attribute of a pointer into a [`VkTypePtrLen`]. The name and
+/// attributes of the element that has the `len` attribute must be passed as well.
+fn parse_ptr_len(
+    entry: &str,
+    element: &str,
+    attributes: &[OwnedAttribute],
+) -> Result<VkTypePtrLen, ParseError> {
+    Ok(if entry == "null-terminated" {
+        VkTypePtrLen::NullTerminated
+    } else if entry == r#"latexmath:[\lceil{\mathit{rasterizationSamples} \over 32}\rceil]"# {
+        VkTypePtrLen::OtherField {
+            before_other_field: "(".to_owned(),
+            other_field: vec!["rasterizationSamples".to_owned()],
+            after_other_field: " + 31) / 32".to_owned(),
+        }
+    } else if entry == r#"latexmath:[\textrm{codeSize} \over 4]"# {
+        VkTypePtrLen::OtherField {
+            before_other_field: "".to_owned(),
+            other_field: vec!["codeSize".to_owned()],
+            after_other_field: " / 4".to_owned(),
+        }
+    } else if find_attr(attributes, "altlen").is_some() {
+        // If `altlen` is something, then this is likely a mathematical expression that needs to
+        // be hardcoded similar to the ones above.
+        return Err(invalid(
+            element,
+            format!(
+                "Field runtime length might have to be hardcoded: {:?}",
+                entry
+            ),
+        ));
+    } else {
+        VkTypePtrLen::OtherField {
+            before_other_field: "".to_owned(),
+            other_field: entry.split("::").map(|v| v.to_owned()).collect(),
+            after_other_field: "".to_owned(),
+        }
+    })
 }
 
 /// Advances the `events_source` until a corresponding `EndElement` with the given `elem` is found.
 ///
 /// Call this function if you find a `StartElement` whose content you don't care about.
-fn advance_until_elem_end(events_source: &mut Events<impl Read>, elem: &OwnedName) {
+fn advance_until_elem_end(
+    events_source: &mut Events<impl Read>,
+    elem: &OwnedName,
+) -> Result<(), ParseError> {
     loop {
         match events_source.next() {
             Some(Ok(XmlEvent::StartElement { name, .. })) => {
-                advance_until_elem_end(events_source, &name)
+                advance_until_elem_end(events_source, &name)?
             }
-            Some(Ok(XmlEvent::EndElement { name })) if &name == elem => return,
+            Some(Ok(XmlEvent::EndElement { name })) if &name == elem => return Ok(()),
             Some(Ok(XmlEvent::CData(..)))
             | Some(Ok(XmlEvent::Comment(..)))
             | Some(Ok(XmlEvent::Characters(..)))
             | Some(Ok(XmlEvent::Whitespace(..))) => {}
-            ev => panic!("Unexpected: {:?}", ev),
+            ev => return Err(unexpected(&elem.local_name, ev)),
         }
     }
 }
 
 /// Call this function if you find a `StartElement`. This function will grab any character within
 /// the element and will return when it encounters the corresponding `EndElement`. Any other
-/// `StartElement` within will trigger a panic.
-fn expect_characters_elem(events_source: &mut Events<impl Read>) -> String {
+/// `StartElement` within will trigger an error.
+fn expect_characters_elem(
+    events_source: &mut Events<impl Read>,
+    element: &str,
+) -> Result<String, ParseError> {
     let mut out = String::new();
 
     loop {
         match events_source.next() {
-            Some(Ok(XmlEvent::EndElement { .. })) => return out,
+            Some(Ok(XmlEvent::EndElement { .. })) => return Ok(out),
             Some(Ok(XmlEvent::CData(s))) => out.push_str(&s),
             Some(Ok(XmlEvent::Comment(s))) => out.push_str(&s),
             Some(Ok(XmlEvent::Characters(s))) => out.push_str(&s),
             Some(Ok(XmlEvent::Whitespace(s))) => out.push_str(&s),
-            ev => panic!("Unexpected: {:?}", ev),
+            ev => return Err(unexpected(element, ev)),
         }
     }
 }
 
+/// Builds the error corresponding to an event that isn't expected while parsing the content of
+/// `element`.
+fn unexpected(element: &str, event: Option<Result<XmlEvent, xml::reader::Error>>) -> ParseError {
+    match event {
+        Some(Ok(event)) => ParseError::UnexpectedEvent {
+            element: element.to_owned(),
+            event: format!("{:?}", event),
+        },
+        Some(Err(err)) => ParseError::Xml(err),
+        None => ParseError::UnexpectedEnd {
+            element: element.to_owned(),
+        },
+    }
+}
+
+/// Builds an [`ParseError::InvalidContent`].
+fn invalid(element: &str, description: impl Into<String>) -> ParseError {
+    ParseError::InvalidContent {
+        element: element.to_owned(),
+        description: description.into(),
+    }
+}
+
 /// Checks whether an `OwnedName` matches the expected name.
 fn name_equals(name: &OwnedName, expected: &str) -> bool {
     name.namespace.is_none() && name.prefix.is_none() && name.local_name == expected
@@ -735,3 +1359,15 @@ fn find_attr<'a>(list: &'a [OwnedAttribute], name: &str) -> Option<&'a str> {
         .find(|a| name_equals(&a.name, name))
         .map(|a| a.value.as_str())
 }
+
+/// Same as [`find_attr`], but returns an error if the attribute of `element` is missing.
+fn required_attr<'a>(
+    list: &'a [OwnedAttribute],
+    element: &str,
+    name: &str,
+) -> Result<&'a str, ParseError> {
+    find_attr(list, name).ok_or_else(|| ParseError::MissingAttribute {
+        element: element.to_owned(),
+        attribute: name.to_owned(),
+    })
+}
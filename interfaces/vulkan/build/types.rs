@@ -0,0 +1,148 @@
+// Copyright (C) 2019  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Generation of the Rust code corresponding to Vulkan types.
+
+use crate::parse;
+
+/// Generates the Rust definition of a `VkTypeDef::Struct` with the given name and fields.
+///
+/// The definition has a `#[repr(C)]` attribute, in order to match the layout of the C
+/// definition. Fixed-size arrays whose length is a constant of the registry are generated with
+/// the value of this constant.
+///
+/// The struct and its fields are private. See the "About items visibility" section of the crate
+/// documentation.
+pub fn gen_struct_def(
+    name: &str,
+    fields: &[(parse::VkType, String)],
+    registry: &parse::VkRegistry,
+) -> String {
+    gen_def("struct", name, fields, registry)
+}
+
+/// Same as [`gen_struct_def`], but for a `VkTypeDef::Union`.
+pub fn gen_union_def(
+    name: &str,
+    fields: &[(parse::VkType, String)],
+    registry: &parse::VkRegistry,
+) -> String {
+    gen_def("union", name, fields, registry)
+}
+
+/// Generates the Rust definition of a struct or union, depending on `keyword`.
+fn gen_def(
+    keyword: &str,
+    name: &str,
+    fields: &[(parse::VkType, String)],
+    registry: &parse::VkRegistry,
+) -> String {
+    let mut out = String::new();
+    out.push_str("#[repr(C)]\n");
+    out.push_str("#[allow(non_snake_case)]\n");
+    out.push_str("#[derive(Copy, Clone)]\n");
+    out.push_str(&format!("{} {} {{\n", keyword, name));
+    for (field_ty, field_name) in fields {
+        out.push_str(&format!(
+            "    r#{}: {},\n",
+            field_name,
+            print_ty_resolved(field_ty, registry)
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Same as [`print_ty`], except that the lengths of arrays that refer to a constant of the
+/// registry are replaced with the value of this constant.
+///
+/// Lengths that can't be resolved to an integer, for example because of a cycle of aliases, are
+/// printed unchanged.
+pub fn print_ty_resolved(ty: &parse::VkType, registry: &parse::VkRegistry) -> String {
+    match ty {
+        parse::VkType::Array(t, len) => {
+            let len = match registry.enum_value(len) {
+                Some(value) => value.to_string(),
+                None => len.clone(),
+            };
+            format!("[{}; {}]", print_ty_resolved(t, registry), len)
+        }
+        ty => print_ty(ty),
+    }
+}
+
+/// Returns the Rust type corresponding to the given Vulkan type.
+pub fn print_ty(ty: &parse::VkType) -> String {
+    match ty {
+        parse::VkType::Ident(ident) if ident == "void" => "()".to_string(),
+        parse::VkType::Ident(ident) if ident == "char" => "u8".to_string(),
+        parse::VkType::Ident(ident) if ident == "int" => "i32".to_string(),
+        parse::VkType::Ident(ident) if ident == "int32_t" => "i32".to_string(),
+        parse::VkType::Ident(ident) if ident == "int64_t" => "i64".to_string(),
+        parse::VkType::Ident(ident) if ident == "uint8_t" => "u8".to_string(),
+        parse::VkType::Ident(ident) if ident == "uint16_t" => "u16".to_string(),
+        parse::VkType::Ident(ident) if ident == "uint32_t" => "u32".to_string(),
+        parse::VkType::Ident(ident) if ident == "uint64_t" => "u64".to_string(),
+        parse::VkType::Ident(ident) if ident == "size_t" => "usize".to_string(),
+        parse::VkType::Ident(ident) if ident == "float" => "f32".to_string(),
+        parse::VkType::Ident(ident) if ident == "double" => "f64".to_string(),
+
+        parse::VkType::Ident(ident) if ident == "VkSampleMask" => "u32".to_string(),
+        parse::VkType::Ident(ident) if ident == "VkBool32" => "u32".to_string(),
+        parse::VkType::Ident(ident) if ident == "VkDeviceAddress" => "u64".to_string(),
+        parse::VkType::Ident(ident) if ident == "VkDeviceSize" => "u64".to_string(),
+
+        parse::VkType::Ident(ident) if ident == "ANativeWindow" => "c_void".to_string(),
+        parse::VkType::Ident(ident) if ident == "AHardwareBuffer" => "c_void".to_string(),
+        parse::VkType::Ident(ident) if ident == "CAMetalLayer" => "c_void".to_string(),
+        parse::VkType::Ident(ident) if ident == "wl_display" => "c_void".to_string(),
+        parse::VkType::Ident(ident) if ident == "wl_surface" => "c_void".to_string(),
+        parse::VkType::Ident(ident) if ident == "Display" => "c_void".to_string(),
+        parse::VkType::Ident(ident) if ident == "LPCWSTR" => "*const u16".to_string(),
+        parse::VkType::Ident(ident) if ident == "HANDLE" => "*mut c_void".to_string(),
+        parse::VkType::Ident(ident) if ident == "HMONITOR" => "*mut c_void".to_string(),
+        parse::VkType::Ident(ident) if ident == "HWND" => "*mut c_void".to_string(),
+        parse::VkType::Ident(ident) if ident == "HINSTANCE" => "*mut c_void".to_string(),
+        parse::VkType::Ident(ident) if ident == "DWORD" => "u32".to_string(),
+        parse::VkType::ConstPointer(t, _)
+            if **t == parse::VkType::Ident("SECURITY_ATTRIBUTES".into()) =>
+        {
+            "*const c_void".to_owned()
+        }
+
+        // FIXME: the definitions below are probably false, but we don't care because we probably won't use them
+        parse::VkType::Ident(ident) if ident == "xcb_connection_t" => "u32".to_string(),
+        parse::VkType::Ident(ident) if ident == "xcb_window_t" => "u32".to_string(),
+        parse::VkType::Ident(ident) if ident == "xcb_visualid_t" => "u32".to_string(), // TODO: definitely wrong
+        parse::VkType::Ident(ident) if ident == "zx_handle_t" => "u32".to_string(),
+        parse::VkType::Ident(ident) if ident == "Window" => "u32".to_string(),
+        parse::VkType::Ident(ident) if ident == "VisualID" => "u32".to_string(),
+        parse::VkType::Ident(ident) if ident == "RROutput" => "u32".to_string(),
+        parse::VkType::Ident(ident) if ident == "GgpFrameToken" => "u32".to_string(),
+        parse::VkType::Ident(ident) if ident == "GgpStreamDescriptor" => "u32".to_string(),
+
+        parse::VkType::Ident(ty) => ty.to_string(),
+
+        parse::VkType::Array(t, arr) => format!("[{}; {}]", print_ty(t), arr),
+        parse::VkType::MutPointer(t, _) if **t == parse::VkType::Ident("void".into()) => {
+            "*mut c_void".to_owned()
+        }
+        parse::VkType::ConstPointer(t, _) if **t == parse::VkType::Ident("void".into()) => {
+            "*const c_void".to_owned()
+        }
+        parse::VkType::MutPointer(t, _) => format!("*mut {}", print_ty(t)),
+        parse::VkType::ConstPointer(t, _) => format!("*const {}", print_ty(t)),
+    }
+}
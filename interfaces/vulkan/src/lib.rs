@@ -69,51 +69,7 @@ pub const INTERFACE: InterfaceHash = InterfaceHash::from_raw_hash([
     0x5c, 0x82, 0x13, 0xe3, 0xcc, 0x04, 0x7b, 0xb7, 0xf6, 0x88, 0x74, 0x1e, 0x7a, 0xf2, 0x84, 0x75,
 ]);
 
-#[allow(non_camel_case_types)]
-type PFN_vkAllocationFunction =
-    extern "system" fn(*mut c_void, usize, usize, VkSystemAllocationScope) -> *mut c_void;
-#[allow(non_camel_case_types)]
-type PFN_vkReallocationFunction = extern "system" fn(
-    *mut c_void,
-    *mut c_void,
-    usize,
-    usize,
-    VkSystemAllocationScope,
-) -> *mut c_void;
-#[allow(non_camel_case_types)]
-type PFN_vkFreeFunction = extern "system" fn(*mut c_void, *mut c_void);
-#[allow(non_camel_case_types)]
-type PFN_vkInternalAllocationNotification = extern "system" fn(
-    *mut c_void,
-    usize,
-    VkInternalAllocationType,
-    VkSystemAllocationScope,
-) -> *mut c_void;
-#[allow(non_camel_case_types)]
-type PFN_vkInternalFreeNotification = extern "system" fn(
-    *mut c_void,
-    usize,
-    VkInternalAllocationType,
-    VkSystemAllocationScope,
-) -> *mut c_void;
-#[allow(non_camel_case_types)]
-type PFN_vkDebugReportCallbackEXT = extern "system" fn(
-    VkDebugReportFlagsEXT,
-    VkDebugReportObjectTypeEXT,
-    u64,
-    usize,
-    i32,
-    *const i8,
-    *const i8,
-    *mut c_void,
-) -> u32;
-#[allow(non_camel_case_types)]
-type PFN_vkDebugUtilsMessengerCallbackEXT = extern "system" fn(
-    VkDebugUtilsMessageSeverityFlagBitsEXT,
-    VkDebugUtilsMessageTypeFlagsEXT,
-    *const VkDebugUtilsMessengerCallbackDataEXT,
-    *mut c_void,
-) -> u32;
+// The other function pointer types are generated from the registry.
 #[allow(non_camel_case_types)]
 pub type PFN_vkVoidFunction = extern "system" fn() -> ();
 
@@ -0,0 +1,670 @@
+// Copyright (C) 2019  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Tests for the parsing of the XML definitions file done by the build script.
+
+#[allow(dead_code)]
+#[path = "../build/parse.rs"]
+mod parse;
+
+use parse::{ParseError, VkExtensionKind, VkRegistry, VkType, VkTypeDef, VkTypePtrLen};
+
+/// Parses a registry containing the given XML code in its `<types>` and `<commands>` elements,
+/// followed with `other`.
+///
+/// A struct named `VkDummy` and a command named `vkDummy` are always added, as the parser refuses
+/// registries without any type or command.
+fn parse_registry(types: &str, commands: &str, other: &str) -> Result<VkRegistry, ParseError> {
+    let source = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<registry>
+    <types>
+        <type category="struct" name="VkDummy">
+            <member><type>uint32_t</type> <name>dummy</name></member>
+        </type>
+        {}
+    </types>
+    <commands>
+        <command>
+            <proto><type>void</type> <name>vkDummy</name></proto>
+        </command>
+        {}
+    </commands>
+    {}
+</registry>
+"#,
+        types, commands, other
+    );
+
+    parse::parse(source.as_bytes())
+}
+
+#[test]
+fn minimal_registry() {
+    let registry = parse_registry("", "", "").unwrap();
+    assert_eq!(registry.commands.len(), 1);
+    assert_eq!(registry.commands[0].name, "vkDummy");
+    assert!(registry.type_defs.contains_key("VkDummy"));
+}
+
+#[test]
+fn invalid_xml() {
+    let source = r#"<?xml version="1.0" encoding="UTF-8"?><registry><types>"#;
+    match parse::parse(source.as_bytes()) {
+        Err(ParseError::Xml(_)) => {}
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn unexpected_element() {
+    match parse_registry("", "<foo/>", "") {
+        Err(ParseError::UnexpectedEvent { element, .. }) => assert_eq!(element, "commands"),
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn missing_attribute() {
+    match parse_registry("", "", r#"<enums><enum value="0"/></enums>"#) {
+        Err(ParseError::MissingAttribute { element, attribute }) => {
+            assert_eq!(element, "enum");
+            assert_eq!(attribute, "name");
+        }
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn unknown_type_category() {
+    match parse_registry(r#"<type category="foo" name="VkFoo"/>"#, "", "") {
+        Err(ParseError::InvalidContent { element, .. }) => assert_eq!(element, "type"),
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn no_command() {
+    let source = r#"<?xml version="1.0" encoding="UTF-8"?>
+<registry>
+    <types>
+        <type category="struct" name="VkDummy">
+            <member><type>uint32_t</type> <name>dummy</name></member>
+        </type>
+    </types>
+</registry>
+"#;
+
+    match parse::parse(source.as_bytes()) {
+        Err(ParseError::InvalidContent { element, .. }) => assert_eq!(element, "registry"),
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn duplicate_type() {
+    let types = r#"<type category="struct" name="VkDummy">
+        <member><type>uint32_t</type> <name>other</name></member>
+    </type>"#;
+
+    match parse_registry(types, "", "") {
+        Err(ParseError::InvalidContent { element, .. }) => assert_eq!(element, "types"),
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn extension_dependencies() {
+    let extensions = r#"<extensions>
+        <extension name="VK_KHR_swapchain" number="2" type="device" depends="VK_KHR_surface+VK_VERSION_1_1" supported="vulkan">
+        </extension>
+        <extension name="VK_KHR_surface" number="1" type="instance" supported="vulkan">
+        </extension>
+    </extensions>"#;
+
+    let registry = parse_registry("", "", extensions).unwrap();
+    let swapchain = registry
+        .extensions
+        .iter()
+        .find(|e| e.name == "VK_KHR_swapchain")
+        .unwrap();
+    assert_eq!(
+        swapchain.depends.as_ref().unwrap(),
+        "VK_KHR_surface+VK_VERSION_1_1"
+    );
+    assert_eq!(
+        swapchain.dependencies().unwrap(),
+        vec!["VK_KHR_surface", "VK_VERSION_1_1"]
+    );
+
+    let surface = registry
+        .extensions
+        .iter()
+        .find(|e| e.name == "VK_KHR_surface")
+        .unwrap();
+    assert!(surface.depends.is_none());
+    assert!(surface.dependencies().unwrap().is_empty());
+}
+
+#[test]
+fn extension_legacy_dependencies() {
+    let extensions = r#"<extensions>
+        <extension name="VK_KHR_display_swapchain" number="4" type="device" requires="VK_KHR_swapchain,VK_KHR_display" requiresCore="1.1" supported="vulkan">
+        </extension>
+    </extensions>"#;
+
+    let registry = parse_registry("", "", extensions).unwrap();
+    assert_eq!(
+        registry.extensions[0].depends.as_ref().unwrap(),
+        "VK_VERSION_1_1+VK_KHR_swapchain+VK_KHR_display"
+    );
+}
+
+#[test]
+fn extension_complex_dependencies() {
+    let extensions = r#"<extensions>
+        <extension name="VK_EXT_foo" number="5" type="device" depends="(VK_KHR_a+VK_KHR_b),VK_VERSION_1_2" supported="vulkan">
+        </extension>
+    </extensions>"#;
+
+    let registry = parse_registry("", "", extensions).unwrap();
+    assert!(registry.extensions[0].dependencies().is_none());
+}
+
+#[test]
+fn command_alias() {
+    let commands = r#"<command>
+        <proto><type>void</type> <name>vkGetPhysicalDeviceProperties2</name></proto>
+        <param><type>VkPhysicalDevice</type> <name>physicalDevice</name></param>
+        <param><type>VkPhysicalDeviceProperties2</type>* <name>pProperties</name></param>
+    </command>
+    <command name="vkGetPhysicalDeviceProperties2KHR" alias="vkGetPhysicalDeviceProperties2"/>"#;
+
+    let registry = parse_registry("", commands, "").unwrap();
+    assert!(registry
+        .commands
+        .iter()
+        .all(|c| c.name != "vkGetPhysicalDeviceProperties2KHR"));
+    assert_eq!(
+        registry
+            .command_aliases
+            .get("vkGetPhysicalDeviceProperties2KHR")
+            .unwrap(),
+        "vkGetPhysicalDeviceProperties2"
+    );
+
+    let resolved = registry
+        .resolve_command("vkGetPhysicalDeviceProperties2KHR")
+        .unwrap();
+    assert_eq!(resolved.name, "vkGetPhysicalDeviceProperties2");
+    assert_eq!(resolved.params.len(), 2);
+}
+
+#[test]
+fn command_alias_cycle() {
+    let commands = r#"<command name="vkFooKHR" alias="vkFooEXT"/>
+    <command name="vkFooEXT" alias="vkFooKHR"/>"#;
+
+    let registry = parse_registry("", commands, "").unwrap();
+    assert!(registry.resolve_command("vkFooKHR").is_none());
+}
+
+#[test]
+fn command_duplicate_alias() {
+    let commands = r#"<command name="vkFooKHR" alias="vkDummy"/>
+    <command name="vkFooKHR" alias="vkDummy"/>"#;
+
+    match parse_registry("", commands, "") {
+        Err(ParseError::InvalidContent { element, .. }) => assert_eq!(element, "commands"),
+        r => panic!("{:?}", r),
+    }
+}
+
+/// Parses a registry containing the given command, and returns the types of its parameters.
+fn command_params_types(command: &str) -> Vec<VkType> {
+    let registry = parse_registry("", command, "").unwrap();
+    registry
+        .commands
+        .into_iter()
+        .find(|c| c.name == "vkTest")
+        .unwrap()
+        .params
+        .into_iter()
+        .map(|(ty, _)| ty)
+        .collect()
+}
+
+fn ident(name: &str) -> VkType {
+    VkType::Ident(name.to_owned())
+}
+
+fn mut_ptr(ty: VkType) -> VkType {
+    VkType::MutPointer(Box::new(ty), VkTypePtrLen::One)
+}
+
+fn const_ptr(ty: VkType) -> VkType {
+    VkType::ConstPointer(Box::new(ty), VkTypePtrLen::One)
+}
+
+#[test]
+fn double_pointers() {
+    let params = command_params_types(
+        r#"<command>
+        <proto><type>void</type> <name>vkTest</name></proto>
+        <param><type>char</type>** <name>a</name></param>
+        <param>const <type>char</type>* const* <name>b</name></param>
+        <param><type>void</type>** <name>c</name></param>
+    </command>"#,
+    );
+
+    assert_eq!(params[0], mut_ptr(mut_ptr(ident("char"))));
+    assert_eq!(params[1], const_ptr(const_ptr(ident("char"))));
+    assert_eq!(params[2], mut_ptr(mut_ptr(ident("void"))));
+}
+
+#[test]
+fn double_pointer_lengths() {
+    let params = command_params_types(
+        r#"<command>
+        <proto><type>void</type> <name>vkTest</name></proto>
+        <param><type>uint32_t</type> <name>count</name></param>
+        <param len="count,null-terminated">const <type>char</type>* const* <name>names</name></param>
+    </command>"#,
+    );
+
+    assert_eq!(
+        params[1],
+        VkType::ConstPointer(
+            Box::new(VkType::ConstPointer(
+                Box::new(ident("char")),
+                VkTypePtrLen::NullTerminated
+            )),
+            VkTypePtrLen::OtherField {
+                before_other_field: "".to_owned(),
+                other_field: vec!["count".to_owned()],
+                after_other_field: "".to_owned(),
+            }
+        )
+    );
+}
+
+#[test]
+fn too_many_pointer_lengths() {
+    let command = r#"<command>
+        <proto><type>void</type> <name>vkTest</name></proto>
+        <param len="count,null-terminated">const <type>char</type>* <name>name</name></param>
+    </command>"#;
+
+    match parse_registry("", command, "") {
+        Err(ParseError::InvalidContent { element, .. }) => assert_eq!(element, "param"),
+        r => panic!("{:?}", r),
+    }
+}
+
+/// Parses a registry containing the given type definitions, and returns the types of the fields
+/// of the struct named `VkTest`.
+fn struct_fields_types(types: &str) -> Vec<VkType> {
+    let registry = parse_registry(types, "", "").unwrap();
+    match registry.type_defs.get("VkTest") {
+        Some(VkTypeDef::Struct { fields }) => fields.iter().map(|(ty, _)| ty.clone()).collect(),
+        t => panic!("{:?}", t),
+    }
+}
+
+fn array(ty: VkType, len: &str) -> VkType {
+    VkType::Array(Box::new(ty), len.to_owned())
+}
+
+#[test]
+fn array_dimensions() {
+    let fields = struct_fields_types(
+        r#"<type category="struct" name="VkTest">
+        <member><type>float</type> <name>a</name>[16]</member>
+        <member><type>uint8_t</type> <name>b</name>[<enum>VK_UUID_SIZE</enum>]</member>
+        <member><type>char</type> <name>c</name>[VK_MAX_DESCRIPTION_SIZE]</member>
+        <member><type>float</type> <name>d</name>[2][4]</member>
+        <member><type>float</type> <name>e</name>[<enum>VK_A</enum>][<enum>VK_B</enum>]</member>
+    </type>"#,
+    );
+
+    assert_eq!(fields[0], array(ident("float"), "16"));
+    assert_eq!(fields[1], array(ident("uint8_t"), "VK_UUID_SIZE"));
+    assert_eq!(fields[2], array(ident("char"), "VK_MAX_DESCRIPTION_SIZE"));
+    assert_eq!(fields[3], array(array(ident("float"), "4"), "2"));
+    assert_eq!(fields[4], array(array(ident("float"), "VK_B"), "VK_A"));
+}
+
+#[test]
+fn unclosed_array_bracket() {
+    let types = r#"<type category="struct" name="VkTest">
+        <member><type>float</type> <name>a</name>[16</member>
+    </type>"#;
+
+    match parse_registry(types, "", "") {
+        Err(ParseError::InvalidContent { element, .. }) => assert_eq!(element, "member"),
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn missing_array_dimension() {
+    let types = r#"<type category="struct" name="VkTest">
+        <member><type>float</type> <name>a</name>[]</member>
+    </type>"#;
+
+    match parse_registry(types, "", "") {
+        Err(ParseError::InvalidContent { element, .. }) => assert_eq!(element, "member"),
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn funcpointer() {
+    let types = r#"<type category="funcpointer">typedef void* (VKAPI_PTR *<name>PFN_vkAllocationFunction</name>)(
+    <type>void</type>*                                       pUserData,
+    <type>size_t</type>                                      size,
+    <type>size_t</type>                                      alignment,
+    <type>VkSystemAllocationScope</type>                     allocationScope);</type>
+    <type category="funcpointer">typedef void (VKAPI_PTR *<name>PFN_vkVoidFunction</name>)(void);</type>"#;
+
+    let registry = parse_registry(types, "", "").unwrap();
+
+    match registry.type_defs.get("PFN_vkAllocationFunction") {
+        Some(VkTypeDef::FuncPointer { ret_ty, params }) => {
+            assert_eq!(*ret_ty, mut_ptr(ident("void")));
+            assert_eq!(
+                *params,
+                vec![
+                    (mut_ptr(ident("void")), "pUserData".to_owned()),
+                    (ident("size_t"), "size".to_owned()),
+                    (ident("size_t"), "alignment".to_owned()),
+                    (
+                        ident("VkSystemAllocationScope"),
+                        "allocationScope".to_owned()
+                    ),
+                ]
+            );
+        }
+        t => panic!("{:?}", t),
+    }
+
+    match registry.type_defs.get("PFN_vkVoidFunction") {
+        Some(VkTypeDef::FuncPointer { ret_ty, params }) => {
+            assert_eq!(*ret_ty, ident("void"));
+            assert!(params.is_empty());
+        }
+        t => panic!("{:?}", t),
+    }
+}
+
+#[test]
+fn funcpointer_without_name() {
+    let types = r#"<type category="funcpointer">typedef void (VKAPI_PTR *PFN_vkFoo)(void);</type>"#;
+
+    match parse_registry(types, "", "") {
+        Err(ParseError::InvalidContent { element, .. }) => assert_eq!(element, "type"),
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn funcpointer_invalid_parameter() {
+    let types = r#"<type category="funcpointer">typedef void (VKAPI_PTR *<name>PFN_vkFoo</name>)(<type>int</type> (*callback)(void));</type>"#;
+
+    match parse_registry(types, "", "") {
+        Err(ParseError::InvalidContent { element, .. }) => assert_eq!(element, "type"),
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn enum_values() {
+    let enums = r#"<enums name="API Constants" comment="Vulkan hardcoded constants">
+        <enum value="256" name="VK_MAX_PHYSICAL_DEVICE_NAME_SIZE"/>
+        <enum value="(~0U)" name="VK_REMAINING_MIP_LEVELS"/>
+    </enums>
+    <enums name="VkResult" type="enum">
+        <enum value="0" name="VK_SUCCESS"/>
+        <enum value="-1" name="VK_ERROR_OUT_OF_HOST_MEMORY"/>
+        <enum value="0x7FFFFFFF" name="VK_RESULT_MAX_ENUM"/>
+        <enum name="VK_ERROR_OUT_OF_HOST_MEMORY_KHR" alias="VK_ERROR_OUT_OF_HOST_MEMORY"/>
+    </enums>
+    <enums name="VkQueueFlagBits" type="bitmask">
+        <enum bitpos="0" name="VK_QUEUE_GRAPHICS_BIT"/>
+        <enum bitpos="3" name="VK_QUEUE_SPARSE_BINDING_BIT"/>
+    </enums>"#;
+
+    let registry = parse_registry("", "", enums).unwrap();
+    assert_eq!(
+        registry.enum_value("VK_MAX_PHYSICAL_DEVICE_NAME_SIZE"),
+        Some(256)
+    );
+    assert_eq!(registry.enum_value("VK_REMAINING_MIP_LEVELS"), None);
+    assert_eq!(registry.enum_value("VK_SUCCESS"), Some(0));
+    assert_eq!(registry.enum_value("VK_ERROR_OUT_OF_HOST_MEMORY"), Some(-1));
+    assert_eq!(registry.enum_value("VK_RESULT_MAX_ENUM"), Some(0x7FFFFFFF));
+    assert_eq!(
+        registry.enum_value("VK_ERROR_OUT_OF_HOST_MEMORY_KHR"),
+        Some(-1)
+    );
+    assert_eq!(registry.enum_value("VK_QUEUE_GRAPHICS_BIT"), Some(1));
+    assert_eq!(registry.enum_value("VK_QUEUE_SPARSE_BINDING_BIT"), Some(8));
+    assert_eq!(registry.enum_value("VK_UNKNOWN"), None);
+}
+
+#[test]
+fn enum_without_value() {
+    match parse_registry("", "", r#"<enums><enum name="VK_FOO"/></enums>"#) {
+        Err(ParseError::InvalidContent { element, .. }) => assert_eq!(element, "enum"),
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn duplicate_enum_value() {
+    let enums = r#"<enums name="VkA"><enum value="0" name="VK_FOO"/></enums>
+    <enums name="VkB"><enum value="1" name="VK_FOO"/></enums>"#;
+
+    match parse_registry("", "", enums) {
+        Err(ParseError::InvalidContent { element, .. }) => assert_eq!(element, "enums"),
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn handle_parents() {
+    let types = r#"<type category="handle" parent="VkDevice"><type>VK_DEFINE_HANDLE</type>(<name>VkQueue</name>)</type>
+    <type category="handle" parent="VkDevice"><type>VK_DEFINE_NON_DISPATCHABLE_HANDLE</type>(<name>VkFence</name>)</type>
+    <type category="handle" parent="VkPhysicalDevice,VkDisplayKHR"><type>VK_DEFINE_NON_DISPATCHABLE_HANDLE</type>(<name>VkDisplayModeKHR</name>)</type>
+    <type category="handle"><type>VK_DEFINE_HANDLE</type>(<name>VkInstance</name>)</type>
+    <type category="handle" name="VkDescriptorUpdateTemplateKHR" alias="VkDescriptorUpdateTemplate"/>"#;
+
+    let registry = parse_registry(types, "", "").unwrap();
+
+    match registry.type_defs.get("VkQueue") {
+        Some(VkTypeDef::DispatchableHandle { parents }) => assert_eq!(*parents, vec!["VkDevice"]),
+        t => panic!("{:?}", t),
+    }
+    match registry.type_defs.get("VkFence") {
+        Some(VkTypeDef::NonDispatchableHandle { parents }) => {
+            assert_eq!(*parents, vec!["VkDevice"])
+        }
+        t => panic!("{:?}", t),
+    }
+    match registry.type_defs.get("VkDisplayModeKHR") {
+        Some(VkTypeDef::NonDispatchableHandle { parents }) => {
+            assert_eq!(*parents, vec!["VkPhysicalDevice", "VkDisplayKHR"])
+        }
+        t => panic!("{:?}", t),
+    }
+    match registry.type_defs.get("VkInstance") {
+        Some(VkTypeDef::DispatchableHandle { parents }) => assert!(parents.is_empty()),
+        t => panic!("{:?}", t),
+    }
+    assert!(!registry
+        .type_defs
+        .contains_key("VkDescriptorUpdateTemplateKHR"));
+}
+
+#[test]
+fn unknown_handle_kind() {
+    let types =
+        r#"<type category="handle"><type>VK_DEFINE_FOO_HANDLE</type>(<name>VkFoo</name>)</type>"#;
+
+    match parse_registry(types, "", "") {
+        Err(ParseError::InvalidContent { element, .. }) => assert_eq!(element, "type"),
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn extension_commands() {
+    let extensions = r#"<extensions comment="Vulkan extensions">
+        <extension name="VK_KHR_surface" number="1" type="instance" author="KHR" supported="vulkan">
+            <require>
+                <enum value="25" name="VK_KHR_SURFACE_SPEC_VERSION"/>
+                <type name="VkSurfaceKHR"/>
+                <command name="vkDestroySurfaceKHR"/>
+            </require>
+            <require comment="Second block">
+                <command name="vkGetPhysicalDeviceSurfaceSupportKHR"/>
+            </require>
+        </extension>
+        <extension name="VK_KHR_swapchain" number="2" type="device" supported="vulkan">
+            <require>
+                <command name="vkCreateSwapchainKHR"/>
+            </require>
+        </extension>
+        <extension name="VK_KHR_disabled" number="3" type="device" supported="disabled">
+            <require>
+                <command name="vkDisabledKHR"/>
+            </require>
+        </extension>
+    </extensions>"#;
+
+    let registry = parse_registry("", "", extensions).unwrap();
+    assert_eq!(registry.extensions.len(), 2);
+
+    let surface = &registry.extensions[0];
+    assert_eq!(surface.name, "VK_KHR_surface");
+    assert_eq!(surface.kind, VkExtensionKind::Instance);
+    assert_eq!(
+        surface.required_commands,
+        vec![
+            "vkDestroySurfaceKHR",
+            "vkGetPhysicalDeviceSurfaceSupportKHR"
+        ]
+    );
+
+    let swapchain = &registry.extensions[1];
+    assert_eq!(swapchain.name, "VK_KHR_swapchain");
+    assert_eq!(swapchain.kind, VkExtensionKind::Device);
+    assert_eq!(swapchain.required_commands, vec!["vkCreateSwapchainKHR"]);
+}
+
+#[test]
+fn unknown_extension_kind() {
+    let extensions = r#"<extensions>
+        <extension name="VK_KHR_foo" number="1" type="foo" supported="vulkan"></extension>
+    </extensions>"#;
+
+    match parse_registry("", "", extensions) {
+        Err(ParseError::InvalidContent { element, .. }) => assert_eq!(element, "extension"),
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn extension_without_type() {
+    let extensions = r#"<extensions>
+        <extension name="VK_KHR_foo" number="1" supported="vulkan"></extension>
+    </extensions>"#;
+
+    match parse_registry("", "", extensions) {
+        Err(ParseError::MissingAttribute { element, attribute }) => {
+            assert_eq!(element, "extension");
+            assert_eq!(attribute, "type");
+        }
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn feature_commands() {
+    let features = r#"<feature api="vulkan" name="VK_VERSION_1_0" number="1.0" comment="Vulkan core API interface definitions">
+        <require comment="Header boilerplate">
+            <type name="vk_platform"/>
+        </require>
+        <require comment="Device initialization">
+            <command name="vkCreateInstance"/>
+        </require>
+    </feature>
+    <feature api="vulkan" name="VK_VERSION_1_1" number="1.1">
+        <require>
+            <enum name="VK_API_VERSION_1_1"/>
+            <enum extends="VkResult" extnumber="70" offset="0" dir="-" name="VK_ERROR_OUT_OF_POOL_MEMORY"/>
+            <command name="vkEnumerateInstanceVersion"/>
+        </require>
+    </feature>"#;
+
+    let registry = parse_registry("", "", features).unwrap();
+    assert_eq!(registry.features.len(), 2);
+
+    let feature = registry
+        .features
+        .iter()
+        .find(|f| f.commands.iter().any(|c| c == "vkEnumerateInstanceVersion"))
+        .unwrap();
+    assert_eq!(feature.api, "vulkan");
+    assert_eq!(feature.name, "VK_VERSION_1_1");
+    assert_eq!(feature.number, "1.1");
+    assert_eq!(feature.commands, vec!["vkEnumerateInstanceVersion"]);
+
+    assert_eq!(registry.features[0].commands, vec!["vkCreateInstance"]);
+    assert!(!registry.enums.contains_key("VK_ERROR_OUT_OF_POOL_MEMORY"));
+}
+
+#[test]
+fn feature_without_number() {
+    let features = r#"<feature api="vulkan" name="VK_VERSION_1_0"></feature>"#;
+
+    match parse_registry("", "", features) {
+        Err(ParseError::MissingAttribute { element, attribute }) => {
+            assert_eq!(element, "feature");
+            assert_eq!(attribute, "number");
+        }
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn pointer_constness() {
+    let params = command_params_types(
+        r#"<command>
+        <proto><type>void</type> <name>vkTest</name></proto>
+        <param>const <type>VkFoo</type>* <name>a</name></param>
+        <param><type>VkFoo</type>* const <name>b</name></param>
+        <param>const <type>VkFoo</type>* const <name>c</name></param>
+        <param><type>VkFoo</type> const* <name>d</name></param>
+        <param><type>VkFoo</type>* <name>e</name></param>
+    </command>"#,
+    );
+
+    assert_eq!(params[0], const_ptr(ident("VkFoo")));
+    assert_eq!(params[1], mut_ptr(ident("VkFoo")));
+    assert_eq!(params[2], const_ptr(ident("VkFoo")));
+    assert_eq!(params[3], const_ptr(ident("VkFoo")));
+    assert_eq!(params[4], mut_ptr(ident("VkFoo")));
+}
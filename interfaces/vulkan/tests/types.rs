@@ -0,0 +1,115 @@
+// Copyright (C) 2019  Pierre Krieger
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Tests for the generation of Rust code done by the build script.
+
+#[allow(dead_code)]
+#[path = "../build/parse.rs"]
+mod parse;
+#[allow(dead_code)]
+#[path = "../build/types.rs"]
+mod types;
+
+const REGISTRY: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<registry>
+    <types>
+        <type category="struct" name="VkTest">
+            <member><type>uint32_t</type> <name>a</name></member>
+            <member>const <type>void</type>* <name>pNext</name></member>
+            <member><type>uint8_t</type> <name>uuid</name>[<enum>VK_UUID_SIZE</enum>]</member>
+            <member><type>uint8_t</type> <name>luid</name>[<enum>VK_LUID_SIZE_KHR</enum>]</member>
+            <member><type>float</type> <name>matrix</name>[2][4]</member>
+        </type>
+        <type category="union" name="VkTestUnion">
+            <member><type>float</type> <name>float32</name>[4]</member>
+            <member><type>char</type> <name>name</name>[<enum>VK_UUID_SIZE</enum>]</member>
+        </type>
+        <type category="struct" name="VkTestCycle">
+            <member><type>uint8_t</type> <name>a</name>[<enum>VK_CYCLE_A</enum>]</member>
+        </type>
+    </types>
+    <enums name="API Constants">
+        <enum value="16" name="VK_UUID_SIZE"/>
+        <enum value="8" name="VK_LUID_SIZE"/>
+        <enum name="VK_LUID_SIZE_KHR" alias="VK_LUID_SIZE"/>
+        <enum name="VK_CYCLE_A" alias="VK_CYCLE_B"/>
+        <enum name="VK_CYCLE_B" alias="VK_CYCLE_A"/>
+    </enums>
+    <commands>
+        <command>
+            <proto><type>void</type> <name>vkDummy</name></proto>
+        </command>
+    </commands>
+</registry>
+"#;
+
+/// Returns the fields of the struct or union with the given name.
+fn fields(registry: &parse::VkRegistry, name: &str) -> Vec<(parse::VkType, String)> {
+    match registry.type_defs.get(name) {
+        Some(parse::VkTypeDef::Struct { fields }) | Some(parse::VkTypeDef::Union { fields }) => {
+            fields.clone()
+        }
+        t => panic!("{:?}", t),
+    }
+}
+
+#[test]
+fn struct_def() {
+    let registry = parse::parse(REGISTRY.as_bytes()).unwrap();
+    let def = types::gen_struct_def("VkTest", &fields(&registry, "VkTest"), &registry);
+
+    assert_eq!(
+        def,
+        "#[repr(C)]
+#[allow(non_snake_case)]
+#[derive(Copy, Clone)]
+struct VkTest {
+    r#a: u32,
+    r#pNext: *const c_void,
+    r#uuid: [u8; 16],
+    r#luid: [u8; 8],
+    r#matrix: [[f32; 4]; 2],
+}
+"
+    );
+}
+
+#[test]
+fn union_def() {
+    let registry = parse::parse(REGISTRY.as_bytes()).unwrap();
+    let def = types::gen_union_def("VkTestUnion", &fields(&registry, "VkTestUnion"), &registry);
+
+    assert_eq!(
+        def,
+        "#[repr(C)]
+#[allow(non_snake_case)]
+#[derive(Copy, Clone)]
+union VkTestUnion {
+    r#float32: [f32; 4],
+    r#name: [u8; 16],
+}
+"
+    );
+}
+
+#[test]
+fn cyclic_array_length() {
+    let registry = parse::parse(REGISTRY.as_bytes()).unwrap();
+    let fields = fields(&registry, "VkTestCycle");
+    assert_eq!(
+        types::print_ty_resolved(&fields[0].0, &registry),
+        "[u8; VK_CYCLE_A]"
+    );
+}
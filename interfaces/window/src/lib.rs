@@ -32,6 +32,7 @@ impl Window {
             redshirt_syscalls_interface::emit_message_with_response(&ffi::INTERFACE, open)
                 .map_err(|_| ())?
                 .await
+                .map_err(|_| ())?
         };
         Ok(Window {
             handle: response.result?,
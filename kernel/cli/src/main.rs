@@ -15,7 +15,8 @@
 
 #![deny(intra_doc_link_resolution_failure)]
 
-use std::{fs, path::PathBuf, process};
+use futures::future::{self, Either};
+use std::{path::PathBuf, process};
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
@@ -33,22 +34,40 @@ fn main() {
 async fn async_main() {
     let cli_requested_process = {
         let cli_opts = CliOptions::from_args();
-        let wasm_file_content = fs::read(cli_opts.wasm_file).expect("failed to read input file");
-        redshirt_core::module::Module::from_bytes(&wasm_file_content)
-            .expect("failed to parse input file")
+        redshirt_core::module::Module::from_file(cli_opts.wasm_file)
+            .expect("failed to load input file")
     };
 
     let mut system = redshirt_core::system::SystemBuilder::new()
         .with_native_program(redshirt_time_hosted::TimerHandler::new())
         .with_native_program(redshirt_stdout_hosted::StdoutHandler::new())
-        .build();
+        .with_time_source(redshirt_time_hosted::MonotonicClock)
+        .build()
+        .expect("failed to start the system");
 
-    let cli_pid = system.execute(&cli_requested_process);
+    let cli_pid = system
+        .execute(&cli_requested_process)
+        .expect("failed to start process");
 
     loop {
-        let outcome = system.run().await;
+        // The `System` doesn't wake itself up when a timeout elapses, so we race it against a
+        // timer.
+        let outcome = match system.next_deadline() {
+            Some(deadline) => {
+                let timer = async_std::task::sleep(
+                    deadline.remaining(&redshirt_time_hosted::MonotonicClock),
+                );
+                let run = system.run();
+                futures::pin_mut!(run, timer);
+                match future::select(run, timer).await {
+                    Either::Left((outcome, _)) => outcome,
+                    Either::Right(((), _)) => continue,
+                }
+            }
+            None => system.run().await,
+        };
         match outcome {
-            redshirt_core::system::SystemRunOutcome::ProgramFinished { pid, outcome }
+            Ok(redshirt_core::system::SystemRunOutcome::ProgramFinished { pid, outcome })
                 if pid == cli_pid =>
             {
                 process::exit(match outcome {
@@ -59,6 +78,7 @@ async fn async_main() {
                     }
                 });
             }
+            Err(err) => eprintln!("{}", err),
             _ => panic!(),
         }
     }
@@ -17,27 +17,61 @@
 
 use futures::prelude::*;
 use redshirt_core::native::{DummyMessageIdWrite, NativeProgramEvent, NativeProgramRef};
-use redshirt_core::{Decode as _, Encode as _, EncodedMessage, InterfaceHash, MessageId, Pid};
+use redshirt_core::{
+    AnswerError, Decode as _, Encode as _, EncodedMessage, InterfaceHash, MessageId, Pid,
+};
 use redshirt_stdout_interface::ffi::{StdoutMessage, INTERFACE};
 use std::{
-    io::{self, Write as _},
+    io::{self, Write},
     pin::Pin,
-    sync::atomic,
+    sync::{atomic, Mutex},
 };
 
 /// Native program for `stdout` interface messages handling.
 pub struct StdoutHandler {
     /// If true, we have sent the interface registration message.
     registered: atomic::AtomicBool,
+    /// Where to write the messages.
+    output: Mutex<Box<dyn Write + Send>>,
+    /// Character to append after each message.
+    line_ending: LineEnding,
+}
+
+/// Character appended by the [`StdoutHandler`] after each message it writes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LineEnding {
+    /// Messages are written as they are. Appropriate for log files and piped output.
+    None,
+    /// A `\r` is appended after each message. Appropriate for a status-line UI on a terminal.
+    CarriageReturn,
+    /// A `\n` is appended after each message.
+    LineFeed,
 }
 
 impl StdoutHandler {
     /// Initializes the new state machine for stdout.
+    ///
+    /// No character is appended after the messages. Use
+    /// [`with_line_ending`](StdoutHandler::with_line_ending) to change this.
     pub fn new() -> Self {
+        StdoutHandler::with_output(io::stdout())
+    }
+
+    /// Initializes the new state machine, writing the messages to the given output instead of
+    /// stdout. This can be used to redirect the messages to stderr or to a file.
+    pub fn with_output(output: impl Write + Send + 'static) -> Self {
         StdoutHandler {
             registered: atomic::AtomicBool::new(false),
+            output: Mutex::new(Box::new(output)),
+            line_ending: LineEnding::None,
         }
     }
+
+    /// Sets the character to append after each message.
+    pub fn with_line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
 }
 
 impl<'a> NativeProgramRef<'a> for &'a StdoutHandler {
@@ -75,9 +109,14 @@ impl<'a> NativeProgramRef<'a> for &'a StdoutHandler {
 
         match StdoutMessage::decode(message) {
             Ok(StdoutMessage::Message(msg)) => {
-                let mut stdout = io::stdout();
-                stdout.write_all(msg.as_bytes()).unwrap();
-                stdout.flush().unwrap();
+                let mut output = self.output.lock().unwrap();
+                output.write_all(msg.as_bytes()).unwrap();
+                match self.line_ending {
+                    LineEnding::None => {}
+                    LineEnding::CarriageReturn => output.write_all(b"\r").unwrap(),
+                    LineEnding::LineFeed => output.write_all(b"\n").unwrap(),
+                }
+                output.flush().unwrap();
             }
             Err(_) => panic!(),
         }
@@ -85,7 +124,60 @@ impl<'a> NativeProgramRef<'a> for &'a StdoutHandler {
 
     fn process_destroyed(self, _: Pid) {}
 
-    fn message_response(self, _: MessageId, _: Result<EncodedMessage, ()>) {
+    fn message_response(self, _: MessageId, _: Result<EncodedMessage, AnswerError>) {
         unreachable!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{LineEnding, StdoutHandler};
+    use redshirt_core::native::NativeProgramRef as _;
+    use redshirt_core::{Encode as _, Pid};
+    use redshirt_stdout_interface::ffi::{StdoutMessage, INTERFACE};
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    /// Output that can be inspected after having been passed to the handler.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn output_of(line_ending: LineEnding) -> Vec<u8> {
+        let buffer = SharedBuffer::default();
+        let handler = StdoutHandler::with_output(buffer.clone()).with_line_ending(line_ending);
+        for msg in &["foo\nbar", "baz"] {
+            let msg = StdoutMessage::Message(msg.to_string()).encode();
+            (&handler).interface_message(INTERFACE, None, Pid::from(1), msg);
+        }
+        let out = buffer.0.lock().unwrap().clone();
+        out
+    }
+
+    #[test]
+    fn line_endings() {
+        assert_eq!(output_of(LineEnding::None), b"foo\nbarbaz");
+        assert_eq!(output_of(LineEnding::CarriageReturn), b"foo\nbar\rbaz\r");
+        assert_eq!(output_of(LineEnding::LineFeed), b"foo\nbar\nbaz\n");
+    }
+
+    #[test]
+    fn default_output_is_verbatim() {
+        let buffer = SharedBuffer::default();
+        let handler = StdoutHandler::with_output(buffer.clone());
+        let msg = StdoutMessage::Message("ab\ncd".to_string()).encode();
+        (&handler).interface_message(INTERFACE, None, Pid::from(1), msg);
+        assert_eq!(&*buffer.0.lock().unwrap(), b"ab\ncd");
+    }
+}
@@ -20,7 +20,10 @@ use futures_timer::Delay;
 use redshirt_core::native::{
     DummyMessageIdWrite, NativeProgramEvent, NativeProgramMessageIdWrite, NativeProgramRef,
 };
-use redshirt_core::{Decode as _, Encode as _, EncodedMessage, InterfaceHash, MessageId, Pid};
+use redshirt_core::time::TimeSource;
+use redshirt_core::{
+    AnswerError, Decode as _, Encode as _, EncodedMessage, InterfaceHash, MessageId, Pid,
+};
 use redshirt_time_interface::ffi::{TimeMessage, INTERFACE};
 use std::{
     convert::TryFrom,
@@ -167,11 +170,23 @@ impl<'a> NativeProgramRef<'a> for &'a TimerHandler {
 
     fn process_destroyed(self, _: Pid) {}
 
-    fn message_response(self, _: MessageId, _: Result<EncodedMessage, ()>) {
+    fn message_response(self, _: MessageId, _: Result<EncodedMessage, AnswerError>) {
         unreachable!()
     }
 }
 
+/// Implementation of [`TimeSource`] that uses the monotonic clock of the operating system.
+///
+/// Uses the same clock as the one used to answer the messages of the time interface.
+#[derive(Debug, Default, Clone)]
+pub struct MonotonicClock;
+
+impl TimeSource for MonotonicClock {
+    fn monotonic_nanos(&self) -> u128 {
+        monotonic_clock()
+    }
+}
+
 fn monotonic_clock() -> u128 {
     lazy_static::lazy_static! {
         static ref CLOCK_START: Instant = Instant::now();
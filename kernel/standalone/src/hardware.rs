@@ -26,7 +26,9 @@ use crossbeam_queue::SegQueue;
 use futures::prelude::*;
 use hashbrown::HashMap;
 use redshirt_core::native::{DummyMessageIdWrite, NativeProgramEvent, NativeProgramRef};
-use redshirt_core::{Decode as _, Encode as _, EncodedMessage, InterfaceHash, MessageId, Pid};
+use redshirt_core::{
+    AnswerError, Decode as _, Encode as _, EncodedMessage, InterfaceHash, MessageId, Pid,
+};
 use redshirt_hardware_interface::ffi::{
     HardwareAccessResponse, HardwareMessage, Operation, INTERFACE,
 };
@@ -40,7 +42,7 @@ pub struct HardwareHandler {
     // TODO: optimize
     allocations: Mutex<HashMap<Pid, Vec<Vec<u8>>>>,
     /// List of messages waiting to be emitted with `next_event`.
-    pending_messages: SegQueue<(MessageId, Result<EncodedMessage, ()>)>,
+    pending_messages: SegQueue<(MessageId, Result<EncodedMessage, AnswerError>)>,
 }
 
 impl HardwareHandler {
@@ -143,7 +145,8 @@ impl<'a> NativeProgramRef<'a> for &'a HardwareHandler {
             Ok(HardwareMessage::InterruptWait(_int_id)) => unimplemented!(), // TODO:
             Err(_) => {
                 if let Some(message_id) = message_id {
-                    self.pending_messages.push((message_id, Err(())))
+                    self.pending_messages
+                        .push((message_id, Err(AnswerError::MalformedRequest)))
                 }
             }
         }
@@ -153,7 +156,7 @@ impl<'a> NativeProgramRef<'a> for &'a HardwareHandler {
         self.allocations.lock().remove(&pid);
     }
 
-    fn message_response(self, _: MessageId, _: Result<EncodedMessage, ()>) {
+    fn message_response(self, _: MessageId, _: Result<EncodedMessage, AnswerError>) {
         unreachable!()
     }
 }
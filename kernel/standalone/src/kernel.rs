@@ -107,15 +107,18 @@ impl Kernel {
 
         let mut system = system_builder
             .with_main_program([0; 32]) // TODO: just a test
-            .build();
+            .build()
+            .unwrap();
 
         loop {
             // TODO: ideally the entire function would be async, and this would be an `await`,
             // but async functions don't work on no_std yet
             match crate::executor::block_on(system.run()) {
-                redshirt_core::system::SystemRunOutcome::ProgramFinished { pid, outcome } => {
+                Ok(redshirt_core::system::SystemRunOutcome::ProgramFinished { pid, outcome }) => {
                     //console.write(&format!("Program finished {:?} => {:?}\n", pid, outcome));
                 }
+                // TODO: report the error somewhere
+                Err(_) => {}
                 _ => panic!(),
             }
         }
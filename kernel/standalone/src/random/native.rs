@@ -23,7 +23,9 @@ use crossbeam_queue::SegQueue;
 use futures::prelude::*;
 use rand_core::RngCore as _;
 use redshirt_core::native::{DummyMessageIdWrite, NativeProgramEvent, NativeProgramRef};
-use redshirt_core::{Decode as _, Encode as _, EncodedMessage, InterfaceHash, MessageId, Pid};
+use redshirt_core::{
+    AnswerError, Decode as _, Encode as _, EncodedMessage, InterfaceHash, MessageId, Pid,
+};
 use redshirt_random_interface::ffi::{GenerateResponse, RandomMessage, INTERFACE};
 
 /// State machine for `random` interface messages handling.
@@ -33,7 +35,7 @@ pub struct RandomNativeProgram {
     /// Queue of random number generators. If it is empty, we generate a new one.
     rngs: SegQueue<KernelRng>,
     /// Message responses waiting to be emitted.
-    pending_messages: SegQueue<(MessageId, Result<EncodedMessage, ()>)>,
+    pending_messages: SegQueue<(MessageId, Result<EncodedMessage, AnswerError>)>,
 }
 
 impl RandomNativeProgram {
@@ -102,13 +104,15 @@ impl<'a> NativeProgramRef<'a> for &'a RandomNativeProgram {
                 self.pending_messages
                     .push((message_id, Ok(response.encode())));
             }
-            Err(_) => self.pending_messages.push((message_id, Err(()))),
+            Err(_) => self
+                .pending_messages
+                .push((message_id, Err(AnswerError::MalformedRequest))),
         }
     }
 
     fn process_destroyed(self, _: Pid) {}
 
-    fn message_response(self, _: MessageId, _: Result<EncodedMessage, ()>) {
+    fn message_response(self, _: MessageId, _: Result<EncodedMessage, AnswerError>) {
         unreachable!()
     }
 }
@@ -26,7 +26,7 @@ fn main() {
 
         let stream = stream::unfold(listener, |mut l| {
             async move {
-                let connec = l.accept().await.0;
+                let connec = l.accept().await.ok()?.0;
                 Some((connec, l))
             }
         });
@@ -72,7 +72,7 @@ impl Transport for TcpConfig {
                 let then = stream::unfold(listener, move |mut s| {
                     let local_addr = local_addr.clone();
                     async move {
-                        let (socket, remote_addr) = s.accept().await;
+                        let (socket, remote_addr) = s.accept().await.ok()?;
                         let ev = ListenerEvent::Upgrade {
                             upgrade: future::ready(Ok(socket)),
                             local_addr: local_addr.clone(),